@@ -0,0 +1,153 @@
+use std::f64::consts::PI;
+use std::path::Path;
+use std::sync::Arc;
+
+use rand::{Rng, RngCore};
+
+use crate::netpbm;
+use crate::scene::{Node, Value};
+use crate::Vec3;
+
+/// Rejection sampling attempts before a lens sample counts as blocked, e.g. for masks that are
+/// almost opaque
+pub(crate) const MAX_TRIES: usize = 1000;
+
+/// Shape of the aperture, which determines the shape of out-of-focus highlights (bokeh).
+#[derive(Clone, Debug)]
+pub enum Aperture {
+    Circular,
+    /// Regular polygon formed by `blades` straight diaphragm blades, rotated by `rotation` degrees.
+    Polygon { blades: u32, rotation: f64 },
+    /// Grayscale image stretched over the aperture, values give the transmission.
+    Mask(Arc<ApertureMask>),
+}
+
+#[derive(Debug)]
+pub struct ApertureMask {
+    image: netpbm::Image,
+}
+
+impl ApertureMask {
+    /// Loads mask from PGM or PPM file (color images are converted to grayscale).
+    pub fn load<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        ApertureMask::new(netpbm::read(path)?)
+    }
+
+    pub fn new(image: netpbm::Image) -> std::io::Result<Self> {
+        let transparent = (0..image.height).any(|y| (0..image.width).any(|x| image.gray(x, y) > 0.0));
+        if !transparent {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "aperture mask is completely opaque"));
+        }
+        Ok(ApertureMask { image })
+    }
+
+    /// Returns description of the transmission as a grayscale image.
+    pub fn describe(&self) -> Node {
+        let (width, height) = (self.image.width, self.image.height);
+        let data = (0..height).flat_map(|y| (0..width).map(move |x| (x, y))).map(|(x, y)| self.image.gray(x, y)).collect();
+        Node::new("mask").with("width", width as f64).with("height", height as f64).with("data", Value::Numbers(data))
+    }
+
+    /// Returns transmission at point (x, y) in [-1, 1]², with y pointing up.
+    fn transmission(&self, x: f64, y: f64) -> f64 {
+        let column = (((x + 1.0) / 2.0 * self.image.width as f64) as usize).min(self.image.width - 1);
+        let row = (((1.0 - y) / 2.0 * self.image.height as f64) as usize).min(self.image.height - 1);
+        self.image.gray(column, row)
+    }
+}
+
+impl Aperture {
+    /// Returns random point (z = 0) on the aperture within the unit disk (or square for masks),
+    /// `None` if no transparent point of a mask was found.
+    pub fn sample(&self, rng: &mut dyn RngCore) -> Option<Vec3> {
+        Some(match self {
+            Aperture::Circular => Vec3::random_in_unit_disk(rng),
+            Aperture::Polygon { blades, rotation } => {
+                let blades = (*blades).max(3);
+                // Pick one of the triangles between center and edges, then sample uniformly within it
+                let edge = rng.gen_range(0..blades) as f64;
+                let angle0 = rotation.to_radians() + 2.0 * PI * edge / blades as f64;
+                let angle1 = angle0 + 2.0 * PI / blades as f64;
+                let (mut a, mut b): (f64, f64) = (rng.gen(), rng.gen());
+                if a + b > 1.0 {
+                    a = 1.0 - a;
+                    b = 1.0 - b;
+                }
+                Vec3::new(
+                    a * angle0.cos() + b * angle1.cos(),
+                    a * angle0.sin() + b * angle1.sin(),
+                    0.0,
+                )
+            }
+            Aperture::Mask(mask) => {
+                for _ in 0..MAX_TRIES {
+                    let (x, y) = (rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0));
+                    if rng.gen::<f64>() < mask.transmission(x, y) {
+                        return Some(Vec3::new(x, y, 0.0));
+                    }
+                }
+                return None;
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::ThinLens;
+
+    /// Returns mask of `width` x 1 pixels that is open in the leftmost `open` columns.
+    fn left_mask(width: usize, open: usize) -> std::io::Result<Aperture> {
+        let data = (0..width).map(|x| if x < open { 1.0 } else { 0.0 }).collect();
+        Ok(Aperture::Mask(Arc::new(ApertureMask::new(netpbm::Image { width, height: 1, channels: 1, data })?)))
+    }
+
+    #[test]
+    fn test_polygon_and_mask() {
+        let mut rng = rand::thread_rng();
+        // Points of a hexagon lie inside all of its edges
+        let hexagon = Aperture::Polygon { blades: 6, rotation: 0.0 };
+        for _ in 0..1000 {
+            let p = hexagon.sample(&mut rng).unwrap();
+            for k in 0..6 {
+                let angle = (k as f64 + 0.5) * PI / 3.0;
+                assert!(p.x * angle.cos() + p.y * angle.sin() <= (PI / 6.0).cos() + 1e-12);
+            }
+        }
+
+        let half = left_mask(2, 1).unwrap();
+        assert!((0..1000).all(|_| half.sample(&mut rng).unwrap().x < 0.0));
+        assert!(left_mask(2, 0).is_err());
+    }
+
+    #[test]
+    fn test_lens_offset() {
+        let mut rng = rand::thread_rng();
+        let (u, v) = (Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        // Unit lens radius, clipped by a circle shifted towards the upper right film corner
+        let mut lens = ThinLens { cat_eye: 1.0, ..ThinLens::new(2.0, 1.0) };
+        let shift = Vec3::new(1.0, 1.0, 0.0) / 2f64.sqrt();
+        for _ in 0..1000 {
+            let p = lens.offset(u, v, 1.0, 1.0, &mut rng).unwrap();
+            assert!(p.length() <= 1.0 + 1e-12 && (p - shift).length() <= 1.0 + 1e-12);
+        }
+        // Not clipped in the center
+        assert!((0..1000).any(|_| lens.offset(u, v, 0.5, 0.5, &mut rng).unwrap().x < -0.5));
+
+        // Out of range values are clamped instead of clipping the whole aperture
+        lens.cat_eye = 5.0;
+        assert!(lens.offset(u, v, 1.0, 1.0, &mut rng).is_some());
+
+        // Open part of the mask lies outside the clipping circle, which gives up instead of hanging
+        lens.cat_eye = 1.0;
+        lens.shape = left_mask(10, 1).unwrap();
+        assert!(lens.offset(u, v, 1.0, 1.0, &mut rng).is_none());
+        assert!(lens.offset(u, v, 0.0, 0.5, &mut rng).is_some());
+
+        let anamorphic = ThinLens { anamorphic_squeeze: 2.0, ..ThinLens::new(2.0, 1.0) };
+        let offsets: Vec<Vec3> = (0..1000).map(|_| anamorphic.offset(u, v, 0.5, 0.5, &mut rng).unwrap()).collect();
+        assert!(offsets.iter().all(|p| p.x.abs() <= 0.5));
+        assert!(offsets.iter().any(|p| p.y.abs() > 0.9));
+    }
+}
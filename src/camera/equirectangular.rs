@@ -0,0 +1,79 @@
+use std::f64::consts::PI;
+
+use rand::RngCore;
+
+use crate::{Ray, Vec3};
+use crate::camera::{Camera, Orientation, Shutter, ThinLens};
+use crate::scene::Node;
+
+/// 360° panorama mapping longitude to s and latitude to t, use an aspect ratio of 2:1.
+pub struct EquirectangularCamera {
+    orientation: Orientation,
+    lens: ThinLens,
+    shutter: Shutter,
+}
+
+impl EquirectangularCamera {
+    pub fn new(orientation: Orientation, lens: ThinLens, shutter: Shutter) -> Self {
+        EquirectangularCamera { orientation, lens, shutter }
+    }
+
+    /// Returns unit world direction of film position (s, t).
+    fn direction(&self, s: f64, t: f64) -> Vec3 {
+        let longitude = (s - 0.5) * 2.0 * PI;
+        let latitude = (t - 0.5) * PI;
+        self.orientation.to_world(spherical_direction(longitude, latitude))
+    }
+}
+
+/// Returns unit direction in camera coordinates, longitude 0 looks along -z.
+pub(crate) fn spherical_direction(longitude: f64, latitude: f64) -> Vec3 {
+    Vec3::new(
+        latitude.cos() * longitude.sin(),
+        latitude.sin(),
+        -latitude.cos() * longitude.cos(),
+    )
+}
+
+impl Camera for EquirectangularCamera {
+    fn get_ray(&self, s: f64, t: f64, rng: &mut dyn RngCore) -> Option<Ray> {
+        let time = self.shutter.sample(rng);
+        self.lens.spherical_ray(self.orientation.origin, self.direction(s, t), time, rng)
+    }
+
+    fn get_ray_differential(&self, s: f64, t: f64, ds: f64, dt: f64, rng: &mut dyn RngCore) -> Option<Ray> {
+        let offsets = (self.direction(s + ds, t), self.direction(s, t + dt));
+        let time = self.shutter.sample(rng);
+        self.lens.spherical_ray_differential(self.orientation.origin, self.direction(s, t), Some(offsets), time, rng)
+    }
+
+    fn describe(&self) -> Option<Node> {
+        Some(self.orientation.describe("equirectangular")
+            .with("lens", self.lens.describe())
+            .with("shutter", self.shutter.describe()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_longitude_and_latitude() {
+        // Looking along -x, so w points along +x
+        let orientation = Orientation::new(Vec3::new(0.0, 1.0, 0.0), Vec3::new(-1.0, 1.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        let camera = EquirectangularCamera::new(orientation, ThinLens::pinhole(), Shutter { time0: 0.2, time1: 0.7 });
+        let mut rng = rand::thread_rng();
+        let direction = |s: f64, t: f64| camera.get_ray(s, t, &mut rand::thread_rng()).unwrap().direction.unit_vector();
+        assert!((direction(0.5, 0.5) - -orientation.w).length() < 1e-12);
+        // Left and right edge at longitude -pi and pi meet behind the camera
+        assert!((direction(0.0, 0.5) - orientation.w).length() < 1e-12);
+        assert!((direction(1.0, 0.5) - orientation.w).length() < 1e-12);
+        assert!((direction(0.75, 0.5) - orientation.u).length() < 1e-12);
+        assert!((direction(0.5, 1.0) - orientation.v).length() < 1e-12);
+        for _ in 0..100 {
+            let ray = camera.get_ray(0.3, 0.6, &mut rng).unwrap();
+            assert!(ray.origin == orientation.origin && (0.2..=0.7).contains(&ray.time));
+        }
+    }
+}
@@ -0,0 +1,132 @@
+use rand::RngCore;
+
+use crate::{Ray, Vec3};
+use crate::camera::{Camera, Orientation, Shutter, ThinLens};
+use crate::scene::Node;
+use crate::utils::degrees_to_radians;
+
+/// Relation between angle θ to the optical axis and distance r from the image center.
+#[derive(Copy, Clone, Debug)]
+pub enum FisheyeMapping {
+    /// r = f * θ
+    Equidistant,
+    /// r = 2f * sin(θ / 2), preserves solid angles
+    Equisolid,
+}
+
+/// Fisheye lens with a circular image inscribed into the film height, pixels outside stay black.
+pub struct FisheyeCamera {
+    orientation: Orientation,
+    fov: f64,
+    max_theta: f64,
+    aspect_ratio: f64,
+    mapping: FisheyeMapping,
+    lens: ThinLens,
+    shutter: Shutter,
+}
+
+impl FisheyeCamera {
+    /// Creates camera with field of view `fov` (in degrees) across the image circle diameter, up to 360°.
+    pub fn new(
+        orientation: Orientation,
+        fov: f64,
+        aspect_ratio: f64,
+        mapping: FisheyeMapping,
+        lens: ThinLens,
+        shutter: Shutter,
+    ) -> Self {
+        let max_theta = degrees_to_radians(fov) / 2.0;
+        FisheyeCamera { orientation, fov, max_theta, aspect_ratio, mapping, lens, shutter }
+    }
+
+    /// Returns angle to the optical axis for distance `r` from the image center (1 at the image circle).
+    fn theta(&self, r: f64) -> f64 {
+        match self.mapping {
+            FisheyeMapping::Equidistant => r * self.max_theta,
+            FisheyeMapping::Equisolid => 2.0 * (r * (self.max_theta / 2.0).sin()).asin(),
+        }
+    }
+
+    /// Returns unit world direction of film position (s, t), `None` outside the image circle.
+    fn direction(&self, s: f64, t: f64) -> Option<Vec3> {
+        let x = (2.0 * s - 1.0) * self.aspect_ratio;
+        let y = 2.0 * t - 1.0;
+        let r = (x * x + y * y).sqrt();
+        if r > 1.0 {
+            return None;
+        }
+        let theta = self.theta(r);
+        let phi = y.atan2(x);
+        let local = Vec3::new(theta.sin() * phi.cos(), theta.sin() * phi.sin(), -theta.cos());
+        Some(self.orientation.to_world(local))
+    }
+}
+
+impl Camera for FisheyeCamera {
+    fn get_ray(&self, s: f64, t: f64, rng: &mut dyn RngCore) -> Option<Ray> {
+        let direction = self.direction(s, t)?;
+        let time = self.shutter.sample(rng);
+        self.lens.spherical_ray(self.orientation.origin, direction, time, rng)
+    }
+
+    fn get_ray_differential(&self, s: f64, t: f64, ds: f64, dt: f64, rng: &mut dyn RngCore) -> Option<Ray> {
+        let direction = self.direction(s, t)?;
+        // No differentials at the rim of the image circle
+        let offsets = self.direction(s + ds, t).zip(self.direction(s, t + dt));
+        let time = self.shutter.sample(rng);
+        self.lens.spherical_ray_differential(self.orientation.origin, direction, offsets, time, rng)
+    }
+
+    fn describe(&self) -> Option<Node> {
+        let mapping = match self.mapping {
+            FisheyeMapping::Equidistant => "equidistant",
+            FisheyeMapping::Equisolid => "equisolid",
+        };
+        Some(self.orientation.describe("fisheye")
+            .with("fov", self.fov)
+            .with("aspect_ratio", self.aspect_ratio)
+            .with("mapping", mapping)
+            .with("lens", self.lens.describe())
+            .with("shutter", self.shutter.describe()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use super::*;
+
+    fn camera(mapping: FisheyeMapping) -> FisheyeCamera {
+        let orientation = Orientation::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0), Vec3::new(0.0, 1.0, 0.0));
+        FisheyeCamera::new(orientation, 180.0, 1.5, mapping, ThinLens::pinhole(), Shutter { time0: 0.2, time1: 0.7 })
+    }
+
+    /// Returns angle to the optical axis of the ray at distance `r` right of the image center.
+    fn theta(camera: &FisheyeCamera, r: f64) -> f64 {
+        let ray = camera.get_ray(0.5 + r / (2.0 * 1.5), 0.5, &mut rand::thread_rng()).unwrap();
+        let direction = ray.direction.unit_vector();
+        assert!(direction.y.abs() < 1e-12 && direction.x >= 0.0);
+        (-direction.z).acos()
+    }
+
+    #[test]
+    fn test_mappings() {
+        let equidistant = camera(FisheyeMapping::Equidistant);
+        let equisolid = camera(FisheyeMapping::Equisolid);
+        for r in [0.0, 0.25, 0.5, 1.0] {
+            assert!((theta(&equidistant, r) - r * PI / 2.0).abs() < 1e-9);
+            assert!((theta(&equisolid, r) - 2.0 * (r * (PI / 4.0).sin()).asin()).abs() < 1e-9);
+        }
+
+        // Black outside the image circle, which touches the top and bottom of the film
+        let mut rng = rand::thread_rng();
+        assert!(equidistant.get_ray(0.5, 1.0, &mut rng).is_some());
+        assert!(equidistant.get_ray(0.5, 1.01, &mut rng).is_none());
+        assert!(equidistant.get_ray(1.0, 0.5, &mut rng).is_none());
+        assert!(equisolid.get_ray(0.95, 0.95, &mut rng).is_none());
+        for _ in 0..100 {
+            assert!((0.2..=0.7).contains(&equisolid.get_ray(0.4, 0.6, &mut rng).unwrap().time));
+        }
+    }
+}
@@ -0,0 +1,225 @@
+pub use aperture::*;
+pub use equirectangular::*;
+pub use fisheye::*;
+pub use omni_stereo::*;
+pub use orthographic::*;
+pub use perspective::*;
+pub use realistic::*;
+
+use rand::{Rng, RngCore};
+
+use crate::{Differentials, Ray, Vec3};
+use crate::scene::{Node, Value};
+
+pub trait Camera {
+    /// Returns ray through film position (s, t) with both coordinates in [0, 1], starting in the
+    /// lower left corner. Returns `None` if no light reaches the film at this position.
+    fn get_ray(&self, s: f64, t: f64, rng: &mut dyn RngCore) -> Option<Ray>;
+
+    /// Like [Self::get_ray], with differentials towards the film positions offset by `ds` and `dt`
+    /// (usually one pixel). By default the offset rays are generated independently, which is exact
+    /// for cameras without random lens samples. Others should override this to reuse the sample.
+    fn get_ray_differential(&self, s: f64, t: f64, ds: f64, dt: f64, rng: &mut dyn RngCore) -> Option<Ray> {
+        let mut ray = self.get_ray(s, t, rng)?;
+        if let (Some(rx), Some(ry)) = (self.get_ray(s + ds, t, rng), self.get_ray(s, t + dt, rng)) {
+            ray.differentials = Some(Differentials {
+                rx_origin: rx.origin,
+                rx_direction: rx.direction,
+                ry_origin: ry.origin,
+                ry_direction: ry.direction,
+            });
+        }
+        Some(ray)
+    }
+
+    /// Returns description for saving scenes (see [crate::configs::save_scene]), `None` if the
+    /// camera can't be saved.
+    fn describe(&self) -> Option<Node> {
+        None
+    }
+}
+
+/// Position and orthonormal basis of a camera, `w` points away from the viewing direction.
+#[derive(Copy, Clone, Debug)]
+pub struct Orientation {
+    pub origin: Vec3,
+    pub u: Vec3,
+    pub v: Vec3,
+    pub w: Vec3,
+}
+
+impl Orientation {
+    pub fn new(lookfrom: Vec3, lookat: Vec3, vup: Vec3) -> Self {
+        let w = (lookfrom - lookat).unit_vector();
+        let u = vup.cross(w).unit_vector();
+        let v = w.cross(u);
+        Orientation { origin: lookfrom, u, v, w }
+    }
+
+    /// Transforms direction from camera coordinates (x right, y up, z backwards) to world coordinates.
+    pub fn to_world(&self, direction: Vec3) -> Vec3 {
+        direction.x * self.u + direction.y * self.v + direction.z * self.w
+    }
+
+    /// Returns node of a camera of `kind` with the position and basis as fields.
+    fn describe(&self, kind: &str) -> Node {
+        Node::new(kind).with("origin", self.origin).with("u", self.u).with("v", self.v).with("w", self.w)
+    }
+}
+
+/// Thin lens model for depth of field, an aperture of zero yields a pinhole camera.
+#[derive(Clone, Debug)]
+pub struct ThinLens {
+    pub aperture: f64,
+    pub focus_dist: f64,
+    pub shape: Aperture,
+    /// Optical vignetting in [0, 1] (clamped), clips the aperture off-axis so that bokeh in the
+    /// image corners gets the shape of a cat's eye (0 disables it).
+    pub cat_eye: f64,
+    /// Horizontal squeeze factor of anamorphic lenses, yields vertically stretched bokeh (1 disables it).
+    pub anamorphic_squeeze: f64,
+}
+
+impl ThinLens {
+    /// Creates lens with circular aperture of diameter `aperture` focused at distance `focus_dist`.
+    pub fn new(aperture: f64, focus_dist: f64) -> Self {
+        ThinLens { aperture, focus_dist, shape: Aperture::Circular, cat_eye: 0.0, anamorphic_squeeze: 1.0 }
+    }
+
+    pub fn pinhole() -> Self {
+        ThinLens::new(0.0, 1.0)
+    }
+
+    /// Returns description for [Camera::describe].
+    fn describe(&self) -> Node {
+        let shape = match self.shape {
+            Aperture::Circular => Node::new("circular"),
+            Aperture::Polygon { blades, rotation } => Node::new("polygon").with("blades", blades as f64).with("rotation", rotation),
+            Aperture::Mask(ref mask) => mask.describe(),
+        };
+        Node::new("thin_lens")
+            .with("aperture", self.aperture)
+            .with("focus_dist", self.focus_dist)
+            .with("shape", shape)
+            .with("cat_eye", self.cat_eye)
+            .with("anamorphic_squeeze", self.anamorphic_squeeze)
+    }
+
+    /// Returns random offset from the lens center in the plane spanned by `u` and `v` for film
+    /// position (s, t), `None` if no light passes the clipped aperture there.
+    fn offset(&self, u: Vec3, v: Vec3, s: f64, t: f64, rng: &mut dyn RngCore) -> Option<Vec3> {
+        if self.aperture == 0.0 {
+            return Some(Vec3::new(0.0, 0.0, 0.0));
+        }
+        // Shift of the clipping circle, reaches `cat_eye` lens radii in the film corners
+        let cat_eye = self.cat_eye.clamp(0.0, 1.0);
+        let shift = Vec3::new(2.0 * s - 1.0, 2.0 * t - 1.0, 0.0) * (cat_eye / 2f64.sqrt());
+        let rd = (0..MAX_TRIES)
+            .filter_map(|_| self.shape.sample(rng))
+            .find(|&p| cat_eye == 0.0 || (p - shift).length_squared() <= 1.0)?;
+        let rd = (self.aperture / 2.0) * Vec3::new(rd.x / self.anamorphic_squeeze, rd.y, 0.0);
+        Some(u * rd.x + v * rd.y)
+    }
+
+    /// Returns ray for panoramic projections, where the points in focus lie on a sphere around
+    /// `origin` and the lens is perpendicular to the (unit) `direction`. `None` if the lens sample
+    /// is blocked, see [Self::offset].
+    fn spherical_ray(&self, origin: Vec3, direction: Vec3, time: f64, rng: &mut dyn RngCore) -> Option<Ray> {
+        if self.aperture == 0.0 {
+            return Some(Ray { origin, direction, time, wavelength: None, differentials: None });
+        }
+        let helper = if direction.x.abs() > 0.9 { Vec3::new(0.0, 1.0, 0.0) } else { Vec3::new(1.0, 0.0, 0.0) };
+        let u = direction.cross(helper).unit_vector();
+        let v = direction.cross(u);
+        let focus = origin + self.focus_dist * direction;
+        // Film position is irrelevant without a planar film, no off-axis clipping
+        let lens_origin = origin + self.offset(u, v, 0.5, 0.5, rng)?;
+        Some(Ray { origin: lens_origin, direction: focus - lens_origin, time, wavelength: None, differentials: None })
+    }
+
+    /// Like [Self::spherical_ray], with differentials towards the unit directions `offsets` (if
+    /// known) from the same lens point.
+    fn spherical_ray_differential(&self, origin: Vec3, direction: Vec3, offsets: Option<(Vec3, Vec3)>, time: f64, rng: &mut dyn RngCore) -> Option<Ray> {
+        let mut ray = self.spherical_ray(origin, direction, time, rng)?;
+        if let Some((dx, dy)) = offsets {
+            // Offset rays pass through the same lens point, towards their points on the focus sphere
+            let towards = |d: Vec3| origin + self.focus_dist * d - ray.origin;
+            ray.differentials = Some(Differentials {
+                rx_origin: ray.origin,
+                rx_direction: towards(dx),
+                ry_origin: ray.origin,
+                ry_direction: towards(dy),
+            });
+        }
+        Some(ray)
+    }
+}
+
+/// Time interval during which the shutter is open.
+#[derive(Copy, Clone, Debug)]
+pub struct Shutter {
+    pub time0: f64,
+    pub time1: f64,
+}
+
+impl Shutter {
+    pub fn instant(time: f64) -> Self {
+        Shutter { time0: time, time1: time }
+    }
+
+    fn describe(&self) -> Value {
+        Value::Numbers(vec![self.time0, self.time1])
+    }
+
+    /// Returns random time while shutter is open.
+    pub fn sample(&self, rng: &mut dyn RngCore) -> f64 {
+        if self.time0 == self.time1 {
+            self.time0
+        } else {
+            rng.gen_range(self.time0..self.time1)
+        }
+    }
+}
+
+mod aperture;
+mod perspective;
+mod orthographic;
+mod equirectangular;
+mod fisheye;
+mod omni_stereo;
+mod realistic;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns distance between the ray through (0.4, 0.6) and its x offset ray of `ds`, measured
+    /// by their origins and unit directions.
+    fn spread(camera: &dyn Camera, ds: f64) -> f64 {
+        let mut rng = rand::thread_rng();
+        let ray = std::iter::repeat_with(|| camera.get_ray_differential(0.4, 0.6, ds, ds, &mut rng))
+            .flatten()
+            .find(|ray| ray.differentials.is_some())
+            .unwrap();
+        let d = ray.differentials.unwrap();
+        (d.rx_origin - ray.origin).length() + (d.rx_direction.unit_vector() - ray.direction.unit_vector()).length()
+    }
+
+    #[test]
+    fn test_differentials_share_lens_sample() {
+        let orientation = Orientation::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0), Vec3::new(0.0, 1.0, 0.0));
+        let shutter = Shutter { time0: 0.2, time1: 0.7 };
+        let elements = parse_lens_data(DOUBLE_GAUSS_50MM).unwrap();
+        let cameras: Vec<Box<dyn Camera>> = vec![
+            Box::new(FisheyeCamera::new(orientation, 180.0, 1.5, FisheyeMapping::Equidistant, ThinLens::new(0.5, 2.0), shutter)),
+            Box::new(EquirectangularCamera::new(orientation, ThinLens::new(0.5, 2.0), shutter)),
+            Box::new(RealisticCamera::new(orientation, &elements, 17.1, 2.0, 35.0, 1.5, shutter).unwrap()),
+        ];
+        // With independent lens samples the offset rays would stay an aperture apart
+        for camera in cameras.iter() {
+            let coarse = spread(camera.as_ref(), 1e-2);
+            let fine = spread(camera.as_ref(), 1e-4);
+            assert!(fine < 0.05 * coarse, "{} vs {}", fine, coarse);
+        }
+    }
+}
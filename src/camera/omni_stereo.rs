@@ -0,0 +1,102 @@
+use std::f64::consts::PI;
+
+use rand::RngCore;
+
+use crate::{Ray, Vec3};
+use crate::camera::{Camera, Orientation, Shutter};
+use crate::camera::equirectangular::spherical_direction;
+use crate::scene::Node;
+
+/// Which eye(s) an omnidirectional stereo camera renders.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum StereoLayout {
+    Left,
+    Right,
+    /// Left eye in the upper, right eye in the lower half, use an aspect ratio of 1:1.
+    OverUnder,
+}
+
+/// Omnidirectional stereo (ODS) panorama for VR. Rays start on a circle with the interpupillary
+/// distance as diameter, tangential to the viewing direction.
+pub struct OmniStereoCamera {
+    orientation: Orientation,
+    interpupillary_distance: f64,
+    layout: StereoLayout,
+    shutter: Shutter,
+}
+
+impl OmniStereoCamera {
+    pub fn new(orientation: Orientation, interpupillary_distance: f64, layout: StereoLayout, shutter: Shutter) -> Self {
+        OmniStereoCamera { orientation, interpupillary_distance, layout, shutter }
+    }
+}
+
+impl Camera for OmniStereoCamera {
+    fn get_ray(&self, s: f64, t: f64, rng: &mut dyn RngCore) -> Option<Ray> {
+        let (eye, t) = match self.layout {
+            StereoLayout::Left => (-1.0, t),
+            StereoLayout::Right => (1.0, t),
+            StereoLayout::OverUnder if t >= 0.5 => (-1.0, 2.0 * t - 1.0),
+            StereoLayout::OverUnder => (1.0, 2.0 * t),
+        };
+        let longitude = (s - 0.5) * 2.0 * PI;
+        let latitude = (t - 0.5) * PI;
+        let direction = spherical_direction(longitude, latitude);
+        // Perpendicular to horizontal viewing direction, pointing to the right
+        let offset = eye * self.interpupillary_distance / 2.0 * Vec3::new(longitude.cos(), 0.0, longitude.sin());
+        Some(Ray {
+            origin: self.orientation.origin + self.orientation.to_world(offset),
+            direction: self.orientation.to_world(direction),
+            time: self.shutter.sample(rng),
+            wavelength: None,
+            differentials: None,
+        })
+    }
+
+    fn describe(&self) -> Option<Node> {
+        let layout = match self.layout {
+            StereoLayout::Left => "left",
+            StereoLayout::Right => "right",
+            StereoLayout::OverUnder => "over_under",
+        };
+        Some(self.orientation.describe("omni_stereo")
+            .with("interpupillary_distance", self.interpupillary_distance)
+            .with("layout", layout)
+            .with("shutter", self.shutter.describe()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn camera(layout: StereoLayout) -> OmniStereoCamera {
+        let orientation = Orientation::new(Vec3::new(1.0, 2.0, 3.0), Vec3::new(1.0, 2.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        OmniStereoCamera::new(orientation, 0.064, layout, Shutter { time0: 0.2, time1: 0.7 })
+    }
+
+    #[test]
+    fn test_eyes() {
+        let mut rng = rand::thread_rng();
+        let center = Vec3::new(1.0, 2.0, 3.0);
+        let (left, right) = (camera(StereoLayout::Left), camera(StereoLayout::Right));
+        for (s, t) in [(0.5, 0.5), (0.1, 0.3), (0.8, 0.9)] {
+            let (l, r) = (left.get_ray(s, t, &mut rng).unwrap(), right.get_ray(s, t, &mut rng).unwrap());
+            // Eyes on opposite sides of the circle, looking along its tangent in the same direction
+            assert!(((l.origin - r.origin).length() - 0.064).abs() < 1e-12);
+            assert!(((l.origin - center).length() - 0.032).abs() < 1e-12 && (l.origin + r.origin - 2.0 * center).length() < 1e-12);
+            assert!(l.direction == r.direction && l.direction.dot(l.origin - center).abs() < 1e-12);
+            assert!((0.2..=0.7).contains(&l.time) && (0.2..=0.7).contains(&r.time));
+        }
+        // Left eye looking ahead is offset to the left
+        let l = left.get_ray(0.5, 0.5, &mut rng).unwrap();
+        assert!((l.origin - Vec3::new(0.968, 2.0, 3.0)).length() < 1e-12);
+
+        // Upper half shows the left eye, lower half the right eye
+        let over_under = camera(StereoLayout::OverUnder);
+        for (t, eye, eye_t) in [(0.75, &left, 0.5), (0.9, &left, 0.8), (0.25, &right, 0.5), (0.1, &right, 0.2)] {
+            let (ray, expected) = (over_under.get_ray(0.3, t, &mut rng).unwrap(), eye.get_ray(0.3, eye_t, &mut rng).unwrap());
+            assert!((ray.origin - expected.origin).length() < 1e-12 && (ray.direction - expected.direction).length() < 1e-12);
+        }
+    }
+}
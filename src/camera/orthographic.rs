@@ -0,0 +1,82 @@
+use rand::RngCore;
+
+use crate::{Differentials, Ray};
+use crate::camera::{Camera, Orientation, Shutter, ThinLens};
+use crate::scene::Node;
+
+/// Parallel projection, e.g. for technical views. Objects keep their size regardless of distance.
+pub struct OrthographicCamera {
+    orientation: Orientation,
+    viewport_width: f64,
+    viewport_height: f64,
+    aspect_ratio: f64,
+    lens: ThinLens,
+    shutter: Shutter,
+}
+
+impl OrthographicCamera {
+    /// Creates camera whose viewport covers `viewport_height` world units vertically.
+    pub fn new(orientation: Orientation, viewport_height: f64, aspect_ratio: f64, lens: ThinLens, shutter: Shutter) -> Self {
+        OrthographicCamera {
+            orientation,
+            viewport_width: aspect_ratio * viewport_height,
+            viewport_height,
+            aspect_ratio,
+            lens,
+            shutter,
+        }
+    }
+}
+
+impl Camera for OrthographicCamera {
+    fn get_ray(&self, s: f64, t: f64, rng: &mut dyn RngCore) -> Option<Ray> {
+        let Orientation { origin, u, v, w } = self.orientation;
+        // Every film position has its own lens centered in front of it
+        let center = origin + (s - 0.5) * self.viewport_width * u + (t - 0.5) * self.viewport_height * v;
+        let focus = center - self.lens.focus_dist * w;
+        let origin = center + self.lens.offset(u, v, s, t, rng)?;
+        Some(Ray { origin, direction: focus - origin, time: self.shutter.sample(rng), wavelength: None, differentials: None })
+    }
+
+    fn get_ray_differential(&self, s: f64, t: f64, ds: f64, dt: f64, rng: &mut dyn RngCore) -> Option<Ray> {
+        let mut ray = self.get_ray(s, t, rng)?;
+        // Lens and focus point shift together, with the same lens sample
+        let Orientation { u, v, .. } = self.orientation;
+        ray.differentials = Some(Differentials {
+            rx_origin: ray.origin + ds * self.viewport_width * u,
+            rx_direction: ray.direction,
+            ry_origin: ray.origin + dt * self.viewport_height * v,
+            ry_direction: ray.direction,
+        });
+        Some(ray)
+    }
+
+    fn describe(&self) -> Option<Node> {
+        Some(self.orientation.describe("orthographic")
+            .with("viewport_height", self.viewport_height)
+            .with("aspect_ratio", self.aspect_ratio)
+            .with("lens", self.lens.describe())
+            .with("shutter", self.shutter.describe()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Vec3;
+
+    #[test]
+    fn test_parallel_rays() {
+        let orientation = Orientation::new(Vec3::new(1.0, 2.0, 3.0), Vec3::new(1.0, 2.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        let camera = OrthographicCamera::new(orientation, 2.0, 2.0, ThinLens::pinhole(), Shutter { time0: 0.2, time1: 0.7 });
+        let mut rng = rand::thread_rng();
+        for (s, t) in [(0.5, 0.5), (0.0, 0.0), (1.0, 0.25), (0.75, 1.0)] {
+            let ray = camera.get_ray(s, t, &mut rng).unwrap();
+            // Viewport of 4 by 2 units around the camera position
+            let origin = Vec3::new(1.0 + 4.0 * (s - 0.5), 2.0 + 2.0 * (t - 0.5), 3.0);
+            assert!((ray.origin - origin).length() < 1e-12, "{:?}", ray.origin);
+            assert!((ray.direction.unit_vector() - Vec3::new(0.0, 0.0, -1.0)).length() < 1e-12);
+            assert!((0.2..=0.7).contains(&ray.time));
+        }
+    }
+}
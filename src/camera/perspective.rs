@@ -0,0 +1,84 @@
+use rand::RngCore;
+
+use crate::{Differentials, Ray, Vec3};
+use crate::camera::{Camera, Orientation, Shutter, ThinLens};
+use crate::scene::Node;
+use crate::utils::degrees_to_radians;
+
+pub struct PerspectiveCamera {
+    orientation: Orientation,
+    vfov: f64,
+    aspect_ratio: f64,
+    lower_left_corner: Vec3,
+    horizontal: Vec3,
+    vertical: Vec3,
+    lens: ThinLens,
+    shutter: Shutter,
+}
+
+impl PerspectiveCamera {
+    pub fn new(orientation: Orientation, vfov: f64, aspect_ratio: f64, lens: ThinLens, shutter: Shutter) -> Self {
+        let theta = degrees_to_radians(vfov);
+        let h = (theta / 2.0).tan();
+        let viewport_height = 2.0 * h;
+        let viewport_width = aspect_ratio * viewport_height;
+
+        // Viewport lies in focus plane
+        let focus_dist = lens.focus_dist;
+        let horizontal = focus_dist * viewport_width * orientation.u;
+        let vertical = focus_dist * viewport_height * orientation.v;
+        let lower_left_corner = orientation.origin - horizontal / 2.0 - vertical / 2.0 - focus_dist * orientation.w;
+
+        PerspectiveCamera { orientation, vfov, aspect_ratio, lower_left_corner, horizontal, vertical, lens, shutter }
+    }
+}
+
+impl Camera for PerspectiveCamera {
+    fn get_ray(&self, s: f64, t: f64, rng: &mut dyn RngCore) -> Option<Ray> {
+        let focus = self.lower_left_corner + s * self.horizontal + t * self.vertical;
+        let Orientation { origin, u, v, .. } = self.orientation;
+        let origin = origin + self.lens.offset(u, v, s, t, rng)?;
+        Some(Ray { origin, direction: focus - origin, time: self.shutter.sample(rng), wavelength: None, differentials: None })
+    }
+
+    fn get_ray_differential(&self, s: f64, t: f64, ds: f64, dt: f64, rng: &mut dyn RngCore) -> Option<Ray> {
+        let mut ray = self.get_ray(s, t, rng)?;
+        // Offset rays pass through the same lens point, towards shifted points in the focus plane
+        let focus = ray.origin + ray.direction;
+        ray.differentials = Some(Differentials {
+            rx_origin: ray.origin,
+            rx_direction: focus + ds * self.horizontal - ray.origin,
+            ry_origin: ray.origin,
+            ry_direction: focus + dt * self.vertical - ray.origin,
+        });
+        Some(ray)
+    }
+
+    fn describe(&self) -> Option<Node> {
+        Some(self.orientation.describe("perspective")
+            .with("vfov", self.vfov)
+            .with("aspect_ratio", self.aspect_ratio)
+            .with("lens", self.lens.describe())
+            .with("shutter", self.shutter.describe()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_field_of_view() {
+        let orientation = Orientation::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0), Vec3::new(0.0, 1.0, 0.0));
+        let camera = PerspectiveCamera::new(orientation, 90.0, 2.0, ThinLens::pinhole(), Shutter { time0: 0.2, time1: 0.7 });
+        let mut rng = rand::thread_rng();
+        let direction = |s: f64, t: f64| camera.get_ray(s, t, &mut rand::thread_rng()).unwrap().direction.unit_vector();
+        assert!((direction(0.5, 0.5) - Vec3::new(0.0, 0.0, -1.0)).length() < 1e-12);
+        // Top edge at 45 degrees, the side twice as far out on the film
+        assert!((direction(0.5, 1.0) - Vec3::new(0.0, 1.0, -1.0).unit_vector()).length() < 1e-12);
+        assert!((direction(0.0, 0.5) - Vec3::new(-2.0, 0.0, -1.0).unit_vector()).length() < 1e-12);
+        for _ in 0..100 {
+            assert!((0.2..=0.7).contains(&camera.get_ray(0.3, 0.6, &mut rng).unwrap().time));
+        }
+    }
+}
@@ -0,0 +1,495 @@
+use std::io::{Error, ErrorKind};
+
+use rand::{Rng, RngCore};
+
+use crate::{Differentials, Ray, Vec3};
+use crate::camera::{Camera, Orientation, Shutter};
+use crate::scene::{Node, Value};
+
+const MM: f64 = 0.001;
+const PUPIL_BINS: usize = 64;
+const PUPIL_SAMPLES: usize = 16 * 1024;
+
+/// Double Gauss lens with 50 mm focal length (US patent 2,673,491), one element per line.
+pub const DOUBLE_GAUSS_50MM: &str = "
+# radius  thickness  ior    aperture
+29.475    3.76       1.67   25.2
+84.83     0.12       1      25.2
+19.275    4.025      1.67   23
+40.77     3.275      1.699  23
+12.75     5.705      1      18
+0         4.5        0      17.1
+-14.495   1.18       1.603  17
+40.77     6.065      1.658  20
+-20.385   0.19       1      20
+437.065   3.22       1.717  20
+-39.73    5.0        1      20
+";
+
+/// Interface of a lens element as found in published lens prescriptions, lengths in millimeters.
+/// A curvature radius of zero marks the aperture stop, an IOR of zero means air.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct LensElement {
+    pub curvature_radius: f64,
+    pub thickness: f64,
+    pub ior: f64,
+    pub aperture_diameter: f64,
+}
+
+/// Parses lens prescription with one element per line (radius, thickness, IOR, aperture diameter),
+/// ordered from the scene side to the film side. Lines starting with `#` are ignored.
+pub fn parse_lens_data(data: &str) -> std::io::Result<Vec<LensElement>> {
+    let mut elements = Vec::new();
+    for (i, line) in data.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let values = line.split_whitespace()
+            .map(|v| v.parse::<f64>())
+            .collect::<Result<Vec<f64>, _>>()
+            .map_err(|e| Error::new(ErrorKind::InvalidData, format!("line {}: {}", i + 1, e)))?;
+        if values.len() != 4 {
+            return Err(Error::new(ErrorKind::InvalidData, format!("line {}: expected 4 values, found {}", i + 1, values.len())));
+        }
+        elements.push(LensElement {
+            curvature_radius: values[0],
+            thickness: values[1],
+            ior: values[2],
+            aperture_diameter: values[3],
+        });
+    }
+    Ok(elements)
+}
+
+/// Lens element in lens space (meters), radius is half the aperture diameter.
+#[derive(Copy, Clone, Debug)]
+struct Interface {
+    curvature_radius: f64,
+    thickness: f64,
+    ior: f64,
+    aperture_radius: f64,
+}
+
+#[derive(Copy, Clone, Debug)]
+struct Bounds {
+    min: (f64, f64),
+    max: (f64, f64),
+}
+
+impl Bounds {
+    fn area(&self) -> f64 {
+        (self.max.0 - self.min.0) * (self.max.1 - self.min.1)
+    }
+}
+
+/// Camera tracing rays through a system of spherical lens elements, which reproduces bokeh,
+/// vignetting and distortion of real lenses. Lens space has the film at z = 0 and the lens
+/// towards negative z, camera space has z pointing into the scene.
+pub struct RealisticCamera {
+    orientation: Orientation,
+    interfaces: Vec<Interface>,
+    film_width: f64,
+    film_height: f64,
+    /// Bounds of exit pupil for rings of increasing distance from film center
+    exit_pupil_bounds: Vec<Bounds>,
+    max_pupil_area: f64,
+    shutter: Shutter,
+    /// Arguments of [RealisticCamera::new] for saving scenes
+    elements: Vec<LensElement>,
+    aperture_diameter: f64,
+    focus_distance: f64,
+    film_diagonal: f64,
+    aspect_ratio: f64,
+}
+
+impl RealisticCamera {
+    /// Creates camera focused at `focus_distance` (scene units are meters). Aperture stop and film
+    /// diagonal are given in millimeters.
+    pub fn new(
+        orientation: Orientation,
+        elements: &[LensElement],
+        aperture_diameter: f64,
+        focus_distance: f64,
+        film_diagonal: f64,
+        aspect_ratio: f64,
+        shutter: Shutter,
+    ) -> std::io::Result<Self> {
+        if elements.is_empty() {
+            return Err(Error::new(ErrorKind::InvalidInput, "lens system has no elements"));
+        }
+        let interfaces = elements.iter().map(|e| {
+            let diameter = if e.curvature_radius == 0.0 { aperture_diameter.min(e.aperture_diameter) } else { e.aperture_diameter };
+            Interface {
+                curvature_radius: e.curvature_radius * MM,
+                thickness: e.thickness * MM,
+                ior: e.ior,
+                aperture_radius: diameter * MM / 2.0,
+            }
+        }).collect();
+        let diagonal = film_diagonal * MM;
+        let film_height = diagonal / (1.0 + aspect_ratio * aspect_ratio).sqrt();
+        let mut camera = RealisticCamera {
+            orientation,
+            interfaces,
+            film_width: aspect_ratio * film_height,
+            film_height,
+            exit_pupil_bounds: Vec::new(),
+            max_pupil_area: 0.0,
+            shutter,
+            elements: elements.to_vec(),
+            aperture_diameter,
+            focus_distance,
+            film_diagonal,
+            aspect_ratio,
+        };
+
+        let film_distance = camera.focus_thick_lens(focus_distance).ok_or_else(|| {
+            Error::new(ErrorKind::InvalidInput, format!("cannot focus lens system at distance {}", focus_distance))
+        })?;
+        camera.interfaces.last_mut().unwrap().thickness = film_distance;
+
+        camera.exit_pupil_bounds = (0..PUPIL_BINS).map(|i| {
+            let r0 = i as f64 / PUPIL_BINS as f64 * diagonal / 2.0;
+            let r1 = (i + 1) as f64 / PUPIL_BINS as f64 * diagonal / 2.0;
+            camera.bound_exit_pupil(r0, r1)
+        }).collect();
+        camera.max_pupil_area = camera.exit_pupil_bounds.iter().map(Bounds::area).fold(0.0, f64::max);
+        Ok(camera)
+    }
+
+    fn lens_rear_z(&self) -> f64 {
+        self.interfaces.last().unwrap().thickness
+    }
+
+    fn lens_front_z(&self) -> f64 {
+        self.interfaces.iter().map(|i| i.thickness).sum()
+    }
+
+    fn rear_element_radius(&self) -> f64 {
+        self.interfaces.last().unwrap().aperture_radius
+    }
+
+    /// Traces ray (in camera space) from the film through the lens system, `None` if it is blocked.
+    fn trace_from_film(&self, ray: &Ray) -> Option<Ray> {
+        let mut element_z = 0.0;
+        let mut origin = flip_z(ray.origin);
+        let mut direction = flip_z(ray.direction);
+        for (i, interface) in self.interfaces.iter().enumerate().rev() {
+            element_z -= interface.thickness;
+            let (t, normal) = if interface.curvature_radius == 0.0 {
+                // Refraction at previous element may point towards film in extreme situations
+                if direction.z >= 0.0 {
+                    return None;
+                }
+                ((element_z - origin.z) / direction.z, None)
+            } else {
+                let z_center = element_z + interface.curvature_radius;
+                let (t, n) = intersect_spherical_element(interface.curvature_radius, z_center, origin, direction)?;
+                (t, Some(n))
+            };
+            let hit = origin + t * direction;
+            if hit.x * hit.x + hit.y * hit.y > interface.aperture_radius * interface.aperture_radius {
+                return None;
+            }
+            origin = hit;
+            if let Some(normal) = normal {
+                let eta_i = if interface.ior != 0.0 { interface.ior } else { 1.0 };
+                let eta_t = if i > 0 && self.interfaces[i - 1].ior != 0.0 { self.interfaces[i - 1].ior } else { 1.0 };
+                direction = refract(-direction.unit_vector(), normal, eta_i / eta_t)?;
+            }
+        }
+        Some(Ray { origin: flip_z(origin), direction: flip_z(direction), time: ray.time, wavelength: None, differentials: None })
+    }
+
+    /// Traces ray (in camera space) from the scene through the lens system, `None` if it is blocked.
+    fn trace_from_scene(&self, ray: &Ray) -> Option<Ray> {
+        let mut element_z = -self.lens_front_z();
+        let mut origin = flip_z(ray.origin);
+        let mut direction = flip_z(ray.direction);
+        for (i, interface) in self.interfaces.iter().enumerate() {
+            let (t, normal) = if interface.curvature_radius == 0.0 {
+                ((element_z - origin.z) / direction.z, None)
+            } else {
+                let z_center = element_z + interface.curvature_radius;
+                let (t, n) = intersect_spherical_element(interface.curvature_radius, z_center, origin, direction)?;
+                (t, Some(n))
+            };
+            let hit = origin + t * direction;
+            if hit.x * hit.x + hit.y * hit.y > interface.aperture_radius * interface.aperture_radius {
+                return None;
+            }
+            origin = hit;
+            if let Some(normal) = normal {
+                let eta_i = if i == 0 || self.interfaces[i - 1].ior == 0.0 { 1.0 } else { self.interfaces[i - 1].ior };
+                let eta_t = if interface.ior != 0.0 { interface.ior } else { 1.0 };
+                direction = refract(-direction.unit_vector(), normal, eta_i / eta_t)?;
+            }
+            element_z += interface.thickness;
+        }
+        Some(Ray { origin: flip_z(origin), direction: flip_z(direction), time: ray.time, wavelength: None, differentials: None })
+    }
+
+    /// Returns z-positions of principal plane and focal point on film side and scene side
+    /// by tracing rays parallel to the optical axis.
+    fn thick_lens_approximation(&self) -> Option<([f64; 2], [f64; 2])> {
+        let x = MM * (self.film_width * self.film_width + self.film_height * self.film_height).sqrt();
+        let scene_ray = Ray { origin: Vec3::new(x, 0.0, self.lens_front_z() + 1.0), direction: Vec3::new(0.0, 0.0, -1.0), time: 0.0, wavelength: None, differentials: None };
+        let film_ray = self.trace_from_scene(&scene_ray)?;
+        let (pz0, fz0) = cardinal_points(&scene_ray, &film_ray);
+
+        let film_ray = Ray { origin: Vec3::new(x, 0.0, self.lens_rear_z() - 1.0), direction: Vec3::new(0.0, 0.0, 1.0), time: 0.0, wavelength: None, differentials: None };
+        let scene_ray = self.trace_from_film(&film_ray)?;
+        let (pz1, fz1) = cardinal_points(&film_ray, &scene_ray);
+        Some(([pz0, pz1], [fz0, fz1]))
+    }
+
+    /// Returns distance between rear element and film that focuses the lens at `focus_distance`.
+    fn focus_thick_lens(&self, focus_distance: f64) -> Option<f64> {
+        let (pz, fz) = self.thick_lens_approximation()?;
+        let f = fz[0] - pz[0];
+        let z = -focus_distance;
+        let c = (pz[1] - z - pz[0]) * (pz[1] - z - 4.0 * f - pz[0]);
+        if c < 0.0 {
+            return None;
+        }
+        let delta = 0.5 * (pz[1] - z + pz[0] - c.sqrt());
+        let distance = self.lens_rear_z() + delta;
+        if distance > 0.0 { Some(distance) } else { None }
+    }
+
+    /// Bounds the area on the rear element plane through which light from film points between
+    /// distance `r0` and `r1` on the x-axis can leave the lens system.
+    fn bound_exit_pupil(&self, r0: f64, r1: f64) -> Bounds {
+        let rear_radius = 1.5 * self.rear_element_radius();
+        let rear_bounds = Bounds { min: (-rear_radius, -rear_radius), max: (rear_radius, rear_radius) };
+        let mut bounds: Option<Bounds> = None;
+        for i in 0..PUPIL_SAMPLES {
+            let film = Vec3::new(lerp((i as f64 + 0.5) / PUPIL_SAMPLES as f64, r0, r1), 0.0, 0.0);
+            let rear = Vec3::new(
+                lerp(radical_inverse(2, i), -rear_radius, rear_radius),
+                lerp(radical_inverse(3, i), -rear_radius, rear_radius),
+                self.lens_rear_z(),
+            );
+            let inside = bounds.is_some_and(|b| {
+                rear.x >= b.min.0 && rear.x <= b.max.0 && rear.y >= b.min.1 && rear.y <= b.max.1
+            });
+            if inside || self.trace_from_film(&Ray { origin: film, direction: rear - film, time: 0.0, wavelength: None, differentials: None }).is_some() {
+                bounds = Some(match bounds {
+                    None => Bounds { min: (rear.x, rear.y), max: (rear.x, rear.y) },
+                    Some(b) => Bounds {
+                        min: (b.min.0.min(rear.x), b.min.1.min(rear.y)),
+                        max: (b.max.0.max(rear.x), b.max.1.max(rear.y)),
+                    },
+                });
+            }
+        }
+        match bounds {
+            None => rear_bounds,
+            Some(b) => {
+                // Expand bounds to account for sample spacing
+                let delta = 2.0 * 2.0 * rear_radius * 2f64.sqrt() / (PUPIL_SAMPLES as f64).sqrt();
+                Bounds { min: (b.min.0 - delta, b.min.1 - delta), max: (b.max.0 + delta, b.max.1 + delta) }
+            }
+        }
+    }
+
+    /// Samples point on rear element plane within the exit pupil of film point (x, y).
+    fn sample_exit_pupil(&self, x: f64, y: f64, rng: &mut dyn RngCore) -> (Vec3, f64) {
+        let r = (x * x + y * y).sqrt();
+        let half_diagonal = (self.film_width * self.film_width + self.film_height * self.film_height).sqrt() / 2.0;
+        let index = ((r / half_diagonal * PUPIL_BINS as f64) as usize).min(PUPIL_BINS - 1);
+        let bounds = self.exit_pupil_bounds[index];
+        let lens_x = lerp(rng.gen(), bounds.min.0, bounds.max.0);
+        let lens_y = lerp(rng.gen(), bounds.min.1, bounds.max.1);
+        // Bounds were computed on the x-axis, rotate by angle of film point
+        let (sin, cos) = if r != 0.0 { (y / r, x / r) } else { (0.0, 1.0) };
+        let point = Vec3::new(cos * lens_x - sin * lens_y, sin * lens_x + cos * lens_y, self.lens_rear_z());
+        (point, bounds.area())
+    }
+}
+
+impl RealisticCamera {
+    /// Returns point on the film (lens space) for film position (s, t).
+    fn film_point(&self, s: f64, t: f64) -> Vec3 {
+        // Lens projects the image upside down
+        Vec3::new(-(s - 0.5) * self.film_width, -(t - 0.5) * self.film_height, 0.0)
+    }
+
+    /// Returns ray from the film point of (s, t) towards the point `rear` on the exit pupil, traced
+    /// through the lens into world space. `None` if it is blocked.
+    fn trace_towards(&self, s: f64, t: f64, rear: Vec3, time: f64) -> Option<Ray> {
+        let film = self.film_point(s, t);
+        let ray = self.trace_from_film(&Ray { origin: film, direction: rear - film, time, wavelength: None, differentials: None })?;
+        let Orientation { origin, u, v, w } = self.orientation;
+        Some(Ray {
+            origin: origin + ray.origin.x * u + ray.origin.y * v - ray.origin.z * w,
+            direction: (ray.direction.x * u + ray.direction.y * v - ray.direction.z * w).unit_vector(),
+            time: ray.time,
+            wavelength: None,
+            differentials: None,
+        })
+    }
+
+    /// Returns random point on the exit pupil for film position (s, t) with the time, `None` if
+    /// vignetting removes the sample.
+    fn sample_rear(&self, s: f64, t: f64, rng: &mut dyn RngCore) -> Option<(Vec3, f64)> {
+        let film = self.film_point(s, t);
+        let (rear, pupil_area) = self.sample_exit_pupil(film.x, film.y, rng);
+        let time = self.shutter.sample(rng);
+
+        // Vignetting by cos^4 falloff and exit pupil size, as Russian roulette to keep rays unweighted
+        let cos_theta = (rear - film).unit_vector().z;
+        let weight = cos_theta.powi(4) * pupil_area / self.max_pupil_area;
+        if rng.gen::<f64>() >= weight {
+            return None;
+        }
+        Some((rear, time))
+    }
+}
+
+impl Camera for RealisticCamera {
+    fn get_ray(&self, s: f64, t: f64, rng: &mut dyn RngCore) -> Option<Ray> {
+        let (rear, time) = self.sample_rear(s, t, rng)?;
+        self.trace_towards(s, t, rear, time)
+    }
+
+    fn get_ray_differential(&self, s: f64, t: f64, ds: f64, dt: f64, rng: &mut dyn RngCore) -> Option<Ray> {
+        let (rear, time) = self.sample_rear(s, t, rng)?;
+        let mut ray = self.trace_towards(s, t, rear, time)?;
+        // Offset rays pass through the same pupil point, blocked ones only drop the differentials
+        let offsets = self.trace_towards(s + ds, t, rear, time).zip(self.trace_towards(s, t + dt, rear, time));
+        ray.differentials = offsets.map(|(rx, ry)| Differentials {
+            rx_origin: rx.origin,
+            rx_direction: rx.direction,
+            ry_origin: ry.origin,
+            ry_direction: ry.direction,
+        });
+        Some(ray)
+    }
+
+    fn describe(&self) -> Option<Node> {
+        let elements = self.elements.iter()
+            .flat_map(|e| [e.curvature_radius, e.thickness, e.ior, e.aperture_diameter])
+            .collect();
+        Some(self.orientation.describe("realistic")
+            .with("elements", Value::Numbers(elements))
+            .with("aperture_diameter", self.aperture_diameter)
+            .with("focus_distance", self.focus_distance)
+            .with("film_diagonal", self.film_diagonal)
+            .with("aspect_ratio", self.aspect_ratio)
+            .with("shutter", self.shutter.describe()))
+    }
+}
+
+fn flip_z(v: Vec3) -> Vec3 {
+    Vec3::new(v.x, v.y, -v.z)
+}
+
+fn lerp(t: f64, a: f64, b: f64) -> f64 {
+    (1.0 - t) * a + t * b
+}
+
+/// Returns the `i`-th element of the van der Corput sequence in the given base.
+fn radical_inverse(base: usize, mut i: usize) -> f64 {
+    let inv_base = 1.0 / base as f64;
+    let mut factor = inv_base;
+    let mut result = 0.0;
+    while i > 0 {
+        result += (i % base) as f64 * factor;
+        i /= base;
+        factor *= inv_base;
+    }
+    result
+}
+
+/// Intersects ray with spherical lens interface, returns distance and normal facing the ray origin.
+fn intersect_spherical_element(radius: f64, z_center: f64, origin: Vec3, direction: Vec3) -> Option<(f64, Vec3)> {
+    let o = origin - Vec3::new(0.0, 0.0, z_center);
+    let a = direction.length_squared();
+    let half_b = o.dot(direction);
+    let c = o.length_squared() - radius * radius;
+    let discriminant = half_b * half_b - a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+    let sqrtd = discriminant.sqrt();
+    let (t0, t1) = ((-half_b - sqrtd) / a, (-half_b + sqrtd) / a);
+    // Element is a spherical cap, pick the intersection on the side of the cap
+    let use_closer = (direction.z > 0.0) ^ (radius < 0.0);
+    let t = if use_closer { t0 } else { t1 };
+    if t < 0.0 {
+        return None;
+    }
+    let normal = (o + t * direction).unit_vector();
+    let normal = if normal.dot(direction) > 0.0 { -normal } else { normal };
+    Some((t, normal))
+}
+
+/// Refracts unit vector `wi` pointing away from the interface, `None` on total internal reflection.
+fn refract(wi: Vec3, normal: Vec3, eta: f64) -> Option<Vec3> {
+    let cos_theta_i = normal.dot(wi);
+    let sin2_theta_i = (1.0 - cos_theta_i * cos_theta_i).max(0.0);
+    let sin2_theta_t = eta * eta * sin2_theta_i;
+    if sin2_theta_t >= 1.0 {
+        return None;
+    }
+    let cos_theta_t = (1.0 - sin2_theta_t).sqrt();
+    Some(eta * -wi + (eta * cos_theta_i - cos_theta_t) * normal)
+}
+
+/// Returns z-positions of principal plane and focal point from a ray parallel to the optical
+/// axis and the same ray after passing through the lens system.
+fn cardinal_points(ray_in: &Ray, ray_out: &Ray) -> (f64, f64) {
+    let tf = -ray_out.origin.x / ray_out.direction.x;
+    let fz = -ray_out.at(tf).z;
+    let tp = (ray_in.origin.x - ray_out.origin.x) / ray_out.direction.x;
+    let pz = -ray_out.at(tp).z;
+    (pz, fz)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn camera(focus_distance: f64) -> RealisticCamera {
+        let elements = parse_lens_data(DOUBLE_GAUSS_50MM).unwrap();
+        let orientation = Orientation::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0), Vec3::new(0.0, 1.0, 0.0));
+        RealisticCamera::new(orientation, &elements, 17.1, focus_distance, 35.0, 1.5, Shutter::instant(0.0)).unwrap()
+    }
+
+    #[test]
+    fn test_parse_lens_data() {
+        let elements = parse_lens_data(DOUBLE_GAUSS_50MM).unwrap();
+        assert_eq!(elements.len(), 11);
+        assert_eq!(elements[5], LensElement { curvature_radius: 0.0, thickness: 4.5, ior: 0.0, aperture_diameter: 17.1 });
+        assert!(parse_lens_data("1 2 3").is_err());
+    }
+
+    #[test]
+    fn test_focus_moves_film_back_for_closer_objects() {
+        let far = camera(100.0).lens_rear_z();
+        let near = camera(0.5).lens_rear_z();
+        assert!(far > 0.0);
+        assert!(near > far);
+    }
+
+    #[test]
+    fn test_rays_converge_at_focus_distance() {
+        let camera = camera(2.0);
+        let focus = |ray: &Ray| ray.at(-(ray.origin.z + 2.0) / ray.direction.z);
+        let film = Vec3::new(0.0, 0.0, 0.0);
+        let rays: Vec<Ray> = [(0.002, 0.0), (-0.002, 0.0), (0.0, 0.002)].iter()
+            .filter_map(|&(x, y)| {
+                let rear = Vec3::new(x, y, camera.lens_rear_z());
+                camera.trace_from_film(&Ray { origin: film, direction: rear - film, time: 0.0, wavelength: None, differentials: None })
+            })
+            .map(|r| Ray { origin: flip_z(r.origin), direction: flip_z(r.direction), time: 0.0, wavelength: None, differentials: None })
+            .collect();
+        assert_eq!(rays.len(), 3);
+        for ray in rays.iter() {
+            let p = focus(ray);
+            assert!(p.x.abs() < 1e-3 && p.y.abs() < 1e-3, "{:?}", p);
+        }
+    }
+}
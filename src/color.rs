@@ -6,6 +6,11 @@ const MAX_PERC: f64 = 0.999;
 pub const BLACK: Vec3 = Vec3 { x: 0.0, y: 0.0, z: 0.0 };
 pub const WHITE: Vec3 = Vec3 { x: 1.0, y: 1.0, z: 1.0 };
 
+/// Returns relative luminance of linear sRGB color.
+pub fn luminance(color: Vec3) -> f64 {
+    0.2126 * color.x + 0.7152 * color.y + 0.0722 * color.z
+}
+
 pub fn get_color(pixel_color: Vec3, samples_per_pixel: i64) -> String {
     // Divide color by number of samples
     let scale = 1.0 / samples_per_pixel as f64;
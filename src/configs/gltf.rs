@@ -0,0 +1,482 @@
+//! Import of glTF 2.0 scenes (.gltf with external or embedded buffers, or binary .glb).
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Error, ErrorKind};
+use std::path::Path;
+use std::sync::Arc;
+
+use ::gltf::camera::Projection;
+use ::gltf::khr_lights_punctual::{Kind, Light};
+use ::gltf::material::AlphaMode;
+use ::gltf::mesh::Mode;
+use ::gltf::texture::{MinFilter, WrappingMode};
+use ::gltf::{buffer, image, Gltf, Node};
+
+use crate::camera::{Camera, Orientation, OrthographicCamera, PerspectiveCamera, Shutter, ThinLens};
+use crate::configs::ImageConfig;
+use crate::material::{AlphaMask, Material, MetallicRoughness, NormalMap, Principled};
+use crate::netpbm::Image;
+use crate::objects::{Aabb, Bvh, Hittable, Hittables, Triangle};
+use crate::texture::{srgb_to_linear, Filter, ImageTexture, SolidColor, Texture, Wrap};
+use crate::Vec3;
+
+use super::lights::{distant_light, point_light, spot_light};
+use super::matrix::{determinant, multiply, transform_normal, transform_point, transform_vector, Matrix, IDENTITY};
+
+/// Extensions that are imported, files requiring others are rejected and others used are reported
+/// in [GltfScene::warnings]
+const SUPPORTED_EXTENSIONS: [&str; 4] = [
+    "KHR_lights_punctual",
+    "KHR_materials_emissive_strength",
+    "KHR_materials_ior",
+    "KHR_materials_transmission",
+];
+
+/// Scene imported from a glTF file.
+pub struct GltfScene {
+    pub config: ImageConfig,
+    /// Features of the file that were ignored, such as optional extensions
+    pub warnings: Vec<String>,
+}
+
+/// Loads the default scene (or else the first) of a glTF file, see [parse_gltf].
+pub fn load_gltf<P: AsRef<Path>>(path: P) -> io::Result<GltfScene> {
+    let path = path.as_ref();
+    parse_gltf(&fs::read(path)?, path.parent())
+}
+
+/// Returns the default scene (or else the first) of the contents of a .gltf or .glb file, with
+/// external buffers and images resolved relative to `base`.
+///
+/// Meshes are flattened into triangles in world space with their normals, first texture
+/// coordinates and vertex colors, while points and lines are skipped. Skins and morph targets are
+/// ignored, showing meshes in their rest pose. Materials become [MetallicRoughness] materials,
+/// wrapped in a [NormalMap] and an [AlphaMask] for the normal texture and the alpha modes mask and
+/// blend. Occlusion textures are ignored, as they approximate what the path tracer computes.
+///
+/// The first camera in the node hierarchy is used, with the aspect ratio 16:9 unless it specifies
+/// one. Scenes without camera are viewed from the front (+z). Punctual lights become small
+/// emitting spheres whose intensity matches that of the light, with point and spot lights at a
+/// hundredth of the scene's size and directional lights far away, covering a few degrees of the
+/// sky. Intensities in candela and lux are taken as radiance of the renderer.
+///
+/// Files requiring unsupported extensions (other than lights, emissive strength, IOR and
+/// transmission) are rejected with an error naming them, unsupported extensions that are only
+/// used are ignored with a warning.
+pub fn parse_gltf(bytes: &[u8], base: Option<&Path>) -> io::Result<GltfScene> {
+    // Check extensions before validation, which rejects them without naming them
+    let gltf = Gltf::from_slice_without_validation(bytes).map_err(gltf_error)?;
+    let unsupported: Vec<&str> = gltf.extensions_required().filter(|e| !SUPPORTED_EXTENSIONS.contains(e)).collect();
+    if !unsupported.is_empty() {
+        return Err(invalid(format!("unsupported glTF extensions required: {}", unsupported.join(", "))));
+    }
+    let warnings = gltf.extensions_used()
+        .filter(|e| !SUPPORTED_EXTENSIONS.contains(e))
+        .map(|e| format!("ignored unsupported glTF extension {}", e))
+        .collect();
+    let Gltf { document, blob } = Gltf::from_slice(bytes).map_err(gltf_error)?;
+    let buffers = ::gltf::import_buffers(&document, base, blob).map_err(gltf_error)?;
+    let images = ::gltf::import_images(&document, base, &buffers).map_err(gltf_error)?;
+    let scene = document.default_scene().or_else(|| document.scenes().next()).ok_or_else(|| invalid("glTF file has no scene".to_string()))?;
+
+    let mut importer = Importer { buffers, images, textures: HashMap::new(), materials: HashMap::new(), triangles: vec![], camera: None, lights: vec![] };
+    for node in scene.nodes() {
+        importer.visit(&node, &IDENTITY)?;
+    }
+    Ok(GltfScene { config: importer.into_config()?, warnings })
+}
+
+/// Scene under construction, with the data of the file's buffers and images.
+struct Importer<'a> {
+    buffers: Vec<buffer::Data>,
+    images: Vec<image::Data>,
+    /// Textures by texture index and whether they store sRGB values
+    textures: HashMap<(usize, bool), Arc<dyn Texture>>,
+    /// Materials by index, `None` for the default material
+    materials: HashMap<Option<usize>, Arc<dyn Material>>,
+    triangles: Vec<Box<dyn Hittable>>,
+    /// First camera with the transformation of its node
+    camera: Option<(::gltf::Camera<'a>, Matrix)>,
+    lights: Vec<(Light<'a>, Matrix)>,
+}
+
+impl<'a> Importer<'a> {
+    fn visit(&mut self, node: &Node<'a>, parent: &Matrix) -> io::Result<()> {
+        let local = node.transform().matrix().map(|column| column.map(f64::from));
+        let transform = multiply(parent, &local);
+        if let Some(mesh) = node.mesh() {
+            for primitive in mesh.primitives() {
+                self.add_primitive(&primitive, &transform)?;
+            }
+        }
+        if let Some(camera) = node.camera() {
+            self.camera.get_or_insert((camera, transform));
+        }
+        if let Some(light) = node.light() {
+            self.lights.push((light, transform));
+        }
+        for child in node.children() {
+            self.visit(&child, &transform)?;
+        }
+        Ok(())
+    }
+
+    fn add_primitive(&mut self, primitive: &::gltf::Primitive, transform: &Matrix) -> io::Result<()> {
+        let mode = primitive.mode();
+        if !matches!(mode, Mode::Triangles | Mode::TriangleStrip | Mode::TriangleFan) {
+            return Ok(());
+        }
+        let material = self.material(&primitive.material())?;
+        let reader = primitive.reader(|buffer| Some(&self.buffers[buffer.index()]));
+        let positions: Vec<Vec3> = reader.read_positions()
+            .ok_or_else(|| invalid("glTF primitive without positions".to_string()))?
+            .map(|p| transform_point(transform, to_vec3(p)))
+            .collect();
+        let normals: Option<Vec<Vec3>> = reader.read_normals()
+            .map(|normals| normals.map(|n| transform_normal(transform, to_vec3(n)).unit_vector()).collect());
+        // Texture coordinates start at the top left in glTF and at the bottom left here
+        let uvs: Option<Vec<(f64, f64)>> = reader.read_tex_coords(0)
+            .map(|uvs| uvs.into_f32().map(|[u, v]| (u as f64, 1.0 - v as f64)).collect());
+        let colors: Option<Vec<Vec3>> = reader.read_colors(0).map(|colors| colors.into_rgb_f32().map(to_vec3).collect());
+        let indices: Vec<usize> = match reader.read_indices() {
+            Some(indices) => indices.into_u32().map(|i| i as usize).collect(),
+            None => (0..positions.len()).collect(),
+        };
+        if let Some(&i) = indices.iter().find(|&&i| i >= positions.len()) {
+            return Err(invalid(format!("glTF vertex index {} out of range", i)));
+        }
+        let vertex_count = [Some(positions.len()), normals.as_ref().map(Vec::len), uvs.as_ref().map(Vec::len), colors.as_ref().map(Vec::len)];
+        if vertex_count.iter().flatten().any(|&n| n != positions.len()) {
+            return Err(invalid("glTF vertex attributes of different lengths".to_string()));
+        }
+
+        // Mirroring transformations reverse the winding order
+        let mirrored = determinant(transform) < 0.0;
+        for [a, b, c] in triangles(mode, &indices) {
+            let [a, b, c] = if mirrored { [a, c, b] } else { [a, b, c] };
+            let mut triangle = Triangle::new(positions[a], positions[b], positions[c], material.clone());
+            if let Some(uvs) = &uvs {
+                triangle.uvs = [uvs[a], uvs[b], uvs[c]];
+            }
+            triangle.normals = normals.as_ref().map(|normals| [normals[a], normals[b], normals[c]]);
+            triangle.colors = colors.as_ref().map(|colors| [colors[a], colors[b], colors[c]]);
+            self.triangles.push(Box::new(triangle));
+        }
+        Ok(())
+    }
+
+    fn material(&mut self, material: &::gltf::Material) -> io::Result<Arc<dyn Material>> {
+        if let Some(material) = self.materials.get(&material.index()) {
+            return Ok(material.clone());
+        }
+        let pbr = material.pbr_metallic_roughness();
+        let [r, g, b, alpha] = pbr.base_color_factor().map(f64::from);
+        // Specular reflectance at normal incidence follows from the IOR, 0.5 corresponds to 4%
+        let ior = material.ior().unwrap_or(1.5) as f64;
+        let specular = ((ior - 1.0) / (ior + 1.0)).powi(2) / 0.08;
+        let principled = Principled {
+            base_color: Vec3::new(r, g, b),
+            metallic: pbr.metallic_factor() as f64,
+            roughness: pbr.roughness_factor() as f64,
+            specular: specular.min(1.0),
+            transmission: material.transmission().map_or(0.0, |t| t.transmission_factor() as f64),
+            ior,
+            ..Default::default()
+        };
+        let mut result = MetallicRoughness::new(principled);
+        if let Some(info) = pbr.base_color_texture() {
+            result.base_color_texture = Some(self.texture(&info.texture(), info.tex_coord(), true)?);
+        }
+        if let Some(info) = pbr.metallic_roughness_texture() {
+            result.metallic_roughness_texture = Some(self.texture(&info.texture(), info.tex_coord(), false)?);
+        }
+        result.emission = material.emissive_strength().unwrap_or(1.0) as f64 * to_vec3(material.emissive_factor());
+        if let Some(info) = material.emissive_texture() {
+            result.emission_texture = Some(self.texture(&info.texture(), info.tex_coord(), true)?);
+        }
+
+        let mut result: Arc<dyn Material> = Arc::new(result);
+        if let Some(normal) = material.normal_texture() {
+            let texture = self.texture(&normal.texture(), normal.tex_coord(), false)?;
+            result = Arc::new(NormalMap { material: result, texture, strength: normal.scale() as f64 });
+        }
+        let cutoff = match material.alpha_mode() {
+            AlphaMode::Opaque => None,
+            AlphaMode::Mask => Some(material.alpha_cutoff().unwrap_or(0.5) as f64),
+            AlphaMode::Blend => Some(0.0),
+        };
+        if let Some(cutoff) = cutoff {
+            let alpha = self.alpha_texture(pbr.base_color_texture(), alpha, cutoff)?;
+            result = Arc::new(AlphaMask { material: result, alpha });
+        }
+        self.materials.insert(material.index(), result.clone());
+        Ok(result)
+    }
+
+    /// Returns the color channels of the texture, decoded to linear values if `srgb`.
+    fn texture(&mut self, texture: &::gltf::Texture, tex_coord: u32, srgb: bool) -> io::Result<Arc<dyn Texture>> {
+        if tex_coord != 0 {
+            return Err(invalid(format!("glTF texture coordinate set {} is not supported, only set 0", tex_coord)));
+        }
+        if let Some(texture) = self.textures.get(&(texture.index(), srgb)) {
+            return Ok(texture.clone());
+        }
+        let (mut image, _) = convert_image(&self.images[texture.source().index()]);
+        if srgb {
+            image.data.iter_mut().for_each(|v| *v = srgb_to_linear(*v));
+        }
+        let result: Arc<dyn Texture> = Arc::new(sampled(image, texture));
+        self.textures.insert((texture.index(), srgb), result.clone());
+        Ok(result)
+    }
+
+    /// Returns alpha of the base color scaled by `factor`, as 0 or 1 depending on whether it
+    /// reaches `cutoff` unless that is 0.
+    fn alpha_texture(&self, info: Option<::gltf::texture::Info>, factor: f64, cutoff: f64) -> io::Result<Arc<dyn Texture>> {
+        let threshold = |alpha: f64| if cutoff > 0.0 { (alpha >= cutoff) as u8 as f64 } else { alpha };
+        let Some(info) = info else {
+            return Ok(Arc::new(SolidColor::gray(threshold(factor))));
+        };
+        if info.tex_coord() != 0 {
+            return Err(invalid(format!("glTF texture coordinate set {} is not supported, only set 0", info.tex_coord())));
+        }
+        let texture = info.texture();
+        let (image, alpha) = convert_image(&self.images[texture.source().index()]);
+        let data = match alpha {
+            Some(alpha) => alpha.into_iter().map(|a| threshold(factor * a)).collect(),
+            None => vec![threshold(factor); image.width * image.height],
+        };
+        Ok(Arc::new(sampled(Image { width: image.width, height: image.height, channels: 1, data }, &texture)))
+    }
+
+    /// Returns camera matching the aspect ratio of the film, with the lights and triangles.
+    fn into_config(self) -> io::Result<ImageConfig> {
+        let bounds = self.triangles.iter().filter_map(|t| t.bounding_box()).reduce(Aabb::surrounding);
+        let (center, radius) = match bounds {
+            Some(bounds) => ((bounds.min + bounds.max) / 2.0, ((bounds.max - bounds.min) / 2.0).length().max(1.0e-3)),
+            None => (Vec3::new(0.0, 0.0, 0.0), 1.0),
+        };
+
+        let mut hittables: Vec<Box<dyn Hittable>> = vec![Box::new(Bvh::new(self.triangles))];
+        for (light, transform) in self.lights.iter() {
+            hittables.push(light_sphere(light, transform, center, radius));
+        }
+
+        let (camera, aspect_ratio): (Box<dyn Camera>, f64) = match &self.camera {
+            Some((camera, transform)) => {
+                let origin = transform_point(transform, Vec3::new(0.0, 0.0, 0.0));
+                // Cameras look along their local -z axis with y up
+                let back = transform_vector(transform, Vec3::new(0.0, 0.0, 1.0));
+                let up = transform_vector(transform, Vec3::new(0.0, 1.0, 0.0));
+                let orientation = Orientation::new(origin, origin - back, up);
+                match camera.projection() {
+                    Projection::Perspective(p) => {
+                        let aspect_ratio = p.aspect_ratio().map_or(16.0 / 9.0, f64::from);
+                        let vfov = (p.yfov() as f64).to_degrees();
+                        (Box::new(PerspectiveCamera::new(orientation, vfov, aspect_ratio, ThinLens::pinhole(), Shutter::instant(0.0))), aspect_ratio)
+                    }
+                    Projection::Orthographic(o) => {
+                        let aspect_ratio = o.xmag() as f64 / o.ymag() as f64;
+                        let height = 2.0 * o.ymag() as f64;
+                        (Box::new(OrthographicCamera::new(orientation, height, aspect_ratio, ThinLens::pinhole(), Shutter::instant(0.0))), aspect_ratio)
+                    }
+                }
+            }
+            None => {
+                let (aspect_ratio, vfov) = (16.0 / 9.0, 40.0f64);
+                let distance = 1.1 * radius / (vfov.to_radians() / 2.0).sin();
+                let orientation = Orientation::new(center + Vec3::new(0.0, 0.0, distance), center, Vec3::new(0.0, 1.0, 0.0));
+                (Box::new(PerspectiveCamera::new(orientation, vfov, aspect_ratio, ThinLens::pinhole(), Shutter::instant(0.0))), aspect_ratio)
+            }
+        };
+        if !(aspect_ratio.is_finite() && aspect_ratio > 0.0) {
+            return Err(invalid(format!("glTF camera with aspect ratio {}", aspect_ratio)));
+        }
+
+        let image_width = 400;
+        Ok(ImageConfig {
+            aspect_ratio,
+            image_width,
+            image_height: (image_width as f64 / aspect_ratio) as i64,
+            samples_per_pixel: 100,
+            max_depth: 50,
+            world: Hittables { hittables },
+            camera,
+            spectral: false,
+        })
+    }
+}
+
+/// Returns emitting sphere for the light, sized relative to the scene's bounding sphere.
+fn light_sphere(light: &Light, transform: &Matrix, center: Vec3, radius: f64) -> Box<dyn Hittable> {
+    let color = light.intensity() as f64 * to_vec3(light.color());
+    let position = transform_point(transform, Vec3::new(0.0, 0.0, 0.0));
+    // Lights shine along their local -z axis
+    let direction = -transform_vector(transform, Vec3::new(0.0, 0.0, 1.0)).unit_vector();
+    // Directional lights give illuminance, the others intensity
+    Box::new(match light.kind() {
+        Kind::Directional => distant_light(direction, color, center, radius),
+        Kind::Point => point_light(position, color, radius),
+        Kind::Spot { inner_cone_angle, outer_cone_angle } => {
+            spot_light(position, direction, color, ((inner_cone_angle as f64).cos(), (outer_cone_angle as f64).cos()), radius)
+        }
+    })
+}
+
+/// Returns vertex indices of the triangles of a primitive.
+fn triangles(mode: Mode, indices: &[usize]) -> Vec<[usize; 3]> {
+    match mode {
+        Mode::TriangleStrip => (2..indices.len())
+            // Every other triangle of a strip is flipped to keep the winding order
+            .map(|i| if i % 2 == 0 { [indices[i - 2], indices[i - 1], indices[i]] } else { [indices[i - 1], indices[i - 2], indices[i]] })
+            .collect(),
+        Mode::TriangleFan => (2..indices.len()).map(|i| [indices[0], indices[i - 1], indices[i]]).collect(),
+        _ => indices.chunks_exact(3).map(|c| [c[0], c[1], c[2]]).collect(),
+    }
+}
+
+/// Returns RGB image with values in [0, 1] and alpha channel if present.
+fn convert_image(data: &image::Data) -> (Image, Option<Vec<f64>>) {
+    use image::Format::*;
+    let (channels, bytes) = match data.format {
+        R8 => (1, 1),
+        R8G8 => (2, 1),
+        R8G8B8 => (3, 1),
+        R8G8B8A8 => (4, 1),
+        R16 => (1, 2),
+        R16G16 => (2, 2),
+        R16G16B16 => (3, 2),
+        R16G16B16A16 => (4, 2),
+        R32G32B32FLOAT => (3, 4),
+        R32G32B32A32FLOAT => (4, 4),
+    };
+    let values: Vec<f64> = match bytes {
+        1 => data.pixels.iter().map(|&v| v as f64 / 255.0).collect(),
+        2 => data.pixels.chunks_exact(2).map(|v| u16::from_ne_bytes([v[0], v[1]]) as f64 / 65535.0).collect(),
+        _ => data.pixels.chunks_exact(4).map(|v| f32::from_ne_bytes([v[0], v[1], v[2], v[3]]) as f64).collect(),
+    };
+    // One and two channels are luminance with optional alpha
+    let mut rgb = Vec::with_capacity(values.len() / channels * 3);
+    for pixel in values.chunks_exact(channels) {
+        if channels < 3 {
+            rgb.extend([pixel[0]; 3]);
+        } else {
+            rgb.extend(&pixel[..3]);
+        }
+    }
+    let alpha = (channels % 2 == 0).then(|| values.chunks_exact(channels).map(|pixel| pixel[channels - 1]).collect());
+    (Image { width: data.width as usize, height: data.height as usize, channels: 3, data: rgb }, alpha)
+}
+
+/// Returns texture of `image` with the wrap mode and filter of the sampler of `texture`.
+fn sampled(image: Image, texture: &::gltf::Texture) -> ImageTexture {
+    let sampler = texture.sampler();
+    let mut result = ImageTexture::new(image);
+    // Both directions share one wrap mode here
+    result.wrap = match sampler.wrap_s() {
+        WrappingMode::ClampToEdge => Wrap::Clamp,
+        WrappingMode::MirroredRepeat => Wrap::Mirror,
+        WrappingMode::Repeat => Wrap::Repeat,
+    };
+    if matches!(sampler.min_filter(), Some(MinFilter::Nearest | MinFilter::Linear)) {
+        result.filter = Filter::Bilinear;
+    }
+    result
+}
+
+fn to_vec3([x, y, z]: [f32; 3]) -> Vec3 {
+    Vec3::new(x as f64, y as f64, z as f64)
+}
+
+fn gltf_error(error: ::gltf::Error) -> Error {
+    match error {
+        ::gltf::Error::Io(e) => e,
+        e => invalid(format!("invalid glTF file: {}", e)),
+    }
+}
+
+fn invalid(message: String) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use super::*;
+    use crate::configs::lights::LIGHT_RADIUS;
+    use crate::Ray;
+
+    /// Returns binary glTF container of `json` and the binary buffer `bin`.
+    fn glb(json: &str, bin: &[u8]) -> Vec<u8> {
+        let pad = |mut data: Vec<u8>, byte: u8| {
+            data.resize(data.len().div_ceil(4) * 4, byte);
+            data
+        };
+        let (json, bin) = (pad(json.as_bytes().to_vec(), b' '), pad(bin.to_vec(), 0));
+        let mut bytes = b"glTF".to_vec();
+        bytes.extend(2u32.to_le_bytes());
+        bytes.extend(((28 + json.len() + bin.len()) as u32).to_le_bytes());
+        for (kind, data) in [(b"JSON", json), (b"BIN\0", bin)] {
+            bytes.extend((data.len() as u32).to_le_bytes());
+            bytes.extend(kind);
+            bytes.extend(data);
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_parse_gltf() {
+        // Triangle 3 units in front of the camera and a point light above the camera
+        let mut bin = vec![];
+        [-1.0f32, -1.0, 0.0, 1.0, -1.0, 0.0, 0.0, 1.0, 0.0].iter().for_each(|v| bin.extend(v.to_le_bytes()));
+        [0u16, 1, 2].iter().for_each(|i| bin.extend(i.to_le_bytes()));
+        let json = r#"{
+            "asset": {"version": "2.0"}, "scene": 0, "scenes": [{"nodes": [0, 1, 2]}],
+            "nodes": [
+                {"mesh": 0, "translation": [0, 0, -3]},
+                {"camera": 0},
+                {"translation": [0, 2, 0], "extensions": {"KHR_lights_punctual": {"light": 0}}}
+            ],
+            "cameras": [{"type": "perspective", "perspective": {"yfov": 0.8, "aspectRatio": 2.0, "znear": 0.1}}],
+            "meshes": [{"primitives": [{"attributes": {"POSITION": 0}, "indices": 1, "material": 0}]}],
+            "materials": [{"pbrMetallicRoughness": {"baseColorFactor": [1, 0, 0, 1], "metallicFactor": 0}}],
+            "accessors": [
+                {"bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3", "min": [-1, -1, 0], "max": [1, 1, 0]},
+                {"bufferView": 1, "componentType": 5123, "count": 3, "type": "SCALAR"}
+            ],
+            "bufferViews": [{"buffer": 0, "byteLength": 36}, {"buffer": 0, "byteOffset": 36, "byteLength": 6}],
+            "buffers": [{"byteLength": 42}],
+            "extensionsUsed": ["KHR_lights_punctual"],
+            "extensions": {"KHR_lights_punctual": {"lights": [{"type": "point", "intensity": 10}]}}
+        }"#;
+        let GltfScene { config: conf, warnings } = parse_gltf(&glb(json, &bin), None).unwrap();
+        assert!(warnings.is_empty());
+        assert!(conf.aspect_ratio == 2.0 && conf.image_height == conf.image_width / 2);
+        assert_eq!(conf.world.hittables.len(), 2);
+
+        let mut rng = rand::thread_rng();
+        let ray = conf.camera.get_ray(0.5, 0.5, &mut rng).unwrap();
+        let hit = conf.world.hit(&ray, 0.001, f64::MAX).unwrap();
+        assert!((hit.point - Vec3::new(0.0, 0.0, -3.0)).length() < 1e-6 && hit.front_face);
+        // Red diffuse base below a white specular reflection
+        let reflected = (0..1000).filter_map(|_| hit.material.scatter(&ray, &hit, &mut rng)).fold(Vec3::new(0.0, 0.0, 0.0), |sum, s| sum + s.attenuation);
+        assert!(reflected.x > 10.0 * reflected.y && reflected.y == reflected.z);
+
+        // Light sphere with a hundredth of the triangle's bounding radius
+        let up = Ray { origin: Vec3::new(0.0, 0.0, 0.0), direction: Vec3::new(0.0, 1.0, 0.0), time: 0.0, wavelength: None, differentials: None };
+        let hit = conf.world.hit(&up, 0.001, f64::MAX).unwrap();
+        let r = LIGHT_RADIUS * 2f64.sqrt();
+        assert!((hit.t - (2.0 - r)).abs() < 1e-9);
+        assert!((hit.material.emitted(&up, &hit).x * PI * r * r - 10.0).abs() < 1e-6);
+
+        let json = json.replacen("\"extensionsUsed\": [", "\"extensionsRequired\": [\"KHR_draco_mesh_compression\"], \"extensionsUsed\": [\"KHR_draco_mesh_compression\", ", 1);
+        let error = parse_gltf(&glb(&json, &bin), None).err().unwrap();
+        assert!(error.kind() == ErrorKind::InvalidData && error.to_string().contains("KHR_draco_mesh_compression"));
+
+        // Optional extensions are reported rather than silently dropped
+        let json = json.replacen("\"extensionsRequired\": [\"KHR_draco_mesh_compression\"], \"extensionsUsed\": [\"KHR_draco_mesh_compression\", ", "\"extensionsUsed\": [\"KHR_texture_transform\", \"KHR_materials_clearcoat\", ", 1);
+        let warnings = parse_gltf(&glb(&json, &bin), None).unwrap().warnings;
+        assert_eq!(warnings, ["ignored unsupported glTF extension KHR_texture_transform", "ignored unsupported glTF extension KHR_materials_clearcoat"]);
+    }
+}
@@ -0,0 +1,40 @@
+//! Emitting spheres standing in for the point, spot and distant lights of scene file formats,
+//! sized relative to the scene's bounding sphere.
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+use crate::material::{DiffuseLight, SpotLight};
+use crate::objects::Sphere;
+use crate::Vec3;
+
+/// Radius of the spheres standing in for point and spot lights, relative to the scene's size
+pub(super) const LIGHT_RADIUS: f64 = 0.01;
+/// Angular radius of the spheres standing in for distant lights
+pub(super) const SUN_ANGULAR_RADIUS: f64 = 2.0 * PI / 180.0;
+
+/// Returns radiance of a light sphere of `scene_radius` with `intensity`. A sphere of radius r and
+/// radiance L has intensity L π r² in every direction.
+fn radiance(intensity: Vec3, scene_radius: f64) -> Vec3 {
+    let r = LIGHT_RADIUS * scene_radius;
+    intensity / (PI * r * r)
+}
+
+pub(super) fn point_light(position: Vec3, intensity: Vec3, scene_radius: f64) -> Sphere {
+    let material = Arc::new(DiffuseLight { emit: radiance(intensity, scene_radius) });
+    Sphere { center: position, radius: LIGHT_RADIUS * scene_radius, material }
+}
+
+/// Returns sphere shining along the unit `direction`, fading out between the cones of the cosines.
+pub(super) fn spot_light(position: Vec3, direction: Vec3, intensity: Vec3, (cos_inner, cos_outer): (f64, f64), scene_radius: f64) -> Sphere {
+    let material = Arc::new(SpotLight { emit: radiance(intensity, scene_radius), direction, cos_inner, cos_outer });
+    Sphere { center: position, radius: LIGHT_RADIUS * scene_radius, material }
+}
+
+/// Returns far away sphere in the opposite of the unit `direction` the light travels, which gives
+/// `irradiance` at the scene around `center`.
+pub(super) fn distant_light(direction: Vec3, irradiance: Vec3, center: Vec3, scene_radius: f64) -> Sphere {
+    // Irradiance E of a distant sphere covering a cone of angular radius α is L π sin² α
+    let distance = 1000.0 * scene_radius;
+    let emit = irradiance / (PI * SUN_ANGULAR_RADIUS.sin().powi(2));
+    Sphere { center: center - distance * direction, radius: distance * SUN_ANGULAR_RADIUS.sin(), material: Arc::new(DiffuseLight { emit }) }
+}
@@ -0,0 +1,62 @@
+//! Affine transformations of scene file formats as 4x4 matrices.
+use crate::Vec3;
+
+/// Column-major 4x4 matrix as stored in glTF and pbrt files
+pub(super) type Matrix = [[f64; 4]; 4];
+
+pub(super) const IDENTITY: Matrix = [[1.0, 0.0, 0.0, 0.0], [0.0, 1.0, 0.0, 0.0], [0.0, 0.0, 1.0, 0.0], [0.0, 0.0, 0.0, 1.0]];
+
+pub(super) fn multiply(a: &Matrix, b: &Matrix) -> Matrix {
+    std::array::from_fn(|column| std::array::from_fn(|row| (0..4).map(|k| a[k][row] * b[column][k]).sum()))
+}
+
+/// Returns matrix with the columns of the linear part and the translation.
+pub(super) fn from_columns(x: Vec3, y: Vec3, z: Vec3, translation: Vec3) -> Matrix {
+    [[x.x, x.y, x.z, 0.0], [y.x, y.y, y.z, 0.0], [z.x, z.y, z.z, 0.0], [translation.x, translation.y, translation.z, 1.0]]
+}
+
+fn columns(m: &Matrix) -> [Vec3; 3] {
+    [0, 1, 2].map(|i| Vec3::new(m[i][0], m[i][1], m[i][2]))
+}
+
+pub(super) fn transform_point(m: &Matrix, p: Vec3) -> Vec3 {
+    transform_vector(m, p) + Vec3::new(m[3][0], m[3][1], m[3][2])
+}
+
+pub(super) fn transform_vector(m: &Matrix, v: Vec3) -> Vec3 {
+    let [x, y, z] = columns(m);
+    v.x * x + v.y * y + v.z * z
+}
+
+pub(super) fn determinant(m: &Matrix) -> f64 {
+    let [a, b, c] = columns(m);
+    a.dot(b.cross(c))
+}
+
+/// Returns rows of the inverse of the linear part times the determinant.
+fn adjugate(m: &Matrix) -> [Vec3; 3] {
+    let [a, b, c] = columns(m);
+    [b.cross(c), c.cross(a), a.cross(b)]
+}
+
+/// Returns inverse of an affine transformation, `None` if it is singular.
+pub(super) fn inverse(m: &Matrix) -> Option<Matrix> {
+    let det = determinant(m);
+    if det.abs() < 1.0e-12 {
+        return None;
+    }
+    let rows = adjugate(m).map(|row| row / det);
+    let x = Vec3::new(rows[0].x, rows[1].x, rows[2].x);
+    let y = Vec3::new(rows[0].y, rows[1].y, rows[2].y);
+    let z = Vec3::new(rows[0].z, rows[1].z, rows[2].z);
+    let t = Vec3::new(m[3][0], m[3][1], m[3][2]);
+    Some(from_columns(x, y, z, -Vec3::new(rows[0].dot(t), rows[1].dot(t), rows[2].dot(t))))
+}
+
+/// Returns normal transformed by the inverse transpose of the linear part, up to a positive factor.
+pub(super) fn transform_normal(m: &Matrix, n: Vec3) -> Vec3 {
+    let sign = determinant(m).signum();
+    let [x, y, z] = adjugate(m);
+    // Transpose of the inverse turns the rows of the adjugate into columns
+    sign * (n.x * x + n.y * y + n.z * z)
+}
@@ -1,4 +1,7 @@
+pub use self::gltf::*;
+pub use pbrt::*;
 pub use random_spheres::*;
+pub use scene::*;
 
 use crate::Camera;
 use crate::objects::Hittables;
@@ -10,8 +13,15 @@ pub struct ImageConfig {
     pub samples_per_pixel: i64,
     pub max_depth: i64,
     pub world: Hittables,
-    pub camera: Camera,
+    pub camera: Box<dyn Camera>,
+    /// Trace wavelengths instead of RGB colors, required for dispersion
+    pub spectral: bool,
 }
 
+mod gltf;
+mod lights;
+mod matrix;
+mod pbrt;
 mod random_spheres;
+mod scene;
 
@@ -0,0 +1,710 @@
+//! Import of a practical subset of the pbrt-v3 scene format.
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Error, ErrorKind};
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::camera::{Orientation, PerspectiveCamera, Shutter, ThinLens};
+use crate::configs::ImageConfig;
+use crate::material::{ComplexIor, DiffuseLight, Material, MaterialRegistry, Param, Params, COPPER};
+use crate::objects::{Aabb, Bvh, Hittable, Hittables, Sphere, Triangle};
+use crate::{ply, Vec3};
+
+use super::lights::{distant_light, point_light, spot_light};
+use super::matrix::{determinant, from_columns, inverse, multiply, transform_normal, transform_point, transform_vector, Matrix, IDENTITY};
+
+/// Loads pbrt-v3 scene, see [parse_pbrt].
+pub fn load_pbrt<P: AsRef<Path>>(path: P, registry: &MaterialRegistry) -> io::Result<ImageConfig> {
+    let path = path.as_ref();
+    parse_pbrt(&fs::read_to_string(path)?, path.parent(), registry)
+}
+
+/// Returns scene of a pbrt-v3 file with PLY meshes resolved relative to `base`. Errors for
+/// unsupported features give the line number.
+///
+/// Supported are the directives `LookAt`, `Translate`, `Scale`, `Rotate`, `Transform`,
+/// `ConcatTransform`, `Camera "perspective"`, `Film`, `Sampler`, `Integrator` (only its
+/// `maxdepth`), `WorldBegin`/`WorldEnd`, `AttributeBegin`/`End`, `TransformBegin`/`End`,
+/// `Material`, `MakeNamedMaterial`, `NamedMaterial`, `Shape "sphere"/"trianglemesh"/"plymesh"`,
+/// `AreaLightSource "diffuse"` and `LightSource "point"/"spot"/"distant"/"infinite"`, while
+/// `PixelFilter` and `Accelerator` are ignored. Parameters must be numbers, RGB colors or strings,
+/// not spectra or textures.
+///
+/// Materials "matte", "metal" and "glass" become Lambertian, rough conductors and (rough)
+/// dielectrics. Other material names are looked up in `registry`, passing the parameters by
+/// name. Shapes with an area light only emit light. Point, spot and distant lights become small
+/// emitting spheres of the same intensity as for [super::parse_gltf], infinite lights of uniform
+/// radiance a sphere around the scene. Scenes without infinite light are dark outside like in pbrt.
+pub fn parse_pbrt(text: &str, base: Option<&Path>, registry: &MaterialRegistry) -> io::Result<ImageConfig> {
+    let mut parser = Parser {
+        tokens: tokenize(text)?,
+        position: 0,
+        registry,
+        base,
+        state: State { transform: IDENTITY, material: None, area_light: None },
+        stack: vec![],
+        named_materials: HashMap::new(),
+        camera: None,
+        resolution: (640, 480),
+        samples_per_pixel: 16,
+        max_depth: 5,
+        objects: vec![],
+        lights: vec![],
+    };
+    while parser.position < parser.tokens.len() {
+        parser.directive()?;
+    }
+    parser.into_config()
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    /// Directive names and unquoted words
+    Word(String),
+    Text(String),
+    Number(f64),
+    Open,
+    Close,
+}
+
+/// Returns tokens with their line numbers.
+fn tokenize(text: &str) -> io::Result<Vec<(Token, usize)>> {
+    let mut tokens = vec![];
+    for (i, line) in text.lines().enumerate() {
+        let mut rest = line.trim_start();
+        while !rest.is_empty() {
+            let (token, length) = if rest.starts_with('#') {
+                break;
+            } else if let Some(quoted) = rest.strip_prefix('"') {
+                let end = quoted.find('"').ok_or_else(|| error(i + 1, "unterminated string"))?;
+                (Token::Text(quoted[..end].to_string()), end + 2)
+            } else if rest.starts_with('[') {
+                (Token::Open, 1)
+            } else if rest.starts_with(']') {
+                (Token::Close, 1)
+            } else {
+                let end = rest.find(|c: char| c.is_whitespace() || "[]\"#".contains(c)).unwrap_or(rest.len());
+                let word = &rest[..end];
+                let token = match word.parse() {
+                    Ok(number) => Token::Number(number),
+                    Err(_) => Token::Word(word.to_string()),
+                };
+                (token, end)
+            };
+            tokens.push((token, i + 1));
+            rest = rest[length..].trim_start();
+        }
+    }
+    Ok(tokens)
+}
+
+#[derive(Clone, Debug)]
+enum Value {
+    Numbers(Vec<f64>),
+    Strings(Vec<String>),
+}
+
+/// Typed parameter list following a directive, e.g. `"float radius" [2]`.
+struct ParamList {
+    /// Type, name and value of each parameter
+    params: Vec<(String, String, Value)>,
+    line: usize,
+}
+
+impl ParamList {
+    fn get(&self, name: &str) -> Option<(&str, &Value)> {
+        self.params.iter().find(|(_, n, _)| n == name).map(|(t, _, v)| (t.as_str(), v))
+    }
+
+    fn numbers(&self, name: &str) -> io::Result<Option<&[f64]>> {
+        match self.get(name) {
+            None => Ok(None),
+            Some((_, Value::Numbers(numbers))) => Ok(Some(numbers)),
+            Some((kind, _)) => Err(error(self.line, format!("parameter \"{} {}\" must be numeric", kind, name))),
+        }
+    }
+
+    fn float(&self, name: &str, default: f64) -> io::Result<f64> {
+        match self.numbers(name)? {
+            None => Ok(default),
+            Some([value]) => Ok(*value),
+            Some(_) => Err(error(self.line, format!("parameter '{}' must be a single number", name))),
+        }
+    }
+
+    /// Returns RGB color, a point or a normal.
+    fn vec3(&self, name: &str, default: Vec3) -> io::Result<Vec3> {
+        if let Some((kind, _)) = self.get(name) {
+            if !["rgb", "color", "point", "point3", "normal", "normal3", "vector", "vector3"].contains(&kind) {
+                return Err(error(self.line, format!("parameter \"{} {}\" is not supported, only RGB values", kind, name)));
+            }
+        }
+        match self.numbers(name)? {
+            None => Ok(default),
+            Some([x, y, z]) => Ok(Vec3::new(*x, *y, *z)),
+            Some(_) => Err(error(self.line, format!("parameter '{}' must have 3 values", name))),
+        }
+    }
+
+    /// Returns list of 3D points or normals.
+    fn vec3s(&self, name: &str) -> io::Result<Option<Vec<Vec3>>> {
+        match self.numbers(name)? {
+            None => Ok(None),
+            Some(numbers) if numbers.len() % 3 == 0 => Ok(Some(numbers.chunks_exact(3).map(|c| Vec3::new(c[0], c[1], c[2])).collect())),
+            Some(_) => Err(error(self.line, format!("number of values of '{}' is not a multiple of 3", name))),
+        }
+    }
+
+    fn string(&self, name: &str) -> io::Result<Option<&str>> {
+        match self.get(name) {
+            None => Ok(None),
+            Some((_, Value::Strings(strings))) if strings.len() == 1 => Ok(Some(&strings[0])),
+            Some((kind, _)) => Err(error(self.line, format!("parameter \"{} {}\" must be a single string", kind, name))),
+        }
+    }
+}
+
+/// Attributes saved by `AttributeBegin`.
+#[derive(Clone)]
+struct State {
+    /// Current transformation from object to world space
+    transform: Matrix,
+    /// `None` for the default, a matte material
+    material: Option<Arc<dyn Material>>,
+    /// Radiance of shapes in the current area light
+    area_light: Option<Vec3>,
+}
+
+/// Light source that is sized relative to the scene.
+enum Light {
+    Point { position: Vec3, intensity: Vec3 },
+    Spot { position: Vec3, direction: Vec3, intensity: Vec3, cos_inner: f64, cos_outer: f64 },
+    /// Light shining along `direction`
+    Distant { direction: Vec3, irradiance: Vec3 },
+    Infinite { radiance: Vec3 },
+}
+
+/// Saved state of `AttributeBegin` or `TransformBegin`.
+enum Saved {
+    Attributes(State),
+    Transform(Matrix),
+}
+
+struct Parser<'a> {
+    tokens: Vec<(Token, usize)>,
+    position: usize,
+    registry: &'a MaterialRegistry,
+    base: Option<&'a Path>,
+    state: State,
+    stack: Vec<Saved>,
+    named_materials: HashMap<String, Arc<dyn Material>>,
+    /// Parameters of the camera with its transformation to world space
+    camera: Option<(ParamList, Matrix)>,
+    resolution: (usize, usize),
+    samples_per_pixel: i64,
+    max_depth: i64,
+    objects: Vec<Box<dyn Hittable>>,
+    lights: Vec<Light>,
+}
+
+impl<'a> Parser<'a> {
+    fn line(&self) -> usize {
+        let index = self.position.min(self.tokens.len().saturating_sub(1));
+        self.tokens.get(index).map_or(1, |(_, line)| *line)
+    }
+
+    fn next(&mut self) -> io::Result<Token> {
+        let token = self.tokens.get(self.position).map(|(token, _)| token.clone());
+        self.position += 1;
+        token.ok_or_else(|| error(self.line(), "unexpected end of file"))
+    }
+
+    fn string(&mut self) -> io::Result<String> {
+        match self.next()? {
+            Token::Text(text) => Ok(text),
+            token => Err(error(self.line(), format!("expected string, found {:?}", token))),
+        }
+    }
+
+    /// Returns `count` numbers, optionally enclosed in brackets.
+    fn numbers(&mut self, count: usize) -> io::Result<Vec<f64>> {
+        let bracketed = self.tokens.get(self.position).is_some_and(|(token, _)| *token == Token::Open);
+        if bracketed {
+            self.position += 1;
+        }
+        let mut numbers = Vec::with_capacity(count);
+        for _ in 0..count {
+            match self.next()? {
+                Token::Number(number) => numbers.push(number),
+                token => return Err(error(self.line(), format!("expected number, found {:?}", token))),
+            }
+        }
+        if bracketed && self.next()? != Token::Close {
+            return Err(error(self.line(), format!("expected {} numbers in brackets", count)));
+        }
+        Ok(numbers)
+    }
+
+    /// Returns the parameters up to the next directive.
+    fn params(&mut self) -> io::Result<ParamList> {
+        let line = self.line();
+        let mut params = vec![];
+        while let Some((Token::Text(declaration), _)) = self.tokens.get(self.position) {
+            let mut parts = declaration.split_whitespace();
+            let (Some(kind), Some(name), None) = (parts.next(), parts.next(), parts.next()) else {
+                return Err(error(self.line(), format!("expected parameter declaration, found \"{}\"", declaration)));
+            };
+            let (kind, name) = (kind.to_string(), name.to_string());
+            self.position += 1;
+            let values = match self.next()? {
+                Token::Open => {
+                    let mut values = vec![];
+                    loop {
+                        match self.next()? {
+                            Token::Close => break,
+                            token => values.push(token),
+                        }
+                    }
+                    values
+                }
+                token => vec![token],
+            };
+            let value = if values.iter().all(|v| matches!(v, Token::Number(_))) {
+                Value::Numbers(values.into_iter().filter_map(|v| if let Token::Number(n) = v { Some(n) } else { None }).collect())
+            } else if values.iter().all(|v| matches!(v, Token::Text(_))) {
+                Value::Strings(values.into_iter().filter_map(|v| if let Token::Text(s) = v { Some(s) } else { None }).collect())
+            } else {
+                return Err(error(self.line(), format!("invalid values of parameter '{}'", name)));
+            };
+            params.push((kind, name, value));
+        }
+        Ok(ParamList { params, line })
+    }
+
+    fn transform(&mut self, m: Matrix) {
+        self.state.transform = multiply(&self.state.transform, &m);
+    }
+
+    fn directive(&mut self) -> io::Result<()> {
+        let line = self.line();
+        let name = match self.next()? {
+            Token::Word(name) => name,
+            token => return Err(error(line, format!("expected directive, found {:?}", token))),
+        };
+        match name.as_str() {
+            "LookAt" => {
+                let n = self.numbers(9)?;
+                let (eye, look, up) = (Vec3::new(n[0], n[1], n[2]), Vec3::new(n[3], n[4], n[5]), Vec3::new(n[6], n[7], n[8]));
+                let direction = (look - eye).unit_vector();
+                let right = up.unit_vector().cross(direction);
+                if right.near_zero() {
+                    return Err(error(line, "up vector and viewing direction of LookAt are parallel"));
+                }
+                let right = right.unit_vector();
+                let camera_to_world = from_columns(right, direction.cross(right), direction, eye);
+                self.transform(inverse(&camera_to_world).unwrap());
+            }
+            "Translate" => {
+                let n = self.numbers(3)?;
+                self.transform(from_columns(Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, 0.0, 1.0), Vec3::new(n[0], n[1], n[2])));
+            }
+            "Scale" => {
+                let n = self.numbers(3)?;
+                self.transform(from_columns(Vec3::new(n[0], 0.0, 0.0), Vec3::new(0.0, n[1], 0.0), Vec3::new(0.0, 0.0, n[2]), Vec3::new(0.0, 0.0, 0.0)));
+            }
+            "Rotate" => {
+                let n = self.numbers(4)?;
+                let axis = Vec3::new(n[1], n[2], n[3]);
+                if axis.near_zero() {
+                    return Err(error(line, "rotation about zero axis"));
+                }
+                let (axis, (sin, cos)) = (axis.unit_vector(), n[0].to_radians().sin_cos());
+                // Rodrigues' rotation formula applied to the basis vectors
+                let rotate = |v: Vec3| cos * v + sin * axis.cross(v) + (1.0 - cos) * axis.dot(v) * axis;
+                self.transform(from_columns(rotate(Vec3::new(1.0, 0.0, 0.0)), rotate(Vec3::new(0.0, 1.0, 0.0)), rotate(Vec3::new(0.0, 0.0, 1.0)), Vec3::new(0.0, 0.0, 0.0)));
+            }
+            "Transform" | "ConcatTransform" => {
+                // Each group of four numbers is a column
+                let n = self.numbers(16)?;
+                let m: Matrix = std::array::from_fn(|column| std::array::from_fn(|row| n[4 * column + row]));
+                if [m[0][3], m[1][3], m[2][3], m[3][3]] != [0.0, 0.0, 0.0, 1.0] {
+                    return Err(error(line, "projective transformations are not supported"));
+                }
+                if name == "Transform" {
+                    self.state.transform = m;
+                } else {
+                    self.transform(m);
+                }
+            }
+            "Identity" => self.state.transform = IDENTITY,
+            "Camera" => {
+                let kind = self.string()?;
+                let params = self.params()?;
+                if kind != "perspective" {
+                    return Err(error(line, format!("unsupported camera \"{}\"", kind)));
+                }
+                // The current transformation maps world to camera space
+                let camera_to_world = inverse(&self.state.transform).ok_or_else(|| error(line, "singular camera transformation"))?;
+                self.camera = Some((params, camera_to_world));
+            }
+            "Film" => {
+                self.string()?;
+                let params = self.params()?;
+                let (x, y) = (params.float("xresolution", 640.0)?, params.float("yresolution", 480.0)?);
+                if x < 1.0 || y < 1.0 {
+                    return Err(error(line, "film resolution must be positive"));
+                }
+                self.resolution = (x as usize, y as usize);
+            }
+            "Sampler" => {
+                self.string()?;
+                self.samples_per_pixel = self.params()?.float("pixelsamples", 16.0)?.max(1.0) as i64;
+            }
+            "Integrator" => {
+                self.string()?;
+                // Rays from the camera count as one bounce here, unlike in pbrt
+                self.max_depth = self.params()?.float("maxdepth", 5.0)? as i64 + 1;
+            }
+            "PixelFilter" | "Accelerator" => {
+                self.string()?;
+                self.params()?;
+            }
+            "WorldEnd" => {}
+            "WorldBegin" => self.state.transform = IDENTITY,
+            "AttributeBegin" => self.stack.push(Saved::Attributes(self.state.clone())),
+            "TransformBegin" => self.stack.push(Saved::Transform(self.state.transform)),
+            "AttributeEnd" | "TransformEnd" => match (self.stack.pop(), name.as_str()) {
+                (Some(Saved::Attributes(state)), "AttributeEnd") => self.state = state,
+                (Some(Saved::Transform(transform)), "TransformEnd") => self.state.transform = transform,
+                _ => return Err(error(line, format!("unmatched {}", name))),
+            },
+            "Material" => {
+                let kind = self.string()?;
+                let params = self.params()?;
+                self.state.material = Some(self.material(&kind, &params)?);
+            }
+            "MakeNamedMaterial" => {
+                let name = self.string()?;
+                let params = self.params()?;
+                let kind = params.string("type")?.ok_or_else(|| error(line, format!("named material \"{}\" without type", name)))?.to_string();
+                let material = self.material(&kind, &params)?;
+                self.named_materials.insert(name, material);
+            }
+            "NamedMaterial" => {
+                let name = self.string()?;
+                let material = self.named_materials.get(&name).ok_or_else(|| error(line, format!("unknown named material \"{}\"", name)))?;
+                self.state.material = Some(material.clone());
+            }
+            "AreaLightSource" => {
+                let kind = self.string()?;
+                let params = self.params()?;
+                if kind != "diffuse" {
+                    return Err(error(line, format!("unsupported area light \"{}\"", kind)));
+                }
+                self.state.area_light = Some(params.float("scale", 1.0)? * params.vec3("L", Vec3::new(1.0, 1.0, 1.0))?);
+            }
+            "LightSource" => {
+                let kind = self.string()?;
+                let params = self.params()?;
+                self.light(&kind, &params)?;
+            }
+            "Shape" => {
+                let kind = self.string()?;
+                let params = self.params()?;
+                self.shape(&kind, &params)?;
+            }
+            _ => return Err(error(line, format!("unsupported directive {}", name))),
+        }
+        Ok(())
+    }
+
+    fn material(&self, kind: &str, params: &ParamList) -> io::Result<Arc<dyn Material>> {
+        let line = params.line;
+        let create = |name: &str, p: Params| self.registry.create(name, &p).map_err(|e| error(line, e));
+        for (kind, name, _) in params.params.iter() {
+            if kind == "texture" || kind == "spectrum" {
+                return Err(error(line, format!("parameter \"{} {}\" is not supported, only RGB values", kind, name)));
+            }
+        }
+        let roughness = |name: &str, default: f64| -> io::Result<f64> {
+            let roughness = params.float(name, params.float("roughness", default)?)?;
+            let remap = params.string("remaproughness")?.unwrap_or("true") == "true";
+            Ok(if remap && roughness > 0.0 { roughness_to_alpha(roughness) } else { roughness })
+        };
+        match kind {
+            "matte" => create("lambertian", Params::new().with("albedo", Param::Color(params.vec3("Kd", Vec3::new(0.5, 0.5, 0.5))?))),
+            "metal" => {
+                let ComplexIor { eta, k } = COPPER;
+                create("rough_conductor", Params::new()
+                    .with("eta", Param::Color(params.vec3("eta", eta)?))
+                    .with("k", Param::Color(params.vec3("k", k)?))
+                    .with("alpha_x", Param::Number(roughness("uroughness", 0.01)?))
+                    .with("alpha_y", Param::Number(roughness("vroughness", 0.01)?)))
+            }
+            "glass" => {
+                let ior = params.float("eta", params.float("index", 1.5)?)?;
+                let (alpha_x, alpha_y) = (roughness("uroughness", 0.0)?, roughness("vroughness", 0.0)?);
+                if alpha_x == 0.0 && alpha_y == 0.0 {
+                    create("dielectric", Params::new().with("ior", Param::Number(ior)))
+                } else {
+                    create("rough_dielectric", Params::new()
+                        .with("ior", Param::Number(ior))
+                        .with("alpha_x", Param::Number(alpha_x))
+                        .with("alpha_y", Param::Number(alpha_y)))
+                }
+            }
+            _ if self.registry.contains(kind) => {
+                let mut p = Params::new();
+                for (_, name, value) in params.params.iter() {
+                    let param = match value {
+                        Value::Numbers(numbers) if numbers.len() == 1 => Param::Number(numbers[0]),
+                        Value::Numbers(numbers) if numbers.len() == 3 => Param::Color(Vec3::new(numbers[0], numbers[1], numbers[2])),
+                        Value::Strings(strings) if strings.len() == 1 => Param::Text(strings[0].clone()),
+                        _ => return Err(error(line, format!("parameter '{}' must be a number, color or string", name))),
+                    };
+                    p.insert(name, param);
+                }
+                create(kind, p)
+            }
+            _ => Err(error(line, format!("unsupported material \"{}\"", kind))),
+        }
+    }
+
+    fn light(&mut self, kind: &str, params: &ParamList) -> io::Result<()> {
+        let line = params.line;
+        let scale = params.float("scale", 1.0)?;
+        let transform = &self.state.transform;
+        let from = transform_point(transform, params.vec3("from", Vec3::new(0.0, 0.0, 0.0))?);
+        let to = transform_point(transform, params.vec3("to", Vec3::new(0.0, 0.0, 1.0))?);
+        let light = match kind {
+            "point" => Light::Point { position: from, intensity: scale * params.vec3("I", Vec3::new(1.0, 1.0, 1.0))? },
+            "spot" => {
+                let cone = params.float("coneangle", 30.0)?;
+                let delta = params.float("conedeltaangle", 5.0)?;
+                Light::Spot {
+                    position: from,
+                    direction: (to - from).unit_vector(),
+                    intensity: scale * params.vec3("I", Vec3::new(1.0, 1.0, 1.0))?,
+                    cos_inner: (cone - delta).to_radians().cos(),
+                    cos_outer: cone.to_radians().cos(),
+                }
+            }
+            "distant" => Light::Distant { direction: (to - from).unit_vector(), irradiance: scale * params.vec3("L", Vec3::new(1.0, 1.0, 1.0))? },
+            "infinite" => {
+                if params.get("mapname").is_some() {
+                    return Err(error(line, "infinite lights with environment maps are not supported"));
+                }
+                Light::Infinite { radiance: scale * params.vec3("L", Vec3::new(1.0, 1.0, 1.0))? }
+            }
+            _ => return Err(error(line, format!("unsupported light source \"{}\"", kind))),
+        };
+        self.lights.push(light);
+        Ok(())
+    }
+
+    fn shape(&mut self, kind: &str, params: &ParamList) -> io::Result<()> {
+        let line = params.line;
+        let material = match (self.state.area_light, &self.state.material) {
+            (Some(emit), _) => Arc::new(DiffuseLight { emit }),
+            (None, Some(material)) => material.clone(),
+            (None, None) => self.material("matte", &ParamList { params: vec![], line })?,
+        };
+        let transform = self.state.transform;
+        match kind {
+            "sphere" => {
+                if ["zmin", "zmax", "phimax"].iter().any(|name| params.get(name).is_some()) {
+                    return Err(error(line, "partial spheres are not supported"));
+                }
+                // Non-uniform scaling is approximated by the average scale
+                let radius = params.float("radius", 1.0)? * determinant(&transform).abs().cbrt();
+                let center = transform_point(&transform, Vec3::new(0.0, 0.0, 0.0));
+                self.objects.push(Box::new(Sphere { center, radius, material }));
+            }
+            "trianglemesh" => {
+                let positions = params.vec3s("P")?.ok_or_else(|| error(line, "triangle mesh without positions \"P\""))?;
+                let indices: Vec<usize> = match params.numbers("indices")? {
+                    Some(indices) => match indices.iter().find(|&&i| i < 0.0 || i.fract() != 0.0) {
+                        Some(i) => return Err(error(line, format!("invalid triangle mesh index {}", i))),
+                        None => indices.iter().map(|&i| i as usize).collect(),
+                    },
+                    None if positions.len() == 3 => vec![0, 1, 2],
+                    None => return Err(error(line, "triangle mesh without \"indices\"")),
+                };
+                let uvs: Option<Vec<(f64, f64)>> = params.numbers("uv")?.or(params.numbers("st")?)
+                    .map(|uvs| uvs.chunks_exact(2).map(|c| (c[0], c[1])).collect());
+                let triangles: Vec<[usize; 3]> = indices.chunks_exact(3).map(|c| [c[0], c[1], c[2]]).collect();
+                if !indices.len().is_multiple_of(3) {
+                    return Err(error(line, "number of triangle mesh indices is not a multiple of 3"));
+                }
+                self.add_mesh(&positions, params.vec3s("N")?.as_deref(), uvs.as_deref(), &triangles, material, line)?;
+            }
+            "plymesh" => {
+                let filename = params.string("filename")?.ok_or_else(|| error(line, "PLY mesh without \"filename\""))?;
+                let path = self.base.map_or(Path::new(filename).to_path_buf(), |base| base.join(filename));
+                let mesh = ply::read(&path).map_err(|e| error(line, format!("{}: {}", path.display(), e)))?;
+                self.add_mesh(&mesh.positions, mesh.normals.as_deref(), mesh.uvs.as_deref(), &mesh.triangles, material, line)?;
+            }
+            _ => return Err(error(line, format!("unsupported shape \"{}\"", kind))),
+        }
+        Ok(())
+    }
+
+    /// Adds triangles in world space, with optional normals and texture coordinates per vertex.
+    fn add_mesh(&mut self, positions: &[Vec3], normals: Option<&[Vec3]>, uvs: Option<&[(f64, f64)]>, triangles: &[[usize; 3]], material: Arc<dyn Material>, line: usize) -> io::Result<()> {
+        let n = positions.len();
+        if triangles.iter().flatten().any(|&i| i >= n) {
+            return Err(error(line, "triangle mesh index out of range"));
+        }
+        if normals.is_some_and(|normals| normals.len() != n) || uvs.is_some_and(|uvs| uvs.len() != n) {
+            return Err(error(line, "triangle mesh attributes of different lengths"));
+        }
+        let transform = &self.state.transform;
+        let positions: Vec<Vec3> = positions.iter().map(|&p| transform_point(transform, p)).collect();
+        let normals: Option<Vec<Vec3>> = normals.map(|normals| normals.iter().map(|&n| transform_normal(transform, n).unit_vector()).collect());
+        // Mirroring transformations reverse the winding order
+        let mirrored = determinant(transform) < 0.0;
+        for &[a, b, c] in triangles {
+            let [a, b, c] = if mirrored { [a, c, b] } else { [a, b, c] };
+            let mut triangle = Triangle::new(positions[a], positions[b], positions[c], material.clone());
+            if let Some(uvs) = uvs {
+                triangle.uvs = [uvs[a], uvs[b], uvs[c]];
+            }
+            triangle.normals = normals.as_ref().map(|normals| [normals[a], normals[b], normals[c]]);
+            self.objects.push(Box::new(triangle));
+        }
+        Ok(())
+    }
+
+    fn into_config(self) -> io::Result<ImageConfig> {
+        let bounds = self.objects.iter().filter_map(|o| o.bounding_box()).reduce(Aabb::surrounding);
+        let (center, radius) = match bounds {
+            Some(bounds) => ((bounds.min + bounds.max) / 2.0, ((bounds.max - bounds.min) / 2.0).length().max(1.0e-3)),
+            None => (Vec3::new(0.0, 0.0, 0.0), 1.0),
+        };
+
+        let mut hittables: Vec<Box<dyn Hittable>> = vec![Box::new(Bvh::new(self.objects))];
+        let mut environment = Vec3::new(0.0, 0.0, 0.0);
+        for light in self.lights {
+            let sphere = match light {
+                Light::Point { position, intensity } => point_light(position, intensity, radius),
+                Light::Spot { position, direction, intensity, cos_inner, cos_outer } => {
+                    spot_light(position, direction, intensity, (cos_inner, cos_outer), radius)
+                }
+                Light::Distant { direction, irradiance } => distant_light(direction, irradiance, center, radius),
+                Light::Infinite { radiance } => {
+                    environment = environment + radiance;
+                    continue;
+                }
+            };
+            hittables.push(Box::new(sphere));
+        }
+        // Hides the sky, which would light the scene unlike in pbrt
+        hittables.push(Box::new(Sphere { center, radius: 10_000.0 * radius, material: Arc::new(DiffuseLight { emit: environment }) }));
+
+        let (width, height) = self.resolution;
+        let (params, camera_to_world) = self.camera.ok_or_else(|| error(1, "scene has no camera"))?;
+        let aspect_ratio = params.float("frameaspectratio", width as f64 / height as f64)?;
+        // Field of view is given for the shorter image axis
+        let fov = params.float("fov", 90.0)?;
+        let vfov = if aspect_ratio >= 1.0 { fov } else { 2.0 * ((fov.to_radians() / 2.0).tan() / aspect_ratio).atan().to_degrees() };
+        // Cameras look along their local +z axis with y up and x to the right
+        let orientation = Orientation {
+            origin: transform_point(&camera_to_world, Vec3::new(0.0, 0.0, 0.0)),
+            u: transform_vector(&camera_to_world, Vec3::new(1.0, 0.0, 0.0)).unit_vector(),
+            v: transform_vector(&camera_to_world, Vec3::new(0.0, 1.0, 0.0)).unit_vector(),
+            w: -transform_vector(&camera_to_world, Vec3::new(0.0, 0.0, 1.0)).unit_vector(),
+        };
+        // Ray directions scale with the focus distance, which is irrelevant for pinholes
+        let lens_radius = params.float("lensradius", 0.0)?;
+        let lens = if lens_radius > 0.0 { ThinLens::new(2.0 * lens_radius, params.float("focaldistance", 1.0e6)?) } else { ThinLens::pinhole() };
+        let camera = Box::new(PerspectiveCamera::new(orientation, vfov, aspect_ratio, lens, Shutter::instant(0.0)));
+
+        Ok(ImageConfig {
+            aspect_ratio,
+            image_width: width as i64,
+            image_height: height as i64,
+            samples_per_pixel: self.samples_per_pixel,
+            max_depth: self.max_depth,
+            world: Hittables { hittables },
+            camera,
+            spectral: false,
+        })
+    }
+}
+
+/// Maps roughness to microfacet alpha like pbrt-v3.
+fn roughness_to_alpha(roughness: f64) -> f64 {
+    let x = roughness.max(1.0e-3).ln();
+    1.62142 + 0.819955 * x + 0.1734 * x * x + 0.0171201 * x * x * x + 0.000640711 * x * x * x * x
+}
+
+fn error(line: usize, message: impl std::fmt::Display) -> Error {
+    Error::new(ErrorKind::InvalidData, format!("line {}: {}", line, message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Ray;
+
+    const SCENE: &str = r#"
+        LookAt 0 0 5  0 0 0  0 1 0 # looking down -z
+        Camera "perspective" "float fov" [30]
+        Film "image" "integer xresolution" [200] "integer yresolution" [100] "string filename" "out.exr"
+        Sampler "halton" "integer pixelsamples" 64
+        Integrator "path" "integer maxdepth" [3]
+        WorldBegin
+        LightSource "point" "rgb I" [4 4 4] "point from" [0 3 0]
+        AttributeBegin
+          Material "matte" "rgb Kd" [0.8 0.2 0.2]
+          Translate 1 0 0
+          Scale 0.5 0.5 0.5
+          Shape "sphere" "float radius" 2
+        AttributeEnd
+        MakeNamedMaterial "shiny" "string type" "principled" "float metallic" 1 "rgb base_color" [1 0.8 0.4]
+        NamedMaterial "shiny"
+        Shape "trianglemesh" "integer indices" [0 1 2] "point P" [-3 -1 -1  -1 -1 -1  -2 1 -1]
+        WorldEnd
+    "#;
+
+    #[test]
+    fn test_parse_pbrt() {
+        let registry = MaterialRegistry::default();
+        let conf = parse_pbrt(SCENE, None, &registry).unwrap();
+        assert!(conf.image_width == 200 && conf.image_height == 100 && conf.samples_per_pixel == 64 && conf.max_depth == 4);
+
+        let mut rng = rand::thread_rng();
+        let ray = conf.camera.get_ray(0.5, 0.5, &mut rng).unwrap();
+        assert!((ray.direction.unit_vector() - Vec3::new(0.0, 0.0, -1.0)).length() < 1e-9);
+        // Sphere of radius 1 around (1, 0, 0) appears on the left, pbrt's image x axis is up × direction
+        let left = conf.camera.get_ray(0.4, 0.5, &mut rng).unwrap();
+        assert!(left.direction.x > 0.0);
+        let hit = conf.world.hit(&left, 0.001, f64::MAX).unwrap();
+        assert!(((hit.point - Vec3::new(1.0, 0.0, 0.0)).length() - 1.0).abs() < 1e-9);
+        let toward_triangle = Ray { direction: Vec3::new(-2.0, -0.3, -6.0), ..left };
+        assert!((conf.world.hit(&toward_triangle, 0.001, f64::MAX).unwrap().point.z + 1.0).abs() < 1e-9);
+
+        // Point light above the scene, everything else is dark
+        let up = Ray { origin: Vec3::new(0.0, 0.0, 0.0), direction: Vec3::new(0.0, 1.0, 0.0), ..left };
+        let hit = conf.world.hit(&up, 0.001, f64::MAX).unwrap();
+        assert!(hit.t < 3.0 && hit.material.emitted(&up, &hit).x > 0.0);
+        let down = Ray { direction: Vec3::new(0.0, -1.0, 0.0), ..up };
+        let hit = conf.world.hit(&down, 0.001, f64::MAX).unwrap();
+        assert_eq!(hit.material.emitted(&down, &hit), Vec3::new(0.0, 0.0, 0.0));
+
+        for (scene, message) in [
+            (SCENE.replace("Shape \"sphere\"", "Shape \"disk\""), "line 13: unsupported shape \"disk\""),
+            (SCENE.replace("Translate 1 0 0", "CoordinateSystem \"a\""), "line 11: unsupported directive CoordinateSystem"),
+            (SCENE.replace("\"matte\"", "\"plastic\""), "line 10: unsupported material \"plastic\""),
+            (SCENE.replace("\"rgb Kd\" [0.8 0.2 0.2]", "\"spectrum Kd\" \"gold.spd\""), "line 10: parameter \"spectrum Kd\" is not supported"),
+            (SCENE.replace("AttributeBegin", ""), "line 14: unmatched AttributeEnd"),
+            (SCENE.replace("Translate 1 0 0", "ReverseOrientation"), "line 11: unsupported directive ReverseOrientation"),
+            (SCENE.replace("[0 1 2]", "[0 -1 2]"), "line 17: invalid triangle mesh index -1"),
+            (SCENE.replace("[0 1 2]", "[0 1.5 2]"), "line 17: invalid triangle mesh index 1.5"),
+        ] {
+            let error = parse_pbrt(&scene, None, &registry).err().unwrap();
+            assert!(error.to_string().starts_with(message), "{}", error);
+        }
+    }
+}
@@ -1,13 +1,17 @@
-use rand::prelude::ThreadRng;
-use rand::Rng;
+use rand::{Rng, RngCore};
 
-use crate::{Camera, Material, Vec3};
+use std::sync::Arc;
+
+use crate::Vec3;
+use crate::material::{Dielectric, Lambertian, Metal, RefractiveIndex};
+use crate::camera::{Orientation, PerspectiveCamera, Shutter, ThinLens};
 use crate::configs::ImageConfig;
 use crate::objects::{Hittable, Hittables, MovingSphere, Sphere};
+use crate::texture::{SolidColor, Texture};
 
-fn random_scene(rng: &mut ThreadRng) -> Hittables {
+fn random_scene(rng: &mut dyn RngCore, ground: Arc<dyn Texture>) -> Hittables {
     let mut hittables: Vec<Box<dyn Hittable>> = Vec::new();
-    let ground_material = Material::Lambertian { albedo: Vec3::new(0.5, 0.5, 0.5) };
+    let ground_material = Arc::new(Lambertian::textured(ground));
     hittables.push(Box::new(Sphere {
         center: Vec3::new(0.0, -1000.0, 0.0),
         radius: 1000.0,
@@ -32,7 +36,7 @@ fn random_scene(rng: &mut ThreadRng) -> Hittables {
                         rng.gen::<f64>() * rng.gen::<f64>(),
                         rng.gen::<f64>() * rng.gen::<f64>(),
                     );
-                    let material = Material::Lambertian { albedo };
+                    let material = Arc::new(Lambertian::new(albedo));
                     Sphere { center, radius: 0.2, material }
                 } else if choose_mat < 0.95 {
                     // Metal
@@ -41,11 +45,11 @@ fn random_scene(rng: &mut ThreadRng) -> Hittables {
                         0.5 * (1.0 + rng.gen::<f64>()),
                         0.5 * (1.0 + rng.gen::<f64>()),
                     );
-                    let material = Material::Metal { albedo, fuzz: 0.5 * rng.gen::<f64>() };
+                    let material = Arc::new(Metal { albedo, fuzz: 0.5 * rng.gen::<f64>() });
                     Sphere { center, radius: 0.2, material }
                 } else {
                     // Glass
-                    Sphere { center, radius: 0.2, material: Material::Dielectric { refractive_index: 1.5 } }
+                    Sphere { center, radius: 0.2, material: Arc::new(Dielectric::new(RefractiveIndex::Constant(1.5))) }
                 };
                 hittables.push(Box::new(sphere));
             }
@@ -57,25 +61,30 @@ fn random_scene(rng: &mut ThreadRng) -> Hittables {
     hittables.push(Box::new(Sphere {
         center: Vec3::new(0.0, 1.0, 0.0),
         radius: 1.0,
-        material: Material::Dielectric { refractive_index: 1.5 },
+        material: Arc::new(Dielectric::new(RefractiveIndex::Constant(1.5))),
     }));
     // Diffuse
     hittables.push(Box::new(Sphere {
         center: Vec3::new(-4.0, 1.0, 0.0),
         radius: 1.0,
-        material: Material::Lambertian { albedo: Vec3::new(0.4, 0.2, 0.1) },
+        material: Arc::new(Lambertian::new(Vec3::new(0.4, 0.2, 0.1))),
     }));
     // Metal
     hittables.push(Box::new(Sphere {
         center: Vec3::new(4.0, 1.0, 0.0),
         radius: 1.0,
-        material: Material::Metal { albedo: Vec3::new(0.7, 0.6, 0.5), fuzz: 0.0 },
+        material: Arc::new(Metal { albedo: Vec3::new(0.7, 0.6, 0.5), fuzz: 0.0 }),
     }));
 
     Hittables { hittables }
 }
 
-pub fn random_spheres(rng: &mut ThreadRng) -> ImageConfig {
+pub fn random_spheres(rng: &mut dyn RngCore) -> ImageConfig {
+    random_spheres_on(rng, Arc::new(SolidColor::gray(0.5)))
+}
+
+/// Returns [random_spheres] scene with a textured ground, e.g. [crate::texture::Marble].
+pub fn random_spheres_on(rng: &mut dyn RngCore, ground: Arc<dyn Texture>) -> ImageConfig {
     // Image
     let aspect_ratio = 3.0 / 2.0;
     let image_width = 1200;
@@ -84,20 +93,16 @@ pub fn random_spheres(rng: &mut ThreadRng) -> ImageConfig {
     let max_depth = 50;
 
     // World
-    let world = random_scene(rng);
+    let world = random_scene(rng, ground);
 
     // Camera
-    let camera = Camera::new(
-        Vec3::new(13.0, 2.0, 3.0),
-        Vec3::new(0.0, 0.0, 0.0),
-        Vec3::new(0.0, 1.0, 0.0),
+    let camera = Box::new(PerspectiveCamera::new(
+        Orientation::new(Vec3::new(13.0, 2.0, 3.0), Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0)),
         20.0,
         aspect_ratio,
-        0.1,
-        10.0,
-        0.0,
-        0.0,
-    );
+        ThinLens::new(0.1, 10.0),
+        Shutter { time0: 0.0, time1: 0.0 },
+    ));
     ImageConfig {
         aspect_ratio,
         image_width,
@@ -106,12 +111,13 @@ pub fn random_spheres(rng: &mut ThreadRng) -> ImageConfig {
         max_depth,
         world,
         camera,
+        spectral: false,
     }
 }
 
-fn moving_random_scene(rng: &mut ThreadRng) -> Hittables {
+fn moving_random_scene(rng: &mut dyn RngCore) -> Hittables {
     let mut hittables: Vec<Box<dyn Hittable>> = Vec::new();
-    let ground_material = Material::Lambertian { albedo: Vec3::new(0.5, 0.5, 0.5) };
+    let ground_material = Arc::new(Lambertian::new(Vec3::new(0.5, 0.5, 0.5)));
     hittables.push(Box::new(Sphere {
         center: Vec3::new(0.0, -1000.0, 0.0),
         radius: 1000.0,
@@ -136,7 +142,7 @@ fn moving_random_scene(rng: &mut ThreadRng) -> Hittables {
                         rng.gen::<f64>() * rng.gen::<f64>(),
                         rng.gen::<f64>() * rng.gen::<f64>(),
                     );
-                    let material = Material::Lambertian { albedo };
+                    let material = Arc::new(Lambertian::new(albedo));
                     let center1 = center + Vec3::new(0.0, rng.gen_range(0.0..0.5), 0.0);
                     Box::new(MovingSphere { center0: center, center1, time0: 0.0, time1: 1.0, radius: 0.2, material })
                 } else if choose_mat < 0.95 {
@@ -146,11 +152,11 @@ fn moving_random_scene(rng: &mut ThreadRng) -> Hittables {
                         0.5 * (1.0 + rng.gen::<f64>()),
                         0.5 * (1.0 + rng.gen::<f64>()),
                     );
-                    let material = Material::Metal { albedo, fuzz: 0.5 * rng.gen::<f64>() };
+                    let material = Arc::new(Metal { albedo, fuzz: 0.5 * rng.gen::<f64>() });
                     Box::new(Sphere { center, radius: 0.2, material })
                 } else {
                     // Glass
-                    Box::new(Sphere { center, radius: 0.2, material: Material::Dielectric { refractive_index: 1.5 } })
+                    Box::new(Sphere { center, radius: 0.2, material: Arc::new(Dielectric::new(RefractiveIndex::Constant(1.5))) })
                 };
                 hittables.push(sphere);
             }
@@ -162,25 +168,25 @@ fn moving_random_scene(rng: &mut ThreadRng) -> Hittables {
     hittables.push(Box::new(Sphere {
         center: Vec3::new(0.0, 1.0, 0.0),
         radius: 1.0,
-        material: Material::Dielectric { refractive_index: 1.5 },
+        material: Arc::new(Dielectric::new(RefractiveIndex::Constant(1.5))),
     }));
     // Diffuse
     hittables.push(Box::new(Sphere {
         center: Vec3::new(-4.0, 1.0, 0.0),
         radius: 1.0,
-        material: Material::Lambertian { albedo: Vec3::new(0.4, 0.2, 0.1) },
+        material: Arc::new(Lambertian::new(Vec3::new(0.4, 0.2, 0.1))),
     }));
     // Metal
     hittables.push(Box::new(Sphere {
         center: Vec3::new(4.0, 1.0, 0.0),
         radius: 1.0,
-        material: Material::Metal { albedo: Vec3::new(0.7, 0.6, 0.5), fuzz: 0.0 },
+        material: Arc::new(Metal { albedo: Vec3::new(0.7, 0.6, 0.5), fuzz: 0.0 }),
     }));
 
     Hittables { hittables }
 }
 
-pub fn moving_random_spheres(rng: &mut ThreadRng) -> ImageConfig {
+pub fn moving_random_spheres(rng: &mut dyn RngCore) -> ImageConfig {
     // Image
     let aspect_ratio = 16.0 / 9.0;
     let image_width = 400;
@@ -192,17 +198,13 @@ pub fn moving_random_spheres(rng: &mut ThreadRng) -> ImageConfig {
     let world = moving_random_scene(rng);
 
     // Camera
-    let camera = Camera::new(
-        Vec3::new(13.0, 2.0, 3.0),
-        Vec3::new(0.0, 0.0, 0.0),
-        Vec3::new(0.0, 1.0, 0.0),
+    let camera = Box::new(PerspectiveCamera::new(
+        Orientation::new(Vec3::new(13.0, 2.0, 3.0), Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0)),
         20.0,
         aspect_ratio,
-        0.1,
-        10.0,
-        0.0,
-        1.0,
-    );
+        ThinLens::new(0.1, 10.0),
+        Shutter { time0: 0.0, time1: 1.0 },
+    ));
     ImageConfig {
         aspect_ratio,
         image_width,
@@ -211,5 +213,6 @@ pub fn moving_random_spheres(rng: &mut ThreadRng) -> ImageConfig {
         max_depth,
         world,
         camera,
+        spectral: false,
     }
 }
\ No newline at end of file
@@ -0,0 +1,831 @@
+//! Saving and loading scenes in the plain-text format of [crate::scene].
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Error, ErrorKind};
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::camera::{
+    Aperture, ApertureMask, Camera, EquirectangularCamera, FisheyeCamera, FisheyeMapping, LensElement, OmniStereoCamera, Orientation, OrthographicCamera,
+    PerspectiveCamera, RealisticCamera, Shutter, StereoLayout, ThinLens,
+};
+use crate::configs::ImageConfig;
+use crate::netpbm::Image;
+use crate::noise::Noise;
+use crate::material::{Material, MaterialRegistry, MaterialTable, Param, Params};
+use crate::medium::{DensityField, NoiseDensity, VoxelGrid};
+use crate::objects::{
+    Bvh, Cone, Csg, Curve, CurveShape, Cylinder, Heightfield, Hittable, Hittables, Hyperboloid, MovingSphere, Operation, Paraboloid, Placement,
+    PointCloud, Quad, Sdf, SdfShape, Solid, Sphere, Splat, Torus, Triangle, Volume, Voxels,
+};
+use crate::scene::{Node, Value};
+use crate::{Frame, Vec3};
+use crate::texture::{Checker, Cloud, Filter, Granite, ImageTexture, Mapping, Marble, SolidColor, Space, Texture, VertexColor, Wood, Wrap};
+
+/// Saves scene, e.g. one generated by [super::random_spheres], see [describe_scene].
+pub fn save_scene<P: AsRef<Path>>(conf: &ImageConfig, path: P) -> io::Result<()> {
+    fs::write(path, describe_scene(conf)?.to_string())
+}
+
+/// Returns description of the image settings, camera, materials and objects. Materials are listed
+/// once and referred to by name, so objects sharing one still do after loading. Fails if the
+/// camera, an object or its material doesn't implement `describe`, see [Camera::describe],
+/// [Hittable::describe] and [Material::describe].
+pub fn describe_scene(conf: &ImageConfig) -> io::Result<Node> {
+    let camera = conf.camera.describe().ok_or_else(|| invalid("camera can't be saved".to_string()))?;
+    let mut materials = MaterialTable::new();
+    let objects = conf.world.hittables.iter().enumerate()
+        .map(|(i, hittable)| hittable.describe(&mut materials).ok_or_else(|| invalid(format!("object {} can't be saved", i))))
+        .collect::<io::Result<Vec<_>>>()?;
+    Ok(Node::new("image")
+        .with("aspect_ratio", conf.aspect_ratio)
+        .with("image_width", conf.image_width as f64)
+        .with("image_height", conf.image_height as f64)
+        .with("samples_per_pixel", conf.samples_per_pixel as f64)
+        .with("max_depth", conf.max_depth as f64)
+        .with("spectral", conf.spectral)
+        .with("camera", camera)
+        .with("materials", materials.describe())
+        .with("world", objects))
+}
+
+/// Loads scene saved by [save_scene], see [parse_scene].
+pub fn load_scene<P: AsRef<Path>>(path: P, registry: &MaterialRegistry) -> io::Result<ImageConfig> {
+    parse_scene(&fs::read_to_string(path)?, registry)
+}
+
+/// Returns scene of the text written by [save_scene], identical to the saved one. Materials are
+/// created by `registry` from their name and parameters, so downstream materials load as well if
+/// they are registered and implement [Material::describe].
+pub fn parse_scene(text: &str, registry: &MaterialRegistry) -> io::Result<ImageConfig> {
+    let node = Node::parse(text)?;
+    if node.kind != "image" {
+        return Err(node.invalid("expected image".to_string()));
+    }
+    let materials = node.node("materials")?.fields.iter()
+        .map(|(name, value)| match value {
+            Value::Node(material_node) => Ok((name.clone(), material(material_node, registry)?)),
+            _ => Err(invalid(format!("material '{}' must be a node", name))),
+        })
+        .collect::<io::Result<Materials>>()?;
+    let hittables = node.list("world")?.iter().map(|object| hittable(object, &materials)).collect::<io::Result<_>>()?;
+    Ok(ImageConfig {
+        aspect_ratio: node.number("aspect_ratio")?,
+        image_width: node.integer("image_width")?,
+        image_height: node.integer("image_height")?,
+        samples_per_pixel: node.integer("samples_per_pixel")?,
+        max_depth: node.integer("max_depth")?,
+        world: Hittables { hittables },
+        camera: camera(node.node("camera")?)?,
+        spectral: node.bool("spectral")?,
+    })
+}
+
+/// Materials of the scene by name
+type Materials = HashMap<String, Arc<dyn Material>>;
+
+/// Returns material of the scene that `node` refers to.
+fn material_ref(node: &Node, materials: &Materials) -> io::Result<Arc<dyn Material>> {
+    let name = node.text("material")?;
+    materials.get(name).cloned().ok_or_else(|| node.invalid(format!("undefined material '{}'", name)))
+}
+
+fn hittable(node: &Node, materials: &Materials) -> io::Result<Box<dyn Hittable>> {
+    let objects = |node: &Node| node.list("objects")?.iter().map(|object| hittable(object, materials)).collect::<io::Result<Vec<_>>>();
+    Ok(match node.kind.as_str() {
+        "sphere" | "moving_sphere" | "volume" | "csg" => solid(node, materials)?,
+        "triangle" => {
+            let uvs = match node.numbers("uvs")? {
+                &[u0, v0, u1, v1, u2, v2] => [(u0, v0), (u1, v1), (u2, v2)],
+                _ => return Err(node.invalid("field 'uvs' must have 6 numbers".to_string())),
+            };
+            Box::new(Triangle {
+                vertices: node.vectors("vertices")?,
+                uvs,
+                colors: node.get("colors").map(|_| node.vectors("colors")).transpose()?,
+                normals: node.get("normals").map(|_| node.vectors("normals")).transpose()?,
+                material: material_ref(node, materials)?,
+            })
+        }
+        "quad" => Box::new(Quad {
+            q: node.vector("q")?,
+            u: node.vector("u")?,
+            v: node.vector("v")?,
+            material: material_ref(node, materials)?,
+        }),
+        "cylinder" => Box::new(cylinder(node, materials)?),
+        "cone" => Box::new(Cone {
+            placement: placement(node)?,
+            radius: node.number("radius")?,
+            height: node.number("height")?,
+            phi_max: node.number("phi_max")?,
+            material: material_ref(node, materials)?,
+        }),
+        "paraboloid" => Box::new(Paraboloid {
+            placement: placement(node)?,
+            radius: node.number("radius")?,
+            z_min: node.number("z_min")?,
+            z_max: node.number("z_max")?,
+            phi_max: node.number("phi_max")?,
+            material: material_ref(node, materials)?,
+        }),
+        "hyperboloid" => {
+            let (p1, p2) = (node.vector("p1")?, node.vector("p2")?);
+            if p1.z == p2.z {
+                return Err(node.invalid("points must differ in z".to_string()));
+            }
+            Box::new(Hyperboloid { placement: placement(node)?, p1, p2, phi_max: node.number("phi_max")?, material: material_ref(node, materials)? })
+        }
+        "torus" => Box::new(torus(node, materials)?),
+        "sdf" => Box::new(SdfShape {
+            epsilon: node.number("epsilon")?,
+            max_steps: node.integer("max_steps")? as usize,
+            step: node.number("step")?,
+            ..SdfShape::new(sdf(node.node("sdf")?)?, node.vector("min")?, node.vector("max")?, material_ref(node, materials)?)
+        }),
+        "heightfield" => {
+            let resolution = match node.numbers("resolution")? {
+                &[x, z] if [x, z].iter().all(|n| *n >= 2.0 && n.fract() == 0.0) => (x as usize, z as usize),
+                _ => return Err(node.invalid("field 'resolution' must be 2 integers of at least 2".to_string())),
+            };
+            let heights = node.numbers("heights")?.to_vec();
+            if heights.len() != resolution.0 * resolution.1 {
+                return Err(node.invalid("number of heights doesn't match the resolution".to_string()));
+            }
+            Box::new(Heightfield::from_scaled_heights(node.vector("origin")?, node.vector("size")?, resolution, heights, material_ref(node, materials)?))
+        }
+        "curve" => {
+            let widths = match node.numbers("widths")? {
+                &[w0, w1] => (w0, w1),
+                _ => return Err(node.invalid("field 'widths' must have 2 numbers".to_string())),
+            };
+            let shape = match node.text("shape")? {
+                "flat" => CurveShape::Flat,
+                "cylinder" => CurveShape::Cylinder,
+                "ribbon" => CurveShape::Ribbon { normals: node.vectors("normals")? },
+                shape => return Err(node.invalid(format!("unknown shape '{}'", shape))),
+            };
+            Box::new(Curve::new(node.vectors("control_points")?, widths, shape, material_ref(node, materials)?))
+        }
+        "voxels" => {
+            let dimensions = match node.numbers("dimensions")? {
+                &[x, y, z] if [x, y, z].iter().all(|n| *n >= 1.0 && n.fract() == 0.0) => [x as usize, y as usize, z as usize],
+                _ => return Err(node.invalid("field 'dimensions' must be 3 positive integers".to_string())),
+            };
+            let values = node.numbers("values")?;
+            if values.len() != dimensions.iter().product::<usize>() || !values.iter().all(|v| (0.0..=255.0).contains(v) && v.fract() == 0.0) {
+                return Err(node.invalid("field 'values' must have one byte per voxel".to_string()));
+            }
+            let palette = node.list("palette")?.iter().map(|entry| material_ref(entry, materials)).collect::<io::Result<_>>()?;
+            let mut grid = Voxels::new(node.vector("origin")?, node.number("voxel_size")?, dimensions, palette);
+            // Values are stored with x varying fastest, then y
+            for (i, &value) in values.iter().enumerate() {
+                let voxel = [i % dimensions[0], i / dimensions[0] % dimensions[1], i / (dimensions[0] * dimensions[1])];
+                grid.set(voxel, value as u8);
+            }
+            Box::new(grid)
+        }
+        "point_cloud" => {
+            let vectors = |name: &str| -> io::Result<Vec<Vec3>> {
+                let numbers = node.numbers(name)?;
+                if numbers.len() % 3 != 0 {
+                    return Err(node.invalid(format!("field '{}' must have a multiple of 3 numbers", name)));
+                }
+                Ok(numbers.chunks_exact(3).map(|c| Vec3::new(c[0], c[1], c[2])).collect())
+            };
+            let (offsets, radii, normals, colors) = (vectors("offsets")?, node.numbers("radii")?, vectors("normals")?, vectors("colors")?);
+            if radii.len() != offsets.len() || ![&normals, &colors].iter().all(|v| v.is_empty() || v.len() == offsets.len()) {
+                return Err(node.invalid("number of radii, normals or colors doesn't match the number of points".to_string()));
+            }
+            let splat = match node.text("splat")? {
+                "disk" => Splat::Disk,
+                "sphere" => Splat::Sphere,
+                splat => return Err(node.invalid(format!("unknown splat '{}'", splat))),
+            };
+            Box::new(PointCloud::around(node.vector("center")?, &offsets, radii, &normals, &colors, splat, material_ref(node, materials)?))
+        }
+        "list" => Box::new(Hittables { hittables: objects(node)? }),
+        "bvh" => Box::new(Bvh::new(objects(node)?)),
+        kind => return Err(invalid(format!("unknown object '{}'", kind))),
+    })
+}
+
+/// Returns solid described by `node`, e.g. an operand of CSG.
+fn solid(node: &Node, materials: &Materials) -> io::Result<Box<dyn Solid>> {
+    Ok(match node.kind.as_str() {
+        "sphere" => Box::new(Sphere {
+            center: node.vector("center")?,
+            radius: node.number("radius")?,
+            material: material_ref(node, materials)?,
+        }),
+        "moving_sphere" => Box::new(MovingSphere {
+            center0: node.vector("center0")?,
+            center1: node.vector("center1")?,
+            time0: node.number("time0")?,
+            time1: node.number("time1")?,
+            radius: node.number("radius")?,
+            material: material_ref(node, materials)?,
+        }),
+        "volume" => Box::new(Volume { min: node.vector("min")?, max: node.vector("max")?, material: material_ref(node, materials)? }),
+        "cylinder" => {
+            let cylinder = cylinder(node, materials)?;
+            if !cylinder.is_closed() {
+                return Err(node.invalid("must be capped and swept a full turn to be a solid".to_string()));
+            }
+            Box::new(cylinder)
+        }
+        "torus" => {
+            let torus = torus(node, materials)?;
+            if !torus.is_closed() {
+                return Err(node.invalid("must be swept a full turn to be a solid".to_string()));
+            }
+            Box::new(torus)
+        }
+        "csg" => {
+            let operation = match node.text("operation")? {
+                "union" => Operation::Union,
+                "intersection" => Operation::Intersection,
+                "difference" => Operation::Difference,
+                operation => return Err(node.invalid(format!("unknown operation '{}'", operation))),
+            };
+            Box::new(Csg { operation, left: solid(node.node("left")?, materials)?, right: solid(node.node("right")?, materials)? })
+        }
+        kind => return Err(invalid(format!("unknown solid '{}'", kind))),
+    })
+}
+
+fn cylinder(node: &Node, materials: &Materials) -> io::Result<Cylinder> {
+    Ok(Cylinder {
+        placement: placement(node)?,
+        radius: node.number("radius")?,
+        z_min: node.number("z_min")?,
+        z_max: node.number("z_max")?,
+        phi_max: node.number("phi_max")?,
+        capped: node.bool("capped")?,
+        material: material_ref(node, materials)?,
+    })
+}
+
+fn torus(node: &Node, materials: &Materials) -> io::Result<Torus> {
+    Ok(Torus {
+        placement: placement(node)?,
+        major_radius: node.number("major_radius")?,
+        minor_radius: node.number("minor_radius")?,
+        phi_max: node.number("phi_max")?,
+        material: material_ref(node, materials)?,
+    })
+}
+
+/// Returns placement of an analytic shape, whose axes must be orthonormal.
+fn placement(node: &Node) -> io::Result<Placement> {
+    let [s, t, n] = node.vectors("axes")?;
+    let orthonormal = [s, t, n].iter().all(|a| (a.length() - 1.0).abs() < 1.0e-6)
+        && s.dot(t).abs() < 1.0e-6 && t.dot(n).abs() < 1.0e-6 && n.dot(s).abs() < 1.0e-6;
+    if !orthonormal {
+        return Err(node.invalid("field 'axes' must be orthonormal".to_string()));
+    }
+    Ok(Placement { origin: node.vector("origin")?, frame: Frame { s, t, n } })
+}
+
+/// Returns signed distance function described by `node`, see [Sdf::describe].
+fn sdf(node: &Node) -> io::Result<Sdf> {
+    let shape = || Ok::<_, Error>(Box::new(sdf(node.node("shape")?)?));
+    let operands = || Ok::<_, Error>((Box::new(sdf(node.node("a")?)?), Box::new(sdf(node.node("b")?)?)));
+    Ok(match node.kind.as_str() {
+        "sphere" => Sdf::Sphere { radius: node.number("radius")? },
+        "rounded_box" => Sdf::RoundedBox { half_size: node.vector("half_size")?, radius: node.number("radius")? },
+        "torus" => Sdf::Torus { major_radius: node.number("major_radius")?, minor_radius: node.number("minor_radius")? },
+        "cylinder" => Sdf::Cylinder { radius: node.number("radius")?, half_height: node.number("half_height")? },
+        "mandelbulb" => Sdf::Mandelbulb { power: node.number("power")?, iterations: node.integer("iterations")? as u32 },
+        "translate" => Sdf::Translate { offset: node.vector("offset")?, shape: shape()? },
+        "scale" => Sdf::Scale { factor: node.number("factor")?, shape: shape()? },
+        "twist" => Sdf::Twist { rate: node.number("rate")?, shape: shape()? },
+        "repeat" => Sdf::Repeat { period: node.vector("period")?, shape: shape()? },
+        "union" => {
+            let (a, b) = operands()?;
+            Sdf::Union(a, b)
+        }
+        "intersection" => {
+            let (a, b) = operands()?;
+            Sdf::Intersection(a, b)
+        }
+        "subtract" => {
+            let (a, b) = operands()?;
+            Sdf::Subtract(a, b)
+        }
+        "smooth_union" => {
+            let (a, b) = operands()?;
+            Sdf::SmoothUnion { a, b, k: node.number("k")? }
+        }
+        "smooth_subtract" => {
+            let (a, b) = operands()?;
+            Sdf::SmoothSubtract { a, b, k: node.number("k")? }
+        }
+        kind => return Err(node.invalid(format!("unknown distance function '{}'", kind))),
+    })
+}
+
+/// Creates material with the registry, vectors become colors and nodes textures or nested
+/// materials.
+fn material(node: &Node, registry: &MaterialRegistry) -> io::Result<Arc<dyn Material>> {
+    let mut params = Params::new();
+    for (name, value) in &node.fields {
+        let param = match value {
+            Value::Number(n) => Param::Number(*n),
+            Value::Numbers(_) => Param::Color(node.vector(name)?),
+            Value::Text(s) => Param::Text(s.clone()),
+            Value::Node(nested) => match (texture(nested)?, density(nested)?) {
+                (Some(texture), _) => Param::Texture(texture),
+                (None, Some(density)) => Param::Density(density),
+                (None, None) => Param::Material(material(nested, registry)?),
+            },
+            Value::Bool(_) | Value::List(_) => return Err(node.invalid(format!("unsupported parameter '{}'", name))),
+        };
+        params.insert(name, param);
+    }
+    registry.create(&node.kind, &params)
+}
+
+/// Returns texture of field `name`, a color or texture node.
+fn texture_field(node: &Node, name: &str) -> io::Result<Arc<dyn Texture>> {
+    match node.get(name) {
+        Some(Value::Node(nested)) => texture(nested)?.ok_or_else(|| invalid(format!("unknown texture '{}'", nested.kind))),
+        _ => Ok(Arc::new(SolidColor::new(node.vector(name)?))),
+    }
+}
+
+/// Returns texture described by `node`, `None` if it is of another kind (e.g. a material).
+fn texture(node: &Node) -> io::Result<Option<Arc<dyn Texture>>> {
+    let mapping = || -> io::Result<(Mapping, Noise)> {
+        let space = match node.text("space")? {
+            "world" => Space::World,
+            "object" => Space::Object,
+            space => return Err(node.invalid(format!("unknown space '{}'", space))),
+        };
+        let mapping = Mapping { space, scale: node.vector("scale")?, offset: node.vector("offset")? };
+        Ok((mapping, noise(node)?))
+    };
+    Ok(Some(match node.kind.as_str() {
+        "vertex_color" => Arc::new(VertexColor { fallback: texture_field(node, "fallback")? }),
+        "checker" => Arc::new(Checker { even: texture_field(node, "even")?, odd: texture_field(node, "odd")?, scale: node.number("scale")? }),
+        "marble" => {
+            let (mapping, noise) = mapping()?;
+            Arc::new(Marble {
+                base: node.vector("base")?,
+                vein: node.vector("vein")?,
+                turbulence: node.number("turbulence")?,
+                octaves: node.integer("octaves")? as u32,
+                mapping,
+                noise,
+            })
+        }
+        "wood" => {
+            let (mapping, noise) = mapping()?;
+            Arc::new(Wood {
+                light: node.vector("light")?,
+                dark: node.vector("dark")?,
+                rings: node.number("rings")?,
+                distortion: node.number("distortion")?,
+                mapping,
+                noise,
+            })
+        }
+        "granite" => {
+            let (mapping, noise) = mapping()?;
+            let minerals = node.numbers("minerals")?;
+            if minerals.len() % 3 != 0 {
+                return Err(node.invalid("field 'minerals' must have a multiple of 3 numbers".to_string()));
+            }
+            let minerals = minerals.chunks_exact(3).map(|c| Vec3::new(c[0], c[1], c[2])).collect();
+            Arc::new(Granite { minerals, seam: node.vector("seam")?, mapping, noise })
+        }
+        "cloud" => {
+            let (mapping, noise) = mapping()?;
+            Arc::new(Cloud {
+                sky: node.vector("sky")?,
+                cloud: node.vector("cloud")?,
+                coverage: node.number("coverage")?,
+                warp: node.number("warp")?,
+                octaves: node.integer("octaves")? as u32,
+                mapping,
+                noise,
+            })
+        }
+        "image" => {
+            let (width, height, channels) = (node.integer("width")? as usize, node.integer("height")? as usize, node.integer("channels")? as usize);
+            let data = node.numbers("data")?.to_vec();
+            if width == 0 || height == 0 || !matches!(channels, 1 | 3) || data.len() != width * height * channels {
+                return Err(node.invalid("image data doesn't match its size".to_string()));
+            }
+            let mut texture = ImageTexture::new(Image { width, height, channels, data });
+            texture.wrap = match node.text("wrap")? {
+                "repeat" => Wrap::Repeat,
+                "clamp" => Wrap::Clamp,
+                "mirror" => Wrap::Mirror,
+                wrap => return Err(node.invalid(format!("unknown wrap '{}'", wrap))),
+            };
+            texture.filter = match node.text("filter")? {
+                "bilinear" => Filter::Bilinear,
+                "trilinear" => Filter::Trilinear,
+                filter => return Err(node.invalid(format!("unknown filter '{}'", filter))),
+            };
+            Arc::new(texture)
+        }
+        _ => return Ok(None),
+    }))
+}
+
+/// Returns noise of the seed saved as decimal text in field `seed`.
+fn noise(node: &Node) -> io::Result<Noise> {
+    let seed = node.text("seed")?.parse().map_err(|_| node.invalid("field 'seed' must be an unsigned 64-bit integer".to_string()))?;
+    Ok(Noise::new(seed))
+}
+
+/// Returns density field described by `node`, `None` if it is of another kind.
+fn density(node: &Node) -> io::Result<Option<Arc<dyn DensityField>>> {
+    Ok(Some(match node.kind.as_str() {
+        "voxel_grid" => {
+            let resolution = match node.numbers("resolution")? {
+                &[x, y, z] if [x, y, z].iter().all(|n| *n >= 1.0 && n.fract() == 0.0) => [x as usize, y as usize, z as usize],
+                _ => return Err(node.invalid("field 'resolution' must be 3 positive integers".to_string())),
+            };
+            let (density, temperature) = (node.numbers("density")?.to_vec(), node.numbers("temperature")?.to_vec());
+            let voxels = resolution.iter().product::<usize>();
+            if density.len() != voxels || !(temperature.is_empty() || temperature.len() == voxels) {
+                return Err(node.invalid("voxel data doesn't match the resolution".to_string()));
+            }
+            Arc::new(VoxelGrid { temperature, ..VoxelGrid::new(node.vector("min")?, node.vector("max")?, resolution, density) })
+        }
+        "noise_density" => Arc::new(NoiseDensity {
+            frequency: node.number("frequency")?,
+            octaves: node.integer("octaves")? as u32,
+            temperature: node.number("temperature")?,
+            noise: noise(node)?,
+            ..NoiseDensity::new(node.vector("center")?, node.number("radius")?, node.number("density")?)
+        }),
+        _ => return Ok(None),
+    }))
+}
+
+fn camera(node: &Node) -> io::Result<Box<dyn Camera>> {
+    let orientation = Orientation { origin: node.vector("origin")?, u: node.vector("u")?, v: node.vector("v")?, w: node.vector("w")? };
+    let shutter = match node.numbers("shutter")? {
+        &[time0, time1] => Shutter { time0, time1 },
+        _ => return Err(node.invalid("field 'shutter' must have 2 numbers".to_string())),
+    };
+    let lens = || lens(node.node("lens")?);
+    Ok(match node.kind.as_str() {
+        "perspective" => Box::new(PerspectiveCamera::new(orientation, node.number("vfov")?, node.number("aspect_ratio")?, lens()?, shutter)),
+        "orthographic" => {
+            Box::new(OrthographicCamera::new(orientation, node.number("viewport_height")?, node.number("aspect_ratio")?, lens()?, shutter))
+        }
+        "equirectangular" => Box::new(EquirectangularCamera::new(orientation, lens()?, shutter)),
+        "fisheye" => {
+            let mapping = match node.text("mapping")? {
+                "equidistant" => FisheyeMapping::Equidistant,
+                "equisolid" => FisheyeMapping::Equisolid,
+                mapping => return Err(node.invalid(format!("unknown mapping '{}'", mapping))),
+            };
+            Box::new(FisheyeCamera::new(orientation, node.number("fov")?, node.number("aspect_ratio")?, mapping, lens()?, shutter))
+        }
+        "omni_stereo" => {
+            let layout = match node.text("layout")? {
+                "left" => StereoLayout::Left,
+                "right" => StereoLayout::Right,
+                "over_under" => StereoLayout::OverUnder,
+                layout => return Err(node.invalid(format!("unknown layout '{}'", layout))),
+            };
+            Box::new(OmniStereoCamera::new(orientation, node.number("interpupillary_distance")?, layout, shutter))
+        }
+        "realistic" => {
+            let elements = node.numbers("elements")?;
+            if elements.len() % 4 != 0 {
+                return Err(node.invalid("field 'elements' must have 4 numbers per lens element".to_string()));
+            }
+            let elements: Vec<LensElement> = elements.chunks_exact(4)
+                .map(|e| LensElement { curvature_radius: e[0], thickness: e[1], ior: e[2], aperture_diameter: e[3] })
+                .collect();
+            let camera = RealisticCamera::new(
+                orientation,
+                &elements,
+                node.number("aperture_diameter")?,
+                node.number("focus_distance")?,
+                node.number("film_diagonal")?,
+                node.number("aspect_ratio")?,
+                shutter,
+            );
+            Box::new(camera.map_err(|e| node.invalid(e.to_string()))?)
+        }
+        kind => return Err(invalid(format!("unknown camera '{}'", kind))),
+    })
+}
+
+fn lens(node: &Node) -> io::Result<ThinLens> {
+    let shape = node.node("shape")?;
+    let shape = match shape.kind.as_str() {
+        "circular" => Aperture::Circular,
+        "polygon" => Aperture::Polygon { blades: shape.integer("blades")? as u32, rotation: shape.number("rotation")? },
+        "mask" => {
+            let (width, height) = (shape.integer("width")? as usize, shape.integer("height")? as usize);
+            let data = shape.numbers("data")?.to_vec();
+            if width == 0 || height == 0 || data.len() != width * height {
+                return Err(shape.invalid("mask data doesn't match its size".to_string()));
+            }
+            let mask = ApertureMask::new(Image { width, height, channels: 1, data }).map_err(|e| shape.invalid(e.to_string()))?;
+            Aperture::Mask(Arc::new(mask))
+        }
+        kind => return Err(invalid(format!("unknown aperture '{}'", kind))),
+    };
+    Ok(ThinLens {
+        aperture: node.number("aperture")?,
+        focus_dist: node.number("focus_dist")?,
+        shape,
+        cat_eye: node.number("cat_eye")?,
+        anamorphic_squeeze: node.number("anamorphic_squeeze")?,
+    })
+}
+
+fn invalid(message: String) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::camera::{parse_lens_data, DOUBLE_GAUSS_50MM};
+    use crate::configs::moving_random_spheres;
+    use crate::material::{AlphaMask, Dielectric, Hair, Lambertian, MetallicRoughness, Mix, NormalMap, Principled, RefractiveIndex, RoughDielectric, SpotLight, Volumetric};
+    use crate::medium::{Heterogeneous, MediumStack};
+    use crate::configs::random_spheres_on;
+    use crate::{render_pixels, Ray, Vec3};
+
+    #[test]
+    fn test_save_scene() {
+        let mut rng = rand::thread_rng();
+        let mut conf = moving_random_spheres(&mut rng);
+        // Pinhole camera whose rays don't depend on random samples
+        let orientation = Orientation::new(Vec3::new(13.0, 2.0, 3.0), Vec3::new(0.0, 0.5, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        conf.camera = Box::new(PerspectiveCamera::new(orientation, 20.0, conf.aspect_ratio, ThinLens::pinhole(), Shutter::instant(0.0)));
+        let mix = Arc::new(Mix {
+            first: Arc::new(Principled::default()),
+            second: Arc::new(Lambertian::new(Vec3::new(0.1, 0.2, 0.3))),
+            weight: Arc::new(crate::texture::SolidColor::gray(0.3)),
+        });
+        let mut triangle = Triangle::new(Vec3::new(-3.0, 0.0, -3.0), Vec3::new(3.0, 0.0, -3.0), Vec3::new(0.0, 3.0, -3.0), mix.clone());
+        triangle.normals = Some([Vec3::new(0.0, 0.0, 1.0), Vec3::new(0.6, 0.0, 0.8), Vec3::new(0.0, 0.6, 0.8)]);
+        let quad = Quad { q: Vec3::new(-5.0, 0.0, -5.0), u: Vec3::new(10.0, 0.0, 0.0), v: Vec3::new(0.0, 5.0, 0.0), material: mix };
+        conf.world.hittables.push(Box::new(Bvh::new(vec![Box::new(triangle), Box::new(quad)])));
+
+        let registry = MaterialRegistry::default();
+        let text = describe_scene(&conf).unwrap().to_string();
+        let loaded = parse_scene(&text, &registry).unwrap();
+        assert_eq!(describe_scene(&loaded).unwrap().to_string(), text);
+        assert_eq!((loaded.image_width, loaded.image_height, loaded.samples_per_pixel), (400, 225, 100));
+
+        // Same rays hit the same points
+        for i in 0..20 {
+            for j in 0..20 {
+                let (s, t) = (i as f64 / 19.0, j as f64 / 19.0);
+                let ray = conf.camera.get_ray(s, t, &mut rng).unwrap();
+                let loaded_ray = loaded.camera.get_ray(s, t, &mut rng).unwrap();
+                assert_eq!((ray.origin, ray.direction), (loaded_ray.origin, loaded_ray.direction));
+                let ray = Ray { time: 0.7, ..ray };
+                let (hit, loaded_hit) = (conf.world.hit(&ray, 0.001, f64::MAX), loaded.world.hit(&ray, 0.001, f64::MAX));
+                assert_eq!(hit.is_some(), loaded_hit.is_some());
+                if let (Some(hit), Some(loaded_hit)) = (hit, loaded_hit) {
+                    assert_eq!(hit.t.to_bits(), loaded_hit.t.to_bits());
+                    assert_eq!((hit.normal, hit.shading.n, hit.u, hit.v), (loaded_hit.normal, loaded_hit.shading.n, loaded_hit.u, loaded_hit.v));
+                    assert_eq!(hit.material.describe(), loaded_hit.material.describe());
+                }
+            }
+        }
+
+        // Renders with the same seed agree
+        let mut loaded = loaded;
+        for c in [&mut conf, &mut loaded] {
+            (c.image_width, c.image_height, c.samples_per_pixel) = (24, 16, 2);
+        }
+        let render = |conf: &ImageConfig| render_pixels(conf, &mut StdRng::seed_from_u64(5));
+        assert_eq!(render(&conf), render(&loaded));
+
+        // Downstream textures that don't implement describe
+        struct Stripes;
+        impl Texture for Stripes {
+            fn value(&self, hit: &crate::objects::Hit) -> Vec3 {
+                Vec3::new(hit.u.fract(), 0.0, 0.0)
+            }
+        }
+        let stripes = Arc::new(Lambertian::textured(Arc::new(Stripes)));
+        conf.world.hittables.insert(1, Box::new(Sphere { center: Vec3::new(0.0, 0.0, 0.0), radius: 1.0, material: stripes }));
+        assert_eq!(describe_scene(&conf).unwrap_err().to_string(), "object 1 can't be saved");
+
+        let err = |text: &str| parse_scene(text, &registry).err().unwrap().to_string();
+        let replaced = |from: &str, to: &str| text.replacen(from, to, 1);
+        assert_eq!(err(&replaced("moving_sphere", "teapot")), "unknown object 'teapot'");
+        assert_eq!(err(&replaced("metal {", "brass {")), "Unknown material 'brass'");
+        assert_eq!(err(&replaced("material \"", "material \"copper")), "sphere: undefined material 'copperlambertian0'");
+        assert_eq!(err(&replaced("vfov", "fov")), "perspective: missing field 'vfov'");
+    }
+
+    /// Asserts that `conf` saves, loads and saves again to the same text and renders the same.
+    fn assert_round_trip(conf: &mut ImageConfig) {
+        let registry = MaterialRegistry::default();
+        let text = describe_scene(conf).unwrap().to_string();
+        let mut loaded = parse_scene(&text, &registry).unwrap();
+        assert_eq!(describe_scene(&loaded).unwrap().to_string(), text);
+        for c in [&mut *conf, &mut loaded] {
+            (c.image_width, c.image_height, c.samples_per_pixel) = (24, 16, 2);
+        }
+        let render = |conf: &ImageConfig| render_pixels(conf, &mut StdRng::seed_from_u64(3));
+        assert_eq!(render(conf), render(&loaded));
+    }
+
+    #[test]
+    fn test_textures() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let marble = Marble { noise: Noise::new(7), ..Marble::new(Vec3::new(0.9, 0.9, 0.9), Vec3::new(0.2, 0.2, 0.3)) };
+        let mut conf = random_spheres_on(&mut rng, Arc::new(marble));
+        conf.camera = Box::new(PerspectiveCamera::new(Orientation::new(Vec3::new(0.0, 2.0, 6.0), Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0)), 40.0, 1.5, ThinLens::pinhole(), Shutter::instant(0.0)));
+
+        let image = Image { width: 3, height: 2, channels: 3, data: (0..18).map(|i| i as f64 / 17.0).collect() };
+        let mut image = ImageTexture::new(image);
+        (image.wrap, image.filter) = (Wrap::Mirror, Filter::Bilinear);
+        let wood = Wood { mapping: Mapping { space: Space::World, ..Mapping::scaled(2.0) }, ..Wood::new(Vec3::new(0.8, 0.6, 0.4), Vec3::new(0.4, 0.2, 0.1)) };
+        let checker = Checker { even: Arc::new(image), odd: Arc::new(wood), scale: 0.5 };
+        let clouds = Cloud { noise: Noise::new(3), ..Cloud::new(Vec3::new(0.3, 0.5, 0.9), Vec3::new(1.0, 1.0, 1.0)) };
+        let materials: [Arc<dyn Material>; 3] = [
+            Arc::new(Lambertian::textured(Arc::new(checker))),
+            Arc::new(Lambertian::textured(Arc::new(VertexColor { fallback: Arc::new(Granite::new()) }))),
+            Arc::new(Mix { first: Arc::new(Lambertian::new(Vec3::new(0.8, 0.1, 0.1))), second: Arc::new(Principled::default()), weight: Arc::new(clouds) }),
+        ];
+        for (i, material) in materials.into_iter().enumerate() {
+            conf.world.hittables.push(Box::new(Sphere { center: Vec3::new(i as f64 - 1.0, 0.5, 2.0), radius: 0.5, material }));
+        }
+        assert_round_trip(&mut conf);
+    }
+
+    #[test]
+    fn test_materials() {
+        let mut conf = random_spheres_on(&mut StdRng::seed_from_u64(2), Arc::new(SolidColor::gray(0.5)));
+        conf.camera = Box::new(PerspectiveCamera::new(Orientation::new(Vec3::new(0.0, 2.0, 8.0), Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0)), 40.0, 1.5, ThinLens::pinhole(), Shutter::instant(0.0)));
+
+        let granite: Arc<dyn Texture> = Arc::new(Granite::new());
+        let red: Arc<dyn Material> = Arc::new(Lambertian::new(Vec3::new(0.8, 0.1, 0.1)));
+        let cloud = NoiseDensity { noise: Noise::new(9), ..NoiseDensity::new(Vec3::new(2.0, 0.5, 2.0), 0.5, 4.0) };
+        let metallic_roughness = MetallicRoughness {
+            base_color_texture: Some(granite.clone()),
+            emission: Vec3::new(0.1, 0.2, 0.0),
+            ..MetallicRoughness::new(Principled { metallic: 0.7, ..Principled::default() })
+        };
+        let materials: [Arc<dyn Material>; 8] = [
+            Arc::new(Dielectric::new(RefractiveIndex::bk7())),
+            Arc::new(RoughDielectric::new(RefractiveIndex::Cauchy { a: 1.5, b: 0.004 }, 0.1, 0.2)),
+            Arc::new(NormalMap { material: red.clone(), texture: granite.clone(), strength: 0.5 }),
+            Arc::new(AlphaMask { material: red, alpha: Arc::new(Wood::new(Vec3::new(1.0, 1.0, 1.0), Vec3::new(0.0, 0.0, 0.0))) }),
+            Arc::new(Hair::from_melanin(0.5, 0.2)),
+            Arc::new(Volumetric::heterogeneous(Arc::new(Heterogeneous::new(cloud, 0.5)), Vec3::new(0.1, 0.1, 0.1), Vec3::new(0.8, 0.8, 0.8))),
+            Arc::new(metallic_roughness),
+            Arc::new(SpotLight { emit: Vec3::new(4.0, 4.0, 4.0), direction: Vec3::new(0.0, -1.0, 0.0), cos_inner: 0.9, cos_outer: 0.8 }),
+        ];
+        for (i, material) in materials.into_iter().enumerate() {
+            conf.world.hittables.push(Box::new(Sphere { center: Vec3::new(i as f64 - 3.5, 0.5, 2.0), radius: 0.5, material }));
+        }
+        assert_round_trip(&mut conf);
+    }
+
+    #[test]
+    fn test_objects() {
+        let mut conf = random_spheres_on(&mut StdRng::seed_from_u64(4), Arc::new(SolidColor::gray(0.5)));
+        conf.camera = Box::new(PerspectiveCamera::new(Orientation::new(Vec3::new(0.0, 3.0, 9.0), Vec3::new(0.0, 0.5, 0.0), Vec3::new(0.0, 1.0, 0.0)), 50.0, 1.5, ThinLens::pinhole(), Shutter::instant(0.0)));
+        let red: Arc<dyn Material> = Arc::new(Lambertian::new(Vec3::new(0.8, 0.1, 0.1)));
+        let blue: Arc<dyn Material> = Arc::new(Lambertian::new(Vec3::new(0.1, 0.1, 0.8)));
+        let fog: Arc<dyn Material> = Arc::new(Volumetric::homogeneous(Vec3::new(0.1, 0.1, 0.1), Vec3::new(0.5, 0.5, 0.5)));
+        let at = |x: f64, z: f64| Vec3::new(x, 0.0, z);
+
+        let sdf = Sdf::RoundedBox { half_size: Vec3::new(0.3, 0.3, 0.3), radius: 0.05 }
+            .smooth_union(Sdf::Torus { major_radius: 0.3, minor_radius: 0.1 }.twist(0.5), 0.1)
+            .smooth_subtract(Sdf::Sphere { radius: 0.2 }.repeat(Vec3::new(0.0, 0.0, 0.0)), 0.05)
+            .union(Sdf::Mandelbulb { power: 8.0, iterations: 4 }.scale(0.2).translate(Vec3::new(0.0, 0.5, 0.0)))
+            .intersection(Sdf::Sphere { radius: 1.0 })
+            .subtract(Sdf::Cylinder { radius: 0.05, half_height: 1.0 })
+            .translate(Vec3::new(-3.0, 0.5, 0.0));
+        let csg = Csg::difference(
+            Csg::union(Sphere { center: Vec3::new(3.0, 0.5, 0.0), radius: 0.5, material: red.clone() }, Volume { min: Vec3::new(2.6, 0.0, 0.2), max: Vec3::new(3.4, 0.4, 0.8), material: blue.clone() }),
+            Sphere { center: Vec3::new(3.0, 0.9, 0.3), radius: 0.3, material: blue.clone() },
+        );
+        let drill = Cylinder { capped: true, z_min: -0.6, ..Cylinder::new(Placement::new(Vec3::new(0.0, 0.5, -2.0), Vec3::new(0.0, 0.0, 1.0)), 0.2, 0.6, blue.clone()) };
+        let ring = Torus::new(Placement::upright(Vec3::new(0.0, 0.5, -2.0)), 0.5, 0.1, red.clone());
+        let drilled = Csg::difference(Sphere { center: Vec3::new(0.0, 0.5, -2.0), radius: 0.5, material: red.clone() }, Csg::union(drill, ring));
+        let curves = [CurveShape::Flat, CurveShape::Cylinder, CurveShape::Ribbon { normals: [Vec3::new(0.0, 0.0, 1.0), Vec3::new(1.0, 0.0, 1.0)] }];
+        let points: Vec<Vec3> = (0..50).map(|i| Vec3::new(1.0 + 0.1 * (i % 7) as f64, 0.1 * (i / 7) as f64, 3.0)).collect();
+        let normals = vec![Vec3::new(0.0, 0.2, 1.0); points.len()];
+        let colors: Vec<Vec3> = (0..points.len()).map(|i| Vec3::new(i as f64 / 50.0, 0.5, 0.2)).collect();
+
+        let mut objects: Vec<Box<dyn Hittable>> = vec![
+            Box::new(SdfShape::new(sdf, Vec3::new(-4.0, -0.5, -1.0), Vec3::new(-2.0, 1.5, 1.0), red.clone())),
+            Box::new(csg),
+            Box::new(drilled),
+            Box::new(Volume { min: Vec3::new(-1.0, 0.0, 2.0), max: Vec3::new(0.0, 1.0, 3.0), material: fog }),
+            Box::new(Cylinder { capped: true, phi_max: 5.0, ..Cylinder::new(Placement::new(at(-2.0, 2.0), Vec3::new(0.2, 1.0, 0.0)), 0.3, 1.0, blue.clone()) }),
+            Box::new(Cone::new(Placement::upright(at(-1.0, 0.0)), 0.4, 1.0, red.clone())),
+            Box::new(Paraboloid { z_min: 0.2, ..Paraboloid::new(Placement::upright(at(0.0, 0.0)), 0.4, 1.0, blue.clone()) }),
+            Box::new(Hyperboloid::new(Placement::upright(at(1.0, 0.0)), Vec3::new(0.3, 0.0, 0.0), Vec3::new(0.0, 0.3, 1.0), red.clone())),
+            Box::new(Torus::new(Placement::new(Vec3::new(2.0, 1.5, 0.0), Vec3::new(0.0, 0.0, 1.0)), 0.4, 0.1, blue.clone())),
+            Box::new(Heightfield::from_fn(Vec3::new(-4.0, 0.0, 2.0), Vec3::new(2.0, 0.5, 2.0), (8, 6), |u, v| u * v, blue.clone())),
+            Box::new(Voxels::from_fn(Vec3::new(2.0, 0.0, 2.0), 0.2, [4, 3, 2], vec![red.clone(), blue.clone()], |x, y, z| ((x + y + z) % 3) as u8)),
+            Box::new(PointCloud::new(&points, &vec![0.04; points.len()], &normals, &colors, Splat::Disk, red.clone())),
+            Box::new(PointCloud::new(&points[..10], &[0.06; 10], &[], &[], Splat::Sphere, blue.clone())),
+        ];
+        for (i, shape) in curves.into_iter().enumerate() {
+            let control_points = [at(i as f64 - 1.0, 4.0), Vec3::new(i as f64 - 0.8, 0.5, 4.0), Vec3::new(i as f64 - 1.2, 1.0, 4.0), Vec3::new(i as f64 - 1.0, 1.5, 4.0)];
+            objects.push(Box::new(Curve::new(control_points, (0.1, 0.02), shape, red.clone())));
+        }
+        conf.world.hittables.extend(objects);
+        assert_round_trip(&mut conf);
+
+        // Open cylinders have no inside to combine
+        let text = describe_scene(&conf).unwrap().to_string();
+        let open = text.replacen("capped true", "capped false", 1);
+        let err = parse_scene(&open, &MaterialRegistry::default()).err().unwrap();
+        assert_eq!(err.to_string(), "cylinder: must be capped and swept a full turn to be a solid");
+    }
+
+    #[test]
+    fn test_cameras() {
+        let orientation = Orientation::new(Vec3::new(0.0, 2.0, 6.0), Vec3::new(0.0, 0.5, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        let (lens, shutter) = (ThinLens::new(0.1, 5.0), Shutter { time0: 0.0, time1: 1.0 });
+        let elements = parse_lens_data(DOUBLE_GAUSS_50MM).unwrap();
+        // Color mask with an opaque center, saved as its gray values
+        let image = Image { width: 3, height: 2, channels: 3, data: (0..18).map(|i| if i / 3 == 1 { 0.0 } else { i as f64 / 17.0 }).collect() };
+        let shape = Aperture::Mask(Arc::new(ApertureMask::new(image).unwrap()));
+        let masked = ThinLens { shape, ..ThinLens::new(0.5, 5.0) };
+        let cameras: [Box<dyn Camera>; 6] = [
+            Box::new(PerspectiveCamera::new(orientation, 40.0, 1.5, masked, shutter)),
+            Box::new(FisheyeCamera::new(orientation, 180.0, 1.5, FisheyeMapping::Equidistant, lens.clone(), shutter)),
+            Box::new(FisheyeCamera::new(orientation, 150.0, 1.5, FisheyeMapping::Equisolid, ThinLens::pinhole(), shutter)),
+            Box::new(OmniStereoCamera::new(orientation, 0.064, StereoLayout::OverUnder, shutter)),
+            Box::new(OmniStereoCamera::new(orientation, 0.064, StereoLayout::Right, Shutter::instant(0.5))),
+            Box::new(RealisticCamera::new(orientation, &elements, 8.0, 6.0, 35.0, 1.5, shutter).unwrap()),
+        ];
+        for camera in cameras {
+            let mut conf = random_spheres_on(&mut StdRng::seed_from_u64(6), Arc::new(SolidColor::gray(0.5)));
+            conf.camera = camera;
+            assert_round_trip(&mut conf);
+        }
+    }
+
+    #[test]
+    fn test_seeds() {
+        // Random seeds use all 64 bits
+        let noise = Noise::new(u64::MAX);
+        let mut conf = random_spheres_on(&mut StdRng::seed_from_u64(7), Arc::new(Marble { noise: noise.clone(), ..Marble::new(Vec3::new(0.9, 0.9, 0.9), Vec3::new(0.2, 0.2, 0.3)) }));
+        conf.camera = Box::new(PerspectiveCamera::new(Orientation::new(Vec3::new(0.0, 2.0, 6.0), Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0)), 40.0, 1.5, ThinLens::pinhole(), Shutter::instant(0.0)));
+        let cloud = NoiseDensity { noise, ..NoiseDensity::new(Vec3::new(0.0, 0.5, 2.0), 0.5, 4.0) };
+        let material = Arc::new(Volumetric::heterogeneous(Arc::new(Heterogeneous::new(cloud, 0.0)), Vec3::new(0.1, 0.1, 0.1), Vec3::new(0.8, 0.8, 0.8)));
+        conf.world.hittables.push(Box::new(Volume { min: Vec3::new(-0.5, 0.0, 1.5), max: Vec3::new(0.5, 1.0, 2.5), material }));
+        let text = describe_scene(&conf).unwrap().to_string();
+        assert_eq!(text.matches("seed \"18446744073709551615\"").count(), 2);
+        assert_round_trip(&mut conf);
+
+        let replaced = text.replacen("18446744073709551615", "18446744073709551616", 1);
+        let err = parse_scene(&replaced, &MaterialRegistry::default()).err().unwrap();
+        assert_eq!(err.to_string(), "marble: field 'seed' must be an unsigned 64-bit integer");
+    }
+
+    #[test]
+    fn test_shared_materials() {
+        // Walls of a glass filled with water, the inner wall shares the material of the outer one
+        let glass: Arc<dyn Material> = Arc::new(Dielectric { priority: 2, ..Dielectric::new(RefractiveIndex::Constant(1.5)) });
+        let water: Arc<dyn Material> = Arc::new(Dielectric { priority: 1, ..Dielectric::new(RefractiveIndex::Constant(1.33)) });
+        let sphere = |radius: f64, material: &Arc<dyn Material>| -> Box<dyn Hittable> {
+            Box::new(Sphere { center: Vec3::new(0.0, 0.0, 0.0), radius, material: material.clone() })
+        };
+        let mut conf = moving_random_spheres(&mut rand::thread_rng());
+        conf.world = Hittables { hittables: vec![sphere(2.0, &glass), sphere(1.9, &water), sphere(-1.8, &glass)] };
+
+        let text = describe_scene(&conf).unwrap().to_string();
+        assert_eq!(text.matches("dielectric {").count(), 2);
+        let loaded = parse_scene(&text, &MaterialRegistry::default()).unwrap();
+        let ray = Ray { origin: Vec3::new(0.0, 0.0, 5.0), direction: Vec3::new(0.0, 0.0, -1.0), time: 0.0, wavelength: None, differentials: None };
+        let address = |i: usize| {
+            let hit = loaded.world.hittables[i].hit(&ray, 0.001, f64::MAX).unwrap();
+            hit.material as *const dyn Material as *const () as usize
+        };
+        assert!(address(0) == address(2) && address(0) != address(1));
+
+        // Inner wall of the glass borders water, as the path leaves the glass it entered
+        let mut stack = MediumStack::new();
+        let mut exterior_iors = vec![];
+        for object in &loaded.world.hittables {
+            let mut hit = object.hit(&ray, 0.001, f64::MAX).unwrap();
+            if stack.prepare(&mut hit, None) {
+                exterior_iors.push(hit.exterior_ior);
+                stack.cross(&hit);
+            }
+        }
+        assert_eq!(exterior_iors, [1.0, 1.33]);
+    }
+}
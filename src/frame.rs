@@ -0,0 +1,85 @@
+use crate::Vec3;
+
+/// Orthonormal basis with tangent `s`, bitangent `t` and normal `n`.
+#[derive(Copy, Clone, Debug)]
+pub struct Frame {
+    pub s: Vec3,
+    pub t: Vec3,
+    pub n: Vec3,
+}
+
+impl Frame {
+    /// Builds frame with arbitrary tangent around unit normal (Duff et al. 2017).
+    pub fn from_normal(n: Vec3) -> Self {
+        let sign = 1f64.copysign(n.z);
+        let a = -1.0 / (sign + n.z);
+        let b = n.x * n.y * a;
+        let s = Vec3::new(1.0 + sign * n.x * n.x * a, sign * b, -sign * n.x);
+        let t = Vec3::new(b, sign + n.y * n.y * a, -n.y);
+        Frame { s, t, n }
+    }
+
+    /// Builds frame around unit normal with tangent as close as possible to `tangent`, falls back
+    /// to an arbitrary tangent if it is (nearly) parallel to the normal.
+    pub fn from_normal_tangent(n: Vec3, tangent: Vec3) -> Self {
+        let s = tangent - tangent.dot(n) * n;
+        if s.length_squared() < 1.0e-12 {
+            return Frame::from_normal(n);
+        }
+        let s = s.unit_vector();
+        Frame { s, t: n.cross(s), n }
+    }
+
+    /// Returns frame for the opposite side of the surface.
+    pub fn flipped(&self) -> Self {
+        Frame { s: self.s, t: -self.t, n: -self.n }
+    }
+
+    pub fn to_local(&self, v: Vec3) -> Vec3 {
+        Vec3::new(v.dot(self.s), v.dot(self.t), v.dot(self.n))
+    }
+
+    pub fn to_world(&self, v: Vec3) -> Vec3 {
+        v.x * self.s + v.y * self.t + v.z * self.n
+    }
+}
+
+/// Bends shading normal `n` towards geometric normal `ng` just enough that the mirror reflection
+/// of `wo` stays above the geometric surface, after Cycles' `ensure_valid_reflection`. All
+/// vectors are unit length and `wo` lies on the side of `ng`.
+pub fn ensure_valid_reflection(ng: Vec3, wo: Vec3, n: Vec3) -> Vec3 {
+    let r = 2.0 * n.dot(wo) * n - wo;
+    // Reflected rays may be at least as shallow as the incoming one
+    let threshold = (0.9 * ng.dot(wo)).min(0.01);
+    if ng.dot(r) >= threshold {
+        return n;
+    }
+
+    // Solve for normal in the plane spanned by ng and n whose reflection has height threshold
+    let x = n - n.dot(ng) * ng;
+    if x.near_zero() {
+        return ng;
+    }
+    let x = x.unit_vector();
+    let (ix, iz) = (wo.dot(x), wo.dot(ng));
+    let a = ix * ix + iz * iz;
+    let b = (ix * ix * (a - threshold * threshold)).max(0.0).sqrt();
+    let c = iz * threshold + a;
+    let candidates = [0.5 * (b + c) / a, 0.5 * (c - b) / a]
+        .map(|nz2| (nz2 > 1.0e-5 && nz2 <= 1.0 + 1.0e-5).then(|| ((1.0 - nz2).max(0.0).sqrt(), nz2.max(0.0).sqrt())));
+    // Height of the reflection for normal (nx, nz) in the plane
+    let height = |(nx, nz): (f64, f64)| 2.0 * (nx * ix + nz * iz) * nz - iz;
+    let (nx, nz) = match candidates {
+        [Some(n1), Some(n2)] => {
+            let (r1, r2) = (height(n1), height(n2));
+            if r1 >= 1.0e-5 && r2 >= 1.0e-5 {
+                // Shallower reflection is closer to the original normal
+                if r1 < r2 { n1 } else { n2 }
+            } else if r1 > r2 { n1 } else { n2 }
+        }
+        [Some(n1), None] => n1,
+        [None, Some(n2)] => n2,
+        [None, None] => return ng,
+    };
+    nx * x + nz * ng
+}
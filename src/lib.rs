@@ -1,17 +1,27 @@
 //! Ray tracer following Peter Shirley's books.
 pub use camera::Camera;
+pub use frame::Frame;
 pub use material::Material;
-pub use ray::Ray;
-pub use raytracer::render;
+pub use ray::{Differentials, Ray};
+pub use raytracer::{render, render_pixels};
 pub use vec3::Vec3;
 
 mod vec3;
+pub mod frame;
+pub mod microfacet;
 pub mod utils;
+pub mod noise;
+pub mod netpbm;
+pub mod ply;
 pub mod color;
+pub mod spectrum;
+pub mod medium;
 mod ray;
-mod material;
+pub mod texture;
+pub mod material;
 pub mod objects;
-mod camera;
+pub mod camera;
 pub mod configs;
+pub mod scene;
 mod raytracer;
 
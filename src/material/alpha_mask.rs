@@ -0,0 +1,53 @@
+use std::sync::Arc;
+
+use rand::RngCore;
+
+use crate::{Ray, Vec3};
+use crate::color::luminance;
+use crate::medium::Interior;
+use crate::objects::Hit;
+use crate::scene::Node;
+use crate::texture::Texture;
+
+use super::{Material, Scatter};
+
+/// Cuts out parts of `material` where the luminance of `alpha` is below one, e.g. for leaves
+/// or fences modelled by textured quads.
+pub struct AlphaMask {
+    pub material: Arc<dyn Material>,
+    pub alpha: Arc<dyn Texture>,
+}
+
+impl Material for AlphaMask {
+    fn scatter(&self, ray: &Ray, hit: &Hit, rng: &mut dyn RngCore) -> Option<Scatter> {
+        self.material.scatter(ray, hit, rng)
+    }
+
+    fn eval(&self, ray: &Ray, hit: &Hit, direction: Vec3) -> Vec3 {
+        self.material.eval(ray, hit, direction)
+    }
+
+    fn pdf(&self, ray: &Ray, hit: &Hit, direction: Vec3) -> f64 {
+        self.material.pdf(ray, hit, direction)
+    }
+
+    fn emitted(&self, ray: &Ray, hit: &Hit) -> Vec3 {
+        self.material.emitted(ray, hit)
+    }
+
+    fn is_dispersive(&self) -> bool {
+        self.material.is_dispersive()
+    }
+
+    fn opacity(&self, hit: &Hit) -> f64 {
+        luminance(self.alpha.value(hit)).clamp(0.0, 1.0) * self.material.opacity(hit)
+    }
+
+    fn interior(&self) -> Option<Interior> {
+        self.material.interior()
+    }
+
+    fn describe(&self) -> Option<Node> {
+        Some(Node::new("alpha_mask").with("material", self.material.describe()?).with("alpha", self.alpha.describe()?))
+    }
+}
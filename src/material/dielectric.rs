@@ -0,0 +1,131 @@
+use rand::{Rng, RngCore};
+
+use crate::{Ray, Vec3};
+use crate::color::{BLACK, WHITE};
+use crate::medium::Interior;
+use crate::objects::Hit;
+use crate::scene::Node;
+
+use super::{relative_ior, scattered, Material, Scatter};
+
+/// Index of refraction, optionally depending on the wavelength (dispersion).
+#[derive(Copy, Clone, Debug)]
+pub enum RefractiveIndex {
+    Constant(f64),
+    /// n(λ) = a + b / λ², with λ in micrometers
+    Cauchy { a: f64, b: f64 },
+    /// n²(λ) = 1 + Σ b_i λ² / (λ² - c_i), with λ in micrometers
+    Sellmeier { b: [f64; 3], c: [f64; 3] },
+}
+
+impl RefractiveIndex {
+    /// Wavelength (in nm) of the Fraunhofer d-line, used for dispersive materials in RGB mode
+    const D_LINE: f64 = 587.56;
+
+    pub fn bk7() -> Self {
+        RefractiveIndex::Sellmeier {
+            b: [1.03961212, 0.231792344, 1.01046945],
+            c: [0.00600069867, 0.0200179144, 103.560653],
+        }
+    }
+
+    pub fn diamond() -> Self {
+        RefractiveIndex::Sellmeier { b: [0.3306, 4.3356, 0.0], c: [0.030625, 0.011236, 0.0] }
+    }
+
+    /// Returns index of refraction at `wavelength` (in nm).
+    pub fn at(&self, wavelength: Option<f64>) -> f64 {
+        let lambda = wavelength.unwrap_or(RefractiveIndex::D_LINE) / 1000.0;
+        let lambda2 = lambda * lambda;
+        match *self {
+            RefractiveIndex::Constant(n) => n,
+            RefractiveIndex::Cauchy { a, b } => a + b / lambda2,
+            RefractiveIndex::Sellmeier { b, c } => {
+                (1.0 + (0..3).map(|i| b[i] * lambda2 / (lambda2 - c[i])).sum::<f64>()).sqrt()
+            }
+        }
+    }
+
+    pub fn is_dispersive(&self) -> bool {
+        !matches!(self, RefractiveIndex::Constant(_))
+    }
+
+    /// Adds the index to the description of a material, as `ior` or the coefficients of the
+    /// dispersion formula read by [super::Params::refractive_index].
+    pub fn describe(&self, node: Node) -> Node {
+        match *self {
+            RefractiveIndex::Constant(n) => node.with("ior", n),
+            RefractiveIndex::Cauchy { a, b } => node.with("cauchy_a", a).with("cauchy_b", b),
+            RefractiveIndex::Sellmeier { b, c } => {
+                node.with("sellmeier_b", Vec3::new(b[0], b[1], b[2])).with("sellmeier_c", Vec3::new(c[0], c[1], c[2]))
+            }
+        }
+    }
+}
+
+/// Smooth glass-like material.
+pub struct Dielectric {
+    pub refractive_index: RefractiveIndex,
+    /// Absorption coefficient of the interior per unit length
+    pub absorption: Vec3,
+    /// Precedence over overlapping interiors, see [Interior]
+    pub priority: u32,
+}
+
+impl Dielectric {
+    /// Returns clear dielectric with lowest priority.
+    pub fn new(refractive_index: RefractiveIndex) -> Self {
+        Dielectric { refractive_index, absorption: BLACK, priority: 0 }
+    }
+}
+
+impl Material for Dielectric {
+    fn scatter(&self, ray: &Ray, hit: &Hit, rng: &mut dyn RngCore) -> Option<Scatter> {
+        let refraction_ratio = 1.0 / relative_ior(self.refractive_index.at(ray.wavelength), hit);
+
+        let unit_direction = ray.direction.unit_vector();
+        let normal = hit.shading.n;
+        let cos_theta = f64::min(-unit_direction.dot(normal), 1.0);
+        let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+
+        let cannot_refract = refraction_ratio * sin_theta > 1.0;
+        let direction = if cannot_refract
+            || reflectance(cos_theta, refraction_ratio) > rng.gen_range(0.0..1.0) {
+            reflect(unit_direction, normal)
+        } else {
+            refract(unit_direction, normal, refraction_ratio)
+        };
+        Some(Scatter { ray: scattered(ray, hit, direction), attenuation: WHITE })
+    }
+
+    fn is_dispersive(&self) -> bool {
+        self.refractive_index.is_dispersive()
+    }
+
+    fn interior(&self) -> Option<Interior> {
+        Some(Interior::clear(self.refractive_index, self.absorption, self.priority))
+    }
+
+    fn describe(&self) -> Option<Node> {
+        Some(self.refractive_index.describe(Node::new("dielectric"))
+            .with("absorption", self.absorption)
+            .with("priority", self.priority as f64))
+    }
+}
+
+pub(super) fn reflect(v: Vec3, normal: Vec3) -> Vec3 {
+    v - 2.0 * v.dot(normal) * normal
+}
+
+fn refract(uv: Vec3, normal: Vec3, etai_over_etat: f64) -> Vec3 {
+    let cos_theta = (-uv).dot(normal).min(1.0);
+    let r_out_perp = etai_over_etat * (uv + cos_theta * normal);
+    let r_out_parallel = -((1.0 - r_out_perp.length_squared()).abs().sqrt()) * normal;
+    r_out_perp + r_out_parallel
+}
+
+fn reflectance(cosine: f64, ref_idx: f64) -> f64 {
+    // Use Schlick's approximation for reflectance
+    let r0 = ((1.0 - ref_idx) / (1.0 + ref_idx)).powi(2);
+    r0 + (1.0 - r0) * (1.0 - cosine).powi(5)
+}
@@ -0,0 +1,365 @@
+use std::f64::consts::{LN_2, PI};
+
+use rand::{Rng, RngCore};
+
+use crate::{Frame, Ray, Vec3};
+use crate::color::{luminance, WHITE};
+use crate::microfacet::fresnel_dielectric;
+use crate::objects::Hit;
+use crate::scene::Node;
+
+use super::{scattered, Material, Scatter};
+
+/// Absorption coefficients of eumelanin and pheomelanin per unit concentration
+const EUMELANIN: Vec3 = Vec3 { x: 0.419, y: 0.697, z: 1.37 };
+const PHEOMELANIN: Vec3 = Vec3 { x: 0.187, y: 0.4, z: 1.05 };
+
+/// Number of lobes modelled separately: R, TT and TRT, higher orders are merged into one
+const P_MAX: usize = 3;
+
+/// Hair fiber after Chiang et al. 2016 (as in pbrt-v3), building on d'Eon et al. 2011: a
+/// dielectric cylinder with tilted cuticle scales, which reflects (R), transmits (TT) and reflects
+/// internally (TRT), colored by absorption inside the fiber. Meant for [crate::objects::Curve]s,
+/// the fiber runs along dp/du and the texture coordinate v gives the offset across it.
+pub struct Hair {
+    /// Absorption coefficient inside the fiber, relative to its diameter
+    pub sigma_a: Vec3,
+    /// Index of refraction of the fiber
+    pub eta: f64,
+    /// Longitudinal roughness in [0, 1]
+    pub beta_m: f64,
+    /// Azimuthal roughness in [0, 1]
+    pub beta_n: f64,
+    /// Tilt of the cuticle scales in degrees
+    pub alpha: f64,
+}
+
+impl Hair {
+    /// Returns hair with typical roughness, IOR and scale tilt.
+    pub fn new(sigma_a: Vec3) -> Self {
+        Hair { sigma_a, eta: 1.55, beta_m: 0.3, beta_n: 0.3, alpha: 2.0 }
+    }
+
+    /// Returns hair colored by its concentrations of eumelanin (brown to black, about 0.3 for
+    /// blond up to 8 for black hair) and pheomelanin (red).
+    pub fn from_melanin(eumelanin: f64, pheomelanin: f64) -> Self {
+        Hair::new(eumelanin * EUMELANIN + pheomelanin * PHEOMELANIN)
+    }
+
+    /// Returns hair that roughly appears in `color` after multiple scattering, for the given
+    /// azimuthal roughness.
+    pub fn from_color(color: Vec3, beta_n: f64) -> Self {
+        let denominator = 5.969 - 0.215 * beta_n + 2.532 * beta_n.powi(2) - 10.73 * beta_n.powi(3)
+            + 5.574 * beta_n.powi(4) + 0.245 * beta_n.powi(5);
+        let sigma_a = |c: f64| (c.max(1.0e-4).ln() / denominator).powi(2);
+        Hair { beta_n, ..Hair::new(Vec3::new(sigma_a(color.x), sigma_a(color.y), sigma_a(color.z))) }
+    }
+
+    /// Returns fiber model at the hit together with the frame it works in: x along the fiber and
+    /// z towards the incoming ray, as seen across the fiber.
+    fn at(&self, ray: &Ray, hit: &Hit) -> (HairBsdf, Frame) {
+        let wo = -ray.direction.unit_vector();
+        let tangent = hit.dpdu.unit_vector();
+        let across = wo - wo.dot(tangent) * tangent;
+        let n = if across.near_zero() { hit.normal } else { across.unit_vector() };
+        let frame = Frame::from_normal_tangent(n, tangent);
+        // Offset h in [-1, 1] along the frame's y axis
+        let h = (2.0 * hit.v - 1.0) * frame.t.dot(hit.dpdv).signum();
+        (HairBsdf::new(self, h), frame)
+    }
+}
+
+impl Material for Hair {
+    fn scatter(&self, ray: &Ray, hit: &Hit, rng: &mut dyn RngCore) -> Option<Scatter> {
+        let (bsdf, frame) = self.at(ray, hit);
+        let wo = frame.to_local(-ray.direction.unit_vector());
+        let wi = bsdf.sample(wo, rng.gen());
+        let pdf = bsdf.pdf(wo, wi);
+        if pdf <= 0.0 {
+            return None;
+        }
+        Some(Scatter { ray: scattered(ray, hit, frame.to_world(wi)), attenuation: bsdf.f(wo, wi) / pdf })
+    }
+
+    fn eval(&self, ray: &Ray, hit: &Hit, direction: Vec3) -> Vec3 {
+        let (bsdf, frame) = self.at(ray, hit);
+        bsdf.f(frame.to_local(-ray.direction.unit_vector()), frame.to_local(direction.unit_vector()))
+    }
+
+    fn pdf(&self, ray: &Ray, hit: &Hit, direction: Vec3) -> f64 {
+        let (bsdf, frame) = self.at(ray, hit);
+        bsdf.pdf(frame.to_local(-ray.direction.unit_vector()), frame.to_local(direction.unit_vector()))
+    }
+
+    fn describe(&self) -> Option<Node> {
+        Some(Node::new("hair")
+            .with("sigma_a", self.sigma_a)
+            .with("eta", self.eta)
+            .with("beta_m", self.beta_m)
+            .with("beta_n", self.beta_n)
+            .with("alpha", self.alpha))
+    }
+}
+
+/// Fiber scattering for a fixed offset `h`. Directions are given by their longitudinal angle
+/// theta (sine along x) and azimuth phi around the fiber, measured from y towards z.
+struct HairBsdf {
+    h: f64,
+    gamma_o: f64,
+    eta: f64,
+    sigma_a: Vec3,
+    /// Longitudinal variance per lobe
+    v: [f64; P_MAX + 1],
+    /// Azimuthal logistic scale
+    s: f64,
+    /// Sines and cosines of 2^k times the scale tilt
+    sin_2k_alpha: [f64; 3],
+    cos_2k_alpha: [f64; 3],
+}
+
+impl HairBsdf {
+    fn new(hair: &Hair, h: f64) -> Self {
+        let beta_m = hair.beta_m;
+        let v0 = (0.726 * beta_m + 0.812 * beta_m.powi(2) + 3.7 * beta_m.powi(20)).powi(2);
+        let beta_n = hair.beta_n;
+        let s = (PI / 8.0).sqrt() * (0.265 * beta_n + 1.194 * beta_n.powi(2) + 5.372 * beta_n.powi(22));
+        let mut sin_2k_alpha = [hair.alpha.to_radians().sin(), 0.0, 0.0];
+        let mut cos_2k_alpha = [safe_sqrt(1.0 - sin_2k_alpha[0].powi(2)), 0.0, 0.0];
+        for i in 1..3 {
+            sin_2k_alpha[i] = 2.0 * cos_2k_alpha[i - 1] * sin_2k_alpha[i - 1];
+            cos_2k_alpha[i] = cos_2k_alpha[i - 1].powi(2) - sin_2k_alpha[i - 1].powi(2);
+        }
+        HairBsdf {
+            h: h.clamp(-1.0, 1.0),
+            gamma_o: h.clamp(-1.0, 1.0).asin(),
+            eta: hair.eta,
+            sigma_a: hair.sigma_a,
+            v: [v0, 0.25 * v0, 4.0 * v0, 4.0 * v0],
+            s,
+            sin_2k_alpha,
+            cos_2k_alpha,
+        }
+    }
+
+    /// Returns sine and cosine of the outgoing longitudinal angle shifted by the scales for lobe `p`.
+    fn tilted(&self, p: usize, sin_theta_o: f64, cos_theta_o: f64) -> (f64, f64) {
+        let (sin, cos) = (self.sin_2k_alpha, self.cos_2k_alpha);
+        let (sin_op, cos_op) = match p {
+            0 => (sin_theta_o * cos[1] - cos_theta_o * sin[1], cos_theta_o * cos[1] + sin_theta_o * sin[1]),
+            1 => (sin_theta_o * cos[0] + cos_theta_o * sin[0], cos_theta_o * cos[0] - sin_theta_o * sin[0]),
+            2 => (sin_theta_o * cos[2] + cos_theta_o * sin[2], cos_theta_o * cos[2] - sin_theta_o * sin[2]),
+            _ => (sin_theta_o, cos_theta_o),
+        };
+        (sin_op, cos_op.abs())
+    }
+
+    /// Returns attenuation of each lobe and the azimuth of the refracted ray inside the fiber.
+    fn attenuations(&self, cos_theta_o: f64) -> ([Vec3; P_MAX + 1], f64) {
+        let sin_theta_o = safe_sqrt(1.0 - cos_theta_o * cos_theta_o);
+        let cos_theta_t = safe_sqrt(1.0 - (sin_theta_o / self.eta).powi(2));
+        // Modified IOR for the projection onto the cross section (Bravais)
+        let eta_p = (self.eta * self.eta - sin_theta_o * sin_theta_o).sqrt() / cos_theta_o;
+        let sin_gamma_t = self.h / eta_p;
+        let cos_gamma_t = safe_sqrt(1.0 - sin_gamma_t * sin_gamma_t);
+        let path = 2.0 * cos_gamma_t / cos_theta_t;
+        let t = Vec3::new((-self.sigma_a.x * path).exp(), (-self.sigma_a.y * path).exp(), (-self.sigma_a.z * path).exp());
+
+        let f = fresnel_dielectric(cos_theta_o * safe_sqrt(1.0 - self.h * self.h), self.eta);
+        let mut ap = [WHITE * f, (1.0 - f).powi(2) * t, WHITE, WHITE];
+        ap[2] = ap[1] * t * f;
+        // Geometric series of all remaining internal reflections
+        let rest = ap[2] * f * t;
+        ap[3] = Vec3::new(rest.x / (1.0 - t.x * f), rest.y / (1.0 - t.y * f), rest.z / (1.0 - t.z * f));
+        (ap, sin_gamma_t.clamp(-1.0, 1.0).asin())
+    }
+
+    /// Returns probabilities of sampling each lobe, proportional to its luminance.
+    fn lobe_pdfs(&self, cos_theta_o: f64) -> [f64; P_MAX + 1] {
+        let (ap, _) = self.attenuations(cos_theta_o);
+        let total: f64 = ap.iter().map(|a| luminance(*a)).sum();
+        ap.map(|a| luminance(a) / total)
+    }
+
+    /// Returns BSDF times cosine, i.e. without the division by the cosine of pbrt-v3.
+    fn f(&self, wo: Vec3, wi: Vec3) -> Vec3 {
+        let (sin_theta_o, cos_theta_o, phi_o) = angles(wo);
+        let (sin_theta_i, cos_theta_i, phi_i) = angles(wi);
+        let (ap, gamma_t) = self.attenuations(cos_theta_o);
+        let phi = phi_i - phi_o;
+        let lobes = (0..P_MAX).fold(Vec3::new(0.0, 0.0, 0.0), |sum, p| {
+            let (sin_op, cos_op) = self.tilted(p, sin_theta_o, cos_theta_o);
+            sum + mp(cos_theta_i, cos_op, sin_theta_i, sin_op, self.v[p]) * np(phi, p, self.s, self.gamma_o, gamma_t) * ap[p]
+        });
+        lobes + mp(cos_theta_i, cos_theta_o, sin_theta_i, sin_theta_o, self.v[P_MAX]) / (2.0 * PI) * ap[P_MAX]
+    }
+
+    fn pdf(&self, wo: Vec3, wi: Vec3) -> f64 {
+        let (sin_theta_o, cos_theta_o, phi_o) = angles(wo);
+        let (sin_theta_i, cos_theta_i, phi_i) = angles(wi);
+        let gamma_t = self.attenuations(cos_theta_o).1;
+        let pdfs = self.lobe_pdfs(cos_theta_o);
+        let phi = phi_i - phi_o;
+        let lobes: f64 = (0..P_MAX).map(|p| {
+            let (sin_op, cos_op) = self.tilted(p, sin_theta_o, cos_theta_o);
+            mp(cos_theta_i, cos_op, sin_theta_i, sin_op, self.v[p]) * pdfs[p] * np(phi, p, self.s, self.gamma_o, gamma_t)
+        }).sum();
+        lobes + mp(cos_theta_i, cos_theta_o, sin_theta_i, sin_theta_o, self.v[P_MAX]) * pdfs[P_MAX] / (2.0 * PI)
+    }
+
+    /// Samples incoming direction from uniform numbers `u` by picking a lobe and sampling its
+    /// longitudinal and azimuthal distributions.
+    fn sample(&self, wo: Vec3, u: [f64; 4]) -> Vec3 {
+        let (sin_theta_o, cos_theta_o, phi_o) = angles(wo);
+        let pdfs = self.lobe_pdfs(cos_theta_o);
+        let mut pick = u[0];
+        let mut p = 0;
+        while p < P_MAX && pick >= pdfs[p] {
+            pick -= pdfs[p];
+            p += 1;
+        }
+
+        let (sin_op, cos_op) = self.tilted(p, sin_theta_o, cos_theta_o);
+        let u2 = u[2].max(1.0e-5);
+        let cos_theta = 1.0 + self.v[p] * (u2 + (1.0 - u2) * (-2.0 / self.v[p]).exp()).ln();
+        let sin_theta = safe_sqrt(1.0 - cos_theta * cos_theta);
+        let cos_phi = (2.0 * PI * u[3]).cos();
+        let sin_theta_i = -cos_theta * sin_op + sin_theta * cos_phi * cos_op;
+        let cos_theta_i = safe_sqrt(1.0 - sin_theta_i * sin_theta_i);
+
+        let gamma_t = self.attenuations(cos_theta_o).1;
+        let d_phi = if p < P_MAX {
+            phi_p(p, self.gamma_o, gamma_t) + sample_trimmed_logistic(u[1], self.s, -PI, PI)
+        } else {
+            2.0 * PI * u[1]
+        };
+        let phi_i = phi_o + d_phi;
+        Vec3::new(sin_theta_i, cos_theta_i * phi_i.cos(), cos_theta_i * phi_i.sin())
+    }
+}
+
+/// Returns sine and cosine of the longitudinal angle and the azimuth of local direction `w`.
+fn angles(w: Vec3) -> (f64, f64, f64) {
+    let sin_theta = w.x.clamp(-1.0, 1.0);
+    (sin_theta, safe_sqrt(1.0 - sin_theta * sin_theta), w.z.atan2(w.y))
+}
+
+fn safe_sqrt(x: f64) -> f64 {
+    x.max(0.0).sqrt()
+}
+
+/// Longitudinal scattering function with variance `v`.
+fn mp(cos_theta_i: f64, cos_theta_o: f64, sin_theta_i: f64, sin_theta_o: f64, v: f64) -> f64 {
+    let a = cos_theta_i * cos_theta_o / v;
+    let b = sin_theta_i * sin_theta_o / v;
+    if v <= 0.1 {
+        // Evaluated in log space, the terms overflow for low roughness
+        (log_i0(a) - b - 1.0 / v + LN_2 + (1.0 / (2.0 * v)).ln()).exp()
+    } else {
+        (-b).exp() * i0(a) / ((1.0 / v).sinh() * 2.0 * v)
+    }
+}
+
+/// Modified Bessel function of the first kind and order zero.
+fn i0(x: f64) -> f64 {
+    let mut value = 0.0;
+    let (mut x2i, mut factorial, mut four_i) = (1.0, 1.0, 1.0);
+    for i in 0..10 {
+        if i > 1 {
+            factorial *= i as f64;
+        }
+        value += x2i / (four_i * factorial * factorial);
+        x2i *= x * x;
+        four_i *= 4.0;
+    }
+    value
+}
+
+fn log_i0(x: f64) -> f64 {
+    if x > 12.0 {
+        x + 0.5 * (-(2.0 * PI).ln() + (1.0 / x).ln() + 1.0 / (8.0 * x))
+    } else {
+        i0(x).ln()
+    }
+}
+
+/// Azimuthal deflection of lobe `p`.
+fn phi_p(p: usize, gamma_o: f64, gamma_t: f64) -> f64 {
+    2.0 * p as f64 * gamma_t - 2.0 * gamma_o + p as f64 * PI
+}
+
+/// Azimuthal scattering function of lobe `p` at azimuth difference `phi`.
+fn np(phi: f64, p: usize, s: f64, gamma_o: f64, gamma_t: f64) -> f64 {
+    let mut d_phi = phi - phi_p(p, gamma_o, gamma_t);
+    while d_phi > PI {
+        d_phi -= 2.0 * PI;
+    }
+    while d_phi < -PI {
+        d_phi += 2.0 * PI;
+    }
+    trimmed_logistic(d_phi, s, -PI, PI)
+}
+
+fn logistic(x: f64, s: f64) -> f64 {
+    let x = x.abs();
+    (-x / s).exp() / (s * (1.0 + (-x / s).exp()).powi(2))
+}
+
+fn logistic_cdf(x: f64, s: f64) -> f64 {
+    1.0 / (1.0 + (-x / s).exp())
+}
+
+/// Logistic distribution restricted to [`a`, `b`].
+fn trimmed_logistic(x: f64, s: f64, a: f64, b: f64) -> f64 {
+    logistic(x, s) / (logistic_cdf(b, s) - logistic_cdf(a, s))
+}
+
+fn sample_trimmed_logistic(u: f64, s: f64, a: f64, b: f64) -> f64 {
+    let k = logistic_cdf(b, s) - logistic_cdf(a, s);
+    let x = -s * (1.0 / (u * k + logistic_cdf(a, s)) - 1.0).ln();
+    x.clamp(a, b)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::Rng;
+
+    use super::*;
+
+    #[test]
+    fn test_hair_energy_conservation() {
+        let mut rng = rand::thread_rng();
+        for beta in [0.2, 0.5, 0.8] {
+            let hair = Hair { beta_m: beta, beta_n: beta, ..Hair::new(Vec3::new(0.0, 0.0, 0.0)) };
+            let bsdf = HairBsdf::new(&hair, rng.gen_range(-1.0..1.0));
+            let wo = Vec3::random_in_unit_sphere(&mut rng).unit_vector();
+
+            // Without absorption, all light leaves the fiber again (white furnace), integrated over
+            // evenly spread directions of a Fibonacci lattice, random ones miss narrow lobes
+            let count = 100_000;
+            let uniform: f64 = (0..count)
+                .map(|i| {
+                    let z = 1.0 - (2 * i + 1) as f64 / count as f64;
+                    let phi = i as f64 * PI * (3.0 - 5f64.sqrt());
+                    let r = (1.0 - z * z).sqrt();
+                    luminance(bsdf.f(wo, Vec3::new(r * phi.cos(), r * phi.sin(), z)))
+                })
+                .sum::<f64>() * 4.0 * PI / count as f64;
+            assert!((uniform - 1.0).abs() < 0.05, "beta {}: {}", beta, uniform);
+
+            // Importance sampling agrees and nearly cancels the BSDF
+            let sampled: f64 = (0..count)
+                .map(|_| {
+                    let wi = bsdf.sample(wo, rng.gen());
+                    luminance(bsdf.f(wo, wi)) / bsdf.pdf(wo, wi)
+                })
+                .sum::<f64>() / count as f64;
+            assert!((sampled - 1.0).abs() < 0.02, "beta {}: {}", beta, sampled);
+        }
+
+        // Melanin absorbs more of the blue end
+        let brown = Hair::from_melanin(1.3, 0.0);
+        let bsdf = HairBsdf::new(&brown, 0.3);
+        let color = bsdf.f(Vec3::new(0.1, 0.3, 0.9).unit_vector(), Vec3::new(-0.1, -0.2, -0.9).unit_vector());
+        assert!(color.x > color.y && color.y > color.z);
+    }
+}
@@ -0,0 +1,55 @@
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+use rand::RngCore;
+
+use crate::{Ray, Vec3};
+use crate::color::BLACK;
+use crate::objects::Hit;
+use crate::scene::Node;
+use crate::texture::{SolidColor, Texture};
+
+use super::{above_horizon, scattered, Material, Scatter};
+
+pub struct Lambertian {
+    pub albedo: Arc<dyn Texture>,
+}
+
+impl Lambertian {
+    pub fn new(albedo: Vec3) -> Self {
+        Lambertian { albedo: Arc::new(SolidColor::new(albedo)) }
+    }
+
+    pub fn textured(albedo: Arc<dyn Texture>) -> Self {
+        Lambertian { albedo }
+    }
+}
+
+impl Material for Lambertian {
+    fn scatter(&self, ray: &Ray, hit: &Hit, rng: &mut dyn RngCore) -> Option<Scatter> {
+        let normal = hit.shading.n;
+        let mut scatter_direction = normal + Vec3::random_in_unit_sphere(rng).unit_vector();
+        if scatter_direction.near_zero() {
+            scatter_direction = normal;
+        }
+        if !above_horizon(hit, scatter_direction) {
+            return None;
+        }
+        Some(Scatter { ray: scattered(ray, hit, scatter_direction), attenuation: self.albedo.value(hit) })
+    }
+
+    fn eval(&self, _ray: &Ray, hit: &Hit, direction: Vec3) -> Vec3 {
+        if !above_horizon(hit, direction) {
+            return BLACK;
+        }
+        self.albedo.value(hit) * (direction.unit_vector().dot(hit.shading.n).max(0.0) / PI)
+    }
+
+    fn pdf(&self, _ray: &Ray, hit: &Hit, direction: Vec3) -> f64 {
+        direction.unit_vector().dot(hit.shading.n).max(0.0) / PI
+    }
+
+    fn describe(&self) -> Option<Node> {
+        Some(Node::new("lambertian").with("albedo", self.albedo.describe()?))
+    }
+}
@@ -0,0 +1,222 @@
+use std::sync::Arc;
+
+use rand::{Rng, RngCore};
+
+use crate::{Ray, Vec3};
+use crate::color::BLACK;
+use crate::medium::Interior;
+use crate::microfacet::{fresnel_dielectric, refract};
+use crate::objects::Hit;
+use crate::scene::Node;
+
+use super::{Dielectric, Material, RefractiveIndex, RoughDielectric, Scatter};
+
+/// Upper bound of interface interactions inside the coating
+const MAX_DEPTH: usize = 16;
+
+/// Dielectric coating over an arbitrary base material, e.g. varnish over wood or the clear coat
+/// of car paint. Scattering is simulated with a stochastic random walk between the interfaces in
+/// the spirit of position-free layered BSDFs (Guo et al. 2018). The coating always faces the
+/// incoming ray.
+pub struct Layered {
+    pub base: Arc<dyn Material>,
+    pub refractive_index: RefractiveIndex,
+    /// GGX alpha of the coating's surface, zero for a smooth coating
+    pub alpha: f64,
+    pub thickness: f64,
+    /// Absorption coefficient of the coating per unit length
+    pub absorption: Vec3,
+}
+
+impl Layered {
+    /// Returns clear, smooth and infinitely thin coating over `base`.
+    pub fn new(base: Arc<dyn Material>, refractive_index: RefractiveIndex) -> Self {
+        Layered { base, refractive_index, alpha: 0.0, thickness: 0.0, absorption: BLACK }
+    }
+
+    /// Calls `f` with the material of the coating's surface.
+    fn with_coating<T>(&self, f: impl FnOnce(&dyn Material) -> T) -> T {
+        let refractive_index = self.refractive_index;
+        if self.alpha > 0.0 {
+            f(&RoughDielectric::new(refractive_index, self.alpha, self.alpha))
+        } else {
+            f(&Dielectric::new(refractive_index))
+        }
+    }
+
+    /// Returns fraction of light crossing the coating at angle with cosine `cos_theta`.
+    fn transmittance(&self, cos_theta: f64) -> Vec3 {
+        let distance = self.thickness / cos_theta.abs().max(1.0e-6);
+        let a = self.absorption * distance;
+        Vec3::new((-a.x).exp(), (-a.y).exp(), (-a.z).exp())
+    }
+
+    fn random_walk(&self, coating: &dyn Material, ray: &Ray, hit: &Hit, rng: &mut dyn RngCore) -> Option<Scatter> {
+        let up = hit.shading.n;
+        let top = Hit { front_face: true, material: coating, ..*hit };
+        let entrance = coating.scatter(ray, &top, rng)?;
+        if entrance.ray.direction.dot(up) > 0.0 {
+            return Some(entrance);
+        }
+
+        // Coating seen from inside the layer and base below it
+        let below = Hit { normal: -hit.normal, shading: hit.shading.flipped(), front_face: false, material: coating, ..*hit };
+        let base = Hit { front_face: true, material: self.base.as_ref(), ..*hit };
+        let mut attenuation = entrance.attenuation;
+        let mut direction = entrance.ray.direction.unit_vector();
+        for depth in 0..MAX_DEPTH {
+            let max = attenuation.x.max(attenuation.y).max(attenuation.z);
+            if depth > 3 && max < 0.25 {
+                let q = 1.0 - max;
+                if rng.gen::<f64>() < q {
+                    return None;
+                }
+                attenuation = attenuation / (1.0 - q);
+            }
+            attenuation = attenuation * self.transmittance(direction.dot(up));
+
+            let downwards = direction.dot(up) < 0.0;
+            let inner = Ray { origin: hit.point, direction, time: ray.time, wavelength: ray.wavelength, differentials: None };
+            let scatter = if downwards {
+                self.base.scatter(&inner, &base, rng)
+            } else {
+                coating.scatter(&inner, &below, rng)
+            }?;
+            attenuation = attenuation * scatter.attenuation;
+            direction = scatter.ray.direction.unit_vector();
+            // Light leaves the layer through the base (if transmissive) or the coating
+            if (direction.dot(up) < 0.0) == downwards {
+                return Some(Scatter { ray: scatter.ray, attenuation });
+            }
+        }
+        None
+    }
+
+    /// Refracts the outgoing and incident directions through a smooth coating, returns the ray
+    /// arriving at the base and the incident direction seen from the base.
+    fn inner_directions(&self, ray: &Ray, hit: &Hit, direction: Vec3) -> Option<(Ray, Vec3)> {
+        let eta = self.refractive_index.at(ray.wavelength);
+        let wo = -ray.direction.unit_vector();
+        let wi = direction.unit_vector();
+        if wo.dot(hit.shading.n) <= 0.0 || wi.dot(hit.shading.n) <= 0.0 {
+            return None;
+        }
+        let inner = Ray { origin: hit.point, direction: refract(wo, hit.shading.n, eta)?, time: ray.time, wavelength: ray.wavelength, differentials: None };
+        Some((inner, -refract(wi, hit.shading.n, eta)?))
+    }
+}
+
+impl Material for Layered {
+    fn scatter(&self, ray: &Ray, hit: &Hit, rng: &mut dyn RngCore) -> Option<Scatter> {
+        self.with_coating(|coating| self.random_walk(coating, ray, hit, rng))
+    }
+
+    /// Approximates BSDF times cosine by reflection at the coating and a single bounce off the
+    /// base, ignoring interreflections within the layer.
+    fn eval(&self, ray: &Ray, hit: &Hit, direction: Vec3) -> Vec3 {
+        let specular = self.with_coating(|coating| {
+            coating.eval(ray, &Hit { front_face: true, material: coating, ..*hit }, direction)
+        });
+        match self.inner_directions(ray, hit, direction) {
+            Some((inner, wi)) => {
+                let base = Hit { front_face: true, material: self.base.as_ref(), ..*hit };
+                let (cos_o, cos_i) = (-ray.direction.unit_vector().dot(hit.shading.n), direction.unit_vector().dot(hit.shading.n));
+                let eta = self.refractive_index.at(ray.wavelength);
+                let fresnel = (1.0 - fresnel_dielectric(cos_o, eta)) * (1.0 - fresnel_dielectric(cos_i, eta));
+                // Refraction compresses the solid angle, the base's cosine is replaced by the outer one
+                let scale = fresnel * cos_i / (eta * eta * wi.dot(hit.shading.n));
+                let transmittance = self.transmittance(inner.direction.dot(hit.shading.n)) * self.transmittance(wi.dot(hit.shading.n));
+                specular + scale * transmittance * self.base.eval(&inner, &base, wi)
+            }
+            None => specular,
+        }
+    }
+
+    /// Approximates the probability density of [Self::scatter] like [Self::eval].
+    fn pdf(&self, ray: &Ray, hit: &Hit, direction: Vec3) -> f64 {
+        let specular = self.with_coating(|coating| {
+            coating.pdf(ray, &Hit { front_face: true, material: coating, ..*hit }, direction)
+        });
+        match self.inner_directions(ray, hit, direction) {
+            Some((inner, wi)) => {
+                let base = Hit { front_face: true, material: self.base.as_ref(), ..*hit };
+                let (cos_o, cos_i) = (-ray.direction.unit_vector().dot(hit.shading.n), direction.unit_vector().dot(hit.shading.n));
+                let eta = self.refractive_index.at(ray.wavelength);
+                let jacobian = cos_i / (eta * eta * wi.dot(hit.shading.n));
+                specular + (1.0 - fresnel_dielectric(cos_o, eta)) * jacobian * self.base.pdf(&inner, &base, wi)
+            }
+            None => specular,
+        }
+    }
+
+    fn is_dispersive(&self) -> bool {
+        self.refractive_index.is_dispersive() || self.base.is_dispersive()
+    }
+
+    fn opacity(&self, hit: &Hit) -> f64 {
+        self.base.opacity(hit)
+    }
+
+    /// Returns interior of the base, the coating is too thin to hold a medium.
+    fn interior(&self) -> Option<Interior> {
+        self.base.interior()
+    }
+
+    fn describe(&self) -> Option<Node> {
+        let node = Node::new("layered").with("base", self.base.describe()?);
+        Some(self.refractive_index.describe(node)
+            .with("alpha", self.alpha)
+            .with("thickness", self.thickness)
+            .with("absorption", self.absorption))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::WHITE;
+    use crate::Frame;
+    use crate::material::Lambertian;
+
+    fn albedo(material: &Layered) -> Vec3 {
+        let mut rng = rand::thread_rng();
+        let normal = Vec3::new(0.0, 0.0, 1.0);
+        let hit = Hit {
+            point: Vec3::new(0.0, 0.0, 0.0),
+            object_point: Vec3::new(0.0, 0.0, 0.0),
+            normal,
+            shading: Frame::from_normal(normal),
+            u: 0.0,
+            v: 0.0,
+            dpdu: Vec3::new(1.0, 0.0, 0.0),
+            dpdv: Vec3::new(0.0, 1.0, 0.0),
+            footprint: Default::default(),
+            color: None,
+            t: 1.0,
+            exterior_ior: 1.0,
+            front_face: true,
+            material: material.base.as_ref(),
+        };
+        let ray = Ray { origin: normal, direction: Vec3::new(0.6, 0.0, -0.8), time: 0.0, wavelength: None, differentials: None };
+        let n = 20_000;
+        let mut sum = Vec3::new(0.0, 0.0, 0.0);
+        for _ in 0..n {
+            if let Some(scatter) = material.scatter(&ray, &hit, &mut rng) {
+                assert!(scatter.ray.direction.dot(normal) > 0.0);
+                sum = sum + scatter.attenuation;
+            }
+        }
+        sum / n as f64
+    }
+
+    #[test]
+    fn test_energy_conservation() {
+        let clear = Layered::new(Arc::new(Lambertian::new(WHITE)), RefractiveIndex::Constant(1.5));
+        let a = albedo(&clear);
+        assert!(a.x > 0.9 && a.x < 1.02, "{:?}", a);
+
+        let tinted = Layered { thickness: 0.1, absorption: Vec3::new(0.0, 2.0, 8.0), alpha: 0.2, ..clear };
+        let a = albedo(&tinted);
+        assert!(a.x > 0.75 && a.y < a.x && a.z < a.y, "{:?}", a);
+    }
+}
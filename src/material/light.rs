@@ -0,0 +1,59 @@
+use rand::RngCore;
+
+use crate::{Ray, Vec3};
+use crate::objects::Hit;
+use crate::scene::Node;
+
+use super::{Material, Scatter};
+
+/// Emits light uniformly in all directions and does not scatter.
+pub struct DiffuseLight {
+    pub emit: Vec3,
+}
+
+impl Material for DiffuseLight {
+    fn scatter(&self, _ray: &Ray, _hit: &Hit, _rng: &mut dyn RngCore) -> Option<Scatter> {
+        None
+    }
+
+    fn emitted(&self, _ray: &Ray, _hit: &Hit) -> Vec3 {
+        self.emit
+    }
+
+    fn describe(&self) -> Option<Node> {
+        Some(Node::new("diffuse_light").with("emit", self.emit))
+    }
+}
+
+/// Emits light within a cone around `direction`, fading out between the inner and outer cone
+/// angle. Meant for small light sources, as it only considers the direction of the outgoing light
+/// and not the orientation of the surface.
+pub struct SpotLight {
+    pub emit: Vec3,
+    /// Unit axis of the cone
+    pub direction: Vec3,
+    /// Cosine of the angle up to which the light has full intensity
+    pub cos_inner: f64,
+    /// Cosine of the angle beyond which no light is emitted
+    pub cos_outer: f64,
+}
+
+impl Material for SpotLight {
+    fn scatter(&self, _ray: &Ray, _hit: &Hit, _rng: &mut dyn RngCore) -> Option<Scatter> {
+        None
+    }
+
+    fn emitted(&self, ray: &Ray, _hit: &Hit) -> Vec3 {
+        let cos_theta = -ray.direction.unit_vector().dot(self.direction);
+        let t = ((cos_theta - self.cos_outer) / (self.cos_inner - self.cos_outer).max(1.0e-9)).clamp(0.0, 1.0);
+        (t * t) * self.emit
+    }
+
+    fn describe(&self) -> Option<Node> {
+        Some(Node::new("spot_light")
+            .with("emit", self.emit)
+            .with("direction", self.direction)
+            .with("cos_inner", self.cos_inner)
+            .with("cos_outer", self.cos_outer))
+    }
+}
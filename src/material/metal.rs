@@ -0,0 +1,29 @@
+use rand::RngCore;
+
+use crate::{Ray, Vec3};
+use crate::objects::Hit;
+use crate::scene::Node;
+
+use super::dielectric::reflect;
+use super::{above_horizon, scattered, Material, Scatter};
+
+pub struct Metal {
+    pub albedo: Vec3,
+    pub fuzz: f64,
+}
+
+impl Material for Metal {
+    fn scatter(&self, ray: &Ray, hit: &Hit, rng: &mut dyn RngCore) -> Option<Scatter> {
+        let reflected = reflect(ray.direction.unit_vector(), hit.shading.n);
+        let direction = reflected + self.fuzz * Vec3::random_in_unit_sphere(rng);
+        if reflected.dot(hit.shading.n) > 0.0 && above_horizon(hit, direction) {
+            Some(Scatter { ray: scattered(ray, hit, direction), attenuation: self.albedo })
+        } else {
+            None
+        }
+    }
+
+    fn describe(&self) -> Option<Node> {
+        Some(Node::new("metal").with("albedo", self.albedo).with("fuzz", self.fuzz))
+    }
+}
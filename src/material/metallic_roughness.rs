@@ -0,0 +1,87 @@
+use std::sync::Arc;
+
+use rand::RngCore;
+
+use crate::{Ray, Vec3};
+use crate::color::WHITE;
+use crate::objects::Hit;
+use crate::scene::Node;
+use crate::texture::Texture;
+
+use super::{Material, Principled, Scatter};
+
+/// Metallic-roughness material of glTF 2.0, a [Principled] BSDF whose base color, metallic and
+/// roughness are scaled by textures and vertex colors ([Hit::color]), plus emission.
+pub struct MetallicRoughness {
+    /// Constant parameters, base color, metallic and roughness are the factors the textures scale
+    pub principled: Principled,
+    pub base_color_texture: Option<Arc<dyn Texture>>,
+    /// Roughness in the green and metallic in the blue channel
+    pub metallic_roughness_texture: Option<Arc<dyn Texture>>,
+    pub emission: Vec3,
+    pub emission_texture: Option<Arc<dyn Texture>>,
+}
+
+impl MetallicRoughness {
+    pub fn new(principled: Principled) -> Self {
+        MetallicRoughness {
+            principled,
+            base_color_texture: None,
+            metallic_roughness_texture: None,
+            emission: Vec3::new(0.0, 0.0, 0.0),
+            emission_texture: None,
+        }
+    }
+
+    /// Returns BSDF parameters at the hit.
+    fn at(&self, hit: &Hit) -> Principled {
+        let mut principled = self.principled;
+        if let Some(texture) = &self.base_color_texture {
+            principled.base_color = principled.base_color * texture.value(hit);
+        }
+        principled.base_color = principled.base_color * hit.color.unwrap_or(WHITE);
+        if let Some(texture) = &self.metallic_roughness_texture {
+            let value = texture.value(hit);
+            principled.roughness *= value.y;
+            principled.metallic *= value.z;
+        }
+        principled
+    }
+}
+
+impl Material for MetallicRoughness {
+    fn scatter(&self, ray: &Ray, hit: &Hit, rng: &mut dyn RngCore) -> Option<Scatter> {
+        Material::scatter(&self.at(hit), ray, hit, rng)
+    }
+
+    fn eval(&self, ray: &Ray, hit: &Hit, direction: Vec3) -> Vec3 {
+        Material::eval(&self.at(hit), ray, hit, direction)
+    }
+
+    fn pdf(&self, ray: &Ray, hit: &Hit, direction: Vec3) -> f64 {
+        Material::pdf(&self.at(hit), ray, hit, direction)
+    }
+
+    fn emitted(&self, _ray: &Ray, hit: &Hit) -> Vec3 {
+        match &self.emission_texture {
+            Some(texture) => self.emission * texture.value(hit),
+            None => self.emission,
+        }
+    }
+
+    fn describe(&self) -> Option<Node> {
+        let mut node = self.principled.describe()?;
+        node.kind = "metallic_roughness".to_string();
+        let textures = [
+            ("base_color_texture", &self.base_color_texture),
+            ("metallic_roughness_texture", &self.metallic_roughness_texture),
+            ("emission_texture", &self.emission_texture),
+        ];
+        for (name, texture) in textures {
+            if let Some(texture) = texture {
+                node = node.with(name, texture.describe()?);
+            }
+        }
+        Some(node.with("emission", self.emission))
+    }
+}
@@ -0,0 +1,74 @@
+use std::sync::Arc;
+
+use rand::{Rng, RngCore};
+
+use crate::{Ray, Vec3};
+use crate::color::luminance;
+use crate::medium::Interior;
+use crate::objects::Hit;
+use crate::scene::Node;
+use crate::texture::Texture;
+
+use super::{Material, Scatter};
+
+/// Blends two materials, `weight` is the fraction of the second one.
+pub struct Mix {
+    pub first: Arc<dyn Material>,
+    pub second: Arc<dyn Material>,
+    pub weight: Arc<dyn Texture>,
+}
+
+impl Mix {
+    /// Returns mixing weight from the luminance of the texture, clamped to [0, 1].
+    fn weight(&self, hit: &Hit) -> f64 {
+        luminance(self.weight.value(hit)).clamp(0.0, 1.0)
+    }
+}
+
+impl Material for Mix {
+    fn scatter(&self, ray: &Ray, hit: &Hit, rng: &mut dyn RngCore) -> Option<Scatter> {
+        // Choosing a material with probability equal to its weight cancels the weight
+        if rng.gen::<f64>() < self.weight(hit) {
+            self.second.scatter(ray, hit, rng)
+        } else {
+            self.first.scatter(ray, hit, rng)
+        }
+    }
+
+    fn eval(&self, ray: &Ray, hit: &Hit, direction: Vec3) -> Vec3 {
+        let w = self.weight(hit);
+        (1.0 - w) * self.first.eval(ray, hit, direction) + w * self.second.eval(ray, hit, direction)
+    }
+
+    fn pdf(&self, ray: &Ray, hit: &Hit, direction: Vec3) -> f64 {
+        let w = self.weight(hit);
+        (1.0 - w) * self.first.pdf(ray, hit, direction) + w * self.second.pdf(ray, hit, direction)
+    }
+
+    fn emitted(&self, ray: &Ray, hit: &Hit) -> Vec3 {
+        let w = self.weight(hit);
+        (1.0 - w) * self.first.emitted(ray, hit) + w * self.second.emitted(ray, hit)
+    }
+
+    fn is_dispersive(&self) -> bool {
+        self.first.is_dispersive() || self.second.is_dispersive()
+    }
+
+    fn opacity(&self, hit: &Hit) -> f64 {
+        let w = self.weight(hit);
+        (1.0 - w) * self.first.opacity(hit) + w * self.second.opacity(hit)
+    }
+
+    /// Returns interior of the first material, or of the second if the first has none. Both
+    /// share the surface, so they can't bound different media.
+    fn interior(&self) -> Option<Interior> {
+        self.first.interior().or_else(|| self.second.interior())
+    }
+
+    fn describe(&self) -> Option<Node> {
+        Some(Node::new("mix")
+            .with("first", self.first.describe()?)
+            .with("second", self.second.describe()?)
+            .with("weight", self.weight.describe()?))
+    }
+}
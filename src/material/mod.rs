@@ -0,0 +1,107 @@
+pub use alpha_mask::AlphaMask;
+pub use dielectric::*;
+pub use hair::Hair;
+pub use lambertian::*;
+pub use layered::Layered;
+pub use light::{DiffuseLight, SpotLight};
+pub use metal::Metal;
+pub use metallic_roughness::MetallicRoughness;
+pub use mix::Mix;
+pub use normal_map::{BumpMap, NormalMap};
+pub use principled::Principled;
+pub use registry::*;
+pub use rough::*;
+pub use subsurface::Subsurface;
+pub use volume::Volumetric;
+
+use rand::RngCore;
+
+use crate::{Ray, Vec3};
+use crate::color::BLACK;
+use crate::medium::Interior;
+use crate::objects::Hit;
+use crate::scene::Node;
+
+/// Describes how light interacts with a surface. Implemented by the materials of this crate and
+/// open for user-defined shading models.
+pub trait Material: Send + Sync {
+    /// Samples the direction light arrives from, `None` if the ray is absorbed.
+    fn scatter(&self, ray: &Ray, hit: &Hit, rng: &mut dyn RngCore) -> Option<Scatter>;
+
+    /// Returns BSDF times cosine for light arriving from (world) `direction` and leaving along
+    /// the reversed incoming ray. Zero for perfectly specular and fuzzy materials.
+    fn eval(&self, _ray: &Ray, _hit: &Hit, _direction: Vec3) -> Vec3 {
+        BLACK
+    }
+
+    /// Returns probability density of [Self::scatter] sampling (world) `direction`.
+    fn pdf(&self, _ray: &Ray, _hit: &Hit, _direction: Vec3) -> f64 {
+        0.0
+    }
+
+    /// Returns radiance emitted towards the origin of the incoming ray.
+    fn emitted(&self, _ray: &Ray, _hit: &Hit) -> Vec3 {
+        BLACK
+    }
+
+    /// Returns true if the scattering direction depends on the wavelength.
+    fn is_dispersive(&self) -> bool {
+        false
+    }
+
+    /// Returns opacity in [0, 1] at the hit, rays pass through the surface with probability
+    /// 1 - opacity (see [crate::objects::hit_opaque]).
+    fn opacity(&self, _hit: &Hit) -> f64 {
+        1.0
+    }
+
+    /// Returns interior of closed surfaces that light refracts into, tracked by the integrator
+    /// for absorption and nesting.
+    fn interior(&self) -> Option<Interior> {
+        None
+    }
+
+    /// Returns registry name (see [MaterialRegistry]) and parameters of the material for saving
+    /// scenes, `None` if it can't be saved.
+    fn describe(&self) -> Option<Node> {
+        None
+    }
+}
+
+/// Scattered ray and the factor by which the light arriving along it is attenuated.
+pub struct Scatter {
+    pub ray: Ray,
+    pub attenuation: Vec3,
+}
+
+/// Returns ray leaving the hit point in `direction`, keeping time and wavelength of `ray`.
+fn scattered(ray: &Ray, hit: &Hit, direction: Vec3) -> Ray {
+    Ray { origin: hit.point, direction, time: ray.time, wavelength: ray.wavelength, differentials: None }
+}
+
+/// Returns true if (world) `direction` leaves the surface on the side of the incoming ray.
+/// Shading normals can make reflections point into the surface, which would leak light.
+fn above_horizon(hit: &Hit, direction: Vec3) -> bool {
+    direction.dot(hit.normal) > 0.0
+}
+
+/// Returns IOR on the far side of the surface relative to the side the ray arrives from.
+fn relative_ior(refractive_index: f64, hit: &Hit) -> f64 {
+    if hit.front_face { refractive_index / hit.exterior_ior } else { hit.exterior_ior / refractive_index }
+}
+
+mod alpha_mask;
+mod dielectric;
+mod hair;
+mod lambertian;
+mod layered;
+mod light;
+mod metal;
+mod metallic_roughness;
+mod mix;
+mod normal_map;
+mod principled;
+mod registry;
+mod rough;
+mod subsurface;
+mod volume;
@@ -0,0 +1,176 @@
+use std::sync::Arc;
+
+use rand::RngCore;
+
+use crate::{Frame, Ray, Vec3};
+use crate::frame::ensure_valid_reflection;
+use crate::medium::Interior;
+use crate::objects::Hit;
+use crate::scene::Node;
+use crate::texture::Texture;
+
+use super::{Material, Scatter};
+
+/// Offset in texture coordinates for finite differences of bump maps
+const BUMP_DELTA: f64 = 0.0005;
+
+/// Perturbs the shading normal of `material` with a tangent-space normal map, whose colors encode
+/// normals as (x, y, z) * 0.5 + 0.5 with z along the surface normal.
+pub struct NormalMap {
+    pub material: Arc<dyn Material>,
+    pub texture: Arc<dyn Texture>,
+    /// Scales the deviation from the unperturbed normal, 1 applies the map as is
+    pub strength: f64,
+}
+
+impl NormalMap {
+    fn shading_normal(&self, hit: &Hit) -> Vec3 {
+        let n = 2.0 * self.texture.value(hit) - Vec3::new(1.0, 1.0, 1.0);
+        let n = Vec3::new(self.strength * n.x, self.strength * n.y, n.z.max(0.0));
+        if n.near_zero() { hit.shading.n } else { hit.shading.to_world(n.unit_vector()) }
+    }
+
+    fn description(&self) -> Option<Node> {
+        Some(Node::new("normal_map")
+            .with("material", self.material.describe()?)
+            .with("texture", self.texture.describe()?)
+            .with("strength", self.strength))
+    }
+}
+
+/// Perturbs the shading normal of `material` by the gradient of a grayscale height map.
+pub struct BumpMap {
+    pub material: Arc<dyn Material>,
+    pub height: Arc<dyn Texture>,
+    /// Height of white relative to black, in units of the texture coordinates
+    pub strength: f64,
+}
+
+impl BumpMap {
+    fn shading_normal(&self, hit: &Hit) -> Vec3 {
+        let height = |u: f64, v: f64| self.height.value(&Hit { u, v, ..*hit }).x;
+        let h = height(hit.u, hit.v);
+        let dh_du = self.strength * (height(hit.u + BUMP_DELTA, hit.v) - h) / BUMP_DELTA;
+        let dh_dv = self.strength * (height(hit.u, hit.v + BUMP_DELTA) - h) / BUMP_DELTA;
+        // Cross product of the displaced tangents, treating the frame's tangents as unit derivatives
+        (hit.shading.n - dh_du * hit.shading.s - dh_dv * hit.shading.t).unit_vector()
+    }
+
+    fn description(&self) -> Option<Node> {
+        Some(Node::new("bump_map")
+            .with("material", self.material.describe()?)
+            .with("height", self.height.describe()?)
+            .with("strength", self.strength))
+    }
+}
+
+/// Returns `hit` with shading normal `n`, bent such that reflections stay above the surface.
+fn perturbed<'a>(ray: &Ray, hit: &Hit<'a>, n: Vec3) -> Hit<'a> {
+    let n = ensure_valid_reflection(hit.normal, -ray.direction.unit_vector(), n);
+    Hit { shading: Frame::from_normal_tangent(n, hit.shading.s), ..*hit }
+}
+
+macro_rules! delegate_material {
+    ($t:ty) => {
+        impl Material for $t {
+            fn scatter(&self, ray: &Ray, hit: &Hit, rng: &mut dyn RngCore) -> Option<Scatter> {
+                self.material.scatter(ray, &perturbed(ray, hit, self.shading_normal(hit)), rng)
+            }
+
+            fn eval(&self, ray: &Ray, hit: &Hit, direction: Vec3) -> Vec3 {
+                self.material.eval(ray, &perturbed(ray, hit, self.shading_normal(hit)), direction)
+            }
+
+            fn pdf(&self, ray: &Ray, hit: &Hit, direction: Vec3) -> f64 {
+                self.material.pdf(ray, &perturbed(ray, hit, self.shading_normal(hit)), direction)
+            }
+
+            fn emitted(&self, ray: &Ray, hit: &Hit) -> Vec3 {
+                self.material.emitted(ray, hit)
+            }
+
+            fn is_dispersive(&self) -> bool {
+                self.material.is_dispersive()
+            }
+
+            fn opacity(&self, hit: &Hit) -> f64 {
+                self.material.opacity(hit)
+            }
+
+            fn interior(&self) -> Option<Interior> {
+                self.material.interior()
+            }
+
+            fn describe(&self) -> Option<Node> {
+                self.description()
+            }
+        }
+    };
+}
+
+delegate_material!(NormalMap);
+delegate_material!(BumpMap);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::WHITE;
+    use crate::material::{Lambertian, Metal};
+    use crate::texture::SolidColor;
+
+    fn hit(material: &dyn Material) -> Hit<'_> {
+        let normal = Vec3::new(0.0, 0.0, 1.0);
+        Hit {
+            point: Vec3::new(0.0, 0.0, 0.0),
+            object_point: Vec3::new(0.0, 0.0, 0.0),
+            normal,
+            shading: Frame::from_normal_tangent(normal, Vec3::new(1.0, 0.0, 0.0)),
+            u: 0.5,
+            v: 0.5,
+            dpdu: Vec3::new(1.0, 0.0, 0.0),
+            dpdv: Vec3::new(0.0, 1.0, 0.0),
+            footprint: Default::default(),
+            color: None,
+            t: 1.0,
+            exterior_ior: 1.0,
+            front_face: true,
+            material,
+        }
+    }
+
+    #[test]
+    fn test_flat_normal_map() {
+        let map = NormalMap {
+            material: Arc::new(Lambertian::new(WHITE)),
+            texture: Arc::new(SolidColor::new(Vec3::new(0.5, 0.5, 1.0))),
+            strength: 1.0,
+        };
+        let n = map.shading_normal(&hit(&map));
+        assert!((n - Vec3::new(0.0, 0.0, 1.0)).length() < 1e-9);
+    }
+
+    #[test]
+    fn test_no_light_below_horizon() {
+        let mut rng = rand::thread_rng();
+        // Normal tilted by about 70 degrees towards +x
+        let tilted = Arc::new(SolidColor::new(Vec3::new(0.97, 0.5, 0.67)));
+        let materials: [Arc<dyn Material>; 2] = [
+            Arc::new(Lambertian::new(WHITE)),
+            Arc::new(Metal { albedo: WHITE, fuzz: 0.0 }),
+        ];
+        // Grazing view from -x, the mirror reflection about the tilted normal points downwards
+        let ray = Ray { origin: Vec3::new(-1.0, 0.0, 0.1), direction: Vec3::new(0.995, 0.0, -0.1), time: 0.0, wavelength: None, differentials: None };
+        for material in materials {
+            let map = NormalMap { material, texture: tilted.clone(), strength: 1.0 };
+            let hit = hit(&map);
+            let mut scattered = 0;
+            for _ in 0..1000 {
+                if let Some(scatter) = map.scatter(&ray, &hit, &mut rng) {
+                    assert!(scatter.ray.direction.dot(hit.normal) > 0.0);
+                    scattered += 1;
+                }
+            }
+            assert!(scattered > 0);
+        }
+    }
+}
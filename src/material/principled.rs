@@ -0,0 +1,327 @@
+use std::f64::consts::PI;
+
+use rand::{Rng, RngCore};
+
+use crate::{Ray, Vec3};
+use crate::color::{luminance, WHITE};
+use crate::microfacet::{fresnel_dielectric, reflect, refract, roughness_to_alpha, TrowbridgeReitz};
+use crate::objects::Hit;
+use crate::scene::Node;
+
+use super::{relative_ior, scattered, Material, Scatter};
+
+/// Parameters of the principled BSDF after Burley (2012, 2015), all in [0, 1] except for the IOR.
+/// Directions in the methods are unit vectors in the local shading frame pointing away from the
+/// surface, with `wo` in the upper hemisphere.
+#[derive(Copy, Clone, Debug)]
+pub struct Principled {
+    pub base_color: Vec3,
+    /// Blends between dielectric (0) and metal (1)
+    pub metallic: f64,
+    pub roughness: f64,
+    /// Specular reflectance at normal incidence of dielectrics, 0.5 corresponds to 4%
+    pub specular: f64,
+    /// Tints dielectric specular reflection towards the base color
+    pub specular_tint: f64,
+    /// Additional grazing reflection for cloth
+    pub sheen: f64,
+    pub sheen_tint: f64,
+    /// Second, white specular lobe with fixed IOR of 1.5
+    pub clearcoat: f64,
+    pub clearcoat_gloss: f64,
+    /// Blends between opaque and glass-like dielectrics
+    pub transmission: f64,
+    /// Index of refraction used for transmission
+    pub ior: f64,
+}
+
+impl Default for Principled {
+    fn default() -> Self {
+        Principled {
+            base_color: Vec3::new(0.8, 0.8, 0.8),
+            metallic: 0.0,
+            roughness: 0.5,
+            specular: 0.5,
+            specular_tint: 0.0,
+            sheen: 0.0,
+            sheen_tint: 0.5,
+            clearcoat: 0.0,
+            clearcoat_gloss: 1.0,
+            transmission: 0.0,
+            ior: 1.5,
+        }
+    }
+}
+
+/// Lobes of the principled BSDF in the order of their selection probabilities
+const DIFFUSE: usize = 0;
+const SPECULAR: usize = 1;
+const CLEARCOAT: usize = 2;
+const TRANSMISSION: usize = 3;
+
+impl Principled {
+    fn distribution(&self) -> TrowbridgeReitz {
+        let alpha = roughness_to_alpha(self.roughness).max(0.001);
+        TrowbridgeReitz::new(alpha, alpha)
+    }
+
+    fn clearcoat_alpha(&self) -> f64 {
+        lerp(self.clearcoat_gloss, 0.1, 0.001)
+    }
+
+    fn diffuse_weight(&self) -> f64 {
+        (1.0 - self.metallic) * (1.0 - self.transmission)
+    }
+
+    fn transmission_weight(&self) -> f64 {
+        (1.0 - self.metallic) * self.transmission
+    }
+
+    /// Base color normalized by luminance, isolates hue and saturation.
+    fn tint(&self) -> Vec3 {
+        let lum = luminance(self.base_color);
+        if lum > 0.0 { self.base_color / lum } else { WHITE }
+    }
+
+    /// Returns specular reflectance for angle between incident direction and microfacet normal.
+    fn specular_fresnel(&self, cos_theta: f64) -> Vec3 {
+        let dielectric = 0.08 * self.specular * lerp_vec(self.specular_tint, WHITE, self.tint());
+        let f0 = lerp_vec(self.metallic, dielectric, self.base_color);
+        f0 + (WHITE - f0) * schlick_weight(cos_theta)
+    }
+
+    /// Returns probabilities of sampling the lobes, estimated from their reflectance towards `wo`.
+    fn lobe_probabilities(&self, wo: Vec3, eta: f64) -> [f64; 4] {
+        let dielectric_fresnel = fresnel_dielectric(wo.z, eta);
+        let weights = [
+            self.diffuse_weight() * (1.0 - dielectric_fresnel) * luminance(self.base_color).max(0.01),
+            luminance(self.specular_fresnel(wo.z)).max(0.01),
+            0.25 * self.clearcoat * (0.04 + 0.96 * schlick_weight(wo.z)),
+            self.transmission_weight() * (1.0 - dielectric_fresnel),
+        ];
+        let sum: f64 = weights.iter().sum();
+        weights.map(|w| w / sum)
+    }
+
+    /// Returns BSDF times cosine of `wi`, `eta` is the IOR below the surface relative to above.
+    pub fn eval(&self, wo: Vec3, wi: Vec3, eta: f64) -> Vec3 {
+        let black = Vec3::new(0.0, 0.0, 0.0);
+        if wo.z <= 0.0 || wi.z == 0.0 {
+            return black;
+        }
+        if wi.z < 0.0 {
+            return match self.distribution().eval_transmission(wo, wi, eta) {
+                Some((value, _, m)) => {
+                    let transmittance = Vec3::new(self.base_color.x.sqrt(), self.base_color.y.sqrt(), self.base_color.z.sqrt());
+                    (self.transmission_weight() * (1.0 - fresnel_dielectric(wo.dot(m), eta)) * value) * transmittance
+                }
+                None => black,
+            };
+        }
+
+        let h = (wo + wi).unit_vector();
+        let cos_d = wi.dot(h);
+        // Diffuse with retro-reflection at grazing angles and sheen
+        let fd90 = 0.5 + 2.0 * self.roughness * cos_d * cos_d;
+        let diffuse = self.base_color / PI
+            * ((1.0 + (fd90 - 1.0) * schlick_weight(wi.z)) * (1.0 + (fd90 - 1.0) * schlick_weight(wo.z)));
+        let sheen = self.sheen * lerp_vec(self.sheen_tint, WHITE, self.tint()) * schlick_weight(cos_d);
+        // Energy reflected by the specular lobes does not reach the diffuse base
+        let dielectric_fresnel = 0.08 * self.specular + (1.0 - 0.08 * self.specular) * schlick_weight(wo.z);
+        let mut result = (self.diffuse_weight() * (1.0 - dielectric_fresnel) * wi.z) * (diffuse + sheen);
+
+        if let Some((value, _, m)) = self.distribution().eval_reflection(wo, wi) {
+            result = result + value * self.specular_fresnel(wo.dot(m));
+        }
+        if self.clearcoat > 0.0 {
+            let alpha = self.clearcoat_alpha();
+            let g = TrowbridgeReitz::new(0.25, 0.25);
+            let fresnel = 0.04 + 0.96 * schlick_weight(wo.dot(h));
+            let value = 0.25 * self.clearcoat * gtr1(h.z, alpha) * fresnel * g.g1(wo) * g.g1(wi) / (4.0 * wo.z);
+            // Coating attenuates the layers below by the light it reflects
+            let coating = 1.0 - 0.25 * self.clearcoat * (0.04 + 0.96 * schlick_weight(wo.z));
+            result = coating * result + WHITE * value;
+        }
+        if let Some((value, _)) = self.total_internal_reflection(wo, wi, eta) {
+            result = result + WHITE * (self.transmission_weight() * value);
+        }
+        result
+    }
+
+    /// Returns value and pdf of microfacet reflection from `wo` to `wi` (see
+    /// [TrowbridgeReitz::eval_reflection]) if refraction through the microfacet is impossible, where
+    /// the transmission lobe reflects.
+    fn total_internal_reflection(&self, wo: Vec3, wi: Vec3, eta: f64) -> Option<(f64, f64)> {
+        match self.distribution().eval_reflection(wo, wi) {
+            Some((value, pdf, m)) if refract(wo, m, eta).is_none() => Some((value, pdf)),
+            _ => None,
+        }
+    }
+
+    pub fn pdf(&self, wo: Vec3, wi: Vec3, eta: f64) -> f64 {
+        if wo.z <= 0.0 || wi.z == 0.0 {
+            return 0.0;
+        }
+        let p = self.lobe_probabilities(wo, eta);
+        if wi.z < 0.0 {
+            return match self.distribution().eval_transmission(wo, wi, eta) {
+                Some((_, pdf, _)) => p[TRANSMISSION] * pdf,
+                None => 0.0,
+            };
+        }
+        let h = (wo + wi).unit_vector();
+        let mut pdf = p[DIFFUSE] * wi.z / PI;
+        if let Some((_, specular_pdf, _)) = self.distribution().eval_reflection(wo, wi) {
+            pdf += p[SPECULAR] * specular_pdf;
+        }
+        if let Some((_, reflection_pdf)) = self.total_internal_reflection(wo, wi, eta) {
+            pdf += p[TRANSMISSION] * reflection_pdf;
+        }
+        pdf += p[CLEARCOAT] * gtr1(h.z, self.clearcoat_alpha()) * h.z / (4.0 * wo.dot(h));
+        pdf
+    }
+
+    /// Samples incident direction `wi`, `None` if the sample is absorbed or lies on the wrong
+    /// side of the surface for its lobe.
+    pub fn sample(&self, wo: Vec3, eta: f64, rng: &mut dyn RngCore) -> Option<Vec3> {
+        let p = self.lobe_probabilities(wo, eta);
+        let u: f64 = rng.gen();
+        let wi = if u < p[DIFFUSE] {
+            let direction = Vec3::new(0.0, 0.0, 1.0) + Vec3::random_in_unit_sphere(rng).unit_vector();
+            if direction.near_zero() { Vec3::new(0.0, 0.0, 1.0) } else { direction.unit_vector() }
+        } else if u < p[DIFFUSE] + p[SPECULAR] {
+            reflect(wo, self.distribution().sample_visible_normal(wo, rng))
+        } else if u < p[DIFFUSE] + p[SPECULAR] + p[CLEARCOAT] {
+            reflect(wo, sample_gtr1(self.clearcoat_alpha(), rng))
+        } else {
+            let m = self.distribution().sample_visible_normal(wo, rng);
+            return match refract(wo, m, eta) {
+                Some(wi) => (wi.z < 0.0).then_some(wi),
+                // Light that can't refract is reflected instead of lost
+                None => Some(reflect(wo, m)).filter(|wi| wi.z > 0.0),
+            };
+        };
+        // Reflections below the surface get no weight from eval and pdf
+        (wi.z > 0.0).then_some(wi)
+    }
+}
+
+impl Material for Principled {
+    fn scatter(&self, ray: &Ray, hit: &Hit, rng: &mut dyn RngCore) -> Option<Scatter> {
+        let eta = relative_ior(self.ior, hit);
+        let frame = hit.shading;
+        let wo = frame.to_local(-ray.direction.unit_vector());
+        let wi = self.sample(wo, eta, rng)?;
+        let pdf = self.pdf(wo, wi, eta);
+        if pdf <= 0.0 {
+            return None;
+        }
+        let attenuation = self.eval(wo, wi, eta) / pdf;
+        Some(Scatter { ray: scattered(ray, hit, frame.to_world(wi)), attenuation })
+    }
+
+    fn eval(&self, ray: &Ray, hit: &Hit, direction: Vec3) -> Vec3 {
+        let frame = hit.shading;
+        let wo = frame.to_local(-ray.direction.unit_vector());
+        Principled::eval(self, wo, frame.to_local(direction.unit_vector()), relative_ior(self.ior, hit))
+    }
+
+    fn pdf(&self, ray: &Ray, hit: &Hit, direction: Vec3) -> f64 {
+        let frame = hit.shading;
+        let wo = frame.to_local(-ray.direction.unit_vector());
+        Principled::pdf(self, wo, frame.to_local(direction.unit_vector()), relative_ior(self.ior, hit))
+    }
+
+    fn describe(&self) -> Option<Node> {
+        Some(Node::new("principled")
+            .with("base_color", self.base_color)
+            .with("metallic", self.metallic)
+            .with("roughness", self.roughness)
+            .with("specular", self.specular)
+            .with("specular_tint", self.specular_tint)
+            .with("sheen", self.sheen)
+            .with("sheen_tint", self.sheen_tint)
+            .with("clearcoat", self.clearcoat)
+            .with("clearcoat_gloss", self.clearcoat_gloss)
+            .with("transmission", self.transmission)
+            .with("ior", self.ior))
+    }
+}
+
+fn lerp(t: f64, a: f64, b: f64) -> f64 {
+    (1.0 - t) * a + t * b
+}
+
+fn lerp_vec(t: f64, a: Vec3, b: Vec3) -> Vec3 {
+    (1.0 - t) * a + t * b
+}
+
+/// Returns (1 - cos θ)^5, the weight of the Schlick Fresnel approximation.
+fn schlick_weight(cos_theta: f64) -> f64 {
+    (1.0 - cos_theta.clamp(0.0, 1.0)).powi(5)
+}
+
+/// Generalized Trowbridge-Reitz distribution with γ = 1 used for the clearcoat.
+fn gtr1(cos_theta: f64, alpha: f64) -> f64 {
+    if cos_theta <= 0.0 {
+        return 0.0;
+    }
+    let alpha2 = alpha * alpha;
+    (alpha2 - 1.0) / (PI * alpha2.ln() * (1.0 + (alpha2 - 1.0) * cos_theta * cos_theta))
+}
+
+/// Samples microfacet normal proportional to GTR1 times cosine.
+fn sample_gtr1(alpha: f64, rng: &mut dyn RngCore) -> Vec3 {
+    let alpha2 = alpha * alpha;
+    let cos_theta = ((1.0 - alpha2.powf(1.0 - rng.gen::<f64>())) / (1.0 - alpha2)).max(0.0).sqrt();
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let phi = 2.0 * PI * rng.gen::<f64>();
+    Vec3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Estimates directional albedo by importance sampling, which requires consistent sample, eval and pdf.
+    fn albedo(material: &Principled, wo: Vec3, eta: f64, rng: &mut dyn RngCore) -> Vec3 {
+        let n = 50_000;
+        let mut sum = Vec3::new(0.0, 0.0, 0.0);
+        for _ in 0..n {
+            if let Some(wi) = material.sample(wo, eta, rng) {
+                let pdf = material.pdf(wo, wi, eta);
+                if pdf > 0.0 {
+                    sum = sum + material.eval(wo, wi, eta) / pdf;
+                }
+            }
+        }
+        sum / n as f64
+    }
+
+    #[test]
+    fn test_energy_conservation() {
+        let mut rng = rand::thread_rng();
+        let materials = [
+            Principled { base_color: WHITE, ..Default::default() },
+            Principled { base_color: WHITE, metallic: 1.0, roughness: 0.3, ..Default::default() },
+            Principled { base_color: WHITE, transmission: 1.0, roughness: 0.2, ..Default::default() },
+            Principled { base_color: WHITE, roughness: 1.0, ..Default::default() },
+            Principled { base_color: WHITE, roughness: 0.0, clearcoat: 1.0, sheen: 1.0, ..Default::default() },
+        ];
+        for wo in [Vec3::new(0.0, 0.0, 1.0), Vec3::new(0.6, 0.0, 0.8), Vec3::new(0.98, 0.0, 0.2)] {
+            for material in materials.iter() {
+                let a = albedo(material, wo, 1.5, &mut rng);
+                assert!(a.x <= 1.02 && a.x > 0.5, "{:?} {:?}", material, a);
+            }
+        }
+    }
+
+    #[test]
+    fn test_total_internal_reflection() {
+        let mut rng = rand::thread_rng();
+        // Leaving glass close to the critical angle, where many microfacets reflect totally
+        let glass = Principled { base_color: WHITE, transmission: 1.0, roughness: 0.4, ..Default::default() };
+        let a = albedo(&glass, Vec3::new(0.6, 0.0, 0.8), 1.0 / 1.5, &mut rng);
+        assert!(a.x <= 1.02 && a.x > 0.85, "{:?}", a);
+    }
+}
@@ -0,0 +1,362 @@
+use std::collections::HashMap;
+use std::io;
+use std::sync::Arc;
+
+use crate::Vec3;
+use crate::color::BLACK;
+use crate::medium::{DensityField, Heterogeneous, VoxelGrid};
+use crate::scene::{Node, Value};
+use crate::texture::{SolidColor, Texture};
+
+use super::*;
+
+/// Value of a material parameter, e.g. read from a scene file.
+#[derive(Clone)]
+pub enum Param {
+    Number(f64),
+    Color(Vec3),
+    Text(String),
+    Material(Arc<dyn Material>),
+    Texture(Arc<dyn Texture>),
+    Density(Arc<dyn DensityField>),
+}
+
+/// Named parameters passed to a material constructor.
+#[derive(Clone, Default)]
+pub struct Params {
+    values: HashMap<String, Param>,
+}
+
+impl Params {
+    pub fn new() -> Self {
+        Params::default()
+    }
+
+    pub fn with(mut self, name: &str, value: Param) -> Self {
+        self.values.insert(name.to_string(), value);
+        self
+    }
+
+    pub fn insert(&mut self, name: &str, value: Param) {
+        self.values.insert(name.to_string(), value);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Param> {
+        self.values.get(name)
+    }
+
+    pub fn number(&self, name: &str, default: f64) -> io::Result<f64> {
+        match self.get(name) {
+            None => Ok(default),
+            Some(Param::Number(n)) => Ok(*n),
+            Some(_) => Err(invalid(format!("Parameter '{}' must be a number", name))),
+        }
+    }
+
+    /// Returns color parameter, a number is interpreted as gray.
+    pub fn color(&self, name: &str, default: Vec3) -> io::Result<Vec3> {
+        match self.get(name) {
+            None => Ok(default),
+            Some(Param::Color(c)) => Ok(*c),
+            Some(Param::Number(n)) => Ok(Vec3::new(*n, *n, *n)),
+            Some(_) => Err(invalid(format!("Parameter '{}' must be a color", name))),
+        }
+    }
+
+    pub fn material(&self, name: &str) -> io::Result<Arc<dyn Material>> {
+        match self.get(name) {
+            Some(Param::Material(m)) => Ok(m.clone()),
+            Some(_) => Err(invalid(format!("Parameter '{}' must be a material", name))),
+            None => Err(invalid(format!("Missing material parameter '{}'", name))),
+        }
+    }
+
+    /// Returns index of refraction `ior`, or a dispersive one if the coefficients `cauchy_a` and
+    /// `cauchy_b` or the colors `sellmeier_b` and `sellmeier_c` (one channel per term) are given.
+    pub fn refractive_index(&self, default: f64) -> io::Result<RefractiveIndex> {
+        let required = |name: &str| match self.get(name) {
+            Some(_) => Ok(()),
+            None => Err(invalid(format!("Missing parameter '{}'", name))),
+        };
+        if self.get("sellmeier_b").is_some() {
+            required("sellmeier_c")?;
+            let (b, c) = (self.color("sellmeier_b", BLACK)?, self.color("sellmeier_c", BLACK)?);
+            return Ok(RefractiveIndex::Sellmeier { b: [b.x, b.y, b.z], c: [c.x, c.y, c.z] });
+        }
+        if self.get("cauchy_a").is_some() {
+            required("cauchy_b")?;
+            return Ok(RefractiveIndex::Cauchy { a: self.number("cauchy_a", 0.0)?, b: self.number("cauchy_b", 0.0)? });
+        }
+        Ok(RefractiveIndex::Constant(self.number("ior", default)?))
+    }
+
+    /// Returns parameters of [Principled], defaulting to its defaults.
+    pub fn principled(&self) -> io::Result<Principled> {
+        let d = Principled::default();
+        Ok(Principled {
+            base_color: self.color("base_color", d.base_color)?,
+            metallic: self.number("metallic", d.metallic)?,
+            roughness: self.number("roughness", d.roughness)?,
+            specular: self.number("specular", d.specular)?,
+            specular_tint: self.number("specular_tint", d.specular_tint)?,
+            sheen: self.number("sheen", d.sheen)?,
+            sheen_tint: self.number("sheen_tint", d.sheen_tint)?,
+            clearcoat: self.number("clearcoat", d.clearcoat)?,
+            clearcoat_gloss: self.number("clearcoat_gloss", d.clearcoat_gloss)?,
+            transmission: self.number("transmission", d.transmission)?,
+            ior: self.number("ior", d.ior)?,
+        })
+    }
+
+    /// Returns texture parameter if given, see [Params::texture].
+    pub fn optional_texture(&self, name: &str) -> io::Result<Option<Arc<dyn Texture>>> {
+        self.get(name).map(|_| self.texture(name)).transpose()
+    }
+
+    /// Returns texture parameter, a color or number is turned into a constant texture.
+    pub fn texture(&self, name: &str) -> io::Result<Arc<dyn Texture>> {
+        match self.get(name) {
+            Some(Param::Texture(t)) => Ok(t.clone()),
+            Some(Param::Color(_) | Param::Number(_)) => Ok(Arc::new(SolidColor::new(self.color(name, Vec3::new(0.0, 0.0, 0.0))?))),
+            Some(_) => Err(invalid(format!("Parameter '{}' must be a texture", name))),
+            None => Err(invalid(format!("Missing texture parameter '{}'", name))),
+        }
+    }
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+pub type MaterialConstructor = Box<dyn Fn(&Params) -> io::Result<Arc<dyn Material>>>;
+
+/// Maps names used in scene files to material constructors. [Default] registers the materials
+/// of this crate, downstream crates can add their own.
+pub struct MaterialRegistry {
+    constructors: HashMap<String, MaterialConstructor>,
+}
+
+impl MaterialRegistry {
+    /// Returns registry without any materials.
+    pub fn empty() -> Self {
+        MaterialRegistry { constructors: HashMap::new() }
+    }
+
+    /// Registers constructor under `name`, replacing any previous one.
+    pub fn register<F>(&mut self, name: &str, constructor: F)
+        where F: Fn(&Params) -> io::Result<Arc<dyn Material>> + 'static {
+        self.constructors.insert(name.to_string(), Box::new(constructor));
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.constructors.contains_key(name)
+    }
+
+    pub fn create(&self, name: &str, params: &Params) -> io::Result<Arc<dyn Material>> {
+        match self.constructors.get(name) {
+            Some(constructor) => constructor(params),
+            None => Err(invalid(format!("Unknown material '{}'", name))),
+        }
+    }
+}
+
+impl Default for MaterialRegistry {
+    fn default() -> Self {
+        let mut registry = MaterialRegistry::empty();
+        let gray = Vec3::new(0.5, 0.5, 0.5);
+        registry.register("lambertian", move |p| {
+            let albedo = if p.get("albedo").is_some() { p.texture("albedo")? } else { Arc::new(SolidColor::new(gray)) };
+            Ok(Arc::new(Lambertian { albedo }))
+        });
+        registry.register("metal", move |p| {
+            Ok(Arc::new(Metal { albedo: p.color("albedo", gray)?, fuzz: p.number("fuzz", 0.0)? }))
+        });
+        registry.register("dielectric", |p| {
+            Ok(Arc::new(Dielectric {
+                absorption: p.color("absorption", Vec3::new(0.0, 0.0, 0.0))?,
+                priority: p.number("priority", 0.0)? as u32,
+                ..Dielectric::new(p.refractive_index(1.5)?)
+            }))
+        });
+        registry.register("rough_conductor", |p| {
+            let alpha = p.number("alpha", 0.1)?;
+            let ior = ComplexIor { eta: p.color("eta", ALUMINIUM.eta)?, k: p.color("k", ALUMINIUM.k)? };
+            Ok(Arc::new(RoughConductor { ior, alpha_x: p.number("alpha_x", alpha)?, alpha_y: p.number("alpha_y", alpha)? }))
+        });
+        registry.register("rough_dielectric", |p| {
+            let alpha = p.number("alpha", 0.1)?;
+            let refractive_index = p.refractive_index(1.5)?;
+            Ok(Arc::new(RoughDielectric {
+                absorption: p.color("absorption", Vec3::new(0.0, 0.0, 0.0))?,
+                priority: p.number("priority", 0.0)? as u32,
+                ..RoughDielectric::new(refractive_index, p.number("alpha_x", alpha)?, p.number("alpha_y", alpha)?)
+            }))
+        });
+        registry.register("principled", |p| Ok(Arc::new(p.principled()?)));
+        registry.register("metallic_roughness", |p| {
+            Ok(Arc::new(MetallicRoughness {
+                base_color_texture: p.optional_texture("base_color_texture")?,
+                metallic_roughness_texture: p.optional_texture("metallic_roughness_texture")?,
+                emission: p.color("emission", Vec3::new(0.0, 0.0, 0.0))?,
+                emission_texture: p.optional_texture("emission_texture")?,
+                ..MetallicRoughness::new(p.principled()?)
+            }))
+        });
+        registry.register("diffuse_light", |p| {
+            Ok(Arc::new(DiffuseLight { emit: p.color("emit", Vec3::new(1.0, 1.0, 1.0))? }))
+        });
+        registry.register("spot_light", |p| {
+            let direction = p.color("direction", Vec3::new(0.0, -1.0, 0.0))?;
+            if direction.near_zero() {
+                return Err(invalid("Parameter 'direction' must not be zero".to_string()));
+            }
+            let cos_outer = p.number("cos_outer", 0.7)?;
+            Ok(Arc::new(SpotLight {
+                emit: p.color("emit", Vec3::new(1.0, 1.0, 1.0))?,
+                direction: direction.unit_vector(),
+                cos_inner: p.number("cos_inner", cos_outer)?,
+                cos_outer,
+            }))
+        });
+        registry.register("mix", |p| {
+            let weight = if p.get("weight").is_some() { p.texture("weight")? } else { Arc::new(SolidColor::gray(0.5)) };
+            Ok(Arc::new(Mix { first: p.material("first")?, second: p.material("second")?, weight }))
+        });
+        registry.register("layered", |p| {
+            Ok(Arc::new(Layered {
+                base: p.material("base")?,
+                refractive_index: p.refractive_index(1.5)?,
+                alpha: p.number("alpha", 0.0)?,
+                thickness: p.number("thickness", 0.0)?,
+                absorption: p.color("absorption", Vec3::new(0.0, 0.0, 0.0))?,
+            }))
+        });
+        registry.register("normal_map", |p| {
+            Ok(Arc::new(NormalMap {
+                material: p.material("material")?,
+                texture: p.texture("texture")?,
+                strength: p.number("strength", 1.0)?,
+            }))
+        });
+        registry.register("bump_map", |p| {
+            Ok(Arc::new(BumpMap {
+                material: p.material("material")?,
+                height: p.texture("height")?,
+                strength: p.number("strength", 1.0)?,
+            }))
+        });
+        registry.register("hair", |p| {
+            let d = Hair::from_melanin(1.3, 0.0);
+            Ok(Arc::new(Hair {
+                sigma_a: p.color("sigma_a", d.sigma_a)?,
+                eta: p.number("eta", d.eta)?,
+                beta_m: p.number("beta_m", d.beta_m)?,
+                beta_n: p.number("beta_n", d.beta_n)?,
+                alpha: p.number("alpha", d.alpha)?,
+            }))
+        });
+        registry.register("subsurface", |p| {
+            let d = Subsurface::new(Vec3::new(0.8, 0.8, 0.8), Vec3::new(0.1, 0.1, 0.1));
+            Ok(Arc::new(Subsurface {
+                refractive_index: p.refractive_index(1.4)?,
+                albedo: p.color("albedo", d.albedo)?,
+                mean_free_path: p.color("mean_free_path", d.mean_free_path)?,
+                anisotropy: p.number("anisotropy", d.anisotropy)?,
+                alpha: p.number("alpha", d.alpha)?,
+                priority: p.number("priority", 0.0)? as u32,
+            }))
+        });
+        registry.register("volumetric", |p| {
+            let emission = p.number("emission", 0.0)?;
+            let density = match p.get("density") {
+                None => None,
+                Some(Param::Text(path)) => Some(Arc::new(Heterogeneous::new(VoxelGrid::load(path)?, emission))),
+                Some(Param::Density(field)) => Some(Arc::new(Heterogeneous::new(field.clone(), emission))),
+                Some(_) => return Err(invalid("Parameter 'density' must be a density field or the path of a voxel grid".to_string())),
+            };
+            Ok(Arc::new(Volumetric {
+                absorption: p.color("absorption", Vec3::new(0.0, 0.0, 0.0))?,
+                scattering: p.color("scattering", Vec3::new(1.0, 1.0, 1.0))?,
+                anisotropy: p.number("anisotropy", 0.0)?,
+                density,
+                priority: p.number("priority", 0.0)? as u32,
+            }))
+        });
+        registry.register("alpha_mask", |p| {
+            Ok(Arc::new(AlphaMask { material: p.material("material")?, alpha: p.texture("alpha")? }))
+        });
+        registry
+    }
+}
+
+/// Materials of a scene being saved, each described once under a name the objects refer to, so
+/// that objects sharing a material (e.g. the walls of a glass or the triangles of a mesh) still
+/// share it when loaded.
+#[derive(Default)]
+pub struct MaterialTable {
+    /// Index of each material by its address
+    indices: HashMap<usize, usize>,
+    entries: Vec<(String, Node)>,
+}
+
+impl MaterialTable {
+    pub fn new() -> Self {
+        MaterialTable::default()
+    }
+
+    /// Returns name of `material`, describing it on first use. `None` if it can't be saved,
+    /// see [Material::describe].
+    pub fn name(&mut self, material: &Arc<dyn Material>) -> Option<Value> {
+        let address = Arc::as_ptr(material) as *const () as usize;
+        let index = match self.indices.get(&address) {
+            Some(&index) => index,
+            None => {
+                let node = material.describe()?;
+                self.entries.push((format!("{}{}", node.kind, self.entries.len()), node));
+                self.indices.insert(address, self.entries.len() - 1);
+                self.entries.len() - 1
+            }
+        };
+        Some(Value::Text(self.entries[index].0.clone()))
+    }
+
+    /// Returns node with the materials as fields, named as referenced.
+    pub fn describe(self) -> Node {
+        let mut node = Node::new("table");
+        node.fields = self.entries.into_iter().map(|(name, node)| (name, Value::Node(node))).collect();
+        node
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::RngCore;
+
+    use super::*;
+    use crate::objects::Hit;
+    use crate::Ray;
+
+    /// Downstream shading model that absorbs everything
+    struct Absorber;
+
+    impl Material for Absorber {
+        fn scatter(&self, _ray: &Ray, _hit: &Hit, _rng: &mut dyn RngCore) -> Option<Scatter> {
+            None
+        }
+    }
+
+    #[test]
+    fn test_registry() {
+        let mut registry = MaterialRegistry::default();
+        registry.register("absorber", |_| Ok(Arc::new(Absorber)));
+        assert!(registry.create("absorber", &Params::new()).is_ok());
+
+        let base = registry.create("lambertian", &Params::new().with("albedo", Param::Number(0.8))).unwrap();
+        let params = Params::new().with("base", Param::Material(base)).with("ior", Param::Number(1.4));
+        assert!(registry.create("layered", &params).is_ok());
+
+        assert!(registry.create("layered", &Params::new()).is_err());
+        assert!(registry.create("metal", &Params::new().with("fuzz", Param::Text("high".to_string()))).is_err());
+        assert!(registry.create("unknown", &Params::new()).is_err());
+    }
+}
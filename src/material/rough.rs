@@ -0,0 +1,165 @@
+use rand::{Rng, RngCore};
+
+use crate::{Ray, Vec3};
+use crate::color::{BLACK, WHITE};
+use crate::medium::Interior;
+use crate::microfacet;
+use crate::microfacet::{fresnel_conductor, fresnel_dielectric, TrowbridgeReitz};
+use crate::objects::Hit;
+use crate::scene::Node;
+
+use super::{relative_ior, scattered, Material, RefractiveIndex, Scatter};
+
+/// Complex index of refraction eta + ik of a conductor, per RGB channel.
+#[derive(Copy, Clone, Debug)]
+pub struct ComplexIor {
+    pub eta: Vec3,
+    pub k: Vec3,
+}
+
+pub const GOLD: ComplexIor = ComplexIor {
+    eta: Vec3 { x: 0.143119, y: 0.374957, z: 1.44248 },
+    k: Vec3 { x: 3.98316, y: 2.38572, z: 1.60322 },
+};
+pub const COPPER: ComplexIor = ComplexIor {
+    eta: Vec3 { x: 0.200438, y: 0.924033, z: 1.10221 },
+    k: Vec3 { x: 3.91295, y: 2.45285, z: 2.14219 },
+};
+pub const ALUMINIUM: ComplexIor = ComplexIor {
+    eta: Vec3 { x: 1.65746, y: 0.880369, z: 0.521229 },
+    k: Vec3 { x: 9.22387, y: 6.26952, z: 4.837 },
+};
+pub const SILVER: ComplexIor = ComplexIor {
+    eta: Vec3 { x: 0.155265, y: 0.116723, z: 0.138342 },
+    k: Vec3 { x: 4.82835, y: 3.12225, z: 2.14696 },
+};
+
+/// Rough metal with GGX microfacets, alphas along tangent and bitangent.
+pub struct RoughConductor {
+    pub ior: ComplexIor,
+    pub alpha_x: f64,
+    pub alpha_y: f64,
+}
+
+impl Material for RoughConductor {
+    fn scatter(&self, ray: &Ray, hit: &Hit, rng: &mut dyn RngCore) -> Option<Scatter> {
+        let frame = hit.shading;
+        let wo = frame.to_local(-ray.direction.unit_vector());
+        let distribution = TrowbridgeReitz::new(self.alpha_x, self.alpha_y);
+        let m = distribution.sample_visible_normal(wo, rng);
+        let wi = microfacet::reflect(wo, m);
+        if wi.z <= 0.0 {
+            return None;
+        }
+        // Sampling visible normals leaves Fresnel and the shadowing part of G2 / G1
+        let attenuation = fresnel_conductor(wo.dot(m), self.ior.eta, self.ior.k)
+            * (distribution.g2(wo, wi) / distribution.g1(wo));
+        Some(Scatter { ray: scattered(ray, hit, frame.to_world(wi)), attenuation })
+    }
+
+    fn eval(&self, ray: &Ray, hit: &Hit, direction: Vec3) -> Vec3 {
+        let frame = hit.shading;
+        let wo = frame.to_local(-ray.direction.unit_vector());
+        let wi = frame.to_local(direction.unit_vector());
+        match TrowbridgeReitz::new(self.alpha_x, self.alpha_y).eval_reflection(wo, wi) {
+            Some((value, _, m)) => value * fresnel_conductor(wo.dot(m), self.ior.eta, self.ior.k),
+            None => BLACK,
+        }
+    }
+
+    fn pdf(&self, ray: &Ray, hit: &Hit, direction: Vec3) -> f64 {
+        let frame = hit.shading;
+        let wo = frame.to_local(-ray.direction.unit_vector());
+        let wi = frame.to_local(direction.unit_vector());
+        TrowbridgeReitz::new(self.alpha_x, self.alpha_y).eval_reflection(wo, wi).map_or(0.0, |(_, pdf, _)| pdf)
+    }
+
+    fn describe(&self) -> Option<Node> {
+        Some(Node::new("rough_conductor")
+            .with("eta", self.ior.eta)
+            .with("k", self.ior.k)
+            .with("alpha_x", self.alpha_x)
+            .with("alpha_y", self.alpha_y))
+    }
+}
+
+/// Rough glass with GGX microfacets, alphas along tangent and bitangent.
+pub struct RoughDielectric {
+    pub refractive_index: RefractiveIndex,
+    pub alpha_x: f64,
+    pub alpha_y: f64,
+    /// Absorption coefficient of the interior per unit length
+    pub absorption: Vec3,
+    /// Precedence over overlapping interiors, see [Interior]
+    pub priority: u32,
+}
+
+impl RoughDielectric {
+    /// Returns clear rough dielectric with lowest priority.
+    pub fn new(refractive_index: RefractiveIndex, alpha_x: f64, alpha_y: f64) -> Self {
+        RoughDielectric { refractive_index, alpha_x, alpha_y, absorption: BLACK, priority: 0 }
+    }
+}
+
+impl Material for RoughDielectric {
+    fn scatter(&self, ray: &Ray, hit: &Hit, rng: &mut dyn RngCore) -> Option<Scatter> {
+        let eta = relative_ior(self.refractive_index.at(ray.wavelength), hit);
+        let frame = hit.shading;
+        let wo = frame.to_local(-ray.direction.unit_vector());
+        let distribution = TrowbridgeReitz::new(self.alpha_x, self.alpha_y);
+        let m = distribution.sample_visible_normal(wo, rng);
+
+        // Choose reflection or refraction proportional to Fresnel reflectance
+        let reflectance = fresnel_dielectric(wo.dot(m), eta);
+        let wi = if rng.gen::<f64>() < reflectance {
+            Some(microfacet::reflect(wo, m)).filter(|wi| wi.z > 0.0)
+        } else {
+            microfacet::refract(wo, m, eta).filter(|wi| wi.z < 0.0)
+        }?;
+        let attenuation = WHITE * (distribution.g2(wo, wi) / distribution.g1(wo));
+        Some(Scatter { ray: scattered(ray, hit, frame.to_world(wi)), attenuation })
+    }
+
+    fn eval(&self, ray: &Ray, hit: &Hit, direction: Vec3) -> Vec3 {
+        let eta = relative_ior(self.refractive_index.at(ray.wavelength), hit);
+        let frame = hit.shading;
+        let wo = frame.to_local(-ray.direction.unit_vector());
+        let wi = frame.to_local(direction.unit_vector());
+        let distribution = TrowbridgeReitz::new(self.alpha_x, self.alpha_y);
+        let value = if wi.z > 0.0 {
+            distribution.eval_reflection(wo, wi).map(|(value, _, m)| value * fresnel_dielectric(wo.dot(m), eta))
+        } else {
+            distribution.eval_transmission(wo, wi, eta).map(|(value, _, m)| value * (1.0 - fresnel_dielectric(wo.dot(m), eta)))
+        };
+        WHITE * value.unwrap_or(0.0)
+    }
+
+    fn pdf(&self, ray: &Ray, hit: &Hit, direction: Vec3) -> f64 {
+        let eta = relative_ior(self.refractive_index.at(ray.wavelength), hit);
+        let frame = hit.shading;
+        let wo = frame.to_local(-ray.direction.unit_vector());
+        let wi = frame.to_local(direction.unit_vector());
+        let distribution = TrowbridgeReitz::new(self.alpha_x, self.alpha_y);
+        if wi.z > 0.0 {
+            distribution.eval_reflection(wo, wi).map_or(0.0, |(_, pdf, m)| pdf * fresnel_dielectric(wo.dot(m), eta))
+        } else {
+            distribution.eval_transmission(wo, wi, eta).map_or(0.0, |(_, pdf, m)| pdf * (1.0 - fresnel_dielectric(wo.dot(m), eta)))
+        }
+    }
+
+    fn is_dispersive(&self) -> bool {
+        self.refractive_index.is_dispersive()
+    }
+
+    fn interior(&self) -> Option<Interior> {
+        Some(Interior::clear(self.refractive_index, self.absorption, self.priority))
+    }
+
+    fn describe(&self) -> Option<Node> {
+        Some(self.refractive_index.describe(Node::new("rough_dielectric"))
+            .with("alpha_x", self.alpha_x)
+            .with("alpha_y", self.alpha_y)
+            .with("absorption", self.absorption)
+            .with("priority", self.priority as f64))
+    }
+}
@@ -0,0 +1,100 @@
+use rand::RngCore;
+
+use crate::{Ray, Vec3};
+use crate::medium::Interior;
+use crate::objects::Hit;
+use crate::scene::Node;
+
+use super::{Dielectric, Material, RefractiveIndex, RoughDielectric, Scatter};
+
+/// Translucent material such as skin, wax, marble or milk. The surface is a dielectric boundary
+/// and light below it performs a volumetric random walk through the interior of the closed
+/// object, which the integrator simulates like any other scattering medium.
+pub struct Subsurface {
+    pub refractive_index: RefractiveIndex,
+    /// Perceived color after multiple scattering, per RGB channel
+    pub albedo: Vec3,
+    /// Average distance between scattering events, per RGB channel
+    pub mean_free_path: Vec3,
+    /// Mean cosine of the phase function, positive values scatter forward
+    pub anisotropy: f64,
+    /// GGX alpha of the boundary, zero for a smooth surface
+    pub alpha: f64,
+    /// Precedence over overlapping interiors, see [Interior]
+    pub priority: u32,
+}
+
+impl Subsurface {
+    pub fn new(albedo: Vec3, mean_free_path: Vec3) -> Self {
+        Subsurface {
+            refractive_index: RefractiveIndex::Constant(1.4),
+            albedo,
+            mean_free_path,
+            anisotropy: 0.0,
+            alpha: 0.0,
+            priority: 0,
+        }
+    }
+
+    /// Calls `f` with the material of the boundary.
+    fn with_boundary<T>(&self, f: impl FnOnce(&dyn Material) -> T) -> T {
+        if self.alpha > 0.0 {
+            f(&RoughDielectric::new(self.refractive_index, self.alpha, self.alpha))
+        } else {
+            f(&Dielectric::new(self.refractive_index))
+        }
+    }
+}
+
+/// Inverts the relation between single-scattering albedo and the albedo perceived after multiple
+/// scattering in a semi-infinite medium (Chiang et al. 2016).
+fn single_scattering_albedo(albedo: f64) -> f64 {
+    let a = albedo.clamp(0.0, 1.0);
+    1.0 - (4.09712 + 4.20863 * a - (9.59217 + 41.6808 * a + 17.7126 * a * a).sqrt()).powi(2)
+}
+
+impl Material for Subsurface {
+    fn scatter(&self, ray: &Ray, hit: &Hit, rng: &mut dyn RngCore) -> Option<Scatter> {
+        self.with_boundary(|boundary| boundary.scatter(ray, hit, rng))
+    }
+
+    fn eval(&self, ray: &Ray, hit: &Hit, direction: Vec3) -> Vec3 {
+        self.with_boundary(|boundary| boundary.eval(ray, hit, direction))
+    }
+
+    fn pdf(&self, ray: &Ray, hit: &Hit, direction: Vec3) -> f64 {
+        self.with_boundary(|boundary| boundary.pdf(ray, hit, direction))
+    }
+
+    fn is_dispersive(&self) -> bool {
+        self.refractive_index.is_dispersive()
+    }
+
+    fn interior(&self) -> Option<Interior> {
+        let channel = |albedo: f64, mfp: f64| {
+            let extinction = 1.0 / mfp.max(1.0e-12);
+            let single = single_scattering_albedo(albedo);
+            (single * extinction, (1.0 - single) * extinction)
+        };
+        let (sx, ax) = channel(self.albedo.x, self.mean_free_path.x);
+        let (sy, ay) = channel(self.albedo.y, self.mean_free_path.y);
+        let (sz, az) = channel(self.albedo.z, self.mean_free_path.z);
+        Some(Interior {
+            refractive_index: self.refractive_index,
+            absorption: Vec3::new(ax, ay, az),
+            scattering: Vec3::new(sx, sy, sz),
+            anisotropy: self.anisotropy,
+            priority: self.priority,
+            density: None,
+        })
+    }
+
+    fn describe(&self) -> Option<Node> {
+        Some(self.refractive_index.describe(Node::new("subsurface"))
+            .with("albedo", self.albedo)
+            .with("mean_free_path", self.mean_free_path)
+            .with("anisotropy", self.anisotropy)
+            .with("alpha", self.alpha)
+            .with("priority", self.priority as f64))
+    }
+}
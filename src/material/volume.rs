@@ -0,0 +1,65 @@
+use std::sync::Arc;
+
+use rand::RngCore;
+
+use crate::{Ray, Vec3};
+use crate::color::WHITE;
+use crate::medium::{Heterogeneous, Interior};
+use crate::objects::Hit;
+use crate::scene::Node;
+
+use super::{Material, RefractiveIndex, Scatter};
+
+/// Invisible boundary of a participating medium such as fog, smoke or fire. Rays pass the surface
+/// unchanged, the integrator samples absorption, scattering and emission inside.
+pub struct Volumetric {
+    /// Absorption coefficient per unit length, per unit density for heterogeneous media
+    pub absorption: Vec3,
+    /// Scattering coefficient per unit length, per unit density for heterogeneous media
+    pub scattering: Vec3,
+    /// Mean cosine of the phase function, positive values scatter forward
+    pub anisotropy: f64,
+    /// Spatially varying density, `None` for a homogeneous medium
+    pub density: Option<Arc<Heterogeneous>>,
+    /// Precedence over overlapping interiors, see [Interior]
+    pub priority: u32,
+}
+
+impl Volumetric {
+    pub fn homogeneous(absorption: Vec3, scattering: Vec3) -> Self {
+        Volumetric { absorption, scattering, anisotropy: 0.0, density: None, priority: 0 }
+    }
+
+    pub fn heterogeneous(density: Arc<Heterogeneous>, absorption: Vec3, scattering: Vec3) -> Self {
+        Volumetric { density: Some(density), ..Volumetric::homogeneous(absorption, scattering) }
+    }
+}
+
+impl Material for Volumetric {
+    fn scatter(&self, ray: &Ray, hit: &Hit, _rng: &mut dyn RngCore) -> Option<Scatter> {
+        // Differentials stay valid as the path continues in a straight line
+        let ray = Ray { origin: hit.point, direction: ray.direction, ..*ray };
+        Some(Scatter { ray, attenuation: WHITE })
+    }
+
+    fn interior(&self) -> Option<Interior> {
+        Some(Interior {
+            scattering: self.scattering,
+            anisotropy: self.anisotropy,
+            density: self.density.clone(),
+            ..Interior::clear(RefractiveIndex::Constant(1.0), self.absorption, self.priority)
+        })
+    }
+
+    fn describe(&self) -> Option<Node> {
+        let mut node = Node::new("volumetric")
+            .with("absorption", self.absorption)
+            .with("scattering", self.scattering)
+            .with("anisotropy", self.anisotropy)
+            .with("priority", self.priority as f64);
+        if let Some(density) = &self.density {
+            node = node.with("density", density.field().describe()?).with("emission", density.emission);
+        }
+        Some(node)
+    }
+}
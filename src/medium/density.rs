@@ -0,0 +1,315 @@
+//! Spatially varying density of heterogeneous media, from voxel grids or procedural noise.
+use std::fs;
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::noise::{fbm, Noise};
+use crate::scene::{Node, Value};
+use crate::Vec3;
+
+/// Density (and optionally temperature) of a medium over space, zero outside its bounds.
+pub trait DensityField: Send + Sync {
+    fn density(&self, p: Vec3) -> f64;
+
+    /// Returns temperature in Kelvin for blackbody emission, zero where nothing glows.
+    fn temperature(&self, _p: Vec3) -> f64 {
+        0.0
+    }
+
+    /// Returns minimum and maximum corner of the box containing all non-zero density.
+    fn bounds(&self) -> (Vec3, Vec3);
+
+    /// Returns upper bound of the density inside the box from `min` to `max`.
+    fn max_density(&self, min: Vec3, max: Vec3) -> f64;
+
+    /// Returns description for saving scenes, `None` if the field can't be saved.
+    fn describe(&self) -> Option<Node> {
+        None
+    }
+}
+
+impl<T: DensityField + ?Sized> DensityField for Arc<T> {
+    fn density(&self, p: Vec3) -> f64 {
+        (**self).density(p)
+    }
+
+    fn temperature(&self, p: Vec3) -> f64 {
+        (**self).temperature(p)
+    }
+
+    fn bounds(&self) -> (Vec3, Vec3) {
+        (**self).bounds()
+    }
+
+    fn max_density(&self, min: Vec3, max: Vec3) -> f64 {
+        (**self).max_density(min, max)
+    }
+
+    fn describe(&self) -> Option<Node> {
+        (**self).describe()
+    }
+}
+
+/// Dense grid of voxels, interpolated trilinearly between voxel centers.
+pub struct VoxelGrid {
+    pub min: Vec3,
+    pub max: Vec3,
+    /// Number of voxels along x, y and z
+    pub resolution: [usize; 3],
+    /// Densities with x varying fastest, then y, then z
+    pub density: Vec<f64>,
+    /// Temperatures in Kelvin in the same order, empty for media that do not glow
+    pub temperature: Vec<f64>,
+}
+
+impl VoxelGrid {
+    pub fn new(min: Vec3, max: Vec3, resolution: [usize; 3], density: Vec<f64>) -> Self {
+        assert_eq!(density.len(), resolution.iter().product::<usize>());
+        VoxelGrid { min, max, resolution, density, temperature: vec![] }
+    }
+
+    /// Returns grid with densities `f` evaluated at the voxel centers, e.g. to bake a procedural field.
+    pub fn from_fn(min: Vec3, max: Vec3, resolution: [usize; 3], f: impl Fn(Vec3) -> f64) -> Self {
+        let [nx, ny, nz] = resolution;
+        let size = max - min;
+        let mut density = Vec::with_capacity(nx * ny * nz);
+        for z in 0..nz {
+            for y in 0..ny {
+                for x in 0..nx {
+                    density.push(f(Vec3::new(
+                        min.x + (x as f64 + 0.5) / nx as f64 * size.x,
+                        min.y + (y as f64 + 0.5) / ny as f64 * size.y,
+                        min.z + (z as f64 + 0.5) / nz as f64 * size.z,
+                    )));
+                }
+            }
+        }
+        VoxelGrid::new(min, max, resolution, density)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        parse(&fs::read_to_string(path)?)
+    }
+
+    fn index(&self, x: usize, y: usize, z: usize) -> usize {
+        (z * self.resolution[1] + y) * self.resolution[0] + x
+    }
+
+    /// Returns continuous voxel coordinates of `p`, voxel centers lie at integers.
+    fn voxel_coordinates(&self, p: Vec3) -> [f64; 3] {
+        let size = self.max - self.min;
+        std::array::from_fn(|a| (p[a] - self.min[a]) / size[a] * self.resolution[a] as f64 - 0.5)
+    }
+
+    fn lookup(&self, values: &[f64], p: Vec3) -> f64 {
+        if values.is_empty() || (0..3).any(|a| p[a] < self.min[a] || p[a] > self.max[a]) {
+            return 0.0;
+        }
+        let c = self.voxel_coordinates(p);
+        let mut i0 = [0; 3];
+        let mut i1 = [0; 3];
+        let mut f = [0.0; 3];
+        for a in 0..3 {
+            let last = self.resolution[a] - 1;
+            let floor = c[a].floor();
+            i0[a] = (floor.max(0.0) as usize).min(last);
+            i1[a] = (i0[a] + 1).min(last);
+            f[a] = if floor < 0.0 { 0.0 } else { c[a] - floor };
+        }
+        let mut value = 0.0;
+        for corner in 0..8 {
+            let pick = |a: usize| corner >> a & 1 == 1;
+            let weight: f64 = (0..3).map(|a| if pick(a) { f[a] } else { 1.0 - f[a] }).product();
+            let [x, y, z]: [usize; 3] = std::array::from_fn(|a| if pick(a) { i1[a] } else { i0[a] });
+            value += weight * values[self.index(x, y, z)];
+        }
+        value
+    }
+}
+
+impl DensityField for VoxelGrid {
+    fn density(&self, p: Vec3) -> f64 {
+        self.lookup(&self.density, p)
+    }
+
+    fn temperature(&self, p: Vec3) -> f64 {
+        self.lookup(&self.temperature, p)
+    }
+
+    fn bounds(&self) -> (Vec3, Vec3) {
+        (self.min, self.max)
+    }
+
+    fn max_density(&self, min: Vec3, max: Vec3) -> f64 {
+        // Interpolation inside the box only involves voxels around it
+        let lo = self.voxel_coordinates(min);
+        let hi = self.voxel_coordinates(max);
+        let range = |a: usize| {
+            let last = self.resolution[a] as f64 - 1.0;
+            (lo[a].floor().clamp(0.0, last) as usize, (hi[a].floor() + 1.0).clamp(0.0, last) as usize)
+        };
+        let ((x0, x1), (y0, y1), (z0, z1)) = (range(0), range(1), range(2));
+        let mut max_density = 0.0f64;
+        for z in z0..=z1 {
+            for y in y0..=y1 {
+                for x in x0..=x1 {
+                    max_density = max_density.max(self.density[self.index(x, y, z)]);
+                }
+            }
+        }
+        max_density
+    }
+
+    fn describe(&self) -> Option<Node> {
+        Some(Node::new("voxel_grid")
+            .with("min", self.min)
+            .with("max", self.max)
+            .with("resolution", Value::Numbers(self.resolution.map(|n| n as f64).to_vec()))
+            .with("density", Value::Numbers(self.density.clone()))
+            .with("temperature", Value::Numbers(self.temperature.clone())))
+    }
+}
+
+/// Parses a voxel grid in a plain text format of whitespace-separated keywords and numbers,
+/// with comments starting at '#':
+///
+/// ```text
+/// VOXELS nx ny nz
+/// BOUNDS min_x min_y min_z max_x max_y max_z
+/// DENSITY (nx * ny * nz values, x varying fastest)
+/// TEMPERATURE (optional, nx * ny * nz values in Kelvin)
+/// ```
+pub fn parse(text: &str) -> std::io::Result<VoxelGrid> {
+    let tokens: Vec<&str> = text.lines()
+        .flat_map(|line| line.split('#').next().unwrap_or("").split_whitespace())
+        .collect();
+    let mut pos = 0;
+    let keyword = |keyword: &str, pos: &mut usize| match tokens.get(*pos) {
+        Some(&t) if t == keyword => {
+            *pos += 1;
+            Ok(())
+        }
+        Some(t) => Err(invalid(format!("expected {}, found {}", keyword, t))),
+        None => Err(invalid(format!("expected {}, found end of data", keyword))),
+    };
+    let numbers = |count: usize, pos: &mut usize| -> std::io::Result<Vec<f64>> {
+        let values = tokens.get(*pos..*pos + count)
+            .ok_or_else(|| invalid(format!("expected {} numbers, found end of data", count)))?;
+        *pos += count;
+        values.iter()
+            .map(|t| t.parse().map_err(|_| invalid(format!("expected number, found {}", t))))
+            .collect()
+    };
+
+    keyword("VOXELS", &mut pos)?;
+    let mut resolution = [0; 3];
+    for (r, n) in resolution.iter_mut().zip(numbers(3, &mut pos)?) {
+        if n < 1.0 || n.fract() != 0.0 {
+            return Err(invalid(format!("invalid resolution {}", n)));
+        }
+        *r = n as usize;
+    }
+    keyword("BOUNDS", &mut pos)?;
+    let b = numbers(6, &mut pos)?;
+    let (min, max) = (Vec3::new(b[0], b[1], b[2]), Vec3::new(b[3], b[4], b[5]));
+    if (0..3).any(|a| min[a] >= max[a]) {
+        return Err(invalid("empty bounds".to_string()));
+    }
+    let count = resolution.iter().product();
+    keyword("DENSITY", &mut pos)?;
+    let mut grid = VoxelGrid::new(min, max, resolution, numbers(count, &mut pos)?);
+    if pos < tokens.len() {
+        keyword("TEMPERATURE", &mut pos)?;
+        grid.temperature = numbers(count, &mut pos)?;
+        if pos < tokens.len() {
+            return Err(invalid(format!("unexpected {} after temperatures", tokens[pos])));
+        }
+    }
+    Ok(grid)
+}
+
+fn invalid(message: String) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+/// Procedural cloud, a ball whose density falls off towards its surface and is broken up by
+/// fractal simplex noise.
+pub struct NoiseDensity {
+    pub center: Vec3,
+    pub radius: f64,
+    /// Highest density inside the cloud
+    pub density: f64,
+    /// Spatial frequency of the coarsest noise octave
+    pub frequency: f64,
+    pub octaves: u32,
+    /// Temperature in Kelvin where the density is highest, zero for clouds that do not glow
+    pub temperature: f64,
+    pub noise: Noise,
+}
+
+impl NoiseDensity {
+    pub fn new(center: Vec3, radius: f64, density: f64) -> Self {
+        NoiseDensity { center, radius, density, frequency: 2.0 / radius, octaves: 4, temperature: 0.0, noise: Noise::default() }
+    }
+
+    /// Returns density relative to the maximum.
+    fn fraction(&self, p: Vec3) -> f64 {
+        let r = (p - self.center).length() / self.radius;
+        if r >= 1.0 {
+            return 0.0;
+        }
+        (1.0 - r + 0.6 * fbm(|q| self.noise.simplex(q), self.frequency * p, self.octaves)).clamp(0.0, 1.0)
+    }
+}
+
+impl DensityField for NoiseDensity {
+    fn density(&self, p: Vec3) -> f64 {
+        self.density * self.fraction(p)
+    }
+
+    fn temperature(&self, p: Vec3) -> f64 {
+        self.temperature * self.fraction(p)
+    }
+
+    fn bounds(&self) -> (Vec3, Vec3) {
+        let r = Vec3::new(self.radius, self.radius, self.radius);
+        (self.center - r, self.center + r)
+    }
+
+    fn max_density(&self, _min: Vec3, _max: Vec3) -> f64 {
+        self.density
+    }
+
+    fn describe(&self) -> Option<Node> {
+        Some(Node::new("noise_density")
+            .with("center", self.center)
+            .with("radius", self.radius)
+            .with("density", self.density)
+            .with("frequency", self.frequency)
+            .with("octaves", self.octaves as f64)
+            .with("temperature", self.temperature)
+            .with("seed", self.noise.seed().to_string().as_str()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_voxel_grid() {
+        let grid = parse("# two voxels\nVOXELS 2 1 1\nBOUNDS 0 0 0 2 1 1\nDENSITY 1 3\nTEMPERATURE 1000 2000\n").unwrap();
+        // Voxel centers at x = 0.5 and 1.5, constant towards the boundary
+        assert_eq!(grid.density(Vec3::new(0.2, 0.5, 0.5)), 1.0);
+        assert_eq!(grid.density(Vec3::new(1.0, 0.5, 0.5)), 2.0);
+        assert_eq!(grid.temperature(Vec3::new(1.5, 0.5, 0.5)), 2000.0);
+        assert_eq!(grid.density(Vec3::new(2.5, 0.5, 0.5)), 0.0);
+        // Only the first voxel is interpolated left of its center
+        assert_eq!(grid.max_density(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.4, 1.0, 1.0)), 1.0);
+        assert_eq!(grid.max_density(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.6, 1.0, 1.0)), 3.0);
+
+        assert!(parse("VOXELS 2 1 1\nBOUNDS 0 0 0 2 1 1\nDENSITY 1").is_err());
+        assert!(parse("VOXELS 2 1 1\nBOUNDS 0 0 0 2 1 1\nDENSITY 1 x").is_err());
+    }
+}
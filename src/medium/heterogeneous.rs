@@ -0,0 +1,243 @@
+//! Media with spatially varying density, sampled with delta and ratio tracking against a grid
+//! of majorants.
+use std::fmt;
+
+use rand::{Rng, RngCore};
+
+use crate::color::{BLACK, WHITE};
+use crate::spectrum::blackbody_rgb;
+use crate::{Ray, Vec3};
+
+use super::{sample_henyey_greenstein, DensityField, Flight, Interior};
+
+/// Number of majorant cells along each axis
+const MAJORANT_RESOLUTION: usize = 16;
+/// Spacing and upper end of the blackbody lookup table in Kelvin
+const BLACKBODY_STEP: f64 = 100.0;
+const BLACKBODY_MAX: f64 = 12000.0;
+
+/// Medium whose coefficients (see [Interior]) are scaled by a [DensityField].
+pub struct Heterogeneous {
+    field: Box<dyn DensityField>,
+    /// Scales the blackbody radiance emitted according to the temperature field
+    pub emission: f64,
+    majorants: MajorantGrid,
+    blackbody: Vec<Vec3>,
+}
+
+impl Heterogeneous {
+    pub fn new(field: impl DensityField + 'static, emission: f64) -> Self {
+        let majorants = MajorantGrid::new(&field, MAJORANT_RESOLUTION);
+        let blackbody = (0..=(BLACKBODY_MAX / BLACKBODY_STEP) as usize)
+            .map(|i| blackbody_rgb(i as f64 * BLACKBODY_STEP))
+            .collect();
+        Heterogeneous { field: Box::new(field), emission, majorants, blackbody }
+    }
+
+    pub fn field(&self) -> &dyn DensityField {
+        self.field.as_ref()
+    }
+
+    /// Returns emitted radiance per unit absorption at `p`.
+    fn emitted(&self, p: Vec3) -> Vec3 {
+        let t = (self.field.temperature(p) / BLACKBODY_STEP).clamp(0.0, (self.blackbody.len() - 1) as f64);
+        let i = (t as usize).min(self.blackbody.len() - 2);
+        let f = t - i as f64;
+        self.emission * ((1.0 - f) * self.blackbody[i] + f * self.blackbody[i + 1])
+    }
+
+    /// Samples flight of `ray` through the medium up to the surface at `distance`. Scattering media
+    /// use spectral delta tracking (Kutz et al. 2017), which picks real or null collisions
+    /// proportional to the path weight. Media that only absorb use ratio tracking, which always
+    /// continues and multiplies the weight by the probability of a null collision.
+    pub(super) fn sample_flight(&self, interior: &Interior, ray: &Ray, distance: f64, rng: &mut dyn RngCore) -> Flight {
+        let direction = ray.direction.unit_vector();
+        let extinction = interior.absorption + interior.scattering;
+        let max_extinction = extinction.x.max(extinction.y).max(extinction.z);
+        let scatters = !interior.scattering.near_zero();
+        let average = |v: Vec3| (v.x + v.y + v.z) / 3.0;
+
+        let mut weight = WHITE;
+        let mut emitted = BLACK;
+        // Distance of a scattering event, infinite for absorption
+        let mut event = None;
+        self.majorants.traverse(ray.origin, direction, distance, |t0, t1, majorant| {
+            let mu = majorant * max_extinction;
+            if mu <= 0.0 {
+                return true;
+            }
+            let mut t = t0;
+            loop {
+                t -= (1.0 - rng.gen::<f64>()).ln() / mu;
+                if t >= t1 {
+                    return true;
+                }
+                let p = ray.origin + t * direction;
+                let density = self.field.density(p);
+                let absorption = density * interior.absorption;
+                let scattering = density * interior.scattering;
+                let null = mu * WHITE - absorption - scattering;
+                let null = Vec3::new(null.x.max(0.0), null.y.max(0.0), null.z.max(0.0));
+                if self.emission > 0.0 {
+                    // Collision estimator, independent of the event chosen below
+                    emitted = emitted + weight * absorption * self.emitted(p) / mu;
+                }
+
+                if !scatters {
+                    weight = weight * null / mu;
+                    if weight.near_zero() {
+                        event = Some(f64::INFINITY);
+                        return false;
+                    }
+                    continue;
+                }
+                let p_scatter = average(weight * scattering);
+                let p_null = average(weight * null);
+                let total = p_scatter + p_null + average(weight * absorption);
+                if total <= 0.0 {
+                    event = Some(f64::INFINITY);
+                    return false;
+                }
+                let u = rng.gen::<f64>() * total;
+                if u < p_scatter {
+                    weight = weight * scattering * (total / (mu * p_scatter));
+                    event = Some(t);
+                    return false;
+                } else if u < p_scatter + p_null {
+                    weight = weight * null * (total / (mu * p_null));
+                } else {
+                    event = Some(f64::INFINITY);
+                    return false;
+                }
+            }
+        });
+
+        match event {
+            None => Flight::Surface { weight, emitted },
+            Some(t) if t.is_infinite() => Flight::Absorbed { emitted },
+            Some(t) => {
+                let direction = sample_henyey_greenstein(direction, interior.anisotropy, rng);
+                Flight::Scatter { distance: t, direction, weight, emitted }
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Heterogeneous {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Heterogeneous").field("emission", &self.emission).finish_non_exhaustive()
+    }
+}
+
+/// Coarse grid over the bounds of a density field storing the maximum density of each cell, such
+/// that tracking can take long steps through thin regions.
+struct MajorantGrid {
+    min: Vec3,
+    max: Vec3,
+    resolution: usize,
+    values: Vec<f64>,
+}
+
+impl MajorantGrid {
+    fn new(field: &dyn DensityField, resolution: usize) -> Self {
+        let (min, max) = field.bounds();
+        let cell = (max - min) / resolution as f64;
+        let mut values = Vec::with_capacity(resolution.pow(3));
+        for z in 0..resolution {
+            for y in 0..resolution {
+                for x in 0..resolution {
+                    let lo = min + Vec3::new(x as f64 * cell.x, y as f64 * cell.y, z as f64 * cell.z);
+                    values.push(field.max_density(lo, lo + cell));
+                }
+            }
+        }
+        MajorantGrid { min, max, resolution, values }
+    }
+
+    /// Walks the cells pierced by the ray from `origin` along unit vector `direction` up to
+    /// `t_max` (3D DDA), calling `f` with the entry and exit distance and majorant of each cell
+    /// until it returns false.
+    fn traverse(&self, origin: Vec3, direction: Vec3, t_max: f64, mut f: impl FnMut(f64, f64, f64) -> bool) {
+        // Clip ray against the bounds
+        let mut t_enter = 0.0f64;
+        let mut t_exit = t_max;
+        for a in 0..3 {
+            let inverse = 1.0 / direction[a];
+            let (t0, t1) = ((self.min[a] - origin[a]) * inverse, (self.max[a] - origin[a]) * inverse);
+            let (t0, t1) = if inverse < 0.0 { (t1, t0) } else { (t0, t1) };
+            t_enter = t_enter.max(if t0.is_nan() { f64::NEG_INFINITY } else { t0 });
+            t_exit = t_exit.min(if t1.is_nan() { f64::INFINITY } else { t1 });
+        }
+        if t_enter >= t_exit {
+            return;
+        }
+
+        let n = self.resolution as f64;
+        let p = origin + t_enter * direction;
+        let mut cell = [0i64; 3];
+        let mut step = [0i64; 3];
+        let mut t_next = [f64::INFINITY; 3];
+        let mut t_delta = [f64::INFINITY; 3];
+        for a in 0..3 {
+            let size = (self.max[a] - self.min[a]) / n;
+            cell[a] = (((p[a] - self.min[a]) / size) as i64).clamp(0, self.resolution as i64 - 1);
+            if direction[a] > 0.0 {
+                step[a] = 1;
+                t_next[a] = t_enter + (self.min[a] + (cell[a] + 1) as f64 * size - p[a]) / direction[a];
+                t_delta[a] = size / direction[a];
+            } else if direction[a] < 0.0 {
+                step[a] = -1;
+                t_next[a] = t_enter + (self.min[a] + cell[a] as f64 * size - p[a]) / direction[a];
+                t_delta[a] = -size / direction[a];
+            }
+        }
+
+        let mut t = t_enter;
+        loop {
+            let axis = (0..3).fold(0, |best, a| if t_next[a] < t_next[best] { a } else { best });
+            let t_end = t_next[axis].min(t_exit);
+            let index = (cell[2] as usize * self.resolution + cell[1] as usize) * self.resolution + cell[0] as usize;
+            if !f(t, t_end, self.values[index]) || t_end >= t_exit {
+                return;
+            }
+            t = t_end;
+            cell[axis] += step[axis];
+            if cell[axis] < 0 || cell[axis] >= self.resolution as i64 {
+                return;
+            }
+            t_next[axis] += t_delta[axis];
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::medium::VoxelGrid;
+    use crate::material::RefractiveIndex;
+
+    #[test]
+    fn test_tracking_transmittance() {
+        let mut rng = rand::thread_rng();
+        // Density rises linearly along x, so the optical depth along the x axis is known
+        let grid = VoxelGrid::from_fn(Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 1.0, 1.0), [64, 4, 4], |p| 2.0 * p.x);
+        let medium = std::sync::Arc::new(Heterogeneous::new(grid, 0.0));
+        let ray = Ray { origin: Vec3::new(-1.0, 0.5, 0.5), direction: Vec3::new(1.0, 0.0, 0.0), time: 0.0, wavelength: None, differentials: None };
+        let expected = (-1.0f64).exp();
+        for scattering in [BLACK, WHITE] {
+            let interior = Interior {
+                scattering,
+                density: Some(medium.clone()),
+                ..Interior::clear(RefractiveIndex::Constant(1.0), WHITE - scattering, 0)
+            };
+            let n = 50_000;
+            let mut transmitted = 0.0;
+            for _ in 0..n {
+                if let Flight::Surface { weight, .. } = medium.sample_flight(&interior, &ray, 3.0, &mut rng) {
+                    transmitted += weight.y;
+                }
+            }
+            assert!((transmitted / n as f64 - expected).abs() < 0.01, "{}", transmitted / n as f64);
+        }
+    }
+}
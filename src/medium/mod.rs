@@ -0,0 +1,298 @@
+//! Tracks the media a path is inside of, for absorption, scattering and nested dielectrics with
+//! priorities (Schmidt and Budge 2002).
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+use rand::{Rng, RngCore};
+
+use crate::color::{BLACK, WHITE};
+use crate::material::{Material, RefractiveIndex};
+use crate::objects::Hit;
+use crate::{Frame, Ray, Vec3};
+
+pub use density::*;
+pub use heterogeneous::Heterogeneous;
+
+mod density;
+mod heterogeneous;
+
+/// Interior of a closed surface, such as glass or a liquid.
+#[derive(Clone, Debug)]
+pub struct Interior {
+    pub refractive_index: RefractiveIndex,
+    /// Absorption coefficient per unit length
+    pub absorption: Vec3,
+    /// Scattering coefficient per unit length, zero for clear media
+    pub scattering: Vec3,
+    /// Mean cosine of the Henyey-Greenstein phase function, positive values scatter forward
+    pub anisotropy: f64,
+    /// Where interiors overlap, the one with the highest priority wins
+    pub priority: u32,
+    /// Spatially varying density scaling absorption and scattering, `None` for homogeneous media
+    pub density: Option<Arc<Heterogeneous>>,
+}
+
+/// Returns absorption coefficient under which light keeps fraction `transmittance` (per channel)
+/// after travelling `distance`.
+pub fn absorption_from_transmittance(transmittance: Vec3, distance: f64) -> Vec3 {
+    let channel = |t: f64| -t.max(1.0e-12).ln() / distance;
+    Vec3::new(channel(transmittance.x), channel(transmittance.y), channel(transmittance.z))
+}
+
+impl Interior {
+    /// Returns interior that only absorbs.
+    pub fn clear(refractive_index: RefractiveIndex, absorption: Vec3, priority: u32) -> Self {
+        Interior { refractive_index, absorption, scattering: BLACK, anisotropy: 0.0, priority, density: None }
+    }
+}
+
+/// Result of sampling the distance light travels through the current medium, `emitted` is the
+/// radiance the medium emits towards the origin along the way.
+pub enum Flight {
+    /// Path reaches the next surface
+    Surface { weight: Vec3, emitted: Vec3 },
+    /// Path scatters at `distance` into a new direction
+    Scatter { distance: f64, direction: Vec3, weight: Vec3, emitted: Vec3 },
+    /// Path ends inside the medium
+    Absorbed { emitted: Vec3 },
+}
+
+struct Entry {
+    /// Address of the material, identifies the interior
+    id: usize,
+    interior: Interior,
+}
+
+fn material_id(material: &dyn Material) -> usize {
+    material as *const dyn Material as *const () as usize
+}
+
+/// Interiors entered along a path, starting in vacuum.
+#[derive(Default)]
+pub struct MediumStack {
+    entries: Vec<Entry>,
+}
+
+impl MediumStack {
+    pub fn new() -> Self {
+        MediumStack::default()
+    }
+
+    /// Returns the interior with highest priority, the most recently entered one among equals.
+    fn current(&self, excluded: Option<usize>) -> Option<&Interior> {
+        self.entries.iter()
+            .filter(|e| Some(e.id) != excluded)
+            .fold(None, |best: Option<&Interior>, e| match best {
+                Some(b) if b.priority > e.interior.priority => Some(b),
+                _ => Some(&e.interior),
+            })
+    }
+
+    fn refractive_index(&self, excluded: Option<usize>, wavelength: Option<f64>) -> f64 {
+        self.current(excluded).map_or(1.0, |interior| interior.refractive_index.at(wavelength))
+    }
+
+    /// Returns absorption coefficient of the medium the path is currently in, per unit density
+    /// for heterogeneous media.
+    pub fn absorption(&self) -> Vec3 {
+        self.current(None).map_or(BLACK, |interior| interior.absorption)
+    }
+
+    /// Returns fraction of light (per channel) kept over `distance` in the current medium,
+    /// assuming it is homogeneous.
+    pub fn transmittance(&self, distance: f64) -> Vec3 {
+        let a = self.absorption() * distance;
+        Vec3::new((-a.x).exp(), (-a.y).exp(), (-a.z).exp())
+    }
+
+    /// Samples how far `ray` gets before it scatters, with the next surface at `distance`. With
+    /// per-channel coefficients, the distance is sampled for a random channel and weighted by the
+    /// average over all channels (chromatic sampling).
+    pub fn sample_flight(&self, ray: &Ray, distance: f64, rng: &mut dyn RngCore) -> Flight {
+        let Some(interior) = self.current(None) else { return Flight::Surface { weight: WHITE, emitted: BLACK } };
+        if let Some(density) = &interior.density {
+            return density.sample_flight(interior, ray, distance, rng);
+        }
+        if interior.scattering.near_zero() {
+            return Flight::Surface { weight: self.transmittance(distance), emitted: BLACK };
+        }
+        let extinction = interior.absorption + interior.scattering;
+        let sigma_t = [extinction.x, extinction.y, extinction.z];
+        let transmittance = |t: f64| Vec3::new((-extinction.x * t).exp(), (-extinction.y * t).exp(), (-extinction.z * t).exp());
+
+        let channel = rng.gen_range(0..3);
+        let t = -(1.0 - rng.gen::<f64>()).ln() / sigma_t[channel];
+        if t < distance {
+            let tr = transmittance(t);
+            let pdf = (extinction.x * tr.x + extinction.y * tr.y + extinction.z * tr.z) / 3.0;
+            let direction = sample_henyey_greenstein(ray.direction.unit_vector(), interior.anisotropy, rng);
+            Flight::Scatter { distance: t, direction, weight: interior.scattering * tr / pdf, emitted: BLACK }
+        } else {
+            let tr = transmittance(distance);
+            let probability = (tr.x + tr.y + tr.z) / 3.0;
+            Flight::Surface { weight: tr / probability, emitted: BLACK }
+        }
+    }
+
+    /// Sets the IOR outside the hit surface. Returns false if the surface lies within an interior
+    /// of higher priority and must be ignored, the ray then passes and the stack is updated.
+    pub fn prepare(&mut self, hit: &mut Hit, wavelength: Option<f64>) -> bool {
+        let id = material_id(hit.material);
+        let interior = match hit.material.interior() {
+            Some(interior) => interior,
+            None => {
+                hit.exterior_ior = self.refractive_index(None, wavelength);
+                return true;
+            }
+        };
+        let visible = if hit.front_face {
+            self.current(None).is_none_or(|current| current.priority <= interior.priority)
+        } else {
+            !self.entries.iter().any(|e| e.id != id && e.interior.priority > interior.priority)
+        };
+        if visible {
+            hit.exterior_ior = self.refractive_index(Some(id), wavelength);
+        } else {
+            self.cross(hit);
+        }
+        visible
+    }
+
+    /// Enters or leaves the interior of the hit surface after the path passed through it.
+    pub fn cross(&mut self, hit: &Hit) {
+        let Some(interior) = hit.material.interior() else { return };
+        let id = material_id(hit.material);
+        if hit.front_face {
+            self.entries.push(Entry { id, interior });
+        } else if let Some(i) = self.entries.iter().rposition(|e| e.id == id) {
+            self.entries.remove(i);
+        }
+    }
+}
+
+/// Samples direction scattered from travel direction `w` by the Henyey-Greenstein phase function.
+/// The weight is one because the sampling is exact.
+fn sample_henyey_greenstein(w: Vec3, g: f64, rng: &mut dyn RngCore) -> Vec3 {
+    let u: f64 = rng.gen();
+    let cos_theta = if g.abs() < 1.0e-3 {
+        1.0 - 2.0 * u
+    } else {
+        let s = (1.0 - g * g) / (1.0 - g + 2.0 * g * u);
+        ((1.0 + g * g - s * s) / (2.0 * g)).clamp(-1.0, 1.0)
+    };
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let phi = 2.0 * PI * rng.gen::<f64>();
+    Frame::from_normal(w).to_world(Vec3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::color::WHITE;
+    use crate::material::{AlphaMask, BumpMap, Dielectric, Lambertian, Layered, Mix, NormalMap};
+    use crate::objects::{Hittable, Sphere};
+    use crate::texture::SolidColor;
+
+    fn dielectric(n: f64, priority: u32) -> Arc<Dielectric> {
+        Arc::new(Dielectric { priority, ..Dielectric::new(RefractiveIndex::Constant(n)) })
+    }
+
+    #[test]
+    fn test_nested_priorities() {
+        // Water overlapping the wall of a glass, which has higher priority
+        let glass = Sphere { center: Vec3::new(0.0, 0.0, 0.0), radius: 2.0, material: dielectric(1.5, 2) };
+        let water = Sphere { center: Vec3::new(0.0, 0.0, 0.0), radius: 1.9, material: dielectric(1.33, 1) };
+        // Negative radius flips the normals of the glass' inner wall
+        let glass_inside = Sphere { center: Vec3::new(0.0, 0.0, 0.0), radius: -1.8, material: glass.material.clone() };
+        let ray = Ray { origin: Vec3::new(0.0, 0.0, 5.0), direction: Vec3::new(0.0, 0.0, -1.0), time: 0.0, wavelength: None, differentials: None };
+        let mut stack = MediumStack::new();
+
+        // Enter glass from air
+        let mut hit = glass.hit(&ray, 0.0, f64::MAX).unwrap();
+        assert!(stack.prepare(&mut hit, None));
+        assert_eq!(hit.exterior_ior, 1.0);
+        stack.cross(&hit);
+        // Water surface inside the glass wall is ignored
+        let mut hit = water.hit(&ray, 0.0, f64::MAX).unwrap();
+        assert!(!stack.prepare(&mut hit, None));
+        // Inner wall of the glass borders water
+        let mut hit = glass_inside.hit(&ray, 0.0, f64::MAX).unwrap();
+        assert!(!hit.front_face);
+        assert!(stack.prepare(&mut hit, None));
+        assert_eq!(hit.exterior_ior, 1.33);
+        stack.cross(&hit);
+        assert_eq!(stack.current(None).unwrap().priority, 1);
+    }
+
+    #[test]
+    fn test_wrapped_interiors() {
+        let glass: Arc<dyn Material> = dielectric(1.5, 3);
+        let gray = Arc::new(SolidColor::gray(0.5));
+        let wrapped: [Arc<dyn Material>; 5] = [
+            Arc::new(NormalMap { material: glass.clone(), texture: gray.clone(), strength: 1.0 }),
+            Arc::new(BumpMap { material: glass.clone(), height: gray.clone(), strength: 1.0 }),
+            Arc::new(AlphaMask { material: glass.clone(), alpha: gray.clone() }),
+            Arc::new(Mix { first: Arc::new(Lambertian::new(WHITE)), second: glass.clone(), weight: gray }),
+            Arc::new(Layered::new(glass, RefractiveIndex::Constant(1.4))),
+        ];
+        let ray = Ray { origin: Vec3::new(0.0, 0.0, 5.0), direction: Vec3::new(0.0, 0.0, -1.0), time: 0.0, wavelength: None, differentials: None };
+        for material in wrapped {
+            assert_eq!(material.interior().unwrap().priority, 3);
+            // Entering through the wrapper and leaving through it again
+            let sphere = Sphere { center: Vec3::new(0.0, 0.0, 0.0), radius: 1.0, material };
+            let mut stack = MediumStack::new();
+            let mut hit = sphere.hit(&ray, 0.0, f64::MAX).unwrap();
+            assert!(stack.prepare(&mut hit, None));
+            stack.cross(&hit);
+            assert_eq!(stack.current(None).unwrap().priority, 3);
+            let mut hit = sphere.hit(&ray, 4.5, f64::MAX).unwrap();
+            assert!(stack.prepare(&mut hit, None));
+            assert_eq!(hit.exterior_ior, 1.0);
+            stack.cross(&hit);
+            assert!(stack.current(None).is_none());
+        }
+    }
+
+    #[test]
+    fn test_absorption() {
+        let a = absorption_from_transmittance(Vec3::new(0.5, 0.25, 1.0), 2.0);
+        let mut stack = MediumStack::new();
+        stack.entries.push(Entry { id: 0, interior: Interior::clear(RefractiveIndex::Constant(1.5), a, 0) });
+        let t = stack.transmittance(2.0);
+        assert!((t.x - 0.5).abs() < 1e-9 && (t.y - 0.25).abs() < 1e-9 && (t.z - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_sample_flight() {
+        let mut rng = rand::thread_rng();
+        // Without absorption, all light either reaches the surface or scatters
+        let scattering = Vec3::new(0.5, 2.0, 8.0);
+        let interior = Interior { scattering, anisotropy: 0.7, ..Interior::clear(RefractiveIndex::Constant(1.3), BLACK, 0) };
+        let stack = MediumStack { entries: vec![Entry { id: 0, interior }] };
+        let w = Vec3::new(0.0, 0.0, 1.0);
+        let ray = Ray { origin: Vec3::new(0.0, 0.0, 0.0), direction: w, time: 0.0, wavelength: None, differentials: None };
+        let n = 100_000;
+        let mut total = Vec3::new(0.0, 0.0, 0.0);
+        let mut mean_cosine = 0.0;
+        let mut scattered = 0;
+        for _ in 0..n {
+            match stack.sample_flight(&ray, 0.5, &mut rng) {
+                Flight::Surface { weight, .. } => total = total + weight,
+                Flight::Scatter { distance, direction, weight, .. } => {
+                    assert!(distance < 0.5);
+                    total = total + weight;
+                    mean_cosine += direction.dot(w);
+                    scattered += 1;
+                }
+                Flight::Absorbed { .. } => unreachable!(),
+            }
+        }
+        let total = total / n as f64;
+        for c in [total.x, total.y, total.z] {
+            assert!((c - 1.0).abs() < 0.03, "{:?}", total);
+        }
+        assert!((mean_cosine / scattered as f64 - 0.7).abs() < 0.02);
+    }
+}
@@ -0,0 +1,205 @@
+//! Trowbridge-Reitz (GGX) microfacet distribution and Fresnel equations. All directions are
+//! unit vectors in the local shading frame with the normal along z, pointing away from the surface.
+use std::f64::consts::PI;
+
+use rand::{Rng, RngCore};
+
+use crate::Vec3;
+
+/// Smallest roughness, smoother surfaces cause numerical problems
+const MIN_ALPHA: f64 = 1.0e-4;
+
+/// Maps perceptually linear roughness in [0, 1] to the distribution's alpha.
+pub fn roughness_to_alpha(roughness: f64) -> f64 {
+    roughness * roughness
+}
+
+/// Anisotropic GGX distribution of microfacet normals.
+#[derive(Copy, Clone, Debug)]
+pub struct TrowbridgeReitz {
+    pub alpha_x: f64,
+    pub alpha_y: f64,
+}
+
+impl TrowbridgeReitz {
+    pub fn new(alpha_x: f64, alpha_y: f64) -> Self {
+        TrowbridgeReitz { alpha_x: alpha_x.max(MIN_ALPHA), alpha_y: alpha_y.max(MIN_ALPHA) }
+    }
+
+    /// Returns density of microfacets with normal `m`.
+    pub fn d(&self, m: Vec3) -> f64 {
+        if m.z <= 0.0 {
+            return 0.0;
+        }
+        let e = (m.x / self.alpha_x).powi(2) + (m.y / self.alpha_y).powi(2) + m.z * m.z;
+        1.0 / (PI * self.alpha_x * self.alpha_y * e * e)
+    }
+
+    /// Smith's auxiliary function, ratio of masked to visible microfacet area.
+    fn lambda(&self, w: Vec3) -> f64 {
+        if w.z == 0.0 {
+            return f64::INFINITY;
+        }
+        let alpha2_tan2 = ((self.alpha_x * w.x).powi(2) + (self.alpha_y * w.y).powi(2)) / (w.z * w.z);
+        ((1.0 + alpha2_tan2).sqrt() - 1.0) / 2.0
+    }
+
+    /// Masking function, fraction of microfacets visible from direction `w`.
+    pub fn g1(&self, w: Vec3) -> f64 {
+        1.0 / (1.0 + self.lambda(w))
+    }
+
+    /// Height-correlated masking-shadowing function.
+    pub fn g2(&self, wo: Vec3, wi: Vec3) -> f64 {
+        1.0 / (1.0 + self.lambda(wo) + self.lambda(wi))
+    }
+
+    /// Returns density of normals visible from `w`.
+    pub fn visible_d(&self, w: Vec3, m: Vec3) -> f64 {
+        if w.z == 0.0 {
+            return 0.0;
+        }
+        self.g1(w) / w.z.abs() * self.d(m) * w.dot(m).abs()
+    }
+
+    /// Samples normal from the distribution of normals visible from `w` (Heitz 2018).
+    pub fn sample_visible_normal(&self, w: Vec3, rng: &mut dyn RngCore) -> Vec3 {
+        // Transform view direction to hemisphere configuration
+        let w = if w.z < 0.0 { -w } else { w };
+        let vh = Vec3::new(self.alpha_x * w.x, self.alpha_y * w.y, w.z).unit_vector();
+        let len2 = vh.x * vh.x + vh.y * vh.y;
+        let t1 = if len2 > 0.0 { Vec3::new(-vh.y, vh.x, 0.0) / len2.sqrt() } else { Vec3::new(1.0, 0.0, 0.0) };
+        let t2 = vh.cross(t1);
+
+        // Sample point on projected disk, warped towards visible half
+        let r = rng.gen::<f64>().sqrt();
+        let phi = 2.0 * PI * rng.gen::<f64>();
+        let p1 = r * phi.cos();
+        let s = 0.5 * (1.0 + vh.z);
+        let p2 = (1.0 - s) * (1.0 - p1 * p1).sqrt() + s * r * phi.sin();
+        let nh = p1 * t1 + p2 * t2 + (1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt() * vh;
+
+        Vec3::new(self.alpha_x * nh.x, self.alpha_y * nh.y, nh.z.max(1.0e-6)).unit_vector()
+    }
+}
+
+impl TrowbridgeReitz {
+    /// Evaluates reflection from `wo` to `wi` without Fresnel term. Returns the BSDF times the
+    /// cosine of `wi`, the pdf of sampling `wi` via visible normals and the microfacet normal.
+    pub fn eval_reflection(&self, wo: Vec3, wi: Vec3) -> Option<(f64, f64, Vec3)> {
+        if wo.z <= 0.0 || wi.z <= 0.0 {
+            return None;
+        }
+        let m = (wo + wi).unit_vector();
+        let value = self.d(m) * self.g2(wo, wi) / (4.0 * wo.z);
+        let pdf = self.visible_d(wo, m) / (4.0 * wo.dot(m).abs());
+        Some((value, pdf, m))
+    }
+
+    /// Evaluates refraction from `wo` to `wi` without Fresnel term, `eta` is the IOR on the side
+    /// of `wi` relative to the side of `wo`. Returns the same values as [Self::eval_reflection].
+    pub fn eval_transmission(&self, wo: Vec3, wi: Vec3, eta: f64) -> Option<(f64, f64, Vec3)> {
+        if wo.z <= 0.0 || wi.z >= 0.0 {
+            return None;
+        }
+        let m = (wo + eta * wi).unit_vector();
+        let m = if m.z < 0.0 { -m } else { m };
+        if wo.dot(m) <= 0.0 || wi.dot(m) >= 0.0 {
+            return None;
+        }
+        let denom = (wo.dot(m) + eta * wi.dot(m)).powi(2);
+        let jacobian = eta * eta * wi.dot(m).abs() / denom;
+        let value = self.d(m) * self.g2(wo, wi) * wo.dot(m) * jacobian / wo.z;
+        let pdf = self.visible_d(wo, m) * jacobian;
+        Some((value, pdf, m))
+    }
+}
+
+/// Fresnel reflectance of a dielectric interface, `eta` is the ratio of the IOR on the
+/// transmitted side to the IOR on the incident side.
+pub fn fresnel_dielectric(cos_theta_i: f64, eta: f64) -> f64 {
+    let cos_theta_i = cos_theta_i.clamp(-1.0, 1.0);
+    let (cos_theta_i, eta) = if cos_theta_i < 0.0 { (-cos_theta_i, 1.0 / eta) } else { (cos_theta_i, eta) };
+    let sin2_theta_t = (1.0 - cos_theta_i * cos_theta_i) / (eta * eta);
+    if sin2_theta_t >= 1.0 {
+        return 1.0;
+    }
+    let cos_theta_t = (1.0 - sin2_theta_t).sqrt();
+    let r_parallel = (eta * cos_theta_i - cos_theta_t) / (eta * cos_theta_i + cos_theta_t);
+    let r_perpendicular = (cos_theta_i - eta * cos_theta_t) / (cos_theta_i + eta * cos_theta_t);
+    (r_parallel * r_parallel + r_perpendicular * r_perpendicular) / 2.0
+}
+
+/// Fresnel reflectance of a conductor with complex index of refraction eta + ik (per channel).
+pub fn fresnel_conductor(cos_theta_i: f64, eta: Vec3, k: Vec3) -> Vec3 {
+    let channel = |eta: f64, k: f64| {
+        let cos2 = cos_theta_i.clamp(0.0, 1.0).powi(2);
+        let sin2 = 1.0 - cos2;
+        let t0 = eta * eta - k * k - sin2;
+        let a2_plus_b2 = (t0 * t0 + 4.0 * eta * eta * k * k).sqrt();
+        let t1 = a2_plus_b2 + cos2;
+        let a = (0.5 * (a2_plus_b2 + t0)).max(0.0).sqrt();
+        let t2 = 2.0 * cos2.sqrt() * a;
+        let rs = (t1 - t2) / (t1 + t2);
+        let t3 = cos2 * a2_plus_b2 + sin2 * sin2;
+        let t4 = t2 * sin2;
+        let rp = rs * (t3 - t4) / (t3 + t4);
+        (rp + rs) / 2.0
+    };
+    Vec3::new(channel(eta.x, k.x), channel(eta.y, k.y), channel(eta.z, k.z))
+}
+
+/// Reflects `w` (pointing away from the surface) about `m`.
+pub fn reflect(w: Vec3, m: Vec3) -> Vec3 {
+    2.0 * w.dot(m) * m - w
+}
+
+/// Refracts `w` (pointing away from the surface, on the same side as `m`) through interface with
+/// relative IOR `eta`, returns `None` on total internal reflection.
+pub fn refract(w: Vec3, m: Vec3, eta: f64) -> Option<Vec3> {
+    let cos_theta_i = w.dot(m);
+    let sin2_theta_t = (1.0 - cos_theta_i * cos_theta_i).max(0.0) / (eta * eta);
+    if sin2_theta_t >= 1.0 {
+        return None;
+    }
+    let cos_theta_t = (1.0 - sin2_theta_t).sqrt();
+    Some(-w / eta + (cos_theta_i / eta - cos_theta_t) * m)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_distribution_is_normalized() {
+        // Integral of D(m) cos(θm) over the hemisphere is one, estimate it with uniform samples
+        let distribution = TrowbridgeReitz::new(0.3, 0.6);
+        let mut rng = rand::thread_rng();
+        let n = 200_000;
+        let mut sum = 0.0;
+        for _ in 0..n {
+            let z: f64 = rng.gen();
+            let phi = 2.0 * PI * rng.gen::<f64>();
+            let r = (1.0 - z * z).sqrt();
+            let m = Vec3::new(r * phi.cos(), r * phi.sin(), z);
+            sum += distribution.d(m) * m.z * 2.0 * PI;
+        }
+        assert!((sum / n as f64 - 1.0).abs() < 0.05);
+
+        let w = Vec3::new(0.6, 0.0, 0.8);
+        for _ in 0..100 {
+            let m = distribution.sample_visible_normal(w, &mut rng);
+            assert!((m.length() - 1.0).abs() < 1e-9);
+            assert!(m.dot(w) > 0.0);
+        }
+    }
+
+    #[test]
+    fn test_fresnel() {
+        assert!((fresnel_dielectric(1.0, 1.5) - 0.04).abs() < 1e-9);
+        assert_eq!(fresnel_dielectric(0.1, 1.0 / 1.5), 1.0);
+        // Conductor without absorption behaves like a dielectric
+        let f = fresnel_conductor(0.7, Vec3::new(1.5, 1.5, 1.5), Vec3::new(0.0, 0.0, 0.0));
+        assert!((f.x - fresnel_dielectric(0.7, 1.5)).abs() < 1e-9);
+    }
+}
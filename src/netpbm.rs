@@ -0,0 +1,121 @@
+//! Reading of Netpbm images (PGM and PPM), the format the renderer writes.
+use std::fs;
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+
+/// Image with channel values normalized to [0, 1], rows stored top to bottom.
+#[derive(Clone, Debug)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    /// 1 for grayscale, 3 for RGB
+    pub channels: usize,
+    pub data: Vec<f64>,
+}
+
+impl Image {
+    /// Returns channel value of pixel in column `x` and row `y` (counted from the top).
+    pub fn get(&self, x: usize, y: usize, channel: usize) -> f64 {
+        self.data[(y * self.width + x) * self.channels + channel]
+    }
+
+    /// Returns average of all channels of the given pixel.
+    pub fn gray(&self, x: usize, y: usize) -> f64 {
+        (0..self.channels).map(|c| self.get(x, y, c)).sum::<f64>() / self.channels as f64
+    }
+}
+
+pub fn read<P: AsRef<Path>>(path: P) -> std::io::Result<Image> {
+    parse(&fs::read(path)?)
+}
+
+/// Parses plain (P2, P3) or raw (P5, P6) PGM/PPM data.
+pub fn parse(bytes: &[u8]) -> std::io::Result<Image> {
+    let mut pos = 0;
+    let magic = next_token(bytes, &mut pos)?;
+    let (channels, raw) = match magic.as_str() {
+        "P2" => (1, false),
+        "P3" => (3, false),
+        "P5" => (1, true),
+        "P6" => (3, true),
+        m => return Err(invalid(format!("unsupported Netpbm format {}", m))),
+    };
+    let width = next_number(bytes, &mut pos)?;
+    let height = next_number(bytes, &mut pos)?;
+    let max = next_number(bytes, &mut pos)?;
+    if max == 0 || max > 65535 {
+        return Err(invalid(format!("invalid maximum value {}", max)));
+    }
+
+    let count = width * height * channels;
+    let data: Vec<f64> = if raw {
+        // Exactly one whitespace character separates header and raster
+        let start = pos + 1;
+        let bytes_per_value = if max < 256 { 1 } else { 2 };
+        let end = start + count * bytes_per_value;
+        if end > bytes.len() {
+            return Err(invalid("raster data too short".to_string()));
+        }
+        bytes[start..end].chunks(bytes_per_value)
+            .map(|c| c.iter().fold(0usize, |acc, &b| acc * 256 + b as usize))
+            .map(|v| v as f64 / max as f64)
+            .collect()
+    } else {
+        (0..count).map(|_| next_number(bytes, &mut pos).map(|v| v as f64 / max as f64))
+            .collect::<std::io::Result<_>>()?
+    };
+    Ok(Image { width, height, channels, data })
+}
+
+fn invalid(message: String) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+/// Returns next whitespace-separated token, skipping comments.
+fn next_token(bytes: &[u8], pos: &mut usize) -> std::io::Result<String> {
+    loop {
+        while *pos < bytes.len() && bytes[*pos].is_ascii_whitespace() {
+            *pos += 1;
+        }
+        if *pos < bytes.len() && bytes[*pos] == b'#' {
+            while *pos < bytes.len() && bytes[*pos] != b'\n' {
+                *pos += 1;
+            }
+        } else {
+            break;
+        }
+    }
+    let start = *pos;
+    while *pos < bytes.len() && !bytes[*pos].is_ascii_whitespace() {
+        *pos += 1;
+    }
+    if start == *pos {
+        return Err(invalid("unexpected end of data".to_string()));
+    }
+    Ok(String::from_utf8_lossy(&bytes[start..*pos]).into_owned())
+}
+
+fn next_number(bytes: &[u8], pos: &mut usize) -> std::io::Result<usize> {
+    let token = next_token(bytes, pos)?;
+    token.parse().map_err(|_| invalid(format!("expected number, found {}", token)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_plain() {
+        let image = parse(b"P3\n# comment\n2 1\n255\n255 0 0 0 0 51\n").unwrap();
+        assert_eq!((image.width, image.height, image.channels), (2, 1, 3));
+        assert_eq!(image.get(0, 0, 0), 1.0);
+        assert_eq!(image.get(1, 0, 2), 0.2);
+    }
+
+    #[test]
+    fn test_parse_raw() {
+        let image = parse(b"P5 2 2 255\n\x00\xff\x33\x00").unwrap();
+        assert_eq!(image.data, vec![0.0, 1.0, 0.2, 0.0]);
+        assert!(parse(b"P5 2 2 255\n\x00").is_err());
+    }
+}
@@ -0,0 +1,191 @@
+//! Gradient and cellular noise for procedural textures and media, with fractal sums and domain
+//! warping on top.
+use crate::Vec3;
+
+/// Edge midpoints of a cube, the gradients of improved Perlin and simplex noise
+const GRADIENTS: [[f64; 3]; 12] = [
+    [1.0, 1.0, 0.0], [-1.0, 1.0, 0.0], [1.0, -1.0, 0.0], [-1.0, -1.0, 0.0],
+    [1.0, 0.0, 1.0], [-1.0, 0.0, 1.0], [1.0, 0.0, -1.0], [-1.0, 0.0, -1.0],
+    [0.0, 1.0, 1.0], [0.0, -1.0, 1.0], [0.0, 1.0, -1.0], [0.0, -1.0, -1.0],
+];
+
+/// Result of cellular noise.
+#[derive(Copy, Clone, Debug)]
+pub struct Worley {
+    /// Distance to the closest feature point
+    pub f1: f64,
+    /// Distance to the second closest feature point
+    pub f2: f64,
+    /// Random number identifying the closest feature point
+    pub id: usize,
+}
+
+/// Noise functions over a permutation table, the same seed gives the same noise.
+#[derive(Clone)]
+pub struct Noise {
+    seed: u64,
+    permutation: [u8; 512],
+}
+
+impl Default for Noise {
+    fn default() -> Self {
+        Noise::new(0)
+    }
+}
+
+impl Noise {
+    pub fn new(seed: u64) -> Self {
+        let mut table: [u8; 256] = std::array::from_fn(|i| i as u8);
+        // Fisher-Yates shuffle driven by SplitMix64
+        let mut state = seed;
+        for i in (1..256).rev() {
+            state = state.wrapping_add(0x9e3779b97f4a7c15);
+            let mut z = state;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+            z ^= z >> 31;
+            table.swap(i, (z % (i as u64 + 1)) as usize);
+        }
+        Noise { seed, permutation: std::array::from_fn(|i| table[i % 256]) }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    fn hash(&self, x: i64, y: i64, z: i64) -> usize {
+        let p = |i: usize| self.permutation[i] as usize;
+        p(p(p((z & 255) as usize) + (y & 255) as usize) + (x & 255) as usize)
+    }
+
+    /// Improved Perlin noise (Perlin 2002), in about [-1, 1] and zero at integer points.
+    pub fn perlin(&self, p: Vec3) -> f64 {
+        let cell = [p.x.floor(), p.y.floor(), p.z.floor()];
+        let f: [f64; 3] = std::array::from_fn(|a| p[a] - cell[a]);
+        let fade = |t: f64| t * t * t * (t * (t * 6.0 - 15.0) + 10.0);
+        let w = f.map(fade);
+        let mut value = 0.0;
+        for corner in 0..8 {
+            let pick = |a: usize| corner >> a & 1;
+            let g = GRADIENTS[self.hash(cell[0] as i64 + pick(0), cell[1] as i64 + pick(1), cell[2] as i64 + pick(2)) % 12];
+            let d: f64 = (0..3).map(|a| g[a] * (f[a] - pick(a) as f64)).sum();
+            let weight: f64 = (0..3).map(|a| if pick(a) == 1 { w[a] } else { 1.0 - w[a] }).product();
+            value += weight * d;
+        }
+        value
+    }
+
+    /// Simplex noise (Perlin 2001, following Gustavson's implementation), in about [-1, 1].
+    /// Cheaper than Perlin noise in higher octaves and without its axis-aligned artifacts.
+    pub fn simplex(&self, p: Vec3) -> f64 {
+        const F3: f64 = 1.0 / 3.0;
+        const G3: f64 = 1.0 / 6.0;
+        // Skew into the grid of simplices and find the containing cell
+        let s = (p.x + p.y + p.z) * F3;
+        let cell = [(p.x + s).floor(), (p.y + s).floor(), (p.z + s).floor()];
+        let t = (cell[0] + cell[1] + cell[2]) * G3;
+        let x0: [f64; 3] = std::array::from_fn(|a| p[a] - (cell[a] - t));
+
+        // Corners are visited along the axes ordered by offset
+        let mut order = [0, 1, 2];
+        order.sort_by(|&a, &b| x0[b].partial_cmp(&x0[a]).unwrap_or(std::cmp::Ordering::Equal));
+        let mut offset = [0i64; 3];
+        let mut value = 0.0;
+        for k in 0..4 {
+            if k > 0 {
+                offset[order[k - 1]] = 1;
+            }
+            let x: [f64; 3] = std::array::from_fn(|a| x0[a] - offset[a] as f64 + k as f64 * G3);
+            let falloff = 0.6 - x[0] * x[0] - x[1] * x[1] - x[2] * x[2];
+            if falloff > 0.0 {
+                let g = GRADIENTS[self.hash(cell[0] as i64 + offset[0], cell[1] as i64 + offset[1], cell[2] as i64 + offset[2]) % 12];
+                value += falloff.powi(4) * (g[0] * x[0] + g[1] * x[1] + g[2] * x[2]);
+            }
+        }
+        32.0 * value
+    }
+
+    /// Cellular noise (Worley 1996) with one random feature point per unit cell.
+    pub fn worley(&self, p: Vec3) -> Worley {
+        let cell = [p.x.floor() as i64, p.y.floor() as i64, p.z.floor() as i64];
+        let mut result = Worley { f1: f64::INFINITY, f2: f64::INFINITY, id: 0 };
+        for dz in -1..=1 {
+            for dy in -1..=1 {
+                for dx in -1..=1 {
+                    let c = [cell[0] + dx, cell[1] + dy, cell[2] + dz];
+                    let h = self.hash(c[0], c[1], c[2]);
+                    let jitter = |i: usize| self.permutation[(h + 73 * i) % 512] as f64 / 255.0;
+                    let feature = Vec3::new(c[0] as f64 + jitter(0), c[1] as f64 + jitter(1), c[2] as f64 + jitter(2));
+                    let d = (feature - p).length();
+                    if d < result.f1 {
+                        result = Worley { f1: d, f2: result.f1, id: h };
+                    } else if d < result.f2 {
+                        result.f2 = d;
+                    }
+                }
+            }
+        }
+        result
+    }
+}
+
+/// Fractional Brownian motion, the sum of `octaves` layers of `noise` with doubling frequency and
+/// halving amplitude. Stays in about [-1, 1] for noise in [-1, 1].
+pub fn fbm(noise: impl Fn(Vec3) -> f64, p: Vec3, octaves: u32) -> f64 {
+    let mut sum = 0.0;
+    let mut amplitude = 0.5;
+    let mut frequency = 1.0;
+    for _ in 0..octaves {
+        sum += amplitude * noise(frequency * p);
+        amplitude *= 0.5;
+        frequency *= 2.0;
+    }
+    sum
+}
+
+/// Like [fbm] but summing absolute values, which gives creases where the noise changes sign.
+/// In about [0, 1].
+pub fn turbulence(noise: impl Fn(Vec3) -> f64, p: Vec3, octaves: u32) -> f64 {
+    fbm(|q| noise(q).abs(), p, octaves)
+}
+
+/// Displaces `p` by `strength` times a vector of noise values (domain warping), such that
+/// patterns evaluated at the result swirl.
+pub fn warp(noise: impl Fn(Vec3) -> f64, p: Vec3, strength: f64) -> Vec3 {
+    // Decorrelate the components by sampling far apart
+    let offset = |x: f64| noise(p + Vec3::new(x, 1.7 * x, 0.3 * x));
+    p + strength * Vec3::new(offset(0.0), offset(31.4), offset(-57.2))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_noise_range() {
+        let noise = Noise::new(7);
+        let mut lattice_zero = true;
+        for i in 0..2000 {
+            let p = Vec3::new(i as f64 * 0.37, i as f64 * -0.11, (i % 13) as f64 * 0.53);
+            let (perlin, simplex) = (noise.perlin(p), noise.simplex(p));
+            assert!(perlin.abs() <= 1.1 && simplex.abs() <= 1.1, "{} {}", perlin, simplex);
+            let cell = noise.worley(p);
+            assert!(cell.f1 <= cell.f2 && cell.f1 < 3.0f64.sqrt());
+            lattice_zero &= noise.perlin(Vec3::new(i as f64, 3.0, -(i as f64))) == 0.0;
+        }
+        assert!(lattice_zero);
+        let p = Vec3::new(0.3, 0.4, 0.5);
+        assert_eq!(Noise::new(7).simplex(p), noise.simplex(p));
+        assert_ne!(Noise::new(8).simplex(p), noise.simplex(p));
+    }
+
+    #[test]
+    fn test_continuity() {
+        let noise = Noise::default();
+        let p = Vec3::new(1.234, 5.678, -9.1011);
+        let q = p + Vec3::new(1e-6, 1e-6, 1e-6);
+        assert!((noise.perlin(p) - noise.perlin(q)).abs() < 1e-4);
+        assert!((noise.simplex(p) - noise.simplex(q)).abs() < 1e-4);
+        assert!((fbm(|x| noise.simplex(x), p, 6) - fbm(|x| noise.simplex(x), q, 6)).abs() < 1e-3);
+    }
+}
@@ -0,0 +1,52 @@
+use crate::{Ray, Vec3};
+
+/// Axis-aligned bounding box.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    pub fn new(min: Vec3, max: Vec3) -> Self {
+        Aabb { min, max }
+    }
+
+    /// Returns smallest box containing all `points`, which must not be empty.
+    pub fn from_points(points: impl IntoIterator<Item = Vec3>) -> Self {
+        let mut points = points.into_iter();
+        let first = points.next().expect("bounding box of no points");
+        points.fold(Aabb::new(first, first), |b, p| b.surrounding(Aabb::new(p, p)))
+    }
+
+    /// Returns smallest box containing both boxes.
+    pub fn surrounding(self, other: Aabb) -> Self {
+        Aabb {
+            min: Vec3::new(self.min.x.min(other.min.x), self.min.y.min(other.min.y), self.min.z.min(other.min.z)),
+            max: Vec3::new(self.max.x.max(other.max.x), self.max.y.max(other.max.y), self.max.z.max(other.max.z)),
+        }
+    }
+
+    pub fn corners(&self) -> [Vec3; 8] {
+        std::array::from_fn(|i| {
+            let pick = |a: usize| if i >> a & 1 == 1 { self.max[a] } else { self.min[a] };
+            Vec3::new(pick(0), pick(1), pick(2))
+        })
+    }
+
+    /// Returns part of [`t_min`, `t_max`] where `ray` lies inside the box (slab test), if any.
+    pub fn clip(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<(f64, f64)> {
+        let (mut t0, mut t1) = (t_min, t_max);
+        for a in 0..3 {
+            let inverse = 1.0 / ray.direction[a];
+            let (near, far) = ((self.min[a] - ray.origin[a]) * inverse, (self.max[a] - ray.origin[a]) * inverse);
+            let (near, far) = if inverse < 0.0 { (far, near) } else { (near, far) };
+            t0 = t0.max(near);
+            t1 = t1.min(far);
+            if t0 > t1 {
+                return None;
+            }
+        }
+        Some((t0, t1))
+    }
+}
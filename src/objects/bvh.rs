@@ -0,0 +1,268 @@
+use std::ops::Range;
+
+use crate::material::MaterialTable;
+use crate::objects::{Aabb, Hit, hit_opaque, Hittable};
+use crate::Ray;
+use crate::scene;
+
+/// Most objects in a leaf
+const MAX_LEAF_SIZE: usize = 4;
+/// Candidate splits per axis for the surface area heuristic
+const BUCKETS: usize = 12;
+
+enum Node {
+    Leaf { bounds: Aabb, start: usize, count: usize },
+    /// The first child directly follows its parent
+    Interior { bounds: Aabb, second: usize, axis: usize },
+}
+
+impl Node {
+    fn bounds(&self) -> &Aabb {
+        match self {
+            Node::Leaf { bounds, .. } | Node::Interior { bounds, .. } => bounds,
+        }
+    }
+}
+
+/// Tree of boxes over items stored in leaf order, shared by [Bvh] and other aggregates.
+pub(crate) struct Tree {
+    nodes: Vec<Node>,
+}
+
+impl Tree {
+    /// Builds tree over `items` with their boxes, reordering them such that each leaf covers a
+    /// range of them.
+    pub(crate) fn build<T>(items: &mut [(Aabb, T)]) -> Self {
+        let mut nodes = vec![];
+        if !items.is_empty() {
+            build(items, 0, &mut nodes);
+        }
+        Tree { nodes }
+    }
+
+    pub(crate) fn bounds(&self) -> Option<Aabb> {
+        self.nodes.first().map(|node| *node.bounds())
+    }
+
+    /// Calls `leaf` with the item range of each leaf whose box the ray passes between `t_min` and
+    /// `t_max`, nearer leaves first. The callback may lower `t_max` (e.g. to the closest hit so
+    /// far) and stops the traversal by returning true.
+    pub(crate) fn traverse(&self, ray: &Ray, t_min: f64, mut t_max: f64, mut leaf: impl FnMut(Range<usize>, &mut f64) -> bool) {
+        let mut stack = Vec::with_capacity(64);
+        if !self.nodes.is_empty() {
+            stack.push(0);
+        }
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if node.bounds().clip(ray, t_min, t_max).is_none() {
+                continue;
+            }
+            match *node {
+                Node::Leaf { start, count, .. } => {
+                    if leaf(start..start + count, &mut t_max) {
+                        return;
+                    }
+                }
+                Node::Interior { second, axis, .. } => {
+                    // Visit the child nearer along the ray first
+                    if ray.direction[axis] < 0.0 {
+                        stack.extend([index + 1, second]);
+                    } else {
+                        stack.extend([second, index + 1]);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Bounding volume hierarchy, a tree of boxes built with the surface area heuristic such that rays
+/// only test objects whose boxes they pass through. Use it for large numbers of objects, e.g. the
+/// curves of a groom or the triangles of a mesh. Objects without a bounding box are tested for
+/// every ray.
+pub struct Bvh {
+    objects: Vec<Box<dyn Hittable>>,
+    tree: Tree,
+    unbounded: Vec<Box<dyn Hittable>>,
+}
+
+impl Bvh {
+    pub fn new(objects: Vec<Box<dyn Hittable>>) -> Self {
+        let (bounded, unbounded): (Vec<_>, Vec<_>) = objects.into_iter()
+            .map(|object| (object.bounding_box(), object))
+            .partition(|(bounds, _)| bounds.is_some());
+        let mut items: Vec<(Aabb, Box<dyn Hittable>)> = bounded.into_iter()
+            .map(|(bounds, object)| (bounds.unwrap(), object))
+            .collect();
+        let tree = Tree::build(&mut items);
+        Bvh {
+            objects: items.into_iter().map(|(_, object)| object).collect(),
+            tree,
+            unbounded: unbounded.into_iter().map(|(_, object)| object).collect(),
+        }
+    }
+}
+
+fn centroid(bounds: &Aabb, axis: usize) -> f64 {
+    0.5 * (bounds.min[axis] + bounds.max[axis])
+}
+
+fn surface_area(bounds: &Aabb) -> f64 {
+    let d = bounds.max - bounds.min;
+    2.0 * (d.x * d.y + d.y * d.z + d.z * d.x)
+}
+
+/// Appends subtree over `items` to `nodes`, reordering `items` into its leaves. Items in leaves
+/// are indexed from `offset` on.
+fn build<T>(items: &mut [(Aabb, T)], offset: usize, nodes: &mut Vec<Node>) {
+    let bounds = items.iter().map(|(b, _)| *b).reduce(Aabb::surrounding).unwrap();
+    let index = nodes.len();
+    let leaf = Node::Leaf { bounds, start: offset, count: items.len() };
+    if items.len() <= MAX_LEAF_SIZE {
+        nodes.push(leaf);
+        return;
+    }
+
+    // Split along the axis where the centroids spread most
+    let centroids = Aabb::from_points(items.iter().map(|(b, _)| (b.min + b.max) / 2.0));
+    let extent = centroids.max - centroids.min;
+    let axis = if extent.x > extent.y && extent.x > extent.z { 0 } else if extent.y > extent.z { 1 } else { 2 };
+    let (lo, hi) = (centroids.min[axis], centroids.max[axis]);
+    if hi - lo <= 0.0 {
+        nodes.push(leaf);
+        return;
+    }
+    let bucket = |b: &Aabb| (((centroid(b, axis) - lo) / (hi - lo) * BUCKETS as f64) as usize).min(BUCKETS - 1);
+
+    // Cost of splitting after each bucket, proportional to the expected number of tested objects
+    let mut buckets: [(usize, Option<Aabb>); BUCKETS] = [(0, None); BUCKETS];
+    for (b, _) in items.iter() {
+        let (count, bounds) = &mut buckets[bucket(b)];
+        *count += 1;
+        *bounds = Some(bounds.map_or(*b, |bounds| bounds.surrounding(*b)));
+    }
+    let side_cost = |buckets: &[(usize, Option<Aabb>)]| {
+        let count: usize = buckets.iter().map(|(count, _)| count).sum();
+        buckets.iter().filter_map(|(_, b)| *b).reduce(Aabb::surrounding).map_or(0.0, |b| count as f64 * surface_area(&b))
+    };
+    let (split, cost) = (1..BUCKETS)
+        .map(|split| (split, side_cost(&buckets[..split]) + side_cost(&buckets[split..])))
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .unwrap();
+    if items.len() <= 2 * MAX_LEAF_SIZE && cost >= items.len() as f64 * surface_area(&bounds) {
+        nodes.push(leaf);
+        return;
+    }
+
+    let mut middle = partition(items, |b| bucket(b) < split);
+    if middle == 0 || middle == items.len() {
+        middle = items.len() / 2;
+        items.sort_by(|a, b| centroid(&a.0, axis).total_cmp(&centroid(&b.0, axis)));
+    }
+    nodes.push(Node::Interior { bounds, second: 0, axis });
+    let (first, second) = items.split_at_mut(middle);
+    build(first, offset, nodes);
+    let second_index = nodes.len();
+    build(second, offset + middle, nodes);
+    if let Node::Interior { second, .. } = &mut nodes[index] {
+        *second = second_index;
+    }
+}
+
+/// Moves items whose box satisfies `predicate` to the front, returns their number.
+fn partition<T>(items: &mut [(Aabb, T)], predicate: impl Fn(&Aabb) -> bool) -> usize {
+    let mut middle = 0;
+    for i in 0..items.len() {
+        if predicate(&items[i].0) {
+            items.swap(i, middle);
+            middle += 1;
+        }
+    }
+    middle
+}
+
+impl Hittable for Bvh {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Hit<'_>> {
+        let mut hit: Option<Hit> = None;
+        let mut closest = t_max;
+        for object in self.unbounded.iter() {
+            if let Some(h) = hit_opaque(object.as_ref(), ray, t_min, closest) {
+                closest = h.t;
+                hit = Some(h);
+            }
+        }
+        self.tree.traverse(ray, t_min, closest, |range, closest| {
+            for object in &self.objects[range] {
+                // Surfaces cut out by alpha masks let the ray pass
+                if let Some(h) = hit_opaque(object.as_ref(), ray, t_min, *closest) {
+                    *closest = h.t;
+                    hit = Some(h);
+                }
+            }
+            false
+        });
+        hit
+    }
+
+    fn occluded(&self, ray: &Ray, t_min: f64, t_max: f64) -> bool {
+        if self.unbounded.iter().any(|object| object.occluded(ray, t_min, t_max)) {
+            return true;
+        }
+        let mut occluded = false;
+        self.tree.traverse(ray, t_min, t_max, |range, _| {
+            occluded = self.objects[range].iter().any(|object| object.occluded(ray, t_min, t_max));
+            occluded
+        });
+        occluded
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        if !self.unbounded.is_empty() {
+            return None;
+        }
+        self.tree.bounds()
+    }
+
+    /// Lists the objects in leaf order, which [Bvh::new] splits into the same leaves again.
+    fn describe(&self, materials: &mut MaterialTable) -> Option<scene::Node> {
+        let objects = self.objects.iter().chain(&self.unbounded).map(|object| object.describe(materials)).collect::<Option<Vec<_>>>()?;
+        Some(scene::Node::new("bvh").with("objects", objects))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::material::Lambertian;
+    use crate::objects::{Hittables, Sphere};
+    use crate::Vec3;
+
+    #[test]
+    fn test_bvh() {
+        let material = Arc::new(Lambertian::new(Vec3::new(0.5, 0.5, 0.5)));
+        let spheres = |material: Arc<Lambertian>| -> Vec<Box<dyn Hittable>> {
+            (0..500).map(|i| {
+                let i = i as f64;
+                let center = Vec3::new(10.0 * (1.3 * i).sin(), 10.0 * (0.7 * i).cos(), 10.0 * (2.1 * i).sin());
+                Box::new(Sphere { center, radius: 0.2 + 0.3 * (0.5 * i).cos().abs(), material: material.clone() }) as Box<dyn Hittable>
+            }).collect()
+        };
+        let bvh = Bvh::new(spheres(material.clone()));
+        let list = Hittables { hittables: spheres(material) };
+        assert_eq!(bvh.bounding_box(), list.bounding_box());
+
+        let mut hits = 0;
+        for k in 0..500 {
+            let k = k as f64;
+            let origin = Vec3::new(15.0 * (0.9 * k).cos(), 15.0 * (1.7 * k).sin(), 15.0 * (0.4 * k).cos());
+            let ray = Ray { origin, direction: Vec3::new((2.3 * k).sin(), (1.1 * k).cos(), 0.5) - 0.05 * origin, time: 0.0, wavelength: None, differentials: None };
+            let (expected, hit) = (list.hit(&ray, 0.001, f64::MAX), bvh.hit(&ray, 0.001, f64::MAX));
+            assert_eq!(expected.map(|h| h.t), hit.map(|h| h.t));
+            assert_eq!(list.occluded(&ray, 0.001, 5.0), bvh.occluded(&ray, 0.001, 5.0));
+            hits += hit.is_some() as usize;
+        }
+        assert!(hits > 50);
+    }
+}
@@ -0,0 +1,193 @@
+use crate::material::MaterialTable;
+use crate::objects::{Aabb, Hit, Hittable, Interval, Solid};
+use crate::scene::Node;
+use crate::Ray;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Operation {
+    Union,
+    Intersection,
+    /// Left solid with the right one cut out
+    Difference,
+}
+
+impl Operation {
+    fn inside(self, left: bool, right: bool) -> bool {
+        match self {
+            Operation::Union => left || right,
+            Operation::Intersection => left && right,
+            Operation::Difference => left && !right,
+        }
+    }
+}
+
+/// Boolean combination of two solids (constructive solid geometry). Surfaces keep the material of
+/// the solid they belong to, also where the right solid of a difference carves out the left one.
+/// Nodes are solids themselves and can be nested.
+pub struct Csg {
+    pub operation: Operation,
+    pub left: Box<dyn Solid>,
+    pub right: Box<dyn Solid>,
+}
+
+impl Csg {
+    pub fn union(left: impl Solid + 'static, right: impl Solid + 'static) -> Self {
+        Csg { operation: Operation::Union, left: Box::new(left), right: Box::new(right) }
+    }
+
+    pub fn intersection(left: impl Solid + 'static, right: impl Solid + 'static) -> Self {
+        Csg { operation: Operation::Intersection, left: Box::new(left), right: Box::new(right) }
+    }
+
+    pub fn difference(left: impl Solid + 'static, right: impl Solid + 'static) -> Self {
+        Csg { operation: Operation::Difference, left: Box::new(left), right: Box::new(right) }
+    }
+}
+
+impl Hittable for Csg {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Hit<'_>> {
+        self.intervals(ray).into_iter()
+            .flat_map(|interval| [interval.enter, interval.exit])
+            .find(|hit| (t_min..=t_max).contains(&hit.t))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let left = self.left.bounding_box()?;
+        match self.operation {
+            Operation::Union => Some(left.surrounding(self.right.bounding_box()?)),
+            // Never larger than the left solid
+            Operation::Intersection | Operation::Difference => Some(left),
+        }
+    }
+
+    fn describe(&self, materials: &mut MaterialTable) -> Option<Node> {
+        let operation = match self.operation {
+            Operation::Union => "union",
+            Operation::Intersection => "intersection",
+            Operation::Difference => "difference",
+        };
+        Some(Node::new("csg")
+            .with("operation", operation)
+            .with("left", self.left.describe(materials)?)
+            .with("right", self.right.describe(materials)?))
+    }
+}
+
+impl Solid for Csg {
+    fn intervals(&self, ray: &Ray) -> Vec<Interval<'_>> {
+        // Walk the boundaries of both solids in order and record where the combination changes
+        let mut boundaries: Vec<(Hit, bool)> = self.left.intervals(ray).into_iter()
+            .flat_map(|interval| [(interval.enter, true), (interval.exit, true)])
+            .chain(self.right.intervals(ray).into_iter().flat_map(|interval| [(interval.enter, false), (interval.exit, false)]))
+            .collect();
+        boundaries.sort_by(|a, b| a.0.t.total_cmp(&b.0.t));
+
+        let (mut in_left, mut in_right) = (false, false);
+        let mut enter = None;
+        let mut intervals = vec![];
+        for (mut hit, is_left) in boundaries {
+            let was_inside = self.operation.inside(in_left, in_right);
+            if is_left {
+                in_left = !in_left;
+            } else {
+                in_right = !in_right;
+                if self.operation == Operation::Difference {
+                    // Carved surfaces face inwards of the right solid
+                    hit.front_face = !hit.front_face;
+                }
+            }
+            match (was_inside, self.operation.inside(in_left, in_right)) {
+                (false, true) => enter = Some(hit),
+                (true, false) => intervals.extend(enter.take().map(|enter| Interval { enter, exit: hit })),
+                _ => {}
+            }
+        }
+        intervals
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::material::Lambertian;
+    use crate::objects::{Cylinder, Placement, Sphere, Torus, Volume};
+    use crate::Vec3;
+
+    fn sphere(x: f64) -> Sphere {
+        Sphere { center: Vec3::new(x, 0.0, 0.0), radius: 1.0, material: Arc::new(Lambertian::new(Vec3::new(0.5, 0.5, 0.5))) }
+    }
+
+    fn spans(solid: &dyn Solid, ray: &Ray) -> Vec<(f64, f64)> {
+        solid.intervals(ray).iter().map(|i| (i.enter.t, i.exit.t)).collect()
+    }
+
+    fn assert_spans(solid: &dyn Solid, ray: &Ray, expected: &[(f64, f64)]) {
+        let spans = spans(solid, ray);
+        assert!(spans.len() == expected.len(), "{:?}", spans);
+        for ((t0, t1), (e0, e1)) in spans.into_iter().zip(expected) {
+            assert!((t0 - e0).abs() < 1e-9 && (t1 - e1).abs() < 1e-9, "{} {}", t0, t1);
+        }
+    }
+
+    #[test]
+    fn test_operations() {
+        // Along the x axis, the left sphere covers [-1, 1] and the right one [0, 2]
+        let ray = Ray { origin: Vec3::new(-5.0, 0.0, 0.0), direction: Vec3::new(1.0, 0.0, 0.0), time: 0.0, wavelength: None, differentials: None };
+        assert_eq!(spans(&Csg::union(sphere(0.0), sphere(1.0)), &ray), [(4.0, 7.0)]);
+        assert_eq!(spans(&Csg::intersection(sphere(0.0), sphere(1.0)), &ray), [(5.0, 6.0)]);
+        assert_eq!(spans(&Csg::difference(sphere(0.0), sphere(1.0)), &ray), [(4.0, 5.0)]);
+        assert_eq!(spans(&Csg::union(sphere(0.0), sphere(3.0)), &ray), [(4.0, 6.0), (7.0, 9.0)]);
+
+        // Ray leaves the difference through the carved surface, which faces back along the ray
+        let difference = Csg::difference(sphere(0.0), sphere(1.0));
+        let hit = difference.hit(&ray, 4.5, f64::MAX).unwrap();
+        assert_eq!(hit.t, 5.0);
+        assert!(!hit.front_face);
+        assert_eq!(hit.normal, Vec3::new(-1.0, 0.0, 0.0));
+
+        // Box with pockets carved into both sides, the ray starts inside the material left between them
+        let material = Arc::new(Lambertian::new(Vec3::new(0.5, 0.5, 0.5)));
+        let block = Volume { min: Vec3::new(-2.0, -2.0, -2.0), max: Vec3::new(2.0, 2.0, 2.0), material };
+        let drilled = Csg::difference(block, Csg::union(sphere(-1.5), sphere(1.5)));
+        let ray = Ray { origin: Vec3::new(0.0, 0.0, 0.0), ..ray };
+        assert_eq!(spans(&drilled, &ray), [(-0.5, 0.5)]);
+        let hit = drilled.hit(&ray, 0.001, f64::MAX).unwrap();
+        assert!(hit.t == 0.5 && !hit.front_face);
+    }
+
+    #[test]
+    fn test_quadric_solids() {
+        let material = Arc::new(Lambertian::new(Vec3::new(0.5, 0.5, 0.5)));
+        // Capped cylinder along the z axis covers [-0.5, 0.5] along x and [-2, 2] along z
+        let placement = Placement::new(Vec3::new(0.0, 0.0, -2.0), Vec3::new(0.0, 0.0, 1.0));
+        let cylinder = || Cylinder { capped: true, ..Cylinder::new(placement, 0.5, 4.0, material.clone()) };
+        let ray = Ray { origin: Vec3::new(-5.0, 0.0, 0.0), direction: Vec3::new(1.0, 0.0, 0.0), time: 0.0, wavelength: None, differentials: None };
+        let drilled = Csg::difference(sphere(0.0), cylinder());
+        assert_spans(&drilled, &ray, &[(4.0, 4.5), (5.5, 6.0)]);
+        let hit = drilled.hit(&ray, 4.2, f64::MAX).unwrap();
+        assert!((hit.t - 4.5).abs() < 1e-9 && !hit.front_face);
+        assert!((hit.normal - Vec3::new(-1.0, 0.0, 0.0)).length() < 1e-9);
+
+        // Along the axis the caps bound the cylinder
+        let along = Ray { origin: Vec3::new(0.0, 0.0, -5.0), direction: Vec3::new(0.0, 0.0, 1.0), ..ray };
+        assert_spans(&Csg::intersection(cylinder(), sphere(0.0)), &along, &[(4.0, 6.0)]);
+        assert_spans(&Csg::difference(sphere(0.0), cylinder()), &along, &[]);
+        assert!(Csg::difference(sphere(0.0), cylinder()).hit(&along, 0.0, f64::MAX).is_none());
+
+        // Open and partial cylinders have no inside
+        let open = Cylinder::new(placement, 0.5, 4.0, material.clone());
+        assert_spans(&Csg::difference(sphere(0.0), open), &ray, &[(4.0, 6.0)]);
+        let half = Cylinder { phi_max: PI, ..cylinder() };
+        assert_spans(&Csg::difference(sphere(0.0), half), &ray, &[(4.0, 6.0)]);
+
+        // Ring around the z axis with its tube crossed at x = -2.5, -1.5, 1.5 and 2.5
+        let torus = || Torus::new(Placement::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 1.0)), 2.0, 0.5, material.clone());
+        assert_spans(&Csg::union(torus(), sphere(0.0)), &ray, &[(2.5, 3.5), (4.0, 6.0), (6.5, 7.5)]);
+        assert_spans(&Csg::difference(torus(), sphere(2.0)), &ray, &[(2.5, 3.5)]);
+        let quarter = Torus { phi_max: 0.5 * PI, ..torus() };
+        assert_spans(&Csg::union(quarter, sphere(0.0)), &ray, &[(4.0, 6.0)]);
+    }
+}
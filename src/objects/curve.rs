@@ -0,0 +1,255 @@
+use std::sync::Arc;
+
+use crate::frame::ensure_valid_reflection;
+use crate::material::{Material, MaterialTable};
+use crate::objects::{Aabb, Hit, Hittable};
+use crate::scene::{Node, Value};
+use crate::{Frame, Ray, Vec3};
+
+/// Cross section of a [Curve].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum CurveShape {
+    /// Strip that always faces the ray, the cheapest choice for thin hair and fur
+    Flat,
+    /// Strip facing the ray whose shading normal bends across the width like on a tube
+    Cylinder,
+    /// Strip oriented by the normals at both ends, e.g. for blades of grass
+    Ribbon { normals: [Vec3; 2] },
+}
+
+/// Cubic Bézier curve with a width varying linearly from start to end, for hair, fur and grass.
+/// The texture coordinate u runs along the curve, v across its width. Rays are intersected by
+/// subdividing the curve until its segments are nearly straight (Nakamaru and Ohno 2002, as in
+/// pbrt-v3).
+pub struct Curve {
+    pub control_points: [Vec3; 4],
+    /// Width at the start and at the end
+    pub widths: (f64, f64),
+    pub shape: CurveShape,
+    pub material: Arc<dyn Material>,
+}
+
+impl Curve {
+    pub fn new(control_points: [Vec3; 4], widths: (f64, f64), shape: CurveShape, material: Arc<dyn Material>) -> Self {
+        Curve { control_points, widths, shape, material }
+    }
+
+    /// Returns smooth strand through `points` (a Catmull-Rom spline), one curve per pair of
+    /// neighbouring points. The width varies linearly over the whole strand.
+    pub fn strand(points: &[Vec3], widths: (f64, f64), shape: CurveShape, material: Arc<dyn Material>) -> Vec<Curve> {
+        let n = points.len();
+        let width = |i: usize| widths.0 + (widths.1 - widths.0) * i as f64 / (n - 1).max(1) as f64;
+        (0..n.saturating_sub(1)).map(|i| {
+            let (p0, p1, p2, p3) = (points[i.saturating_sub(1)], points[i], points[i + 1], points[(i + 2).min(n - 1)]);
+            let control_points = [p1, p1 + (p2 - p0) / 6.0, p2 - (p3 - p1) / 6.0, p2];
+            Curve::new(control_points, (width(i), width(i + 1)), shape, material.clone())
+        }).collect()
+    }
+
+    fn width(&self, u: f64) -> f64 {
+        self.widths.0 + u * (self.widths.1 - self.widths.0)
+    }
+
+    /// Returns closest hit with a distance in [`z_min`, `z_max`] along the ray, for control points
+    /// `cp` of the section [`u0`, `u1`] in ray space (ray starting at the origin along z).
+    fn subdivide(&self, cp: [Vec3; 4], (u0, u1): (f64, f64), ribbon_normals: Option<[Vec3; 2]>, depth: u32, z_min: f64, z_max: &mut f64) -> Option<CurveHit> {
+        let half_width = 0.5 * self.width(u0).max(self.width(u1));
+        let bounds = Aabb::from_points(cp);
+        if bounds.min.x > half_width || bounds.max.x < -half_width || bounds.min.y > half_width || bounds.max.y < -half_width
+            || bounds.min.z > *z_max + half_width || bounds.max.z < z_min - half_width {
+            return None;
+        }
+        if depth > 0 {
+            let split = split_bezier(cp);
+            let u_mid = 0.5 * (u0 + u1);
+            let first = self.subdivide([split[0], split[1], split[2], split[3]], (u0, u_mid), ribbon_normals, depth - 1, z_min, z_max);
+            let second = self.subdivide([split[3], split[4], split[5], split[6]], (u_mid, u1), ribbon_normals, depth - 1, z_min, z_max);
+            return second.or(first);
+        }
+
+        // The ray must pass between the planes perpendicular to the segment at both ends
+        if (cp[1].y - cp[0].y) * -cp[0].y + cp[0].x * (cp[0].x - cp[1].x) < 0.0
+            || (cp[2].y - cp[3].y) * -cp[3].y + cp[3].x * (cp[3].x - cp[2].x) < 0.0 {
+            return None;
+        }
+        // Parameter of the point on the line through the segment closest to the ray
+        let (dx, dy) = (cp[3].x - cp[0].x, cp[3].y - cp[0].y);
+        let denominator = dx * dx + dy * dy;
+        if denominator == 0.0 {
+            return None;
+        }
+        let w = (-cp[0].x * dx - cp[0].y * dy) / denominator;
+        let u = (u0 + w * (u1 - u0)).clamp(u0, u1);
+        let (pc, _) = eval_bezier(cp, w.clamp(0.0, 1.0));
+        let mut width = self.width(u);
+        if let Some([n0, n1]) = ribbon_normals {
+            // Ribbons seen at an angle look narrower
+            width *= slerp(u, n0, n1).z.abs();
+        }
+        if pc.x * pc.x + pc.y * pc.y > 0.25 * width * width || pc.z < z_min || pc.z > *z_max {
+            return None;
+        }
+        *z_max = pc.z;
+        Some(CurveHit { z: pc.z, u, offset: (-pc.x, -pc.y) })
+    }
+}
+
+/// Hit in ray space: distance along the ray, curve parameter and offset of the ray from the curve.
+struct CurveHit {
+    z: f64,
+    u: f64,
+    offset: (f64, f64),
+}
+
+impl Hittable for Curve {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Hit<'_>> {
+        let length = ray.direction.length();
+        let frame = Frame::from_normal(ray.direction / length);
+        let cp = self.control_points.map(|p| frame.to_local(p - ray.origin));
+        let ribbon_normals = match self.shape {
+            CurveShape::Ribbon { normals } => Some(normals.map(|n| frame.to_local(n.unit_vector()))),
+            _ => None,
+        };
+
+        // Subdivide until segments deviate from straight lines by a fraction of the width
+        let flatness = (0..2)
+            .map(|i| cp[i] - 2.0 * cp[i + 1] + cp[i + 2])
+            .fold(0.0_f64, |l, d| l.max(d.x.abs()).max(d.y.abs()).max(d.z.abs()));
+        let epsilon = 0.05 * self.widths.0.max(self.widths.1);
+        let depth = if flatness > 0.0 {
+            ((std::f64::consts::SQRT_2 * 6.0 * flatness / (8.0 * epsilon)).log2() / 2.0).clamp(0.0, 10.0) as u32
+        } else {
+            0
+        };
+        let mut z_max = t_max * length;
+        let curve_hit = self.subdivide(cp, (0.0, 1.0), ribbon_normals, depth, t_min * length, &mut z_max)?;
+
+        let (_, dpdu) = eval_bezier(self.control_points, curve_hit.u);
+        let dpdv = match self.shape {
+            CurveShape::Ribbon { normals: [n0, n1] } => {
+                let n = slerp(curve_hit.u, n0.unit_vector(), n1.unit_vector());
+                n.cross(dpdu).unit_vector() * self.width(curve_hit.u)
+            }
+            // Across the curve as seen along the ray
+            _ => {
+                let local = frame.to_local(dpdu);
+                frame.to_world(Vec3::new(-local.y, local.x, 0.0).unit_vector()) * self.width(curve_hit.u)
+            }
+        };
+        // Fraction of the projected width between the edge at v = 0 and the ray
+        let side = frame.to_local(dpdv);
+        let side_squared = side.x * side.x + side.y * side.y;
+        let v = (0.5 + (curve_hit.offset.0 * side.x + curve_hit.offset.1 * side.y) / side_squared).clamp(0.0, 1.0);
+
+        let outward_normal = dpdu.cross(dpdv).unit_vector();
+        let mut hit = Hit::new(ray, curve_hit.z / length, outward_normal, (curve_hit.u, v), (dpdu, dpdv), self.material.as_ref());
+        if self.shape == CurveShape::Cylinder {
+            // Normal of a tube whose silhouette is the strip
+            let sin = 2.0 * v - 1.0;
+            let n = (1.0 - sin * sin).max(0.0).sqrt() * hit.normal + sin * dpdv.unit_vector();
+            let n = ensure_valid_reflection(hit.normal, -ray.direction.unit_vector(), n);
+            hit.shading = Frame::from_normal_tangent(n, dpdu);
+        }
+        Some(hit)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let bounds = Aabb::from_points(self.control_points);
+        let half_width = 0.5 * self.widths.0.max(self.widths.1);
+        let margin = Vec3::new(half_width, half_width, half_width);
+        Some(Aabb::new(bounds.min - margin, bounds.max + margin))
+    }
+
+    fn describe(&self, materials: &mut MaterialTable) -> Option<Node> {
+        let mut node = Node::new("curve")
+            .with("control_points", self.control_points)
+            .with("widths", Value::Numbers(vec![self.widths.0, self.widths.1]));
+        node = match self.shape {
+            CurveShape::Flat => node.with("shape", "flat"),
+            CurveShape::Cylinder => node.with("shape", "cylinder"),
+            CurveShape::Ribbon { normals } => node.with("shape", "ribbon").with("normals", normals),
+        };
+        Some(node.with("material", materials.name(&self.material)?))
+    }
+}
+
+/// Returns point and derivative of the cubic Bézier curve at `u` (de Casteljau).
+fn eval_bezier(cp: [Vec3; 4], u: f64) -> (Vec3, Vec3) {
+    let lerp = |a: Vec3, b: Vec3| a + u * (b - a);
+    let (a, b, c) = (lerp(cp[0], cp[1]), lerp(cp[1], cp[2]), lerp(cp[2], cp[3]));
+    let (d, e) = (lerp(a, b), lerp(b, c));
+    // Degenerate control points leave the chord as direction
+    let derivative = if (e - d).length_squared() > 0.0 { 3.0 * (e - d) } else { cp[3] - cp[0] };
+    (lerp(d, e), derivative)
+}
+
+/// Splits the cubic Bézier curve at its middle, the halves share the fourth point.
+fn split_bezier(cp: [Vec3; 4]) -> [Vec3; 7] {
+    [
+        cp[0],
+        (cp[0] + cp[1]) / 2.0,
+        (cp[0] + 2.0 * cp[1] + cp[2]) / 4.0,
+        (cp[0] + 3.0 * cp[1] + 3.0 * cp[2] + cp[3]) / 8.0,
+        (cp[1] + 2.0 * cp[2] + cp[3]) / 4.0,
+        (cp[2] + cp[3]) / 2.0,
+        cp[3],
+    ]
+}
+
+/// Spherical interpolation between unit vectors.
+fn slerp(u: f64, a: Vec3, b: Vec3) -> Vec3 {
+    let angle = a.dot(b).clamp(-1.0, 1.0).acos();
+    if angle < 1.0e-6 {
+        return a;
+    }
+    ((((1.0 - u) * angle).sin() * a) + ((u * angle).sin() * b)) / angle.sin()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+
+    #[test]
+    fn test_curve() {
+        let material = Arc::new(Lambertian::new(Vec3::new(0.5, 0.5, 0.5)));
+        let points = [Vec3::new(-1.0, 0.0, 0.0), Vec3::new(-0.3, 0.0, 0.0), Vec3::new(0.3, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0)];
+        let ray = |x: f64, y: f64| Ray { origin: Vec3::new(x, y, 5.0), direction: Vec3::new(0.0, 0.0, -2.0), time: 0.0, wavelength: None, differentials: None };
+
+        let flat = Curve::new(points, (0.2, 0.2), CurveShape::Flat, material.clone());
+        let hit = flat.hit(&ray(0.0, 0.0), 0.0, f64::MAX).unwrap();
+        assert!((hit.t - 2.5).abs() < 1e-9 && (hit.u - 0.5).abs() < 1e-6 && (hit.v - 0.5).abs() < 1e-9);
+        assert_eq!(hit.normal, Vec3::new(0.0, 0.0, 1.0));
+        let (below, above) = (flat.hit(&ray(0.0, -0.05), 0.0, f64::MAX).unwrap(), flat.hit(&ray(0.0, 0.05), 0.0, f64::MAX).unwrap());
+        assert!((below.v - above.v).abs() - 0.5 < 1e-9 && (below.v + above.v - 1.0).abs() < 1e-9);
+        assert!(flat.hit(&ray(0.0, 0.15), 0.0, f64::MAX).is_none());
+        assert!(flat.hit(&ray(0.0, 0.0), 0.0, 2.0).is_none());
+
+        // Tapered tip
+        let tapered = Curve::new(points, (0.2, 0.0), CurveShape::Flat, material.clone());
+        assert!(tapered.hit(&ray(-0.9, 0.05), 0.0, f64::MAX).is_some());
+        assert!(tapered.hit(&ray(0.9, 0.05), 0.0, f64::MAX).is_none());
+
+        // Shading normal of a tube, tilted towards the edge
+        let cylinder = Curve::new(points, (0.2, 0.2), CurveShape::Cylinder, material.clone());
+        let hit = cylinder.hit(&ray(0.0, 0.05), 0.0, f64::MAX).unwrap();
+        assert!((hit.shading.n - Vec3::new(0.0, 0.5, 0.75_f64.sqrt())).length() < 1e-9);
+
+        // Ribbons vanish when seen edge-on
+        let facing = Curve::new(points, (0.2, 0.2), CurveShape::Ribbon { normals: [Vec3::new(0.0, 0.0, 1.0); 2] }, material.clone());
+        assert!(facing.hit(&ray(0.0, 0.05), 0.0, f64::MAX).is_some());
+        let edge_on = Curve::new(points, (0.2, 0.2), CurveShape::Ribbon { normals: [Vec3::new(0.0, 1.0, 0.0); 2] }, material.clone());
+        assert!(edge_on.hit(&ray(0.0, 0.05), 0.0, f64::MAX).is_none());
+
+        // Strand through an arc stays on it
+        let arc: Vec<Vec3> = (0..8).map(|i| {
+            let angle = i as f64 * 0.2;
+            Vec3::new(angle.cos(), angle.sin(), 0.0)
+        }).collect();
+        let strand = Curve::strand(&arc, (0.05, 0.01), CurveShape::Flat, material);
+        assert_eq!(strand.len(), 7);
+        let (middle, _) = eval_bezier(strand[3].control_points, 0.5);
+        assert!((middle.length() - 1.0).abs() < 1e-3);
+        assert!((strand[3].widths.0 - (0.05 - 0.04 * 3.0 / 7.0)).abs() < 1e-12);
+    }
+}
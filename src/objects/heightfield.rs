@@ -0,0 +1,285 @@
+use std::sync::Arc;
+
+use crate::frame::ensure_valid_reflection;
+use crate::material::{Material, MaterialTable};
+use crate::netpbm::Image;
+use crate::objects::triangle::intersect_triangle;
+use crate::objects::{Aabb, Hit, Hittable};
+use crate::scene::{Node, Value};
+use crate::{Frame, Ray, Vec3};
+
+/// Minimum and maximum height of each node of one quadtree level, the finest level has one node
+/// per grid cell.
+struct Level {
+    width: usize,
+    depth: usize,
+    ranges: Vec<(f64, f64)>,
+}
+
+/// Terrain over a regular grid of heights in the xz-plane, two triangles per grid cell with normals
+/// interpolated between the grid points. Rays descend a quadtree of height ranges (min-max mipmap)
+/// and only test cells whose range they pass through, so large grids stay cheap. The texture
+/// coordinates u and v run from 0 to 1 along x and z.
+pub struct Heightfield {
+    origin: Vec3,
+    size: Vec3,
+    /// Number of grid points along x and z
+    resolution: (usize, usize),
+    /// Heights relative to the origin, x varying fastest
+    heights: Vec<f64>,
+    normals: Vec<Vec3>,
+    /// Quadtree levels from single cells up to one node covering the whole grid
+    levels: Vec<Level>,
+    pub material: Arc<dyn Material>,
+}
+
+impl Heightfield {
+    /// Returns terrain with the grid corner of smallest x and z at `origin`, extending `size.x` and
+    /// `size.z` along these axes. `heights` holds `resolution.0` by `resolution.1` values in rows of
+    /// constant z, each scaled by `size.y`.
+    pub fn new(origin: Vec3, size: Vec3, resolution: (usize, usize), heights: Vec<f64>, material: Arc<dyn Material>) -> Self {
+        let heights = heights.into_iter().map(|h| h * size.y).collect();
+        Heightfield::from_scaled_heights(origin, size, resolution, heights, material)
+    }
+
+    /// Returns terrain like [Heightfield::new] whose `heights` are already scaled by `size.y`.
+    pub fn from_scaled_heights(origin: Vec3, size: Vec3, resolution: (usize, usize), heights: Vec<f64>, material: Arc<dyn Material>) -> Self {
+        let (nx, nz) = resolution;
+        assert!(nx >= 2 && nz >= 2, "heightfield needs at least 2 by 2 grid points");
+        assert_eq!(heights.len(), nx * nz, "number of heights does not match resolution");
+        let mut heightfield = Heightfield { origin, size, resolution, heights, normals: vec![], levels: vec![], material };
+        heightfield.normals = (0..nz).flat_map(|j| (0..nx).map(move |i| (i, j)))
+            .map(|(i, j)| heightfield.vertex_normal(i, j))
+            .collect();
+        heightfield.levels = heightfield.build_levels();
+        heightfield
+    }
+
+    /// Returns terrain from the gray values of `image`, columns run along x and rows along z. Height
+    /// maps in other formats can be read with [crate::texture::ImageTexture::load].
+    pub fn from_image(image: &Image, origin: Vec3, size: Vec3, material: Arc<dyn Material>) -> Self {
+        let heights = (0..image.height).flat_map(|y| (0..image.width).map(move |x| image.gray(x, y))).collect();
+        Heightfield::new(origin, size, (image.width, image.height), heights, material)
+    }
+
+    /// Returns terrain sampling `height(u, v)` with u and v from 0 to 1 along x and z, e.g. from
+    /// a noise function.
+    pub fn from_fn(origin: Vec3, size: Vec3, resolution: (usize, usize), height: impl Fn(f64, f64) -> f64, material: Arc<dyn Material>) -> Self {
+        let (nx, nz) = resolution;
+        let heights = (0..nz)
+            .flat_map(|j| (0..nx).map(move |i| (i as f64 / (nx - 1) as f64, j as f64 / (nz - 1) as f64)))
+            .map(|(u, v)| height(u, v))
+            .collect();
+        Heightfield::new(origin, size, resolution, heights, material)
+    }
+
+    fn cell_size(&self) -> (f64, f64) {
+        (self.size.x / (self.resolution.0 - 1) as f64, self.size.z / (self.resolution.1 - 1) as f64)
+    }
+
+    fn height(&self, i: usize, j: usize) -> f64 {
+        self.heights[j * self.resolution.0 + i]
+    }
+
+    fn vertex(&self, i: usize, j: usize) -> Vec3 {
+        let (dx, dz) = self.cell_size();
+        self.origin + Vec3::new(i as f64 * dx, self.height(i, j), j as f64 * dz)
+    }
+
+    /// Normal from central differences, one-sided at the border.
+    fn vertex_normal(&self, i: usize, j: usize) -> Vec3 {
+        let (nx, nz) = self.resolution;
+        let (dx, dz) = self.cell_size();
+        let (i0, i1) = (i.saturating_sub(1), (i + 1).min(nx - 1));
+        let (j0, j1) = (j.saturating_sub(1), (j + 1).min(nz - 1));
+        let dh_dx = (self.height(i1, j) - self.height(i0, j)) / ((i1 - i0) as f64 * dx);
+        let dh_dz = (self.height(i, j1) - self.height(i, j0)) / ((j1 - j0) as f64 * dz);
+        Vec3::new(-dh_dx, 1.0, -dh_dz).unit_vector()
+    }
+
+    fn build_levels(&self) -> Vec<Level> {
+        let (width, depth) = (self.resolution.0 - 1, self.resolution.1 - 1);
+        let ranges = (0..depth).flat_map(|j| (0..width).map(move |i| (i, j)))
+            .map(|(i, j)| {
+                let corners = [self.height(i, j), self.height(i + 1, j), self.height(i, j + 1), self.height(i + 1, j + 1)];
+                corners.iter().fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), &h| (lo.min(h), hi.max(h)))
+            })
+            .collect();
+        let mut levels = vec![Level { width, depth, ranges }];
+        while let Some(finer) = levels.last().filter(|l| l.width > 1 || l.depth > 1) {
+            let (width, depth) = (finer.width.div_ceil(2), finer.depth.div_ceil(2));
+            let ranges = (0..depth).flat_map(|j| (0..width).map(move |i| (i, j)))
+                .map(|(i, j)| {
+                    let children = (2 * j..(2 * j + 2).min(finer.depth))
+                        .flat_map(|cj| (2 * i..(2 * i + 2).min(finer.width)).map(move |ci| (ci, cj)));
+                    children.map(|(ci, cj)| finer.ranges[cj * finer.width + ci])
+                        .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), (l, h)| (lo.min(l), hi.max(h)))
+                })
+                .collect();
+            levels.push(Level { width, depth, ranges });
+        }
+        levels
+    }
+
+    /// Returns box of node (`i`, `j`) on `level`, slightly enlarged so that rays touching a cell's
+    /// edge still reach its triangles.
+    fn node_bounds(&self, level: usize, i: usize, j: usize) -> Aabb {
+        let (dx, dz) = self.cell_size();
+        let span = 1 << level;
+        let (width, depth) = (self.levels[0].width, self.levels[0].depth);
+        let (lo, hi) = self.levels[level].ranges[j * self.levels[level].width + i];
+        let margin = 1.0e-9 * (self.size.x.abs() + self.size.y.abs() + self.size.z.abs()) * Vec3::new(1.0, 1.0, 1.0);
+        let min = Vec3::new((i * span) as f64 * dx, lo, (j * span) as f64 * dz);
+        let max = Vec3::new(((i + 1) * span).min(width) as f64 * dx, hi, ((j + 1) * span).min(depth) as f64 * dz);
+        Aabb::new(self.origin + min - margin, self.origin + max + margin)
+    }
+
+    /// Returns closest triangle hit below node (`i`, `j`) on `level` as distance, cell and
+    /// barycentric coordinates, shrinking `t_max` to it.
+    fn descend(&self, ray: &Ray, level: usize, (i, j): (usize, usize), t_min: f64, t_max: &mut f64) -> Option<CellHit> {
+        self.node_bounds(level, i, j).clip(ray, t_min, *t_max)?;
+        if level == 0 {
+            return self.cell_hit(ray, i, j, t_min, t_max);
+        }
+        // Children nearer along the ray first, so farther ones are mostly clipped away
+        let finer = &self.levels[level - 1];
+        let xs = if ray.direction.x >= 0.0 { [0, 1] } else { [1, 0] };
+        let zs = if ray.direction.z >= 0.0 { [0, 1] } else { [1, 0] };
+        let mut closest = None;
+        for cj in zs.map(|d| 2 * j + d).into_iter().filter(|&cj| cj < finer.depth) {
+            for ci in xs.map(|d| 2 * i + d).into_iter().filter(|&ci| ci < finer.width) {
+                closest = self.descend(ray, level - 1, (ci, cj), t_min, t_max).or(closest);
+            }
+        }
+        closest
+    }
+
+    fn cell_hit(&self, ray: &Ray, i: usize, j: usize, t_min: f64, t_max: &mut f64) -> Option<CellHit> {
+        let mut closest = None;
+        for upper in [false, true] {
+            if let Some((t, b1, b2)) = intersect_triangle(ray, self.triangle(i, j, upper), t_min, *t_max) {
+                *t_max = t;
+                closest = Some(CellHit { t, cell: (i, j), upper, b1, b2 });
+            }
+        }
+        closest
+    }
+
+    /// Returns vertices of one of the two triangles splitting cell (`i`, `j`) along its diagonal,
+    /// the lower one touches the cell's edge of smaller z. Both wind counterclockwise seen from above.
+    fn triangle_indices(i: usize, j: usize, upper: bool) -> [(usize, usize); 3] {
+        if upper {
+            [(i, j), (i, j + 1), (i + 1, j + 1)]
+        } else {
+            [(i, j), (i + 1, j + 1), (i + 1, j)]
+        }
+    }
+
+    fn triangle(&self, i: usize, j: usize, upper: bool) -> [Vec3; 3] {
+        Heightfield::triangle_indices(i, j, upper).map(|(i, j)| self.vertex(i, j))
+    }
+
+    fn surface_hit(&self, ray: &Ray, hit: CellHit) -> Hit<'_> {
+        let CellHit { t, cell: (i, j), upper, b1, b2 } = hit;
+        let (nx, nz) = self.resolution;
+        let [p0, p1, p2] = self.triangle(i, j, upper);
+        // Position within the cell and derivatives along the cell's sides
+        let ((fu, fv), dp_dfu, dp_dfv) = if upper {
+            ((b2, b1 + b2), p2 - p1, p1 - p0)
+        } else {
+            ((b1 + b2, b1), p2 - p0, p1 - p2)
+        };
+        let uv = ((i as f64 + fu) / (nx - 1) as f64, (j as f64 + fv) / (nz - 1) as f64);
+        let derivatives = ((nx - 1) as f64 * dp_dfu, (nz - 1) as f64 * dp_dfv);
+        let outward_normal = (p1 - p0).cross(p2 - p0).unit_vector();
+        let mut hit = Hit::new(ray, t, outward_normal, uv, derivatives, self.material.as_ref());
+
+        let [n0, n1, n2] = Heightfield::triangle_indices(i, j, upper).map(|(i, j)| self.normals[j * nx + i]);
+        let n = ((1.0 - b1 - b2) * n0 + b1 * n1 + b2 * n2).unit_vector();
+        let n = if hit.front_face { n } else { -n };
+        let n = ensure_valid_reflection(hit.normal, -ray.direction.unit_vector(), n);
+        hit.shading = Frame::from_normal_tangent(n, derivatives.0);
+        hit
+    }
+}
+
+struct CellHit {
+    t: f64,
+    cell: (usize, usize),
+    upper: bool,
+    b1: f64,
+    b2: f64,
+}
+
+impl Hittable for Heightfield {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Hit<'_>> {
+        let mut t_max = t_max;
+        let hit = self.descend(ray, self.levels.len() - 1, (0, 0), t_min, &mut t_max)?;
+        Some(self.surface_hit(ray, hit))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let top = self.levels.len() - 1;
+        Some(self.node_bounds(top, 0, 0))
+    }
+
+    fn describe(&self, materials: &mut MaterialTable) -> Option<Node> {
+        Some(Node::new("heightfield")
+            .with("origin", self.origin)
+            .with("size", self.size)
+            .with("resolution", Value::Numbers(vec![self.resolution.0 as f64, self.resolution.1 as f64]))
+            .with("heights", Value::Numbers(self.heights.clone()))
+            .with("material", materials.name(&self.material)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+    use crate::noise::{fbm, Noise};
+    use crate::objects::{Hittables, Triangle};
+
+    #[test]
+    fn test_heightfield() {
+        let material = Arc::new(Lambertian::new(Vec3::new(0.5, 0.5, 0.5)));
+        let origin = Vec3::new(-2.0, -1.0, -3.0);
+        let size = Vec3::new(4.0, 2.0, 6.0);
+        let noise = Noise::new(7);
+        let terrain = Heightfield::from_fn(origin, size, (37, 23), |u, v| fbm(|p| noise.perlin(p), Vec3::new(4.0 * u, 0.0, 4.0 * v), 4), material.clone());
+
+        // Same triangles without the quadtree
+        let mut triangles: Vec<Box<dyn Hittable>> = vec![];
+        for j in 0..22 {
+            for i in 0..36 {
+                for upper in [false, true] {
+                    let [p0, p1, p2] = terrain.triangle(i, j, upper);
+                    triangles.push(Box::new(Triangle::new(p0, p1, p2, material.clone())));
+                }
+            }
+        }
+        let triangles = Hittables { hittables: triangles };
+        for k in 0..200 {
+            let k = k as f64;
+            let origin = Vec3::new(3.0 * (0.7 * k).sin(), 2.0 + (0.3 * k).cos(), 4.0 * (1.3 * k).cos());
+            let target = Vec3::new(2.0 * (1.1 * k).cos(), -1.0, 3.0 * (0.9 * k).sin());
+            let ray = Ray { origin, direction: target - origin, time: 0.0, wavelength: None, differentials: None };
+            let (expected, hit) = (triangles.hit(&ray, 0.0, f64::MAX), terrain.hit(&ray, 0.0, f64::MAX));
+            assert_eq!(expected.is_some(), hit.is_some());
+            if let (Some(expected), Some(hit)) = (expected, hit) {
+                assert!((expected.t - hit.t).abs() < 1e-9 && (expected.normal - hit.normal).length() < 1e-9);
+                assert!(hit.shading.n.dot(hit.normal) > 0.0);
+            }
+        }
+
+        // Planar ramp rising along x: exact heights, normals and texture coordinates
+        let ramp = Heightfield::from_fn(origin, size, (5, 4), |u, _| u, material);
+        let ray = Ray { origin: Vec3::new(1.0, 5.0, 0.5), direction: Vec3::new(0.0, -1.0, 0.0), time: 0.0, wavelength: None, differentials: None };
+        let hit = ramp.hit(&ray, 0.0, f64::MAX).unwrap();
+        assert!((hit.point.y - 0.5).abs() < 1e-9 && (hit.u - 0.75).abs() < 1e-9 && (hit.v - 0.5833333333333334).abs() < 1e-9);
+        assert!((hit.shading.n - Vec3::new(-1.0, 2.0, 0.0).unit_vector()).length() < 1e-9);
+        assert!((hit.dpdu - Vec3::new(4.0, 2.0, 0.0)).length() < 1e-9 && (hit.dpdv - Vec3::new(0.0, 0.0, 6.0)).length() < 1e-9);
+        let bounds = ramp.bounding_box().unwrap();
+        assert!((bounds.min - Vec3::new(-2.0, -1.0, -3.0)).length() < 1e-6 && (bounds.max - Vec3::new(2.0, 1.0, 3.0)).length() < 1e-6);
+    }
+}
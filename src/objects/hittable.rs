@@ -1,14 +1,157 @@
-use crate::material::Material;
-use crate::{Ray, Vec3};
+use crate::material::{Material, MaterialTable};
+use crate::objects::Aabb;
+use crate::scene::Node;
+use crate::{Frame, Ray, Vec3};
 
+#[derive(Copy, Clone)]
 pub struct Hit<'a> {
     pub point: Vec3,
+    /// Hit point relative to the object, moves along with it for solid textures
+    pub object_point: Vec3,
+    /// Geometric normal, facing against the incoming ray
     pub normal: Vec3,
+    /// Tangent, bitangent and (possibly perturbed) normal used for shading, the normal lies on
+    /// the same side as the geometric one
+    pub shading: Frame,
+    /// Surface coordinates for texture lookups
+    pub u: f64,
+    pub v: f64,
+    /// Partial derivatives of the point with respect to the surface coordinates
+    pub dpdu: Vec3,
+    pub dpdv: Vec3,
+    /// Change of the surface coordinates across a pixel, see [Hit::compute_footprint]
+    pub footprint: Footprint,
+    /// Color attribute of the surface (e.g. of a point cloud or mesh vertices), read by
+    /// [crate::texture::VertexColor]
+    pub color: Option<Vec3>,
     pub t: f64,
     pub front_face: bool,
-    pub material: &'a Material,
+    /// IOR of the medium outside the surface, set by the integrator for nested dielectrics
+    pub exterior_ior: f64,
+    pub material: &'a dyn Material,
+}
+
+/// Change of the texture coordinates u and v between neighbouring pixels in x and y, all zero
+/// if unknown (e.g. after diffuse bounces).
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Footprint {
+    pub dudx: f64,
+    pub dvdx: f64,
+    pub dudy: f64,
+    pub dvdy: f64,
+}
+
+impl<'a> Hit<'a> {
+    /// Returns hit of `ray` at distance `t` on a surface with unit `outward_normal`, whose
+    /// geometric normal is flipped to face the ray. The shading frame follows dp/du and the
+    /// object point equals the world point.
+    pub fn new(ray: &Ray, t: f64, outward_normal: Vec3, (u, v): (f64, f64), (dpdu, dpdv): (Vec3, Vec3), material: &'a dyn Material) -> Self {
+        let point = ray.at(t);
+        let front_face = ray.direction.dot(outward_normal) < 0.0;
+        let normal = if front_face { outward_normal } else { -outward_normal };
+        Hit {
+            point,
+            object_point: point,
+            normal,
+            shading: Frame::from_normal_tangent(normal, dpdu),
+            u,
+            v,
+            dpdu,
+            dpdv,
+            footprint: Footprint::default(),
+            color: None,
+            t,
+            front_face,
+            exterior_ior: 1.0,
+            material,
+        }
+    }
+
+    /// Estimates the footprint from the differentials of `ray` by intersecting them with the
+    /// tangent plane (Igehy 1999) and projecting the offsets onto `dpdu` and `dpdv`.
+    pub fn compute_footprint(&mut self, ray: &Ray) {
+        self.footprint = Footprint::default();
+        let Some(d) = ray.differentials else { return };
+        let n = self.normal;
+        let offset = |origin: Vec3, direction: Vec3| {
+            let t = (n.dot(self.point) - n.dot(origin)) / n.dot(direction);
+            origin + t * direction - self.point
+        };
+        let (dpdx, dpdy) = (offset(d.rx_origin, d.rx_direction), offset(d.ry_origin, d.ry_direction));
+
+        // Least squares solution of dpdu * du + dpdv * dv = dp
+        let (a00, a01, a11) = (self.dpdu.dot(self.dpdu), self.dpdu.dot(self.dpdv), self.dpdv.dot(self.dpdv));
+        let inverse_determinant = 1.0 / (a00 * a11 - a01 * a01);
+        let solve = |dp: Vec3| {
+            let (b0, b1) = (self.dpdu.dot(dp), self.dpdv.dot(dp));
+            let du = (a11 * b0 - a01 * b1) * inverse_determinant;
+            let dv = (a00 * b1 - a01 * b0) * inverse_determinant;
+            if du.is_finite() && dv.is_finite() { (du.clamp(-1.0e8, 1.0e8), dv.clamp(-1.0e8, 1.0e8)) } else { (0.0, 0.0) }
+        };
+        let ((dudx, dvdx), (dudy, dvdy)) = (solve(dpdx), solve(dpdy));
+        self.footprint = Footprint { dudx, dvdx, dudy, dvdy };
+    }
 }
 
 pub trait Hittable {
-    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Hit>;
-}
\ No newline at end of file
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Hit<'_>>;
+
+    /// Returns box containing the object at all times, `None` if it is unbounded.
+    fn bounding_box(&self) -> Option<Aabb> {
+        None
+    }
+
+    /// Returns true if anything opaque lies between `t_min` and `t_max`, e.g. for shadow rays.
+    fn occluded(&self, ray: &Ray, t_min: f64, t_max: f64) -> bool {
+        hit_opaque(self, ray, t_min, t_max).is_some()
+    }
+
+    /// Returns description for saving scenes (see [crate::configs::save_scene]) with materials
+    /// referring to `materials`, `None` if the object or its material can't be saved.
+    fn describe(&self, _materials: &mut MaterialTable) -> Option<Node> {
+        None
+    }
+}
+
+/// Section of a ray inside a solid, from the surface where the ray enters to where it leaves.
+#[derive(Copy, Clone)]
+pub struct Interval<'a> {
+    pub enter: Hit<'a>,
+    pub exit: Hit<'a>,
+}
+
+/// Closed shape with a well-defined inside, as needed for constructive solid geometry.
+pub trait Solid: Hittable {
+    /// Returns all intervals inside the solid along the entire line of `ray` (also at negative t),
+    /// disjoint and sorted by distance.
+    fn intervals(&self, ray: &Ray) -> Vec<Interval<'_>>;
+}
+
+/// Returns closest hit that is not cut out by the opacity of its material. Each hit passes with
+/// probability 1 - opacity, decided by hashing the ray so that repeated queries of the same ray
+/// (e.g. shadow rays) agree.
+pub fn hit_opaque<'a, H: Hittable + ?Sized>(hittable: &'a H, ray: &Ray, t_min: f64, t_max: f64) -> Option<Hit<'a>> {
+    let mut t_min = t_min;
+    while let Some(hit) = hittable.hit(ray, t_min, t_max) {
+        let opacity = hit.material.opacity(&hit);
+        if opacity >= 1.0 || (opacity > 0.0 && hash_ray(ray, hit.t) < opacity) {
+            return Some(hit);
+        }
+        // Continue behind the rejected surface
+        t_min = hit.t + 1.0e-9 * hit.t.abs().max(1.0);
+    }
+    None
+}
+
+/// Hashes ray and distance to a number in [0, 1).
+fn hash_ray(ray: &Ray, t: f64) -> f64 {
+    let values = [ray.origin.x, ray.origin.y, ray.origin.z, ray.direction.x, ray.direction.y, ray.direction.z, t];
+    let mut h: u64 = 0x9e3779b97f4a7c15;
+    for v in values {
+        // SplitMix64 finalizer
+        h = (h ^ v.to_bits()).wrapping_mul(0xbf58476d1ce4e5b9);
+        h = (h ^ (h >> 27)).wrapping_mul(0x94d049bb133111eb);
+        h ^= h >> 31;
+    }
+    (h >> 11) as f64 / (1u64 << 53) as f64
+}
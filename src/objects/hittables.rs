@@ -1,21 +1,91 @@
-use crate::objects::{Hit, Hittable};
+use crate::material::MaterialTable;
+use crate::objects::{Aabb, Hit, hit_opaque, Hittable};
 use crate::Ray;
+use crate::scene::Node;
 
 pub struct Hittables {
     pub hittables: Vec<Box<dyn Hittable>>,
 }
 
 impl Hittable for Hittables {
-    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Hit> {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Hit<'_>> {
         let mut hit: Option<Hit> = None;
         let mut closest = t_max;
 
         for hittable in self.hittables.iter() {
-            if let Some(h) = hittable.hit(ray, t_min, closest) {
+            // Surfaces cut out by alpha masks let the ray pass
+            if let Some(h) = hit_opaque(hittable.as_ref(), ray, t_min, closest) {
                 closest = h.t;
                 hit = Some(h);
             }
         }
         hit
     }
-}
\ No newline at end of file
+
+    fn occluded(&self, ray: &Ray, t_min: f64, t_max: f64) -> bool {
+        self.hittables.iter().any(|hittable| hittable.occluded(ray, t_min, t_max))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let mut boxes = self.hittables.iter().map(|hittable| hittable.bounding_box());
+        let first = boxes.next()??;
+        boxes.try_fold(first, |b, other| Some(b.surrounding(other?)))
+    }
+
+    fn describe(&self, materials: &mut MaterialTable) -> Option<Node> {
+        let objects = self.hittables.iter().map(|hittable| hittable.describe(materials)).collect::<Option<Vec<_>>>()?;
+        Some(Node::new("list").with("objects", objects))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::material::{AlphaMask, Lambertian, Material};
+    use crate::objects::{Quad, Sphere, Triangle};
+    use crate::texture::{ImageTexture, SolidColor};
+    use crate::netpbm::Image;
+    use crate::Vec3;
+
+    fn masked(alpha: f64) -> Arc<dyn Material> {
+        let material = Arc::new(Lambertian::new(Vec3::new(0.5, 0.5, 0.5)));
+        Arc::new(AlphaMask { material, alpha: Arc::new(SolidColor::gray(alpha)) })
+    }
+
+    fn ray(x: f64, y: f64) -> Ray {
+        Ray { origin: Vec3::new(x, y, 5.0), direction: Vec3::new(0.0, 0.0, -1.0), time: 0.0, wavelength: None, differentials: None }
+    }
+
+    #[test]
+    fn test_alpha_mask() {
+        // Left half of the quad is transparent, the right half opaque
+        let image = Image { width: 2, height: 1, channels: 1, data: vec![0.0, 1.0] };
+        let material = Arc::new(AlphaMask {
+            material: Arc::new(Lambertian::new(Vec3::new(0.5, 0.5, 0.5))),
+            alpha: Arc::new(ImageTexture::new(image)),
+        });
+        let quad = Quad { q: Vec3::new(-1.0, -1.0, 1.0), u: Vec3::new(2.0, 0.0, 0.0), v: Vec3::new(0.0, 2.0, 0.0), material };
+        let sphere = Sphere { center: Vec3::new(0.0, 0.0, -2.0), radius: 1.5, material: masked(1.0) };
+        let world = Hittables { hittables: vec![Box::new(quad), Box::new(sphere)] };
+
+        // Rays through the texel centers
+        assert!((world.hit(&ray(0.5, 0.0), 0.001, f64::MAX).unwrap().t - 4.0).abs() < 1e-9);
+        assert!(world.hit(&ray(-0.5, 0.0), 0.001, f64::MAX).unwrap().t > 5.0);
+        assert!(world.occluded(&ray(0.5, 0.0), 0.001, 4.5));
+        assert!(!world.occluded(&ray(-0.5, 0.0), 0.001, 4.5));
+    }
+
+    #[test]
+    fn test_stochastic_opacity() {
+        let triangle = Triangle::new(Vec3::new(-1.0, -1.0, 0.0), Vec3::new(1.0, -1.0, 0.0), Vec3::new(-1.0, 1.0, 0.0), masked(0.3));
+        let world = Hittables { hittables: vec![Box::new(triangle)] };
+        let n = 10_000;
+        let hits = (0..n).filter(|i| {
+            let ray = ray(-0.9 + 0.9 * (*i as f64 / n as f64), -0.5);
+            world.hit(&ray, 0.001, f64::MAX).is_some()
+        }).count();
+        assert!((hits as f64 / n as f64 - 0.3).abs() < 0.03, "{}", hits);
+    }
+}
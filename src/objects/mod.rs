@@ -1,9 +1,35 @@
+pub use aabb::*;
+pub use bvh::*;
+pub use csg::*;
+pub use curve::*;
+pub use heightfield::*;
 pub use hittable::*;
 pub use hittables::*;
 pub use moving_sphere::*;
+pub use point_cloud::*;
+pub use quad::*;
+pub use quadric::*;
+pub use sdf::*;
 pub use sphere::*;
+pub use torus::*;
+pub use triangle::*;
+pub use volume::*;
+pub use voxels::*;
 
+mod aabb;
 mod hittable;
 mod sphere;
 mod moving_sphere;
-mod hittables;
\ No newline at end of file
+mod hittables;
+mod triangle;
+mod quad;
+mod volume;
+mod csg;
+mod sdf;
+mod quadric;
+mod torus;
+mod heightfield;
+mod curve;
+mod bvh;
+mod voxels;
+mod point_cloud;
@@ -1,5 +1,10 @@
+use std::sync::Arc;
+
 use crate::{Material, Ray, Vec3};
-use crate::objects::{Hit, Hittable};
+use crate::material::MaterialTable;
+use crate::objects::{Aabb, Hit, Hittable, Interval, Solid};
+use crate::objects::sphere::{sphere_hit, sphere_roots};
+use crate::scene::Node;
 
 pub struct MovingSphere {
     pub center0: Vec3,
@@ -7,7 +12,7 @@ pub struct MovingSphere {
     pub time0: f64,
     pub time1: f64,
     pub radius: f64,
-    pub material: Material,
+    pub material: Arc<dyn Material>,
 }
 
 impl MovingSphere {
@@ -18,34 +23,40 @@ impl MovingSphere {
 }
 
 impl Hittable for MovingSphere {
-    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Hit> {
-        let oc = ray.origin - self.center(ray.time);
-        let a = ray.direction.length_squared();
-        let half_b = oc.dot(ray.direction);
-        let c = oc.length_squared() - self.radius * self.radius;
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Hit<'_>> {
+        // Center position of sphere depends on time
+        let center = self.center(ray.time);
+        let (t0, t1) = sphere_roots(center, self.radius, ray)?;
+        // Find nearest root that lies in acceptable range.
+        let t = [t0, t1].into_iter().find(|t| (t_min..=t_max).contains(t))?;
+        Some(sphere_hit(center, self.radius, self.material.as_ref(), ray, t))
+    }
 
-        let discriminant = half_b * half_b - a * c;
-        if discriminant < 0.0 {
-            return None;
-        }
-        let sqrtd = discriminant.sqrt();
+    fn bounding_box(&self) -> Option<Aabb> {
+        let r = Vec3::new(self.radius, self.radius, self.radius);
+        Some(Aabb::new(self.center0 - r, self.center0 + r).surrounding(Aabb::new(self.center1 - r, self.center1 + r)))
+    }
 
-        // Find nearest root that lies in acceptable range.
-        let mut root = (-half_b - sqrtd) / a;
-        if root < t_min || t_max < root {
-            root = (-half_b + sqrtd) / a;
-            if root < t_min || t_max < root {
-                return None;
+    fn describe(&self, materials: &mut MaterialTable) -> Option<Node> {
+        Some(Node::new("moving_sphere")
+            .with("center0", self.center0)
+            .with("center1", self.center1)
+            .with("time0", self.time0)
+            .with("time1", self.time1)
+            .with("radius", self.radius)
+            .with("material", materials.name(&self.material)?))
+    }
+}
+
+impl Solid for MovingSphere {
+    fn intervals(&self, ray: &Ray) -> Vec<Interval<'_>> {
+        let center = self.center(ray.time);
+        match sphere_roots(center, self.radius, ray) {
+            Some((t0, t1)) if t0 < t1 => {
+                let hit = |t: f64| sphere_hit(center, self.radius, self.material.as_ref(), ray, t);
+                vec![Interval { enter: hit(t0), exit: hit(t1) }]
             }
+            _ => vec![],
         }
-
-        let point = ray.at(root);
-        // Center position of sphere depends on time
-        let outward_normal = (point - self.center(ray.time)) / self.radius;
-        let t = root;
-        let front_face = ray.direction.dot(outward_normal) < 0.0;
-        let normal = if front_face { outward_normal } else { -outward_normal };
-
-        Some(Hit { point, normal, t, front_face, material: &self.material })
     }
-}
\ No newline at end of file
+}
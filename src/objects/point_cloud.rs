@@ -0,0 +1,217 @@
+use std::sync::Arc;
+
+use crate::material::{Material, MaterialTable};
+use crate::objects::bvh::Tree;
+use crate::objects::sphere::{sphere_hit, sphere_roots};
+use crate::objects::{Aabb, Hit, Hittable};
+use crate::ply::Ply;
+use crate::scene::{Node, Value};
+use crate::{Frame, Ray, Vec3};
+
+/// Shape each point of a [PointCloud] is drawn as.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Splat {
+    /// Disk perpendicular to the point's normal, facing the ray for points without normal
+    Disk,
+    Sphere,
+}
+
+/// Many small disks or spheres with a radius and optional normal and color per point, e.g. from
+/// a lidar scan. Points are stored in single precision relative to the center of their bounds, in
+/// the leaf order of an internal bounding volume hierarchy. Colors are passed on as [Hit::color], combine the material with a
+/// [crate::texture::VertexColor] texture to show them.
+pub struct PointCloud {
+    /// Center of the bounds, which keeps far away clouds precise
+    center: Vec3,
+    /// Relative to `center`
+    positions: Vec<[f32; 3]>,
+    radii: Vec<f32>,
+    /// Empty if the points have no normals
+    normals: Vec<[f32; 3]>,
+    /// Empty if the points have no colors
+    colors: Vec<[f32; 3]>,
+    tree: Tree,
+    pub splat: Splat,
+    pub material: Arc<dyn Material>,
+}
+
+fn pack(v: Vec3) -> [f32; 3] {
+    [v.x as f32, v.y as f32, v.z as f32]
+}
+
+fn unpack(v: [f32; 3]) -> Vec3 {
+    Vec3::new(v[0] as f64, v[1] as f64, v[2] as f64)
+}
+
+impl PointCloud {
+    /// Returns cloud of points with the given `radii`, `normals` and `colors` (either empty or
+    /// one per point).
+    pub fn new(positions: &[Vec3], radii: &[f64], normals: &[Vec3], colors: &[Vec3], splat: Splat, material: Arc<dyn Material>) -> Self {
+        let center = positions.iter().map(|&p| Aabb::new(p, p)).reduce(Aabb::surrounding)
+            .map_or(Vec3::new(0.0, 0.0, 0.0), |bounds| 0.5 * (bounds.min + bounds.max));
+        let offsets: Vec<Vec3> = positions.iter().map(|&p| p - center).collect();
+        PointCloud::around(center, &offsets, radii, normals, colors, splat, material)
+    }
+
+    /// Returns cloud like [PointCloud::new] whose `offsets` are the positions relative to `center`.
+    pub fn around(center: Vec3, offsets: &[Vec3], radii: &[f64], normals: &[Vec3], colors: &[Vec3], splat: Splat, material: Arc<dyn Material>) -> Self {
+        let n = offsets.len();
+        assert!(radii.len() == n, "number of radii does not match number of points");
+        assert!(normals.is_empty() || normals.len() == n, "number of normals does not match number of points");
+        assert!(colors.is_empty() || colors.len() == n, "number of colors does not match number of points");
+        // Boxes enclose the stored points, which the rays are tested against
+        let stored: Vec<([f32; 3], f32)> = (0..n).map(|i| (pack(offsets[i]), radii[i] as f32)).collect();
+        let mut items: Vec<(Aabb, usize)> = stored.iter().enumerate()
+            .map(|(i, &(position, radius))| {
+                let (p, r) = (center + unpack(position), radius as f64);
+                (Aabb::new(p - Vec3::new(r, r, r), p + Vec3::new(r, r, r)), i)
+            })
+            .collect();
+        let tree = Tree::build(&mut items);
+        PointCloud {
+            center,
+            positions: items.iter().map(|&(_, i)| stored[i].0).collect(),
+            radii: items.iter().map(|&(_, i)| stored[i].1).collect(),
+            normals: if normals.is_empty() { vec![] } else { items.iter().map(|&(_, i)| pack(normals[i].unit_vector())).collect() },
+            colors: if colors.is_empty() { vec![] } else { items.iter().map(|&(_, i)| pack(colors[i])).collect() },
+            tree,
+            splat,
+            material,
+        }
+    }
+
+    /// Returns points of `ply` with their normals and colors, of radius `radius` unless given in
+    /// the file.
+    pub fn from_ply(ply: &Ply, radius: f64, splat: Splat, material: Arc<dyn Material>) -> Self {
+        let radii = ply.radii.clone().unwrap_or_else(|| vec![radius; ply.positions.len()]);
+        let normals = ply.normals.as_deref().unwrap_or(&[]);
+        let colors = ply.colors.as_deref().unwrap_or(&[]);
+        PointCloud::new(&ply.positions, &radii, normals, colors, splat, material)
+    }
+
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    fn hit_point(&self, i: usize, ray: &Ray, t_min: f64, t_max: f64) -> Option<Hit<'_>> {
+        let (center, radius) = (self.center + unpack(self.positions[i]), self.radii[i] as f64);
+        let mut hit = match self.splat {
+            Splat::Sphere => {
+                let (t0, t1) = sphere_roots(center, radius, ray)?;
+                let t = [t0, t1].into_iter().find(|t| (t_min..=t_max).contains(t))?;
+                sphere_hit(center, radius, self.material.as_ref(), ray, t)
+            }
+            Splat::Disk => {
+                let normal = self.normals.get(i).map_or(-ray.direction.unit_vector(), |&n| unpack(n));
+                let denominator = ray.direction.dot(normal);
+                if denominator.abs() < 1.0e-12 {
+                    return None;
+                }
+                let t = (center - ray.origin).dot(normal) / denominator;
+                let offset = ray.at(t) - center;
+                if !(t_min..=t_max).contains(&t) || offset.length_squared() > radius * radius {
+                    return None;
+                }
+                // Texture coordinates span the disk's bounding square
+                let frame = Frame::from_normal(normal);
+                let uv = (0.5 + offset.dot(frame.s) / (2.0 * radius), 0.5 + offset.dot(frame.t) / (2.0 * radius));
+                let derivatives = (2.0 * radius * frame.s, 2.0 * radius * frame.t);
+                Hit { object_point: offset, ..Hit::new(ray, t, normal, uv, derivatives, self.material.as_ref()) }
+            }
+        };
+        hit.color = self.colors.get(i).map(|&c| unpack(c));
+        Some(hit)
+    }
+}
+
+impl Hittable for PointCloud {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Hit<'_>> {
+        let mut hit = None;
+        self.tree.traverse(ray, t_min, t_max, |range, closest| {
+            for i in range {
+                if let Some(h) = self.hit_point(i, ray, t_min, *closest) {
+                    *closest = h.t;
+                    hit = Some(h);
+                }
+            }
+            false
+        });
+        hit
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.tree.bounds()
+    }
+
+    fn describe(&self, materials: &mut MaterialTable) -> Option<Node> {
+        let numbers = |vectors: &[[f32; 3]]| Value::Numbers(vectors.iter().flatten().map(|&x| x as f64).collect());
+        let splat = match self.splat {
+            Splat::Disk => "disk",
+            Splat::Sphere => "sphere",
+        };
+        Some(Node::new("point_cloud")
+            .with("center", self.center)
+            .with("offsets", numbers(&self.positions))
+            .with("radii", Value::Numbers(self.radii.iter().map(|&r| r as f64).collect()))
+            .with("normals", numbers(&self.normals))
+            .with("colors", numbers(&self.colors))
+            .with("splat", splat)
+            .with("material", materials.name(&self.material)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+
+    #[test]
+    fn test_point_cloud() {
+        let material = Arc::new(Lambertian::new(Vec3::new(0.5, 0.5, 0.5)));
+        // Grid of points in the xy-plane, colored by their position
+        let positions: Vec<Vec3> = (0..400).map(|i| Vec3::new((i % 20) as f64, (i / 20) as f64, 0.0)).collect();
+        let colors: Vec<Vec3> = positions.iter().map(|p| *p / 20.0).collect();
+        let radii: Vec<f64> = (0..400).map(|i| if i == 21 { 0.6 } else { 0.3 }).collect();
+        let ray = |x: f64, y: f64| Ray { origin: Vec3::new(x, y, 5.0), direction: Vec3::new(0.1, 0.0, -1.0), time: 0.0, wavelength: None, differentials: None };
+
+        let disks = PointCloud::new(&positions, &radii, &[], &colors, Splat::Disk, material.clone());
+        assert_eq!(disks.len(), 400);
+        let hit = disks.hit(&ray(6.5, 7.2), 0.0, f64::MAX).unwrap();
+        assert!((hit.t - 5.0).abs() < 1e-9 && (hit.color.unwrap() - Vec3::new(0.35, 0.35, 0.0)).length() < 1e-6);
+        assert!(disks.hit(&ray(6.0, 7.5), 0.0, f64::MAX).is_none());
+        // Larger point at (1, 1)
+        assert!(disks.hit(&ray(0.6, 1.4), 0.0, f64::MAX).is_some());
+
+        // Tilted disks appear narrower along x
+        let tilted = PointCloud::new(&positions, &radii, &vec![Vec3::new(1.0, 0.0, 1.0); 400], &[], Splat::Disk, material.clone());
+        assert!(tilted.hit(&ray(6.5, 7.0), 0.0, f64::MAX).is_some());
+        assert!(tilted.hit(&ray(6.75, 7.0), 0.0, f64::MAX).is_none());
+        assert!(tilted.hit(&ray(6.5, 7.0), 0.0, f64::MAX).unwrap().color.is_none());
+
+        let spheres = PointCloud::new(&positions, &radii, &[], &[], Splat::Sphere, material);
+        let hit = spheres.hit(&ray(3.5, 4.0), 0.0, f64::MAX).unwrap();
+        assert!(((hit.point - Vec3::new(4.0, 4.0, 0.0)).length() - 0.3).abs() < 1e-6 && hit.point.z > 0.0);
+        let bounds = spheres.bounding_box().unwrap();
+        assert!((bounds.min - Vec3::new(-0.3, -0.3, -0.6)).length() < 1e-6 && (bounds.max - Vec3::new(19.3, 19.3, 0.6)).length() < 1e-6);
+    }
+
+    #[test]
+    fn test_far_away() {
+        // Millimeter points a kilometer away, beyond single precision in absolute coordinates
+        let material = Arc::new(Lambertian::new(Vec3::new(0.5, 0.5, 0.5)));
+        let offset = Vec3::new(1.0e6, 1.0e6, 0.0);
+        let positions: Vec<Vec3> = (0..100).map(|i| offset + 0.002 * Vec3::new((i % 10) as f64, (i / 10) as f64, 0.0)).collect();
+        let spheres = PointCloud::new(&positions, &[0.0005; 100], &[], &[], Splat::Sphere, material);
+        for (i, &p) in positions.iter().enumerate() {
+            let ray = Ray { origin: p + Vec3::new(0.0, 0.0, 1.0), direction: Vec3::new(0.0, 0.0, -1.0), time: 0.0, wavelength: None, differentials: None };
+            let hit = spheres.hit(&ray, 0.0, f64::MAX).unwrap_or_else(|| panic!("point {} missed", i));
+            assert!((hit.point - (p + Vec3::new(0.0, 0.0, 0.0005))).length() < 1e-6);
+        }
+        let bounds = spheres.bounding_box().unwrap();
+        assert!((bounds.min - (offset - Vec3::new(0.0005, 0.0005, 0.0005))).length() < 1e-6);
+    }
+}
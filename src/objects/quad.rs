@@ -0,0 +1,55 @@
+use std::sync::Arc;
+
+use crate::material::{Material, MaterialTable};
+use crate::objects::{Aabb, Hit, Hittable};
+use crate::scene::Node;
+use crate::{Ray, Vec3};
+
+/// Parallelogram spanned by the edges `u` and `v` starting at corner `q`, texture coordinates run
+/// from 0 to 1 along the edges.
+pub struct Quad {
+    pub q: Vec3,
+    pub u: Vec3,
+    pub v: Vec3,
+    pub material: Arc<dyn Material>,
+}
+
+impl Hittable for Quad {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Hit<'_>> {
+        let n = self.u.cross(self.v);
+        let outward_normal = n.unit_vector();
+        let denominator = outward_normal.dot(ray.direction);
+        // Ray parallel to the plane
+        if denominator.abs() < 1.0e-8 {
+            return None;
+        }
+        let t = (outward_normal.dot(self.q) - outward_normal.dot(ray.origin)) / denominator;
+        if t < t_min || t_max < t {
+            return None;
+        }
+
+        // Express hit point in the plane's coordinates along the edges
+        let point = ray.at(t);
+        let planar = point - self.q;
+        let w = n / n.dot(n);
+        let alpha = w.dot(planar.cross(self.v));
+        let beta = w.dot(self.u.cross(planar));
+        if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) {
+            return None;
+        }
+
+        Some(Hit::new(ray, t, outward_normal, (alpha, beta), (self.u, self.v), self.material.as_ref()))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(Aabb::from_points([self.q, self.q + self.u, self.q + self.v, self.q + self.u + self.v]))
+    }
+
+    fn describe(&self, materials: &mut MaterialTable) -> Option<Node> {
+        Some(Node::new("quad")
+            .with("q", self.q)
+            .with("u", self.u)
+            .with("v", self.v)
+            .with("material", materials.name(&self.material)?))
+    }
+}
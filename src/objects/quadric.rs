@@ -0,0 +1,460 @@
+//! Quadrics of revolution. Each is defined around the z-axis of a local coordinate system set by
+//! a [Placement] and can be swept by less than a full turn.
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+use crate::material::{Material, MaterialTable};
+use crate::objects::{Aabb, Hit, Hittable, Interval, Solid};
+use crate::scene::Node;
+use crate::{Frame, Ray, Vec3};
+
+/// Position and orientation of an analytic shape, whose local x-, y- and z-axis map to the
+/// tangent, bitangent and normal of `frame`.
+#[derive(Copy, Clone, Debug)]
+pub struct Placement {
+    pub origin: Vec3,
+    pub frame: Frame,
+}
+
+impl Placement {
+    /// Returns placement with the local z-axis along `axis`.
+    pub fn new(origin: Vec3, axis: Vec3) -> Self {
+        Placement { origin, frame: Frame::from_normal(axis.unit_vector()) }
+    }
+
+    /// Returns placement with the local z-axis pointing up (along y) and the x-axis along x.
+    pub fn upright(origin: Vec3) -> Self {
+        Placement { origin, frame: Frame::from_normal_tangent(Vec3::new(0.0, 1.0, 0.0), Vec3::new(1.0, 0.0, 0.0)) }
+    }
+
+    /// Returns origin and direction of `ray` in local coordinates, distances along it are the same.
+    pub fn to_local(&self, ray: &Ray) -> (Vec3, Vec3) {
+        (self.frame.to_local(ray.origin - self.origin), self.frame.to_local(ray.direction))
+    }
+
+    /// Returns `node` with the origin and the axes of the frame (tangent, bitangent and normal).
+    pub fn describe(&self, node: Node) -> Node {
+        node.with("origin", self.origin).with("axes", [self.frame.s, self.frame.t, self.frame.n])
+    }
+
+    /// Returns world box around the local box `bounds`.
+    pub fn bounding_box(&self, bounds: Aabb) -> Aabb {
+        Aabb::from_points(bounds.corners().map(|p| self.origin + self.frame.to_world(p)))
+    }
+
+    /// Returns hit with `outward_normal`, derivatives and object point given in local coordinates.
+    pub(crate) fn hit<'a>(&self, ray: &Ray, t: f64, local: LocalHit, material: &'a dyn Material) -> Hit<'a> {
+        let (dpdu, dpdv) = (self.frame.to_world(local.dpdu), self.frame.to_world(local.dpdv));
+        let outward_normal = self.frame.to_world(local.outward_normal);
+        Hit { object_point: local.point, ..Hit::new(ray, t, outward_normal, local.uv, (dpdu, dpdv), material) }
+    }
+}
+
+/// Surface point in the local coordinates of a [Placement].
+pub(crate) struct LocalHit {
+    pub point: Vec3,
+    pub outward_normal: Vec3,
+    pub uv: (f64, f64),
+    pub dpdu: Vec3,
+    pub dpdv: Vec3,
+}
+
+/// Returns angle of `p` around the z-axis in [0, 2pi), starting at the x-axis.
+pub(crate) fn azimuth(p: Vec3) -> f64 {
+    let phi = p.y.atan2(p.x);
+    if phi < 0.0 { phi + 2.0 * PI } else { phi }
+}
+
+/// Surface of revolution whose squared radius `a z^2 + b z + c` is a quadratic function of the
+/// height z in [`z_min`, `z_max`], swept from the x-axis by `phi_max`. Covers all quadrics of
+/// revolution, e.g. cylinders have a = b = 0.
+#[derive(Copy, Clone, Debug)]
+struct Profile {
+    a: f64,
+    b: f64,
+    c: f64,
+    z_min: f64,
+    z_max: f64,
+    phi_max: f64,
+}
+
+impl Profile {
+    /// Returns closest intersection with the local ray from `o` along `d` within [`t_min`, `t_max`].
+    fn intersect(&self, o: Vec3, d: Vec3, t_min: f64, t_max: f64) -> Option<(f64, LocalHit)> {
+        self.roots(o, d)?.into_iter().filter(|t| (t_min..=t_max).contains(t)).find_map(|t| self.side_hit(o, d, t))
+    }
+
+    /// Returns distances along the local ray from `o` along `d` to the unbounded surface in
+    /// ascending order, NaN for a missing second one.
+    fn roots(&self, o: Vec3, d: Vec3) -> Option<[f64; 2]> {
+        // (o.x + t d.x)^2 + (o.y + t d.y)^2 = a (o.z + t d.z)^2 + b (o.z + t d.z) + c
+        let qa = d.x * d.x + d.y * d.y - self.a * d.z * d.z;
+        let qb = 2.0 * (o.x * d.x + o.y * d.y - self.a * o.z * d.z) - self.b * d.z;
+        let qc = o.x * o.x + o.y * o.y - self.a * o.z * o.z - self.b * o.z - self.c;
+        let roots = if qa.abs() < 1.0e-12 * (qb.abs() + qc.abs()).max(1.0e-300) {
+            if qb == 0.0 {
+                return None;
+            }
+            [-qc / qb, f64::NAN]
+        } else {
+            let discriminant = qb * qb - 4.0 * qa * qc;
+            if discriminant < 0.0 {
+                return None;
+            }
+            // Numerically stable form avoiding cancellation
+            let q = -0.5 * (qb + qb.signum() * discriminant.sqrt());
+            let (t0, t1) = (q / qa, qc / q);
+            [t0.min(t1), t0.max(t1)]
+        };
+        Some(roots)
+    }
+
+    /// Returns hit at distance `t` along the local ray, if that point lies within the swept part.
+    fn side_hit(&self, o: Vec3, d: Vec3, t: f64) -> Option<(f64, LocalHit)> {
+        let p = o + t * d;
+        let phi = azimuth(p);
+        ((self.z_min..=self.z_max).contains(&p.z) && phi <= self.phi_max).then(|| (t, self.local_hit(p, phi)))
+    }
+
+    fn local_hit(&self, p: Vec3, phi: f64) -> LocalHit {
+        let height = self.z_max - self.z_min;
+        // Half the gradient of x^2 + y^2 - a z^2 - b z - c
+        let slope = self.a * p.z + 0.5 * self.b;
+        let outward_normal = Vec3::new(p.x, p.y, -slope).unit_vector();
+        // dr/dz = slope / r, split into x and y by cos and sin of phi
+        let r_squared = (p.x * p.x + p.y * p.y).max(1.0e-12);
+        LocalHit {
+            point: p,
+            outward_normal,
+            uv: (phi / self.phi_max, (p.z - self.z_min) / height),
+            dpdu: self.phi_max * Vec3::new(-p.y, p.x, 0.0),
+            dpdv: height * Vec3::new(slope * p.x / r_squared, slope * p.y / r_squared, 1.0),
+        }
+    }
+
+    fn bounds(&self) -> Aabb {
+        let radius_squared = |z: f64| self.a * z * z + self.b * z + self.c;
+        let r = radius_squared(self.z_min).max(radius_squared(self.z_max)).max(0.0).sqrt();
+        Aabb::new(Vec3::new(-r, -r, self.z_min), Vec3::new(r, r, self.z_max))
+    }
+}
+
+/// Returns hit of the local ray from `o` along `d` with the disk of `radius` at height `z`,
+/// facing up or down, swept by `phi_max`. Texture coordinate v runs from the rim to the center.
+fn disk_intersect(o: Vec3, d: Vec3, z: f64, radius: f64, up: bool, phi_max: f64) -> Option<(f64, LocalHit)> {
+    if d.z == 0.0 {
+        return None;
+    }
+    let t = (z - o.z) / d.z;
+    let p = o + t * d;
+    let r = (p.x * p.x + p.y * p.y).sqrt();
+    let phi = azimuth(p);
+    if r > radius || phi > phi_max {
+        return None;
+    }
+    let hit = LocalHit {
+        point: p,
+        outward_normal: Vec3::new(0.0, 0.0, if up { 1.0 } else { -1.0 }),
+        uv: (phi / phi_max, 1.0 - r / radius),
+        dpdu: phi_max * Vec3::new(-p.y, p.x, 0.0),
+        dpdv: -radius / r.max(1.0e-12) * Vec3::new(p.x, p.y, 0.0),
+    };
+    Some((t, hit))
+}
+
+/// Returns closest of the hits with the side `profile` and optional `caps` (height and radius).
+fn revolved_hit<'a>(
+    placement: &Placement,
+    profile: Profile,
+    caps: &[(f64, f64)],
+    ray: &Ray,
+    t_min: f64,
+    t_max: f64,
+    material: &'a dyn Material,
+) -> Option<Hit<'a>> {
+    let (o, d) = placement.to_local(ray);
+    let mut closest = profile.intersect(o, d, t_min, t_max);
+    for (i, &(z, radius)) in caps.iter().enumerate() {
+        let limit = closest.as_ref().map_or(t_max, |(t, _)| *t);
+        if let Some((t, hit)) = disk_intersect(o, d, z, radius, i == 1, profile.phi_max) {
+            if t_min <= t && t < limit {
+                closest = Some((t, hit));
+            }
+        }
+    }
+    closest.map(|(t, local)| placement.hit(ray, t, local, material))
+}
+
+/// Cylinder of `radius` around the local z-axis from `z_min` to `z_max`, open at both ends
+/// unless `capped`.
+pub struct Cylinder {
+    pub placement: Placement,
+    pub radius: f64,
+    pub z_min: f64,
+    pub z_max: f64,
+    /// Angle in radians swept around the axis, 2 pi for a full cylinder
+    pub phi_max: f64,
+    pub capped: bool,
+    pub material: Arc<dyn Material>,
+}
+
+impl Cylinder {
+    /// Returns open cylinder standing on the origin of `placement`.
+    pub fn new(placement: Placement, radius: f64, height: f64, material: Arc<dyn Material>) -> Self {
+        Cylinder { placement, radius, z_min: 0.0, z_max: height, phi_max: 2.0 * PI, capped: false, material }
+    }
+
+    /// Returns whether the cylinder encloses a volume, i.e. is capped and swept a full turn, which
+    /// is needed to use it as a [Solid].
+    pub fn is_closed(&self) -> bool {
+        self.capped && self.phi_max >= 2.0 * PI
+    }
+
+    fn profile(&self) -> Profile {
+        let (z_min, z_max, phi_max) = (self.z_min, self.z_max, self.phi_max);
+        Profile { a: 0.0, b: 0.0, c: self.radius * self.radius, z_min, z_max, phi_max }
+    }
+}
+
+impl Hittable for Cylinder {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Hit<'_>> {
+        let caps = [(self.z_min, self.radius), (self.z_max, self.radius)];
+        let caps: &[(f64, f64)] = if self.capped { &caps } else { &[] };
+        revolved_hit(&self.placement, self.profile(), caps, ray, t_min, t_max, self.material.as_ref())
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.placement.bounding_box(self.profile().bounds()))
+    }
+
+    fn describe(&self, materials: &mut MaterialTable) -> Option<Node> {
+        Some(self.placement.describe(Node::new("cylinder"))
+            .with("radius", self.radius)
+            .with("z_min", self.z_min)
+            .with("z_max", self.z_max)
+            .with("phi_max", self.phi_max)
+            .with("capped", self.capped)
+            .with("material", materials.name(&self.material)?))
+    }
+}
+
+impl Solid for Cylinder {
+    /// Returns no intervals unless the cylinder [is closed](Self::is_closed).
+    fn intervals(&self, ray: &Ray) -> Vec<Interval<'_>> {
+        if !self.is_closed() {
+            return vec![];
+        }
+        let (o, d) = self.placement.to_local(ray);
+        let profile = self.profile();
+        let mut crossings: Vec<(f64, LocalHit)> = profile.roots(o, d).into_iter().flatten()
+            .filter(|t| !t.is_nan())
+            .filter_map(|t| profile.side_hit(o, d, t))
+            .chain(disk_intersect(o, d, self.z_min, self.radius, false, self.phi_max))
+            .chain(disk_intersect(o, d, self.z_max, self.radius, true, self.phi_max))
+            .collect();
+        crossings.sort_by(|a, b| a.0.total_cmp(&b.0));
+        // Convex, so the ray is inside between the first and the last crossing
+        if crossings.len() < 2 {
+            return vec![];
+        }
+        let exit = crossings.pop().unwrap();
+        let enter = crossings.swap_remove(0);
+        if enter.0 >= exit.0 {
+            return vec![];
+        }
+        let hit = |(t, local)| self.placement.hit(ray, t, local, self.material.as_ref());
+        vec![Interval { enter: hit(enter), exit: hit(exit) }]
+    }
+}
+
+/// Cone with base of `radius` at the local origin and apex at `height` on the z-axis.
+pub struct Cone {
+    pub placement: Placement,
+    pub radius: f64,
+    pub height: f64,
+    pub phi_max: f64,
+    pub material: Arc<dyn Material>,
+}
+
+impl Cone {
+    pub fn new(placement: Placement, radius: f64, height: f64, material: Arc<dyn Material>) -> Self {
+        Cone { placement, radius, height, phi_max: 2.0 * PI, material }
+    }
+
+    fn profile(&self) -> Profile {
+        // r^2 = radius^2 (1 - z / height)^2
+        let k = self.radius * self.radius / (self.height * self.height);
+        let (z_min, z_max) = (0.0, self.height);
+        Profile { a: k, b: -2.0 * k * self.height, c: self.radius * self.radius, z_min, z_max, phi_max: self.phi_max }
+    }
+}
+
+impl Hittable for Cone {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Hit<'_>> {
+        revolved_hit(&self.placement, self.profile(), &[], ray, t_min, t_max, self.material.as_ref())
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.placement.bounding_box(self.profile().bounds()))
+    }
+
+    fn describe(&self, materials: &mut MaterialTable) -> Option<Node> {
+        Some(self.placement.describe(Node::new("cone"))
+            .with("radius", self.radius)
+            .with("height", self.height)
+            .with("phi_max", self.phi_max)
+            .with("material", materials.name(&self.material)?))
+    }
+}
+
+/// Paraboloid opening along the local z-axis with its vertex at the origin, reaching `radius` at
+/// `z_max` and cut off below `z_min`.
+pub struct Paraboloid {
+    pub placement: Placement,
+    pub radius: f64,
+    pub z_min: f64,
+    pub z_max: f64,
+    pub phi_max: f64,
+    pub material: Arc<dyn Material>,
+}
+
+impl Paraboloid {
+    pub fn new(placement: Placement, radius: f64, height: f64, material: Arc<dyn Material>) -> Self {
+        Paraboloid { placement, radius, z_min: 0.0, z_max: height, phi_max: 2.0 * PI, material }
+    }
+
+    fn profile(&self) -> Profile {
+        let b = self.radius * self.radius / self.z_max;
+        Profile { a: 0.0, b, c: 0.0, z_min: self.z_min, z_max: self.z_max, phi_max: self.phi_max }
+    }
+}
+
+impl Hittable for Paraboloid {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Hit<'_>> {
+        revolved_hit(&self.placement, self.profile(), &[], ray, t_min, t_max, self.material.as_ref())
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.placement.bounding_box(self.profile().bounds()))
+    }
+
+    fn describe(&self, materials: &mut MaterialTable) -> Option<Node> {
+        Some(self.placement.describe(Node::new("paraboloid"))
+            .with("radius", self.radius)
+            .with("z_min", self.z_min)
+            .with("z_max", self.z_max)
+            .with("phi_max", self.phi_max)
+            .with("material", materials.name(&self.material)?))
+    }
+}
+
+/// Surface swept by the line from `p1` to `p2` around the local z-axis, a hyperboloid of one
+/// sheet unless the line lies in a plane with the axis. The points must differ in z.
+pub struct Hyperboloid {
+    pub placement: Placement,
+    pub p1: Vec3,
+    pub p2: Vec3,
+    pub phi_max: f64,
+    pub material: Arc<dyn Material>,
+}
+
+impl Hyperboloid {
+    pub fn new(placement: Placement, p1: Vec3, p2: Vec3, material: Arc<dyn Material>) -> Self {
+        Hyperboloid { placement, p1, p2, phi_max: 2.0 * PI, material }
+    }
+
+    fn profile(&self) -> Profile {
+        // Squared distance from the axis along the line, with s = (z - z1) / (z2 - z1):
+        // |f + s e|^2 for f the start and e the offset in the xy-plane
+        let (f, e) = (Vec3::new(self.p1.x, self.p1.y, 0.0), Vec3::new(self.p2.x - self.p1.x, self.p2.y - self.p1.y, 0.0));
+        let (z1, dz) = (self.p1.z, self.p2.z - self.p1.z);
+        let (ee, fe, ff) = (e.dot(e) / (dz * dz), f.dot(e) / dz, f.dot(f));
+        Profile {
+            a: ee,
+            b: 2.0 * fe - 2.0 * z1 * ee,
+            c: ff - 2.0 * z1 * fe + z1 * z1 * ee,
+            z_min: self.p1.z.min(self.p2.z),
+            z_max: self.p1.z.max(self.p2.z),
+            phi_max: self.phi_max,
+        }
+    }
+}
+
+impl Hittable for Hyperboloid {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Hit<'_>> {
+        revolved_hit(&self.placement, self.profile(), &[], ray, t_min, t_max, self.material.as_ref())
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.placement.bounding_box(self.profile().bounds()))
+    }
+
+    fn describe(&self, materials: &mut MaterialTable) -> Option<Node> {
+        Some(self.placement.describe(Node::new("hyperboloid"))
+            .with("p1", self.p1)
+            .with("p2", self.p2)
+            .with("phi_max", self.phi_max)
+            .with("material", materials.name(&self.material)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+
+    fn material() -> Arc<dyn Material> {
+        Arc::new(Lambertian::new(Vec3::new(0.5, 0.5, 0.5)))
+    }
+
+    fn ray(origin: Vec3, direction: Vec3) -> Ray {
+        Ray { origin, direction, time: 0.0, wavelength: None, differentials: None }
+    }
+
+    fn assert_hit(hittable: &dyn Hittable, ray: &Ray, t: f64, normal: Vec3) {
+        let hit = hittable.hit(ray, 0.001, f64::MAX).expect("no hit");
+        assert!((hit.t - t).abs() < 1e-9 && (hit.normal - normal).length() < 1e-9, "t {} normal {}", hit.t, hit.normal);
+        let bounds = hittable.bounding_box().unwrap();
+        assert!((0..3).all(|a| bounds.min[a] - 1e-9 <= hit.point[a] && hit.point[a] <= bounds.max[a] + 1e-9));
+    }
+
+    #[test]
+    fn test_quadrics() {
+        let upright = Placement::upright(Vec3::new(0.0, 1.0, 0.0));
+        let cylinder = Cylinder::new(upright, 0.5, 2.0, material());
+        let side = ray(Vec3::new(0.0, 2.0, 5.0), Vec3::new(0.0, 0.0, -1.0));
+        assert_hit(&cylinder, &side, 4.5, Vec3::new(0.0, 0.0, 1.0));
+        // Open cylinder is hit from the inside when looking down, a capped one on its top
+        let top = ray(Vec3::new(0.2, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        assert!(cylinder.hit(&top, 0.001, f64::MAX).is_none());
+        let capped = Cylinder { capped: true, ..Cylinder::new(upright, 0.5, 2.0, material()) };
+        assert_hit(&capped, &top, 2.0, Vec3::new(0.0, 1.0, 0.0));
+        let hit = capped.hit(&ray(Vec3::new(0.2, 2.0, 0.0), Vec3::new(0.0, -1.0, 0.0)), 0.001, f64::MAX).unwrap();
+        assert!(!hit.front_face && (hit.t - 1.0).abs() < 1e-9);
+
+        // Half cylinder lets the ray through its missing front, hitting the back from inside
+        let half = Cylinder { phi_max: PI, ..Cylinder::new(Placement::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 1.0)), 0.5, 2.0, material()) };
+        let hit = half.hit(&ray(Vec3::new(0.0, -5.0, 1.0), Vec3::new(0.0, 1.0, 0.0)), 0.001, f64::MAX).unwrap();
+        assert!((hit.t - 5.5).abs() < 1e-9 && !hit.front_face);
+        assert!((hit.u - 0.5).abs() < 1e-9 && (hit.v - 0.5).abs() < 1e-9);
+
+        // Cone of slope 1 is hit at 45 degrees
+        let cone = Cone::new(upright, 1.0, 1.0, material());
+        let s = 0.5f64.sqrt();
+        assert_hit(&cone, &ray(Vec3::new(0.0, 1.5, 5.0), Vec3::new(0.0, 0.0, -1.0)), 4.5, Vec3::new(0.0, s, s));
+
+        // Paraboloid z = r^2
+        let paraboloid = Paraboloid::new(upright, 1.0, 1.0, material());
+        let hit = paraboloid.hit(&ray(Vec3::new(0.5, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0)), 0.001, f64::MAX).unwrap();
+        assert!((hit.point.y - 1.25).abs() < 1e-9 && !hit.front_face);
+
+        // Hyperboloid through vertical line is a cylinder, through skewed line narrower in the middle
+        let straight = Hyperboloid::new(upright, Vec3::new(0.5, 0.0, 0.0), Vec3::new(0.5, 0.0, 2.0), material());
+        assert_hit(&straight, &side, 4.5, Vec3::new(0.0, 0.0, 1.0));
+        let skewed = Hyperboloid::new(upright, Vec3::new(0.5, -0.5, 0.0), Vec3::new(0.5, 0.5, 2.0), material());
+        assert_hit(&skewed, &side, 4.5, Vec3::new(0.0, 0.0, 1.0));
+        let waist = skewed.hit(&ray(Vec3::new(0.0, 2.0, 5.0), Vec3::new(0.0, 0.0, -1.0)), 0.001, f64::MAX).unwrap();
+        let rim = skewed.hit(&ray(Vec3::new(0.0, 1.0 + 1e-9, 5.0), Vec3::new(0.0, 0.0, -1.0)), 0.001, f64::MAX).unwrap();
+        assert!(waist.t > rim.t);
+    }
+}
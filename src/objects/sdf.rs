@@ -0,0 +1,320 @@
+use std::sync::Arc;
+
+use crate::material::{Material, MaterialTable};
+use crate::objects::{Aabb, Hit, Hittable};
+use crate::scene::Node;
+use crate::{Frame, Ray, Vec3};
+
+/// Tree of signed distance functions, negative inside the shape. Primitives are centered at the
+/// origin, transformations and combinations wrap other nodes. Distances of some nodes (twist,
+/// smooth combinations, fractals) are only estimates, see [SdfShape::step].
+#[derive(Clone, Debug)]
+pub enum Sdf {
+    Sphere { radius: f64 },
+    /// Box with edges rounded off by `radius`, `half_size` includes the rounding
+    RoundedBox { half_size: Vec3, radius: f64 },
+    /// Ring in the xz-plane
+    Torus { major_radius: f64, minor_radius: f64 },
+    /// Capped cylinder along the y-axis
+    Cylinder { radius: f64, half_height: f64 },
+    /// Power 8 gives the classic Mandelbulb, which fits into a sphere of radius 1.2
+    Mandelbulb { power: f64, iterations: u32 },
+    Translate { offset: Vec3, shape: Box<Sdf> },
+    Scale { factor: f64, shape: Box<Sdf> },
+    /// Rotation around the y-axis by `rate` radians per unit height
+    Twist { rate: f64, shape: Box<Sdf> },
+    /// Infinite repetition with the given period along each axis, zero for no repetition. The
+    /// shape should fit into a single cell.
+    Repeat { period: Vec3, shape: Box<Sdf> },
+    Union(Box<Sdf>, Box<Sdf>),
+    Intersection(Box<Sdf>, Box<Sdf>),
+    /// First shape with the second one cut out
+    Subtract(Box<Sdf>, Box<Sdf>),
+    /// Union blending the shapes where they are closer than `k`
+    SmoothUnion { a: Box<Sdf>, b: Box<Sdf>, k: f64 },
+    /// Subtraction with a fillet of size `k`
+    SmoothSubtract { a: Box<Sdf>, b: Box<Sdf>, k: f64 },
+}
+
+impl Sdf {
+    pub fn translate(self, offset: Vec3) -> Self {
+        Sdf::Translate { offset, shape: Box::new(self) }
+    }
+
+    pub fn scale(self, factor: f64) -> Self {
+        Sdf::Scale { factor, shape: Box::new(self) }
+    }
+
+    pub fn twist(self, rate: f64) -> Self {
+        Sdf::Twist { rate, shape: Box::new(self) }
+    }
+
+    pub fn repeat(self, period: Vec3) -> Self {
+        Sdf::Repeat { period, shape: Box::new(self) }
+    }
+
+    pub fn union(self, other: Sdf) -> Self {
+        Sdf::Union(Box::new(self), Box::new(other))
+    }
+
+    pub fn intersection(self, other: Sdf) -> Self {
+        Sdf::Intersection(Box::new(self), Box::new(other))
+    }
+
+    pub fn subtract(self, other: Sdf) -> Self {
+        Sdf::Subtract(Box::new(self), Box::new(other))
+    }
+
+    pub fn smooth_union(self, other: Sdf, k: f64) -> Self {
+        Sdf::SmoothUnion { a: Box::new(self), b: Box::new(other), k }
+    }
+
+    pub fn smooth_subtract(self, other: Sdf, k: f64) -> Self {
+        Sdf::SmoothSubtract { a: Box::new(self), b: Box::new(other), k }
+    }
+
+    /// Returns description of the tree for saving scenes, nested nodes in the fields `shape` or
+    /// `a` and `b`.
+    pub fn describe(&self) -> Node {
+        match self {
+            Sdf::Sphere { radius } => Node::new("sphere").with("radius", *radius),
+            Sdf::RoundedBox { half_size, radius } => Node::new("rounded_box").with("half_size", *half_size).with("radius", *radius),
+            Sdf::Torus { major_radius, minor_radius } => {
+                Node::new("torus").with("major_radius", *major_radius).with("minor_radius", *minor_radius)
+            }
+            Sdf::Cylinder { radius, half_height } => Node::new("cylinder").with("radius", *radius).with("half_height", *half_height),
+            Sdf::Mandelbulb { power, iterations } => Node::new("mandelbulb").with("power", *power).with("iterations", *iterations as f64),
+            Sdf::Translate { offset, shape } => Node::new("translate").with("offset", *offset).with("shape", shape.describe()),
+            Sdf::Scale { factor, shape } => Node::new("scale").with("factor", *factor).with("shape", shape.describe()),
+            Sdf::Twist { rate, shape } => Node::new("twist").with("rate", *rate).with("shape", shape.describe()),
+            Sdf::Repeat { period, shape } => Node::new("repeat").with("period", *period).with("shape", shape.describe()),
+            Sdf::Union(a, b) => Node::new("union").with("a", a.describe()).with("b", b.describe()),
+            Sdf::Intersection(a, b) => Node::new("intersection").with("a", a.describe()).with("b", b.describe()),
+            Sdf::Subtract(a, b) => Node::new("subtract").with("a", a.describe()).with("b", b.describe()),
+            Sdf::SmoothUnion { a, b, k } => Node::new("smooth_union").with("a", a.describe()).with("b", b.describe()).with("k", *k),
+            Sdf::SmoothSubtract { a, b, k } => Node::new("smooth_subtract").with("a", a.describe()).with("b", b.describe()).with("k", *k),
+        }
+    }
+
+    /// Returns signed distance from `p` to the surface, formulas after Inigo Quilez.
+    pub fn distance(&self, p: Vec3) -> f64 {
+        match self {
+            Sdf::Sphere { radius } => p.length() - radius,
+            Sdf::RoundedBox { half_size, radius } => {
+                let q = Vec3::new(p.x.abs(), p.y.abs(), p.z.abs()) - *half_size + Vec3::new(*radius, *radius, *radius);
+                let outside = Vec3::new(q.x.max(0.0), q.y.max(0.0), q.z.max(0.0)).length();
+                outside + q.x.max(q.y).max(q.z).min(0.0) - radius
+            }
+            Sdf::Torus { major_radius, minor_radius } => {
+                let ring = (p.x * p.x + p.z * p.z).sqrt() - major_radius;
+                (ring * ring + p.y * p.y).sqrt() - minor_radius
+            }
+            Sdf::Cylinder { radius, half_height } => {
+                let (dr, dy) = ((p.x * p.x + p.z * p.z).sqrt() - radius, p.y.abs() - half_height);
+                dr.max(dy).min(0.0) + (dr.max(0.0).powi(2) + dy.max(0.0).powi(2)).sqrt()
+            }
+            Sdf::Mandelbulb { power, iterations } => mandelbulb(p, *power, *iterations),
+            Sdf::Translate { offset, shape } => shape.distance(p - *offset),
+            Sdf::Scale { factor, shape } => shape.distance(p / *factor) * factor,
+            Sdf::Twist { rate, shape } => {
+                let (sin, cos) = (rate * p.y).sin_cos();
+                shape.distance(Vec3::new(cos * p.x - sin * p.z, p.y, sin * p.x + cos * p.z))
+            }
+            Sdf::Repeat { period, shape } => {
+                let wrap = |x: f64, period: f64| if period > 0.0 { x - period * (x / period).round() } else { x };
+                shape.distance(Vec3::new(wrap(p.x, period.x), wrap(p.y, period.y), wrap(p.z, period.z)))
+            }
+            Sdf::Union(a, b) => a.distance(p).min(b.distance(p)),
+            Sdf::Intersection(a, b) => a.distance(p).max(b.distance(p)),
+            Sdf::Subtract(a, b) => a.distance(p).max(-b.distance(p)),
+            Sdf::SmoothUnion { a, b, k } => {
+                let (a, b) = (a.distance(p), b.distance(p));
+                let h = (0.5 + 0.5 * (b - a) / k).clamp(0.0, 1.0);
+                b + h * (a - b) - k * h * (1.0 - h)
+            }
+            Sdf::SmoothSubtract { a, b, k } => {
+                let (a, b) = (a.distance(p), b.distance(p));
+                let h = (0.5 - 0.5 * (a + b) / k).clamp(0.0, 1.0);
+                a + h * (-b - a) + k * h * (1.0 - h)
+            }
+        }
+    }
+}
+
+/// Distance estimate of the Mandelbulb from the running derivative of the iteration.
+fn mandelbulb(p: Vec3, power: f64, iterations: u32) -> f64 {
+    let mut z = p;
+    let mut dr = 1.0;
+    let mut r = z.length();
+    for _ in 0..iterations {
+        if r > 2.0 || r == 0.0 {
+            break;
+        }
+        // z -> z^power + p in spherical coordinates
+        let theta = (z.z / r).acos() * power;
+        let phi = z.y.atan2(z.x) * power;
+        dr = r.powf(power - 1.0) * power * dr + 1.0;
+        let zr = r.powf(power);
+        z = zr * Vec3::new(theta.sin() * phi.cos(), theta.sin() * phi.sin(), theta.cos()) + p;
+        r = z.length();
+    }
+    if r == 0.0 {
+        return 0.0;
+    }
+    0.5 * r.ln() * r / dr
+}
+
+/// Upper bound of the default [SdfShape::epsilon], below the usual `t_min` of 0.001
+const MAX_EPSILON: f64 = 1.0e-4;
+
+/// Shape given by a signed distance function, intersected by sphere tracing (Hart 1996) inside
+/// an axis-aligned box containing the surface. There are no surface coordinates, use solid
+/// textures.
+pub struct SdfShape {
+    pub sdf: Sdf,
+    pub min: Vec3,
+    pub max: Vec3,
+    pub material: Arc<dyn Material>,
+    /// Distance to the surface at which marching stops, keep it well below the `t_min` of
+    /// secondary rays
+    pub epsilon: f64,
+    pub max_steps: usize,
+    /// Fraction of the distance bound taken per step, reduce below one for distance estimates
+    /// that overshoot (e.g. strong twists)
+    pub step: f64,
+}
+
+impl SdfShape {
+    pub fn new(sdf: Sdf, min: Vec3, max: Vec3, material: Arc<dyn Material>) -> Self {
+        let epsilon = (1.0e-5 * (max - min).length()).min(MAX_EPSILON);
+        SdfShape { sdf, min, max, material, epsilon, max_steps: 1000, step: 1.0 }
+    }
+
+    /// Returns gradient of the distance at `p` by central differences along the vertices of a
+    /// tetrahedron, which only needs four evaluations.
+    fn gradient(&self, p: Vec3) -> Vec3 {
+        let h = self.epsilon;
+        [Vec3::new(1.0, -1.0, -1.0), Vec3::new(-1.0, -1.0, 1.0), Vec3::new(-1.0, 1.0, -1.0), Vec3::new(1.0, 1.0, 1.0)]
+            .into_iter()
+            .fold(Vec3::new(0.0, 0.0, 0.0), |sum, k| sum + self.sdf.distance(p + h * k) * k)
+    }
+
+    fn surface_hit(&self, ray: &Ray, t: f64) -> Hit<'_> {
+        let outward_normal = self.gradient(ray.at(t)).unit_vector();
+        let frame = Frame::from_normal(outward_normal);
+        Hit::new(ray, t, outward_normal, (0.0, 0.0), (frame.s, frame.t), self.material.as_ref())
+    }
+}
+
+impl Hittable for SdfShape {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Hit<'_>> {
+        let (t0, t1) = Aabb::new(self.min, self.max).clip(ray, t_min, t_max)?;
+
+        let length = ray.direction.length();
+        let mut t = t0;
+        let mut steps = 0;
+        // Rays leaving the surface first step out of the epsilon shell, where the sign is reliable
+        let mut distance = self.sdf.distance(ray.at(t));
+        while distance.abs() <= self.epsilon {
+            t += self.epsilon / length;
+            steps += 1;
+            if t > t1 || steps >= self.max_steps {
+                return None;
+            }
+            distance = self.sdf.distance(ray.at(t));
+        }
+
+        // Rays starting inside march towards the surface from below
+        let sign = distance.signum();
+        for _ in steps..self.max_steps {
+            let distance = sign * self.sdf.distance(ray.at(t));
+            if distance < self.epsilon {
+                return (t <= t1).then(|| self.surface_hit(ray, t));
+            }
+            t += self.step * distance / length;
+            if t > t1 {
+                return None;
+            }
+        }
+        None
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(Aabb::new(self.min, self.max))
+    }
+
+    fn describe(&self, materials: &mut MaterialTable) -> Option<Node> {
+        Some(Node::new("sdf")
+            .with("sdf", self.sdf.describe())
+            .with("min", self.min)
+            .with("max", self.max)
+            .with("epsilon", self.epsilon)
+            .with("max_steps", self.max_steps as f64)
+            .with("step", self.step)
+            .with("material", materials.name(&self.material)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+    use crate::objects::Sphere;
+
+    fn shape(sdf: Sdf, extent: f64) -> SdfShape {
+        let e = Vec3::new(extent, extent, extent);
+        SdfShape::new(sdf, -e, e, Arc::new(Lambertian::new(Vec3::new(0.5, 0.5, 0.5))))
+    }
+
+    fn ray(origin: Vec3, direction: Vec3) -> Ray {
+        Ray { origin, direction, time: 0.0, wavelength: None, differentials: None }
+    }
+
+    #[test]
+    fn test_sphere_tracing() {
+        // Matches the analytic sphere, from outside and inside
+        let sdf = shape(Sdf::Sphere { radius: 1.0 }.translate(Vec3::new(0.3, 0.0, 0.0)), 2.0);
+        let material = Arc::new(Lambertian::new(Vec3::new(0.5, 0.5, 0.5)));
+        let sphere = Sphere { center: Vec3::new(0.3, 0.0, 0.0), radius: 1.0, material };
+        for r in [ray(Vec3::new(-0.5, 0.4, 5.0), Vec3::new(0.1, 0.0, -2.0)), ray(Vec3::new(0.2, 0.1, 0.0), Vec3::new(0.3, 1.0, 0.2))] {
+            let (a, b) = (sdf.hit(&r, 0.001, f64::MAX).unwrap(), sphere.hit(&r, 0.001, f64::MAX).unwrap());
+            assert!((a.t - b.t).abs() < 1e-4 && (a.normal - b.normal).length() < 1e-4);
+            assert_eq!(a.front_face, b.front_face);
+        }
+        assert!(sdf.hit(&ray(Vec3::new(0.0, 3.0, 5.0), Vec3::new(0.0, 0.0, -1.0)), 0.001, f64::MAX).is_none());
+
+        // Smooth union bridges the gap between two spheres
+        let pair = |k: f64| {
+            let ball = Sdf::Sphere { radius: 0.5 };
+            let sdf = ball.clone().translate(Vec3::new(-0.6, 0.0, 0.0)).smooth_union(ball.translate(Vec3::new(0.6, 0.0, 0.0)), k);
+            shape(sdf, 2.0).hit(&ray(Vec3::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0)), 0.001, f64::MAX).is_some()
+        };
+        assert!(!pair(0.01) && pair(0.5));
+
+        // Twisted, repeated and fractal shapes with overestimated distances
+        let twisted = Sdf::RoundedBox { half_size: Vec3::new(0.5, 1.0, 0.2), radius: 0.05 }.twist(1.5);
+        let columns = SdfShape { step: 0.5, ..shape(twisted.repeat(Vec3::new(2.0, 0.0, 0.0)), 5.0) };
+        assert!(columns.hit(&ray(Vec3::new(4.0, 0.5, 5.0), Vec3::new(0.0, 0.0, -1.0)), 0.001, f64::MAX).is_some());
+        let bulb = SdfShape { step: 0.5, ..shape(Sdf::Mandelbulb { power: 8.0, iterations: 12 }, 1.2) };
+        let hit = bulb.hit(&ray(Vec3::new(0.0, 0.0, 3.0), Vec3::new(0.0, 0.0, -1.0)), 0.001, f64::MAX).unwrap();
+        assert!(hit.t > 1.8 && hit.t < 3.0 && hit.front_face);
+    }
+
+    #[test]
+    fn test_leaving_surface() {
+        // Large bounds don't make the epsilon exceed t_min
+        let big = shape(Sdf::Sphere { radius: 100.0 }, 200.0);
+        assert!(big.epsilon < 0.001);
+
+        // Rays leaving a hit point don't hit the surface again, the sign is taken past the
+        // epsilon shell
+        let ball = shape(Sdf::Sphere { radius: 1.0 }, 2.0);
+        let hit = ball.hit(&ray(Vec3::new(0.2, 0.1, 5.0), Vec3::new(0.0, 0.0, -1.0)), 0.001, f64::MAX).unwrap();
+        let reflected = ray(hit.point, Vec3::new(0.0, 0.0, 1.0));
+        assert!(ball.hit(&reflected, 0.001, f64::MAX).is_none());
+        let refracted = ball.hit(&ray(hit.point, Vec3::new(0.0, 0.0, -1.0)), 0.001, f64::MAX).unwrap();
+        assert!(!refracted.front_face && (refracted.point.length() - 1.0).abs() < 1e-3);
+        let grazing = big.hit(&ray(Vec3::new(0.0, 0.0, 100.0), Vec3::new(1.0, 0.0, 0.0)), 0.0, f64::MAX);
+        assert!(grazing.is_none());
+    }
+}
@@ -1,41 +1,81 @@
-use crate::objects::hittable::{Hit, Hittable};
-use crate::material::Material;
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+use crate::objects::{Aabb, Hit, Hittable, Interval, Solid};
+use crate::material::{Material, MaterialTable};
+use crate::scene::Node;
 use crate::{Ray, Vec3};
 
 pub struct Sphere {
     pub center: Vec3,
     pub radius: f64,
-    pub material: Material,
+    pub material: Arc<dyn Material>,
 }
 
 impl Hittable for Sphere {
-    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Hit> {
-        let oc = ray.origin - self.center;
-        let a = ray.direction.length_squared();
-        let half_b = oc.dot(ray.direction);
-        let c = oc.length_squared() - self.radius * self.radius;
-
-        let discriminant = half_b * half_b - a * c;
-        if discriminant < 0.0 {
-            return None;
-        }
-        let sqrtd = discriminant.sqrt();
-
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Hit<'_>> {
+        let (t0, t1) = sphere_roots(self.center, self.radius, ray)?;
         // Find nearest root that lies in acceptable range.
-        let mut root = (-half_b - sqrtd) / a;
-        if root < t_min || t_max < root {
-            root = (-half_b + sqrtd) / a;
-            if root < t_min || t_max < root {
-                return None;
+        let t = [t0, t1].into_iter().find(|t| (t_min..=t_max).contains(t))?;
+        Some(sphere_hit(self.center, self.radius, self.material.as_ref(), ray, t))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let r = Vec3::new(self.radius, self.radius, self.radius);
+        Some(Aabb::new(self.center - r, self.center + r))
+    }
+
+    fn describe(&self, materials: &mut MaterialTable) -> Option<Node> {
+        Some(Node::new("sphere")
+            .with("center", self.center)
+            .with("radius", self.radius)
+            .with("material", materials.name(&self.material)?))
+    }
+}
+
+impl Solid for Sphere {
+    fn intervals(&self, ray: &Ray) -> Vec<Interval<'_>> {
+        match sphere_roots(self.center, self.radius, ray) {
+            Some((t0, t1)) if t0 < t1 => {
+                let hit = |t: f64| sphere_hit(self.center, self.radius, self.material.as_ref(), ray, t);
+                vec![Interval { enter: hit(t0), exit: hit(t1) }]
             }
+            _ => vec![],
         }
+    }
+}
 
-        let point = ray.at(root);
-        let outward_normal = (point - self.center) / self.radius;
-        let t = root;
-        let front_face = ray.direction.dot(outward_normal) < 0.0;
-        let normal = if front_face { outward_normal } else { -outward_normal };
+/// Returns both distances along `ray` to the sphere's surface in ascending order, if any.
+pub(crate) fn sphere_roots(center: Vec3, radius: f64, ray: &Ray) -> Option<(f64, f64)> {
+    let oc = ray.origin - center;
+    let a = ray.direction.length_squared();
+    let half_b = oc.dot(ray.direction);
+    let c = oc.length_squared() - radius * radius;
 
-        Some(Hit { point, normal, t, front_face, material: &self.material })
+    let discriminant = half_b * half_b - a * c;
+    if discriminant < 0.0 {
+        return None;
     }
+    let sqrtd = discriminant.sqrt();
+    Some(((-half_b - sqrtd) / a, (-half_b + sqrtd) / a))
+}
+
+/// Returns hit of `ray` with the sphere's surface at distance `t`.
+pub(crate) fn sphere_hit<'a>(center: Vec3, radius: f64, material: &'a dyn Material, ray: &Ray, t: f64) -> Hit<'a> {
+    let object_point = ray.at(t) - center;
+    let outward_normal = object_point / radius;
+    let (u, v, dpdu, dpdv) = sphere_uv(outward_normal);
+    Hit { object_point, ..Hit::new(ray, t, outward_normal, (u, v), (radius * dpdu, radius * dpdv), material) }
+}
+
+/// Returns texture coordinates and their partial derivatives dp/du and dp/dv for a point on the
+/// unit sphere. u is the angle around the y-axis starting at -x, v the angle from -y, both in [0, 1].
+pub(crate) fn sphere_uv(p: Vec3) -> (f64, f64, Vec3, Vec3) {
+    let theta = (-p.y).clamp(-1.0, 1.0).acos();
+    let phi = (-p.z).atan2(p.x) + PI;
+    // Distance from the y-axis, kept positive such that the poles get a valid dp/dv
+    let rho = (p.x * p.x + p.z * p.z).sqrt().max(1.0e-9);
+    let dpdu = 2.0 * PI * Vec3::new(p.z, 0.0, -p.x);
+    let dpdv = PI * Vec3::new(-p.x * p.y / rho, rho, -p.z * p.y / rho);
+    (phi / (2.0 * PI), theta / PI, dpdu, dpdv)
 }
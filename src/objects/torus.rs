@@ -0,0 +1,148 @@
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+use crate::material::{Material, MaterialTable};
+use crate::objects::quadric::{azimuth, LocalHit};
+use crate::objects::{Aabb, Hit, Hittable, Interval, Placement, Solid};
+use crate::scene::Node;
+use crate::utils::polynomial_roots;
+use crate::{Ray, Vec3};
+
+/// Ring around the local z-axis, a tube of `minor_radius` whose center circle has `major_radius`.
+pub struct Torus {
+    pub placement: Placement,
+    pub major_radius: f64,
+    pub minor_radius: f64,
+    /// Angle in radians swept around the axis, 2 pi for a full ring
+    pub phi_max: f64,
+    pub material: Arc<dyn Material>,
+}
+
+impl Torus {
+    pub fn new(placement: Placement, major_radius: f64, minor_radius: f64, material: Arc<dyn Material>) -> Self {
+        Torus { placement, major_radius, minor_radius, phi_max: 2.0 * PI, material }
+    }
+
+    /// Returns whether the ring is swept a full turn and so encloses a volume, which is needed to
+    /// use it as a [Solid].
+    pub fn is_closed(&self) -> bool {
+        self.phi_max >= 2.0 * PI
+    }
+
+    fn local_bounds(&self) -> Aabb {
+        let (r, extent) = (self.minor_radius, self.major_radius + self.minor_radius);
+        Aabb::new(Vec3::new(-extent, -extent, -r), Vec3::new(extent, extent, r))
+    }
+
+    fn local_hit(&self, p: Vec3, phi: f64) -> LocalHit {
+        let rho = (p.x * p.x + p.y * p.y).sqrt().max(1.0e-12);
+        // Angle around the tube, starting at the outer equator
+        let theta = p.z.atan2(rho - self.major_radius);
+        let theta = if theta < 0.0 { theta + 2.0 * PI } else { theta };
+        let (cos, sin) = (p.x / rho, p.y / rho);
+        LocalHit {
+            point: p,
+            outward_normal: (p - self.major_radius * Vec3::new(cos, sin, 0.0)).unit_vector(),
+            uv: (phi / self.phi_max, theta / (2.0 * PI)),
+            dpdu: self.phi_max * Vec3::new(-p.y, p.x, 0.0),
+            dpdv: 2.0 * PI * Vec3::new(-p.z * cos, -p.z * sin, rho - self.major_radius),
+        }
+    }
+
+    /// Returns distances along `ray` within [`t_min`, `t_max`] and local points where it crosses
+    /// the full ring, in ascending order.
+    fn crossings(&self, ray: &Ray, t_min: f64, t_max: f64) -> Vec<(f64, Vec3)> {
+        let (o, d) = self.placement.to_local(ray);
+        let local_ray = Ray { origin: o, direction: d, time: ray.time, wavelength: None, differentials: None };
+        // Slightly enlarged bounds keep roots where the ray touches them inside the search interval
+        let bounds = self.local_bounds();
+        let margin = 1.0e-6 * bounds.max;
+        let Some((t0, t1)) = Aabb::new(bounds.min - margin, bounds.max + margin).clip(&local_ray, t_min, t_max) else {
+            return vec![];
+        };
+
+        // Solve for the distance s along the unit direction from the entry into the bounds, which
+        // keeps the coefficients well scaled
+        let length = d.length();
+        let d = d / length;
+        let o = o + t0 * length * d;
+        let (major, minor) = (self.major_radius * self.major_radius, self.minor_radius * self.minor_radius);
+        // (|p|^2 + R^2 - r^2)^2 = 4 R^2 (x^2 + y^2) with |p(s)|^2 = s^2 + 2 f s + |o|^2
+        let f = o.dot(d);
+        let h = o.dot(o) + major - minor;
+        let coefficients = [
+            h * h - 4.0 * major * (o.x * o.x + o.y * o.y),
+            4.0 * f * h - 8.0 * major * (o.x * d.x + o.y * d.y),
+            4.0 * f * f + 2.0 * h - 4.0 * major * (d.x * d.x + d.y * d.y),
+            4.0 * f,
+            1.0,
+        ];
+        polynomial_roots(&coefficients, 0.0, (t1 - t0) * length).into_iter().map(|s| (t0 + s / length, o + s * d)).collect()
+    }
+}
+
+impl Hittable for Torus {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Hit<'_>> {
+        self.crossings(ray, t_min, t_max).into_iter().find_map(|(t, p)| {
+            let phi = azimuth(p);
+            (phi <= self.phi_max).then(|| self.placement.hit(ray, t, self.local_hit(p, phi), self.material.as_ref()))
+        })
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.placement.bounding_box(self.local_bounds()))
+    }
+
+    fn describe(&self, materials: &mut MaterialTable) -> Option<Node> {
+        Some(self.placement.describe(Node::new("torus"))
+            .with("major_radius", self.major_radius)
+            .with("minor_radius", self.minor_radius)
+            .with("phi_max", self.phi_max)
+            .with("material", materials.name(&self.material)?))
+    }
+}
+
+impl Solid for Torus {
+    /// Returns no intervals unless the ring [is closed](Self::is_closed).
+    fn intervals(&self, ray: &Ray) -> Vec<Interval<'_>> {
+        if !self.is_closed() {
+            return vec![];
+        }
+        let hit = |(t, p): (f64, Vec3)| self.placement.hit(ray, t, self.local_hit(p, azimuth(p)), self.material.as_ref());
+        // Crossings alternate between entering and leaving the tube, a touching ray may leave one out
+        self.crossings(ray, -f64::MAX, f64::MAX).chunks_exact(2)
+            .map(|pair| Interval { enter: hit(pair[0]), exit: hit(pair[1]) })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+
+    #[test]
+    fn test_torus() {
+        let material = Arc::new(Lambertian::new(Vec3::new(0.5, 0.5, 0.5)));
+        let torus = Torus::new(Placement::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 1.0)), 2.0, 0.5, material);
+        let ray = Ray { origin: Vec3::new(-5.0, 0.0, 0.0), direction: Vec3::new(2.0, 0.0, 0.0), time: 0.0, wavelength: None, differentials: None };
+        // Tube crossed at x = -2.5, -1.5, 1.5 and 2.5
+        for (t_min, t, front_face) in [(0.0, 1.25, true), (1.3, 1.75, false), (1.8, 3.25, true), (3.3, 3.75, false)] {
+            let hit = torus.hit(&ray, t_min, f64::MAX).unwrap();
+            assert!((hit.t - t).abs() < 1e-9 && hit.front_face == front_face, "{} {}", hit.t, hit.front_face);
+            assert!((hit.normal - Vec3::new(-1.0, 0.0, 0.0)).length() < 1e-9);
+        }
+        assert!(torus.hit(&ray, 3.8, f64::MAX).is_none());
+
+        // Top of the tube, a quarter around it
+        let down = Ray { origin: Vec3::new(0.0, 2.0, 3.0), direction: Vec3::new(0.0, 0.0, -1.0), ..ray };
+        let hit = torus.hit(&down, 0.0, f64::MAX).unwrap();
+        assert!((hit.t - 2.5).abs() < 1e-9 && (hit.u - 0.25).abs() < 1e-9 && (hit.v - 0.25).abs() < 1e-9);
+
+        // Quarter ring only covers positive x and y
+        let quarter = Torus { phi_max: 0.5 * PI, ..torus };
+        assert!(quarter.hit(&ray, 0.0, f64::MAX).unwrap().t > 3.0);
+        let bounds = quarter.bounding_box().unwrap();
+        assert_eq!((bounds.min, bounds.max), (Vec3::new(-2.5, -2.5, -0.5), Vec3::new(2.5, 2.5, 0.5)));
+    }
+}
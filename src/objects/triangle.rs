@@ -0,0 +1,109 @@
+use std::sync::Arc;
+
+use crate::frame::ensure_valid_reflection;
+use crate::material::{Material, MaterialTable};
+use crate::objects::{Aabb, Hit, Hittable};
+use crate::scene::{Node, Value};
+use crate::{Frame, Ray, Vec3};
+
+pub struct Triangle {
+    pub vertices: [Vec3; 3],
+    /// Texture coordinates of the vertices
+    pub uvs: [(f64, f64); 3],
+    /// Colors of the vertices, interpolated into [Hit::color]
+    pub colors: Option<[Vec3; 3]>,
+    /// Normals of the vertices, interpolated into the shading normal for smooth shading
+    pub normals: Option<[Vec3; 3]>,
+    pub material: Arc<dyn Material>,
+}
+
+impl Triangle {
+    /// Returns triangle with texture coordinates (0, 0), (1, 0) and (0, 1).
+    pub fn new(v0: Vec3, v1: Vec3, v2: Vec3, material: Arc<dyn Material>) -> Self {
+        Triangle { vertices: [v0, v1, v2], uvs: [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)], colors: None, normals: None, material }
+    }
+
+    /// Returns partial derivatives dp/du and dp/dv on the triangle's plane, an arbitrary
+    /// orthonormal pair for degenerate texture coordinates.
+    fn derivatives(&self, normal: Vec3) -> (Vec3, Vec3) {
+        let [p0, p1, p2] = self.vertices;
+        let [uv0, uv1, uv2] = self.uvs;
+        let (du02, dv02) = (uv0.0 - uv2.0, uv0.1 - uv2.1);
+        let (du12, dv12) = (uv1.0 - uv2.0, uv1.1 - uv2.1);
+        let determinant = du02 * dv12 - dv02 * du12;
+        if determinant.abs() < 1.0e-12 {
+            let frame = Frame::from_normal(normal);
+            return (frame.s, frame.t);
+        }
+        let dpdu = (dv12 * (p0 - p2) - dv02 * (p1 - p2)) / determinant;
+        let dpdv = (du02 * (p1 - p2) - du12 * (p0 - p2)) / determinant;
+        (dpdu, dpdv)
+    }
+}
+
+impl Hittable for Triangle {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Hit<'_>> {
+        let (t, b1, b2) = intersect_triangle(ray, self.vertices, t_min, t_max)?;
+        let b0 = 1.0 - b1 - b2;
+        let [uv0, uv1, uv2] = self.uvs;
+        let u = b0 * uv0.0 + b1 * uv1.0 + b2 * uv2.0;
+        let v = b0 * uv0.1 + b1 * uv1.1 + b2 * uv2.1;
+
+        let [p0, p1, p2] = self.vertices;
+        let outward_normal = (p1 - p0).cross(p2 - p0).unit_vector();
+        let color = self.colors.map(|[c0, c1, c2]| b0 * c0 + b1 * c1 + b2 * c2);
+        let derivatives = self.derivatives(outward_normal);
+        let mut hit = Hit { color, ..Hit::new(ray, t, outward_normal, (u, v), derivatives, self.material.as_ref()) };
+        if let Some([n0, n1, n2]) = self.normals {
+            let n = (b0 * n0 + b1 * n1 + b2 * n2).unit_vector();
+            let n = if hit.front_face { n } else { -n };
+            let n = ensure_valid_reflection(hit.normal, -ray.direction.unit_vector(), n);
+            hit.shading = Frame::from_normal_tangent(n, derivatives.0);
+        }
+        Some(hit)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(Aabb::from_points(self.vertices))
+    }
+
+    fn describe(&self, materials: &mut MaterialTable) -> Option<Node> {
+        let uvs = self.uvs.iter().flat_map(|&(u, v)| [u, v]).collect();
+        let mut node = Node::new("triangle").with("vertices", self.vertices).with("uvs", Value::Numbers(uvs));
+        if let Some(colors) = self.colors {
+            node = node.with("colors", colors);
+        }
+        if let Some(normals) = self.normals {
+            node = node.with("normals", normals);
+        }
+        Some(node.with("material", materials.name(&self.material)?))
+    }
+}
+
+/// Möller-Trumbore intersection, returns distance and barycentric coordinates of the second and
+/// third vertex.
+pub(crate) fn intersect_triangle(ray: &Ray, [p0, p1, p2]: [Vec3; 3], t_min: f64, t_max: f64) -> Option<(f64, f64, f64)> {
+    let edge1 = p1 - p0;
+    let edge2 = p2 - p0;
+    let pvec = ray.direction.cross(edge2);
+    let determinant = edge1.dot(pvec);
+    if determinant.abs() < 1.0e-12 {
+        return None;
+    }
+    let inv_determinant = 1.0 / determinant;
+    let tvec = ray.origin - p0;
+    let b1 = tvec.dot(pvec) * inv_determinant;
+    if !(0.0..=1.0).contains(&b1) {
+        return None;
+    }
+    let qvec = tvec.cross(edge1);
+    let b2 = ray.direction.dot(qvec) * inv_determinant;
+    if b2 < 0.0 || b1 + b2 > 1.0 {
+        return None;
+    }
+    let t = edge2.dot(qvec) * inv_determinant;
+    if t < t_min || t_max < t {
+        return None;
+    }
+    Some((t, b1, b2))
+}
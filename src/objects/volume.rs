@@ -0,0 +1,82 @@
+use std::sync::Arc;
+
+use crate::material::{Material, MaterialTable};
+use crate::objects::{Aabb, Hit, Hittable, Interval, Solid};
+use crate::scene::Node;
+use crate::{Ray, Vec3};
+
+/// Axis-aligned box. Encloses participating media with a [crate::material::Volumetric] material
+/// whose density field spans the same bounds, but also serves as solid box, e.g. for CSG.
+pub struct Volume {
+    pub min: Vec3,
+    pub max: Vec3,
+    pub material: Arc<dyn Material>,
+}
+
+impl Volume {
+    /// Returns distances where the line of `ray` enters and leaves the box (slab test), each with
+    /// the axis of the face crossed there.
+    fn slabs(&self, ray: &Ray) -> Option<((f64, usize), (f64, usize))> {
+        let (mut t_enter, mut enter_axis) = (f64::NEG_INFINITY, 0);
+        let (mut t_exit, mut exit_axis) = (f64::INFINITY, 0);
+        for a in 0..3 {
+            let inverse = 1.0 / ray.direction[a];
+            let (t0, t1) = ((self.min[a] - ray.origin[a]) * inverse, (self.max[a] - ray.origin[a]) * inverse);
+            let (t0, t1) = if inverse < 0.0 { (t1, t0) } else { (t0, t1) };
+            if t0 > t_enter {
+                (t_enter, enter_axis) = (t0, a);
+            }
+            if t1 < t_exit {
+                (t_exit, exit_axis) = (t1, a);
+            }
+        }
+        if t_enter > t_exit {
+            return None;
+        }
+        Some(((t_enter, enter_axis), (t_exit, exit_axis)))
+    }
+
+    /// Returns hit of `ray` at distance `t` on a face perpendicular to `axis`.
+    fn face_hit(&self, ray: &Ray, t: f64, axis: usize) -> Hit<'_> {
+        let point = ray.at(t);
+        let size = self.max - self.min;
+        let unit = |a: usize| Vec3::new((a == 0) as u8 as f64, (a == 1) as u8 as f64, (a == 2) as u8 as f64);
+        let outward_normal = if point[axis] - self.min[axis] < self.max[axis] - point[axis] { -unit(axis) } else { unit(axis) };
+        let (a, b) = ((axis + 1) % 3, (axis + 2) % 3);
+        let uv = ((point[a] - self.min[a]) / size[a], (point[b] - self.min[b]) / size[b]);
+        Hit::new(ray, t, outward_normal, uv, (size[a] * unit(a), size[b] * unit(b)), self.material.as_ref())
+    }
+}
+
+impl Hittable for Volume {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Hit<'_>> {
+        let ((t_enter, enter_axis), (t_exit, exit_axis)) = self.slabs(ray)?;
+        let (t, axis) = if (t_min..=t_max).contains(&t_enter) {
+            (t_enter, enter_axis)
+        } else if (t_min..=t_max).contains(&t_exit) {
+            (t_exit, exit_axis)
+        } else {
+            return None;
+        };
+        Some(self.face_hit(ray, t, axis))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(Aabb::new(self.min, self.max))
+    }
+
+    fn describe(&self, materials: &mut MaterialTable) -> Option<Node> {
+        Some(Node::new("volume").with("min", self.min).with("max", self.max).with("material", materials.name(&self.material)?))
+    }
+}
+
+impl Solid for Volume {
+    fn intervals(&self, ray: &Ray) -> Vec<Interval<'_>> {
+        match self.slabs(ray) {
+            Some(((t_enter, enter_axis), (t_exit, exit_axis))) if t_enter < t_exit => {
+                vec![Interval { enter: self.face_hit(ray, t_enter, enter_axis), exit: self.face_hit(ray, t_exit, exit_axis) }]
+            }
+            _ => vec![],
+        }
+    }
+}
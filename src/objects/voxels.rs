@@ -0,0 +1,221 @@
+use std::sync::Arc;
+
+use crate::material::{Material, MaterialTable};
+use crate::objects::{Aabb, Hit, Hittable};
+use crate::scene::{Node, Value};
+use crate::{Ray, Vec3};
+
+/// Dense grid of cubic voxels, one byte each: value 0 is empty and value v is filled with material
+/// `palette[v - 1]` (empty if the palette has no such entry). Rays step through the cells with a 3D
+/// DDA (Amanatides and Woo 1987) and hit the faces where the value changes, so neighbouring voxels
+/// of the same value form one solid without inner faces (e.g. a block of glass). Texture
+/// coordinates run across each voxel face like on a [crate::objects::Volume].
+pub struct Voxels {
+    /// Corner with the smallest coordinates
+    pub origin: Vec3,
+    /// Edge length of a voxel
+    pub voxel_size: f64,
+    pub palette: Vec<Arc<dyn Material>>,
+    dimensions: [usize; 3],
+    values: Vec<u8>,
+}
+
+impl Voxels {
+    /// Returns empty grid of `dimensions` voxels along x, y and z.
+    pub fn new(origin: Vec3, voxel_size: f64, dimensions: [usize; 3], palette: Vec<Arc<dyn Material>>) -> Self {
+        let values = vec![0; dimensions.iter().product()];
+        Voxels { origin, voxel_size, palette, dimensions, values }
+    }
+
+    /// Returns grid with the value of each voxel given by `value(x, y, z)` of its indices.
+    pub fn from_fn(origin: Vec3, voxel_size: f64, dimensions: [usize; 3], palette: Vec<Arc<dyn Material>>, value: impl Fn(usize, usize, usize) -> u8) -> Self {
+        let mut grid = Voxels::new(origin, voxel_size, dimensions, palette);
+        for z in 0..dimensions[2] {
+            for y in 0..dimensions[1] {
+                for x in 0..dimensions[0] {
+                    grid.set([x, y, z], value(x, y, z));
+                }
+            }
+        }
+        grid
+    }
+
+    pub fn dimensions(&self) -> [usize; 3] {
+        self.dimensions
+    }
+
+    fn index(&self, [x, y, z]: [usize; 3]) -> usize {
+        assert!(x < self.dimensions[0] && y < self.dimensions[1] && z < self.dimensions[2], "voxel out of bounds");
+        (z * self.dimensions[1] + y) * self.dimensions[0] + x
+    }
+
+    pub fn get(&self, voxel: [usize; 3]) -> u8 {
+        self.values[self.index(voxel)]
+    }
+
+    pub fn set(&mut self, voxel: [usize; 3], value: u8) {
+        let index = self.index(voxel);
+        self.values[index] = value;
+    }
+
+    fn material(&self, value: u8) -> Option<&dyn Material> {
+        (value > 0).then(|| self.palette.get(value as usize - 1)).flatten().map(|m| m.as_ref())
+    }
+
+    /// Returns value at signed voxel indices, 0 outside the grid.
+    fn value_at(&self, cell: [i64; 3]) -> u8 {
+        let inside = (0..3).all(|a| (0..self.dimensions[a] as i64).contains(&cell[a]));
+        if !inside {
+            return 0;
+        }
+        self.values[self.index(cell.map(|c| c as usize))]
+    }
+
+    fn max(&self) -> Vec3 {
+        let [x, y, z] = self.dimensions.map(|d| d as f64 * self.voxel_size);
+        self.origin + Vec3::new(x, y, z)
+    }
+
+    /// Returns hit at distance `t` on the face perpendicular to `axis` between a voxel of `from`
+    /// and the next one along the ray of `to`, `None` if neither is filled.
+    fn face_hit(&self, ray: &Ray, t: f64, axis: usize, from: u8, to: u8) -> Option<Hit<'_>> {
+        let step = ray.direction[axis].signum();
+        let unit = |a: usize| Vec3::new((a == 0) as u8 as f64, (a == 1) as u8 as f64, (a == 2) as u8 as f64);
+        // Entering a filled voxel shows its front face, leaving one into empty space its back face
+        let (material, outward_normal) = match self.material(to) {
+            Some(material) => (material, -step * unit(axis)),
+            None => (self.material(from)?, step * unit(axis)),
+        };
+        let point = ray.at(t);
+        let (a, b) = ((axis + 1) % 3, (axis + 2) % 3);
+        let fraction = |a: usize| {
+            let x = (point[a] - self.origin[a]) / self.voxel_size;
+            x - x.floor()
+        };
+        let derivatives = (self.voxel_size * unit(a), self.voxel_size * unit(b));
+        Some(Hit::new(ray, t, outward_normal, (fraction(a), fraction(b)), derivatives, material))
+    }
+}
+
+impl Hittable for Voxels {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Hit<'_>> {
+        let (min, max) = (self.origin, self.max());
+        let (mut t_enter, mut enter_axis) = (t_min, None);
+        let mut t_exit = t_max;
+        for a in 0..3 {
+            let inverse = 1.0 / ray.direction[a];
+            let (t0, t1) = ((min[a] - ray.origin[a]) * inverse, (max[a] - ray.origin[a]) * inverse);
+            let (t0, t1) = if inverse < 0.0 { (t1, t0) } else { (t0, t1) };
+            if t0 > t_enter {
+                (t_enter, enter_axis) = (t0, Some(a));
+            }
+            t_exit = t_exit.min(t1);
+        }
+        if t_enter > t_exit {
+            return None;
+        }
+
+        let start = ray.at(t_enter);
+        let mut cell: [i64; 3] = std::array::from_fn(|a| {
+            let index = ((start[a] - self.origin[a]) / self.voxel_size).floor() as i64;
+            index.clamp(0, self.dimensions[a] as i64 - 1)
+        });
+        let mut value = self.value_at(cell);
+        // Rays from outside may hit the grid's boundary right away
+        if let Some(axis) = enter_axis {
+            if let Some(hit) = self.face_hit(ray, t_enter, axis, 0, value) {
+                return Some(hit);
+            }
+        }
+
+        // Distances to the next cell boundary along each axis and between boundaries
+        let step = [0, 1, 2].map(|a| if ray.direction[a] < 0.0 { -1 } else { 1 });
+        let t_delta = [0, 1, 2].map(|a| self.voxel_size / ray.direction[a].abs());
+        let mut t_next: [f64; 3] = std::array::from_fn(|a| {
+            if ray.direction[a] == 0.0 {
+                return f64::INFINITY;
+            }
+            let boundary = self.origin[a] + (cell[a] + (step[a] > 0) as i64) as f64 * self.voxel_size;
+            (boundary - ray.origin[a]) / ray.direction[a]
+        });
+        loop {
+            let axis = if t_next[0] < t_next[1] && t_next[0] < t_next[2] { 0 } else if t_next[1] < t_next[2] { 1 } else { 2 };
+            let t = t_next[axis];
+            if t > t_max {
+                return None;
+            }
+            cell[axis] += step[axis];
+            let next = self.value_at(cell);
+            if next != value && t >= t_min {
+                if let Some(hit) = self.face_hit(ray, t, axis, value, next) {
+                    return Some(hit);
+                }
+            }
+            if !(0..self.dimensions[axis] as i64).contains(&cell[axis]) {
+                return None;
+            }
+            value = next;
+            t_next[axis] += t_delta[axis];
+        }
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(Aabb::new(self.origin, self.max()))
+    }
+
+    fn describe(&self, materials: &mut MaterialTable) -> Option<Node> {
+        let palette = self.palette.iter()
+            .map(|material| Some(Node::new("entry").with("material", materials.name(material)?)))
+            .collect::<Option<Vec<_>>>()?;
+        Some(Node::new("voxels")
+            .with("origin", self.origin)
+            .with("voxel_size", self.voxel_size)
+            .with("dimensions", Value::Numbers(self.dimensions.map(|d| d as f64).to_vec()))
+            .with("values", Value::Numbers(self.values.iter().map(|&v| v as f64).collect()))
+            .with("palette", palette))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::DiffuseLight;
+
+    #[test]
+    fn test_voxel_grid() {
+        let palette: Vec<Arc<dyn Material>> = (1..=2)
+            .map(|i| Arc::new(DiffuseLight { emit: Vec3::new(i as f64, 0.0, 0.0) }) as Arc<dyn Material>)
+            .collect();
+        let mut grid = Voxels::new(Vec3::new(0.0, 0.0, 0.0), 0.5, [4, 4, 4], palette);
+        // Two voxels of the first material next to one of the second along x, at y = z = 1
+        grid.set([1, 1, 1], 1);
+        grid.set([2, 1, 1], 1);
+        grid.set([3, 1, 1], 2);
+        let ray = Ray { origin: Vec3::new(-1.0, 0.75, 0.75), direction: Vec3::new(1.0, 0.0, 0.0), time: 0.0, wavelength: None, differentials: None };
+        let emitted = |hit: &Hit| hit.material.emitted(&ray, hit).x;
+
+        let hit = grid.hit(&ray, 0.0, f64::MAX).unwrap();
+        assert!(hit.t == 1.5 && hit.front_face && emitted(&hit) == 1.0);
+        assert_eq!(hit.normal, Vec3::new(-1.0, 0.0, 0.0));
+        assert!(hit.u == 0.5 && hit.v == 0.5);
+        // No face between voxels of equal value
+        let hit = grid.hit(&ray, 1.6, f64::MAX).unwrap();
+        assert!(hit.t == 2.5 && hit.front_face && emitted(&hit) == 2.0);
+        // Back face where the ray leaves the grid
+        let hit = grid.hit(&ray, 2.6, f64::MAX).unwrap();
+        assert!(hit.t == 3.0 && !hit.front_face && emitted(&hit) == 2.0);
+        assert!(grid.hit(&ray, 3.1, f64::MAX).is_none());
+
+        // Ray starting inside a filled voxel sees its back face only when leaving into empty space
+        let up = Ray { origin: Vec3::new(0.75, 0.6, 0.75), direction: Vec3::new(0.0, 1.0, 0.0), ..ray };
+        let hit = grid.hit(&up, 0.0, f64::MAX).unwrap();
+        assert!((hit.t - 0.4).abs() < 1e-12 && !hit.front_face && emitted(&hit) == 1.0);
+
+        // Diagonal ray from above the grid enters through the top
+        let diagonal = Ray { origin: Vec3::new(0.2, 3.0, 0.6), direction: Vec3::new(0.5, -1.0, 0.3), ..ray };
+        let filled = Voxels::from_fn(Vec3::new(0.0, 0.0, 0.0), 0.5, [4, 4, 4], grid.palette.clone(), |_, y, _| (y == 0) as u8);
+        let hit = filled.hit(&diagonal, 0.0, f64::MAX).unwrap();
+        assert!((hit.point.y - 0.5).abs() < 1e-12 && hit.normal == Vec3::new(0.0, 1.0, 0.0));
+        assert!(grid.hit(&diagonal, 0.0, f64::MAX).is_none());
+    }
+}
@@ -0,0 +1,359 @@
+//! Reader for PLY (Stanford polygon) files with point clouds or triangle meshes.
+use std::fs;
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::material::Material;
+use crate::objects::Triangle;
+use crate::texture::srgb_to_linear;
+use crate::Vec3;
+
+/// Vertices with their optional attributes and triangles of a PLY file.
+#[derive(Clone, Debug, Default)]
+pub struct Ply {
+    pub positions: Vec<Vec3>,
+    /// From properties nx, ny and nz
+    pub normals: Option<Vec<Vec3>>,
+    /// From properties red, green and blue, linear (integer values are taken as sRGB)
+    pub colors: Option<Vec<Vec3>>,
+    /// From property radius, e.g. of splats
+    pub radii: Option<Vec<f64>>,
+    /// From properties u and v (or s and t, texture_u and texture_v)
+    pub uvs: Option<Vec<(f64, f64)>>,
+    /// Vertex indices of the faces, polygons are split into triangle fans
+    pub triangles: Vec<[usize; 3]>,
+}
+
+impl Ply {
+    /// Returns the faces as triangles with the vertices' texture coordinates, colors and normals.
+    pub fn triangle_mesh(&self, material: Arc<dyn Material>) -> Vec<Triangle> {
+        self.triangles.iter().map(|&[a, b, c]| {
+            let mut triangle = Triangle::new(self.positions[a], self.positions[b], self.positions[c], material.clone());
+            if let Some(uvs) = &self.uvs {
+                triangle.uvs = [uvs[a], uvs[b], uvs[c]];
+            }
+            triangle.colors = self.colors.as_ref().map(|colors| [colors[a], colors[b], colors[c]]);
+            triangle.normals = self.normals.as_ref().map(|normals| [normals[a], normals[b], normals[c]]);
+            triangle
+        }).collect()
+    }
+}
+
+pub fn read<P: AsRef<Path>>(path: P) -> std::io::Result<Ply> {
+    parse(&fs::read(path)?)
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Scalar {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl Scalar {
+    fn parse(name: &str) -> std::io::Result<Self> {
+        Ok(match name {
+            "char" | "int8" => Scalar::I8,
+            "uchar" | "uint8" => Scalar::U8,
+            "short" | "int16" => Scalar::I16,
+            "ushort" | "uint16" => Scalar::U16,
+            "int" | "int32" => Scalar::I32,
+            "uint" | "uint32" => Scalar::U32,
+            "float" | "float32" => Scalar::F32,
+            "double" | "float64" => Scalar::F64,
+            _ => return Err(invalid(format!("unknown property type {}", name))),
+        })
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Scalar::I8 | Scalar::U8 => 1,
+            Scalar::I16 | Scalar::U16 => 2,
+            Scalar::I32 | Scalar::U32 | Scalar::F32 => 4,
+            Scalar::F64 => 8,
+        }
+    }
+
+    /// Returns largest value of integer types, which colors are normalized by.
+    fn max(self) -> Option<f64> {
+        match self {
+            Scalar::U8 | Scalar::I8 => Some(255.0),
+            Scalar::U16 | Scalar::I16 => Some(65535.0),
+            Scalar::U32 | Scalar::I32 => Some(u32::MAX as f64),
+            Scalar::F32 | Scalar::F64 => None,
+        }
+    }
+}
+
+enum Property {
+    Scalar { name: String, kind: Scalar },
+    List { name: String, count: Scalar, item: Scalar },
+}
+
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+/// Parses ASCII or binary PLY data. Elements other than vertex and face are skipped.
+pub fn parse(bytes: &[u8]) -> std::io::Result<Ply> {
+    let (format, elements, body) = parse_header(bytes)?;
+    let mut values = Values { bytes: &bytes[body..], pos: 0, format };
+    let mut ply = Ply::default();
+    for element in elements.iter() {
+        match element.name.as_str() {
+            "vertex" => read_vertices(element, &mut values, &mut ply)?,
+            "face" => read_faces(element, &mut values, &mut ply)?,
+            _ => {
+                for _ in 0..element.count {
+                    for property in element.properties.iter() {
+                        values.skip(property)?;
+                    }
+                }
+            }
+        }
+    }
+    let count = ply.positions.len();
+    if let Some(index) = ply.triangles.iter().flatten().find(|&&i| i >= count) {
+        return Err(invalid(format!("vertex index {} out of range", index)));
+    }
+    Ok(ply)
+}
+
+/// Returns format, elements and the offset of the body.
+fn parse_header(bytes: &[u8]) -> std::io::Result<(Format, Vec<Element>, usize)> {
+    let end = bytes.windows(10).position(|w| w == b"end_header")
+        .ok_or_else(|| invalid("missing end_header".to_string()))?;
+    // The body starts after the line break following end_header
+    let body = bytes[end..].iter().position(|&b| b == b'\n').map_or(bytes.len(), |i| end + i + 1);
+    let header = String::from_utf8_lossy(&bytes[..end]);
+    let mut lines = header.lines().map(str::trim);
+    if lines.next() != Some("ply") {
+        return Err(invalid("not a PLY file".to_string()));
+    }
+
+    let mut format = None;
+    let mut elements: Vec<Element> = vec![];
+    for line in lines {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            ["format", name, _version] => {
+                format = Some(match *name {
+                    "ascii" => Format::Ascii,
+                    "binary_little_endian" => Format::BinaryLittleEndian,
+                    "binary_big_endian" => Format::BinaryBigEndian,
+                    _ => return Err(invalid(format!("unknown format {}", name))),
+                });
+            }
+            ["element", name, count] => {
+                let count = count.parse().map_err(|_| invalid(format!("invalid element count {}", count)))?;
+                elements.push(Element { name: name.to_string(), count, properties: vec![] });
+            }
+            ["property", "list", count, item, name] => {
+                let element = elements.last_mut().ok_or_else(|| invalid("property before element".to_string()))?;
+                element.properties.push(Property::List { name: name.to_string(), count: Scalar::parse(count)?, item: Scalar::parse(item)? });
+            }
+            ["property", kind, name] => {
+                let element = elements.last_mut().ok_or_else(|| invalid("property before element".to_string()))?;
+                element.properties.push(Property::Scalar { name: name.to_string(), kind: Scalar::parse(kind)? });
+            }
+            ["comment", ..] | ["obj_info", ..] | [] => {}
+            _ => return Err(invalid(format!("invalid header line: {}", line))),
+        }
+    }
+    let format = format.ok_or_else(|| invalid("missing format".to_string()))?;
+    Ok((format, elements, body))
+}
+
+fn read_vertices(element: &Element, values: &mut Values, ply: &mut Ply) -> std::io::Result<()> {
+    let find = |name: &str| element.properties.iter().position(|p| matches!(p, Property::Scalar { name: n, .. } if n == name));
+    let find_any = |names: [&str; 3]| names.into_iter().find_map(find);
+    let [x, y, z] = ["x", "y", "z"].map(find);
+    let (Some(x), Some(y), Some(z)) = (x, y, z) else {
+        return Err(invalid("vertex without x, y and z".to_string()));
+    };
+    let normal = match ["nx", "ny", "nz"].map(find) {
+        [Some(nx), Some(ny), Some(nz)] => Some([nx, ny, nz]),
+        _ => None,
+    };
+    let color = match ["red", "green", "blue"].map(find) {
+        [Some(r), Some(g), Some(b)] => Some([r, g, b]),
+        _ => None,
+    };
+    let color_max = color.and_then(|[r, _, _]| match element.properties[r] {
+        Property::Scalar { kind, .. } => kind.max(),
+        Property::List { .. } => None,
+    });
+    let radius = find("radius");
+    let uv = find_any(["u", "s", "texture_u"]).zip(find_any(["v", "t", "texture_v"]));
+
+    let mut normals = vec![];
+    let mut colors = vec![];
+    let mut radii = vec![];
+    let mut uvs = vec![];
+    let mut row = vec![0.0; element.properties.len()];
+    for _ in 0..element.count {
+        for (value, property) in row.iter_mut().zip(element.properties.iter()) {
+            match property {
+                Property::Scalar { kind, .. } => *value = values.next(*kind)?,
+                Property::List { .. } => values.skip(property)?,
+            }
+        }
+        ply.positions.push(Vec3::new(row[x], row[y], row[z]));
+        if let Some([nx, ny, nz]) = normal {
+            normals.push(Vec3::new(row[nx], row[ny], row[nz]));
+        }
+        if let Some([r, g, b]) = color {
+            let channel = |i: usize| color_max.map_or(row[i], |max| srgb_to_linear(row[i] / max));
+            colors.push(Vec3::new(channel(r), channel(g), channel(b)));
+        }
+        if let Some(radius) = radius {
+            radii.push(row[radius]);
+        }
+        if let Some((u, v)) = uv {
+            uvs.push((row[u], row[v]));
+        }
+    }
+    ply.normals = normal.map(|_| normals);
+    ply.colors = color.map(|_| colors);
+    ply.radii = radius.map(|_| radii);
+    ply.uvs = uv.map(|_| uvs);
+    Ok(())
+}
+
+fn read_faces(element: &Element, values: &mut Values, ply: &mut Ply) -> std::io::Result<()> {
+    for _ in 0..element.count {
+        for property in element.properties.iter() {
+            match property {
+                Property::List { name, count, item } if name == "vertex_indices" || name == "vertex_index" => {
+                    let n = values.next(*count)? as usize;
+                    let indices = (0..n).map(|_| vertex_index(values.next(*item)?)).collect::<std::io::Result<Vec<_>>>()?;
+                    ply.triangles.extend((2..n).map(|i| [indices[0], indices[i - 1], indices[i]]));
+                }
+                _ => values.skip(property)?,
+            }
+        }
+    }
+    Ok(())
+}
+
+fn vertex_index(i: f64) -> std::io::Result<usize> {
+    if i < 0.0 || i.fract() != 0.0 {
+        return Err(invalid(format!("invalid vertex index {}", i)));
+    }
+    Ok(i as usize)
+}
+
+/// Reads property values from the body.
+struct Values<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    format: Format,
+}
+
+impl Values<'_> {
+    fn next(&mut self, kind: Scalar) -> std::io::Result<f64> {
+        if self.format == Format::Ascii {
+            while self.pos < self.bytes.len() && self.bytes[self.pos].is_ascii_whitespace() {
+                self.pos += 1;
+            }
+            let start = self.pos;
+            while self.pos < self.bytes.len() && !self.bytes[self.pos].is_ascii_whitespace() {
+                self.pos += 1;
+            }
+            let token = String::from_utf8_lossy(&self.bytes[start..self.pos]);
+            return token.parse().map_err(|_| invalid(format!("expected number, found '{}'", token)));
+        }
+
+        let size = kind.size();
+        let mut raw = self.bytes.get(self.pos..self.pos + size)
+            .ok_or_else(|| invalid("unexpected end of data".to_string()))?
+            .to_vec();
+        self.pos += size;
+        if self.format == Format::BinaryBigEndian {
+            raw.reverse();
+        }
+        let array = |raw: &[u8]| -> [u8; 8] { std::array::from_fn(|i| raw.get(i).copied().unwrap_or(0)) };
+        let raw = array(&raw);
+        Ok(match kind {
+            Scalar::I8 => i8::from_le_bytes([raw[0]]) as f64,
+            Scalar::U8 => raw[0] as f64,
+            Scalar::I16 => i16::from_le_bytes([raw[0], raw[1]]) as f64,
+            Scalar::U16 => u16::from_le_bytes([raw[0], raw[1]]) as f64,
+            Scalar::I32 => i32::from_le_bytes([raw[0], raw[1], raw[2], raw[3]]) as f64,
+            Scalar::U32 => u32::from_le_bytes([raw[0], raw[1], raw[2], raw[3]]) as f64,
+            Scalar::F32 => f32::from_le_bytes([raw[0], raw[1], raw[2], raw[3]]) as f64,
+            Scalar::F64 => f64::from_le_bytes(raw),
+        })
+    }
+
+    fn skip(&mut self, property: &Property) -> std::io::Result<()> {
+        match property {
+            Property::Scalar { kind, .. } => {
+                self.next(*kind)?;
+            }
+            Property::List { count, item, .. } => {
+                for _ in 0..self.next(*count)? as usize {
+                    self.next(*item)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn invalid(message: String) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ascii() {
+        let ply = parse(b"ply\nformat ascii 1.0\ncomment quad\nelement vertex 4\nproperty float x\nproperty float y\nproperty float z\n\
+            property uchar red\nproperty uchar green\nproperty uchar blue\nelement face 1\nproperty list uchar int vertex_indices\n\
+            end_header\n0 0 0 255 0 0\n1 0 0 0 255 0\n1 1 0 0 0 255\n0 1 0 255 255 255\n4 0 1 2 3\n").unwrap();
+        assert_eq!(ply.positions[2], Vec3::new(1.0, 1.0, 0.0));
+        assert_eq!(ply.colors.as_ref().unwrap()[1], Vec3::new(0.0, 1.0, 0.0));
+        assert_eq!(ply.triangles, vec![[0, 1, 2], [0, 2, 3]]);
+        assert!(ply.normals.is_none() && ply.radii.is_none());
+        assert_eq!(ply.triangle_mesh(Arc::new(crate::material::Lambertian::new(Vec3::new(0.5, 0.5, 0.5)))).len(), 2);
+
+        assert!(parse(b"ply\nformat ascii 1.0\nelement vertex 1\nproperty float x\nproperty float y\nproperty float z\n\
+            element face 1\nproperty list uchar int vertex_indices\nend_header\n0 0 0\n3 0 1 2\n").is_err());
+        let negative = parse(b"ply\nformat ascii 1.0\nelement vertex 3\nproperty float x\nproperty float y\nproperty float z\n\
+            element face 1\nproperty list uchar int vertex_indices\nend_header\n0 0 0\n1 0 0\n0 1 0\n3 0 1 -1\n");
+        assert_eq!(negative.unwrap_err().to_string(), "invalid vertex index -1");
+    }
+
+    #[test]
+    fn test_parse_binary() {
+        let mut bytes = b"ply\nformat binary_little_endian 1.0\nelement vertex 2\nproperty float x\nproperty float y\n\
+            property float z\nproperty double radius\nelement camera 1\nproperty list uchar ushort data\nend_header\n".to_vec();
+        for (position, radius) in [([1.0f32, 2.0, 3.0], 0.5f64), ([-1.0, 0.0, 4.5], 0.25)] {
+            position.iter().for_each(|c| bytes.extend(c.to_le_bytes()));
+            bytes.extend(radius.to_le_bytes());
+        }
+        bytes.extend([2, 1, 0, 2, 0]);
+        let ply = parse(&bytes).unwrap();
+        assert_eq!(ply.positions, vec![Vec3::new(1.0, 2.0, 3.0), Vec3::new(-1.0, 0.0, 4.5)]);
+        assert_eq!(ply.radii, Some(vec![0.5, 0.25]));
+        assert!(parse(&bytes[..bytes.len() - 8]).is_err());
+    }
+}
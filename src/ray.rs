@@ -4,10 +4,35 @@ pub struct Ray {
     pub origin: Vec3,
     pub direction: Vec3,
     pub time: f64,
+    /// Hero wavelength in nm when rendering spectrally
+    pub wavelength: Option<f64>,
+    /// Offset rays through the neighbouring pixels, for filtering textures
+    pub differentials: Option<Differentials>,
+}
+
+/// Origins and directions of the rays one pixel to the right (x) and up (y) of a camera ray. The
+/// footprint of a pixel on a surface follows from where they intersect the tangent plane.
+#[derive(Copy, Clone, Debug)]
+pub struct Differentials {
+    pub rx_origin: Vec3,
+    pub rx_direction: Vec3,
+    pub ry_origin: Vec3,
+    pub ry_direction: Vec3,
 }
 
 impl Ray {
     pub fn at(&self, t: f64) -> Vec3 {
         self.origin + (self.direction * t)
     }
+
+    /// Scales the offset to the neighbouring rays by `scale`, e.g. to account for multiple samples
+    /// per pixel.
+    pub fn scale_differentials(&mut self, scale: f64) {
+        if let Some(d) = &mut self.differentials {
+            d.rx_origin = self.origin + scale * (d.rx_origin - self.origin);
+            d.ry_origin = self.origin + scale * (d.ry_origin - self.origin);
+            d.rx_direction = self.direction + scale * (d.rx_direction - self.direction);
+            d.ry_direction = self.direction + scale * (d.ry_direction - self.direction);
+        }
+    }
 }
@@ -1,37 +1,179 @@
 use std::io::{BufWriter, stdout, Write};
 
-use rand::prelude::ThreadRng;
-use rand::Rng;
+use rand::{Rng, RngCore};
 
 use crate::{Ray, Vec3};
 use crate::color::{BLACK, get_color, WHITE};
 use crate::configs::ImageConfig;
+use crate::medium::{Flight, MediumStack};
 use crate::objects::{Hittable, Hittables};
+use crate::spectrum::{N_WAVELENGTHS, sample_rgb, SampledWavelengths};
 
-fn ray_color(ray: Ray, world: &Hittables, rng: &mut ThreadRng, depth: i64) -> Vec3 {
+/// Scattering events in media after which random walks are continued by Russian roulette
+const ROULETTE_BOUNCES: usize = 16;
+/// Scattering events in media after which random walks are cut off
+const MAX_VOLUME_BOUNCES: usize = 1024;
+
+/// Decides whether a random walk in a medium continues after `volume_bounces` scattering events,
+/// returning the probability it survived with to divide `throughput` by.
+fn survival(throughput: f64, volume_bounces: usize, rng: &mut dyn RngCore) -> Option<f64> {
+    if volume_bounces > MAX_VOLUME_BOUNCES {
+        return None;
+    }
+    if volume_bounces <= ROULETTE_BOUNCES {
+        return Some(1.0);
+    }
+    let p = throughput.clamp(0.05, 1.0);
+    (rng.gen::<f64>() < p).then_some(p)
+}
+
+/// Traces path through the scene, `depth` limits the surface bounces. Scattering in media and
+/// surfaces hidden by media of higher priority don't count towards it.
+fn ray_color(mut ray: Ray, world: &Hittables, media: &mut MediumStack, rng: &mut dyn RngCore, depth: i64) -> Vec3 {
     if depth <= 0 {
-        BLACK
-    } else if let Some(hit) = world.hit(&ray, 0.001, f64::MAX) {
-        if let Some(scattered) = hit.material.scatter(&ray, &hit, rng) {
-            hit.material.attenuate(ray_color(scattered, world, rng, depth - 1))
+        return BLACK;
+    }
+    // Light emitted along the path through media so far and the fraction arriving from beyond
+    let mut radiance = BLACK;
+    let mut throughput = WHITE;
+    let mut volume_bounces = 0;
+    loop {
+        let Some(mut hit) = world.hit(&ray, 0.001, f64::MAX) else {
+            return radiance + throughput * background(&ray);
+        };
+        let (transmittance, medium_emitted) = match media.sample_flight(&ray, hit.t * ray.direction.length(), rng) {
+            Flight::Scatter { distance, direction, weight, emitted } => {
+                // Continue random walk inside the medium
+                radiance = radiance + throughput * emitted;
+                throughput = throughput * weight;
+                volume_bounces += 1;
+                let Some(p) = survival(throughput.x.max(throughput.y).max(throughput.z), volume_bounces, rng) else {
+                    return radiance;
+                };
+                throughput = throughput / p;
+                ray = Ray { origin: ray.origin + distance * ray.direction.unit_vector(), direction, differentials: None, ..ray };
+                continue;
+            }
+            Flight::Absorbed { emitted } => return radiance + throughput * emitted,
+            Flight::Surface { weight, emitted } => (weight, emitted),
+        };
+        radiance = radiance + throughput * medium_emitted;
+        throughput = throughput * transmittance;
+        hit.compute_footprint(&ray);
+        if !media.prepare(&mut hit, ray.wavelength) {
+            // Surface is hidden inside a medium of higher priority
+            ray = Ray { origin: hit.point, ..ray };
+            continue;
+        }
+        let emitted = hit.material.emitted(&ray, &hit);
+        let color = if let Some(scatter) = hit.material.scatter(&ray, &hit, rng) {
+            if scatter.ray.direction.dot(hit.normal) < 0.0 {
+                media.cross(&hit);
+            }
+            emitted + scatter.attenuation * ray_color(scatter.ray, world, media, rng, depth - 1)
         } else {
-            BLACK
+            emitted
+        };
+        return radiance + throughput * color;
+    }
+}
+
+/// Traces path carrying radiance at the sampled wavelengths instead of RGB, like [ray_color].
+fn spectral_ray_color(
+    mut ray: Ray,
+    world: &Hittables,
+    media: &mut MediumStack,
+    wavelengths: &mut SampledWavelengths,
+    rng: &mut dyn RngCore,
+    depth: i64,
+) -> [f64; N_WAVELENGTHS] {
+    if depth <= 0 {
+        return [0.0; N_WAVELENGTHS];
+    }
+    let mut radiance = [0.0; N_WAVELENGTHS];
+    let mut throughput = [1.0; N_WAVELENGTHS];
+    let mut volume_bounces = 0;
+    loop {
+        let Some(mut hit) = world.hit(&ray, 0.001, f64::MAX) else {
+            let background = sample_rgb(background(&ray), wavelengths);
+            return std::array::from_fn(|i| radiance[i] + throughput[i] * background[i]);
+        };
+        let (transmittance, medium_emitted) = match media.sample_flight(&ray, hit.t * ray.direction.length(), rng) {
+            Flight::Scatter { distance, direction, weight, emitted } => {
+                let weight = sample_rgb(weight, wavelengths);
+                let emitted = sample_rgb(emitted, wavelengths);
+                radiance = std::array::from_fn(|i| radiance[i] + throughput[i] * emitted[i]);
+                throughput = std::array::from_fn(|i| throughput[i] * weight[i]);
+                volume_bounces += 1;
+                let Some(p) = survival(throughput.iter().fold(0.0, |a, &b| a.max(b)), volume_bounces, rng) else {
+                    return radiance;
+                };
+                throughput = throughput.map(|t| t / p);
+                ray = Ray { origin: ray.origin + distance * ray.direction.unit_vector(), direction, differentials: None, ..ray };
+                continue;
+            }
+            Flight::Absorbed { emitted } => {
+                let emitted = sample_rgb(emitted, wavelengths);
+                return std::array::from_fn(|i| radiance[i] + throughput[i] * emitted[i]);
+            }
+            Flight::Surface { weight, emitted } => (sample_rgb(weight, wavelengths), sample_rgb(emitted, wavelengths)),
+        };
+        radiance = std::array::from_fn(|i| radiance[i] + throughput[i] * medium_emitted[i]);
+        throughput = std::array::from_fn(|i| throughput[i] * transmittance[i]);
+        hit.compute_footprint(&ray);
+        if !media.prepare(&mut hit, ray.wavelength) {
+            ray = Ray { origin: hit.point, ..ray };
+            continue;
         }
-    } else {
-        let unit_direction = ray.direction.unit_vector();
-        // Transform y-value from range [-1, 1] to [0, 1]
-        let t = 0.5 * (unit_direction.y + 1.0);
-        // Return linear interpolation between white (1, 1, 1) and blue (0.5, 0.7, 1)
-        (1.0 - t) * WHITE + t * Vec3::new(0.5, 0.7, 1.0)
+        if hit.material.is_dispersive() {
+            // Direction is only valid for the hero wavelength
+            wavelengths.terminate_secondary();
+        }
+        let emitted = sample_rgb(hit.material.emitted(&ray, &hit), wavelengths);
+        let color = if let Some(scatter) = hit.material.scatter(&ray, &hit, rng) {
+            if scatter.ray.direction.dot(hit.normal) < 0.0 {
+                media.cross(&hit);
+            }
+            let attenuation = sample_rgb(scatter.attenuation, wavelengths);
+            let incoming = spectral_ray_color(scatter.ray, world, media, wavelengths, rng, depth - 1);
+            std::array::from_fn(|i| emitted[i] + attenuation[i] * incoming[i])
+        } else {
+            emitted
+        };
+        return std::array::from_fn(|i| radiance[i] + throughput[i] * color[i]);
     }
 }
 
+fn background(ray: &Ray) -> Vec3 {
+    let unit_direction = ray.direction.unit_vector();
+    // Transform y-value from range [-1, 1] to [0, 1]
+    let t = 0.5 * (unit_direction.y + 1.0);
+    // Return linear interpolation between white (1, 1, 1) and blue (0.5, 0.7, 1)
+    (1.0 - t) * WHITE + t * Vec3::new(0.5, 0.7, 1.0)
+}
+
 /// Takes [ImageConfig] and renders PPM image to stdout.
-pub fn render(conf: ImageConfig, rng: &mut ThreadRng) -> std::io::Result<()> {
+pub fn render(conf: ImageConfig, rng: &mut dyn RngCore) -> std::io::Result<()> {
     eprintln!("Rendering {}x{} image", conf.image_width, conf.image_height);
+    let pixels = render_pixels(&conf, rng);
     let mut buf = BufWriter::with_capacity(100 * 1000, stdout());
     writeln!(buf, "P3\n{} {}\n255", conf.image_width, conf.image_height)?;
+    for color in pixels {
+        writeln!(buf, "{}", get_color(color, 1))?;
+    }
+    buf.flush()?;
+    eprintln!("Done");
+    Ok(())
+}
 
+/// Returns the average color of the samples of each pixel, row by row from the top. The image
+/// only depends on `rng`, so a seeded generator (e.g. [rand::rngs::StdRng]) renders it again.
+pub fn render_pixels(conf: &ImageConfig, rng: &mut dyn RngCore) -> Vec<Vec3> {
+    // Offsets to the neighbouring pixels, narrowed for more samples as each covers less of the pixel
+    let (du, dv) = (1.0 / (conf.image_width as f64 - 1.0), 1.0 / (conf.image_height as f64 - 1.0));
+    let differential_scale = (1.0 / (conf.samples_per_pixel as f64).sqrt()).max(0.125);
+
+    let mut pixels = Vec::with_capacity((conf.image_width * conf.image_height) as usize);
     for j in (0..conf.image_height).rev() {
         for i in 0..conf.image_width {
             let mut pixel_color = Vec3::new(0.0, 0.0, 0.0);
@@ -39,13 +181,73 @@ pub fn render(conf: ImageConfig, rng: &mut ThreadRng) -> std::io::Result<()> {
                 let u = (i as f64 + rng.gen_range(0.0..1.0)) / (conf.image_width as f64 - 1.0);
                 let v = (j as f64 + rng.gen_range(0.0..1.0)) / (conf.image_height as f64 - 1.0);
                 // Vector from origin to pixel
-                let ray = conf.camera.get_ray(u, v, rng);
-                pixel_color = pixel_color + ray_color(ray, &conf.world, rng, conf.max_depth);
+                if let Some(mut ray) = conf.camera.get_ray_differential(u, v, du, dv, rng) {
+                    ray.scale_differentials(differential_scale);
+                    let color = if conf.spectral {
+                        let mut wavelengths = SampledWavelengths::sample_visible(rng);
+                        ray.wavelength = Some(wavelengths.hero());
+                        let radiance = spectral_ray_color(ray, &conf.world, &mut MediumStack::new(), &mut wavelengths, rng, conf.max_depth);
+                        wavelengths.to_rgb(radiance)
+                    } else {
+                        ray_color(ray, &conf.world, &mut MediumStack::new(), rng, conf.max_depth)
+                    };
+                    pixel_color = pixel_color + color;
+                }
             }
-            writeln!(buf, "{}", get_color(pixel_color, conf.samples_per_pixel))?;
+            pixels.push(pixel_color / conf.samples_per_pixel as f64);
         }
     }
-    buf.flush()?;
-    eprintln!("Done");
-    Ok(())
-}
\ No newline at end of file
+    pixels
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use crate::camera::{Orientation, PerspectiveCamera, Shutter, ThinLens};
+    use crate::material::{Lambertian, Volumetric};
+    use crate::objects::Sphere;
+
+    #[test]
+    fn test_volume_bounces() {
+        // Dense fog that only scatters, paths take dozens of steps to leave it
+        let fog = Arc::new(Volumetric::homogeneous(BLACK, Vec3::new(10.0, 10.0, 10.0)));
+        let world = Hittables { hittables: vec![Box::new(Sphere { center: Vec3::new(0.0, 0.0, 0.0), radius: 1.0, material: fog })] };
+        let mut rng = rand::thread_rng();
+        let n = 2000;
+        let mut sum = BLACK;
+        for _ in 0..n {
+            let ray = Ray { origin: Vec3::new(0.0, 0.0, -3.0), direction: Vec3::new(0.0, 0.0, 1.0), time: 0.0, wavelength: None, differentials: None };
+            sum = sum + ray_color(ray, &world, &mut MediumStack::new(), &mut rng, 3);
+        }
+        // Energy is conserved, all light leaving the fog comes from the background
+        let green = sum.y / n as f64;
+        assert!(green > 0.7 && green < 1.05, "{}", green);
+    }
+
+    #[test]
+    fn test_seeded_render() {
+        let fog = Arc::new(Volumetric::homogeneous(Vec3::new(0.1, 0.2, 0.3), Vec3::new(1.0, 1.0, 1.0)));
+        let ground = Arc::new(Lambertian::new(Vec3::new(0.5, 0.5, 0.5)));
+        let world = Hittables {
+            hittables: vec![
+                Box::new(Sphere { center: Vec3::new(0.0, 0.0, -1.0), radius: 0.5, material: fog }),
+                Box::new(Sphere { center: Vec3::new(0.0, -100.5, -1.0), radius: 100.0, material: ground }),
+            ],
+        };
+        let orientation = Orientation::new(Vec3::new(0.0, 0.0, 1.0), Vec3::new(0.0, 0.0, -1.0), Vec3::new(0.0, 1.0, 0.0));
+        let lens = ThinLens { aperture: 0.1, focus_dist: 2.0, ..ThinLens::pinhole() };
+        let camera = Box::new(PerspectiveCamera::new(orientation, 40.0, 2.0, lens, Shutter::instant(0.0)));
+        let mut conf = ImageConfig { aspect_ratio: 2.0, image_width: 16, image_height: 8, samples_per_pixel: 4, max_depth: 8, world, camera, spectral: false };
+
+        let render = |conf: &ImageConfig, seed: u64| render_pixels(conf, &mut StdRng::seed_from_u64(seed));
+        assert_eq!(render(&conf, 1), render(&conf, 1));
+        assert_ne!(render(&conf, 1), render(&conf, 2));
+        conf.spectral = true;
+        assert_eq!(render(&conf, 1), render(&conf, 1));
+    }
+}
@@ -0,0 +1,417 @@
+//! Plain-text scene format of this crate, a tree of nodes with named fields, e.g.
+//!
+//! ```text
+//! sphere {
+//!     center [0.0 1.0 0.0]
+//!     radius 1.0
+//!     material metal {
+//!         albedo [0.7 0.6 0.5]
+//!         fuzz 0.0
+//!     }
+//! }
+//! ```
+//!
+//! Numbers are written with the shortest representation that parses back to the same `f64`, so
+//! saved scenes are loaded bit for bit. See [crate::configs::save_scene] and
+//! [crate::configs::load_scene].
+use std::fmt;
+use std::io::{self, Error, ErrorKind};
+
+use crate::Vec3;
+
+/// Value of a field.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Number(f64),
+    /// Flat list of numbers, e.g. the coordinates of vectors
+    Numbers(Vec<f64>),
+    Bool(bool),
+    Text(String),
+    Node(Node),
+    List(Vec<Node>),
+}
+
+impl From<f64> for Value {
+    fn from(n: f64) -> Self {
+        Value::Number(n)
+    }
+}
+
+impl From<Vec3> for Value {
+    fn from(v: Vec3) -> Self {
+        Value::Numbers(vec![v.x, v.y, v.z])
+    }
+}
+
+impl<const N: usize> From<[Vec3; N]> for Value {
+    fn from(vs: [Vec3; N]) -> Self {
+        Value::Numbers(vs.iter().flat_map(|v| [v.x, v.y, v.z]).collect())
+    }
+}
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Self {
+        Value::Bool(b)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Self {
+        Value::Text(s.to_string())
+    }
+}
+
+impl From<Node> for Value {
+    fn from(node: Node) -> Self {
+        Value::Node(node)
+    }
+}
+
+impl From<Vec<Node>> for Value {
+    fn from(nodes: Vec<Node>) -> Self {
+        Value::List(nodes)
+    }
+}
+
+/// Object of kind `kind` (e.g. "sphere") with its fields in order.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Node {
+    pub kind: String,
+    pub fields: Vec<(String, Value)>,
+}
+
+impl Node {
+    pub fn new(kind: &str) -> Self {
+        Node { kind: kind.to_string(), fields: vec![] }
+    }
+
+    pub fn with(mut self, name: &str, value: impl Into<Value>) -> Self {
+        self.fields.push((name.to_string(), value.into()));
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.fields.iter().find(|(n, _)| n == name).map(|(_, value)| value)
+    }
+
+    fn field(&self, name: &str) -> io::Result<&Value> {
+        self.get(name).ok_or_else(|| self.invalid(format!("missing field '{}'", name)))
+    }
+
+    /// Returns error about this node, naming its kind.
+    pub fn invalid(&self, message: String) -> Error {
+        Error::new(ErrorKind::InvalidData, format!("{}: {}", self.kind, message))
+    }
+
+    pub fn number(&self, name: &str) -> io::Result<f64> {
+        match self.field(name)? {
+            Value::Number(n) => Ok(*n),
+            _ => Err(self.invalid(format!("field '{}' must be a number", name))),
+        }
+    }
+
+    /// Returns number field that must be a non-negative integer.
+    pub fn integer(&self, name: &str) -> io::Result<i64> {
+        let n = self.number(name)?;
+        // Integers beyond 2^53 are not exact as f64
+        if n >= 0.0 && n.fract() == 0.0 && n <= 2f64.powi(53) {
+            Ok(n as i64)
+        } else {
+            Err(self.invalid(format!("field '{}' must be a non-negative integer", name)))
+        }
+    }
+
+    pub fn numbers(&self, name: &str) -> io::Result<&[f64]> {
+        match self.field(name)? {
+            Value::Numbers(ns) => Ok(ns),
+            Value::List(nodes) if nodes.is_empty() => Ok(&[]),
+            _ => Err(self.invalid(format!("field '{}' must be a list of numbers", name))),
+        }
+    }
+
+    pub fn vector(&self, name: &str) -> io::Result<Vec3> {
+        Ok(self.vectors::<1>(name)?[0])
+    }
+
+    /// Returns field of exactly `N` vectors.
+    pub fn vectors<const N: usize>(&self, name: &str) -> io::Result<[Vec3; N]> {
+        match self.numbers(name)? {
+            ns if ns.len() == 3 * N => Ok(std::array::from_fn(|i| Vec3::new(ns[3 * i], ns[3 * i + 1], ns[3 * i + 2]))),
+            _ => Err(self.invalid(format!("field '{}' must have {} numbers", name, 3 * N))),
+        }
+    }
+
+    pub fn bool(&self, name: &str) -> io::Result<bool> {
+        match self.field(name)? {
+            Value::Bool(b) => Ok(*b),
+            _ => Err(self.invalid(format!("field '{}' must be true or false", name))),
+        }
+    }
+
+    pub fn text(&self, name: &str) -> io::Result<&str> {
+        match self.field(name)? {
+            Value::Text(s) => Ok(s),
+            _ => Err(self.invalid(format!("field '{}' must be a string", name))),
+        }
+    }
+
+    pub fn node(&self, name: &str) -> io::Result<&Node> {
+        match self.field(name)? {
+            Value::Node(node) => Ok(node),
+            _ => Err(self.invalid(format!("field '{}' must be a node", name))),
+        }
+    }
+
+    pub fn list(&self, name: &str) -> io::Result<&[Node]> {
+        match self.field(name)? {
+            Value::List(nodes) => Ok(nodes),
+            Value::Numbers(ns) if ns.is_empty() => Ok(&[]),
+            _ => Err(self.invalid(format!("field '{}' must be a list of nodes", name))),
+        }
+    }
+
+    /// Parses text holding a single node. Syntax errors give the line number.
+    pub fn parse(text: &str) -> io::Result<Node> {
+        let mut parser = Parser { tokens: tokenize(text)?, position: 0 };
+        let node = match parser.next()? {
+            (Token::Word(kind), _) => parser.node(kind)?,
+            (token, line) => return Err(error(line, format!("expected node kind, found {}", token))),
+        };
+        match parser.tokens.get(parser.position) {
+            None => Ok(node),
+            Some((token, line)) => Err(error(*line, format!("unexpected {} after the node", token))),
+        }
+    }
+
+    fn write(&self, f: &mut fmt::Formatter, indent: usize) -> fmt::Result {
+        if self.fields.is_empty() {
+            return write!(f, "{} {{}}", self.kind);
+        }
+        writeln!(f, "{} {{", self.kind)?;
+        for (name, value) in &self.fields {
+            write!(f, "{:1$}{2} ", "", 4 * (indent + 1), name)?;
+            match value {
+                Value::Number(n) => write!(f, "{}", format_number(*n))?,
+                Value::Numbers(ns) => {
+                    let ns: Vec<String> = ns.iter().map(|n| format_number(*n)).collect();
+                    write!(f, "[{}]", ns.join(" "))?;
+                }
+                Value::Bool(b) => write!(f, "{}", b)?,
+                Value::Text(s) => write!(f, "\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))?,
+                Value::Node(node) => node.write(f, indent + 1)?,
+                Value::List(nodes) => {
+                    writeln!(f, "[")?;
+                    for node in nodes {
+                        write!(f, "{:1$}", "", 4 * (indent + 2))?;
+                        node.write(f, indent + 2)?;
+                        writeln!(f)?;
+                    }
+                    write!(f, "{:1$}]", "", 4 * (indent + 1))?;
+                }
+            }
+            writeln!(f)?;
+        }
+        write!(f, "{:1$}}}", "", 4 * indent)
+    }
+}
+
+/// Returns shortest text that parses to the same number, integers without a fraction.
+fn format_number(n: f64) -> String {
+    if n.fract() == 0.0 && n.abs() < 1.0e15 {
+        format!("{}", n)
+    } else {
+        format!("{:?}", n)
+    }
+}
+
+/// Writes node in the syntax read by [Node::parse].
+impl fmt::Display for Node {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.write(f, 0)?;
+        writeln!(f)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    /// Number, boolean, node kind or field name
+    Word(String),
+    Text(String),
+    Symbol(char),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Token::Word(w) => write!(f, "'{}'", w),
+            Token::Text(s) => write!(f, "string \"{}\"", s),
+            Token::Symbol(c) => write!(f, "'{}'", c),
+        }
+    }
+}
+
+/// Splits text into tokens with their line numbers, skipping comments from `#` to the end of line.
+fn tokenize(text: &str) -> io::Result<Vec<(Token, usize)>> {
+    let mut tokens = vec![];
+    let mut chars = text.chars().peekable();
+    let mut line = 1;
+    while let Some(c) = chars.next() {
+        match c {
+            '\n' => line += 1,
+            c if c.is_whitespace() => {}
+            '#' => {
+                while chars.next_if(|&c| c != '\n').is_some() {}
+            }
+            '{' | '}' | '[' | ']' => tokens.push((Token::Symbol(c), line)),
+            '"' => {
+                let start = line;
+                let mut s = String::new();
+                loop {
+                    match chars.next() {
+                        None => return Err(error(start, "unterminated string")),
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\')) => s.push(c),
+                            _ => return Err(error(line, "invalid escape in string")),
+                        },
+                        Some(c) => {
+                            if c == '\n' {
+                                line += 1;
+                            }
+                            s.push(c);
+                        }
+                    }
+                }
+                tokens.push((Token::Text(s), start));
+            }
+            c => {
+                let mut word = c.to_string();
+                while let Some(c) = chars.next_if(|&c| !c.is_whitespace() && !"{}[]\"#".contains(c)) {
+                    word.push(c);
+                }
+                tokens.push((Token::Word(word), line));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    position: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> io::Result<(Token, usize)> {
+        match self.tokens.get(self.position) {
+            Some(token) => {
+                self.position += 1;
+                Ok(token.clone())
+            }
+            None => Err(error(self.tokens.last().map_or(1, |(_, line)| *line), "unexpected end of file")),
+        }
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position).map(|(token, _)| token)
+    }
+
+    /// Parses fields of node of `kind` in braces.
+    fn node(&mut self, kind: String) -> io::Result<Node> {
+        match self.next()? {
+            (Token::Symbol('{'), _) => {}
+            (token, line) => return Err(error(line, format!("expected '{{' after '{}', found {}", kind, token))),
+        }
+        let mut node = Node { kind, fields: vec![] };
+        loop {
+            match self.next()? {
+                (Token::Symbol('}'), _) => return Ok(node),
+                (Token::Word(name), line) => {
+                    if node.get(&name).is_some() {
+                        return Err(error(line, format!("duplicate field '{}'", name)));
+                    }
+                    let value = self.value()?;
+                    node.fields.push((name, value));
+                }
+                (token, line) => return Err(error(line, format!("expected field name, found {}", token))),
+            }
+        }
+    }
+
+    fn value(&mut self) -> io::Result<Value> {
+        match self.next()? {
+            (Token::Word(word), _) => Ok(match word.as_str() {
+                "true" => Value::Bool(true),
+                "false" => Value::Bool(false),
+                _ => match word.parse() {
+                    Ok(n) => Value::Number(n),
+                    Err(_) => Value::Node(self.node(word)?),
+                },
+            }),
+            (Token::Text(s), _) => Ok(Value::Text(s)),
+            (Token::Symbol('['), _) => {
+                let numbers = matches!(self.peek(), Some(Token::Word(w)) if w.parse::<f64>().is_ok());
+                let mut ns = vec![];
+                let mut nodes = vec![];
+                loop {
+                    match self.next()? {
+                        (Token::Symbol(']'), _) => break,
+                        (Token::Word(word), line) if numbers => {
+                            ns.push(word.parse().map_err(|_| error(line, format!("expected number, found '{}'", word)))?);
+                        }
+                        (Token::Word(kind), _) => nodes.push(self.node(kind)?),
+                        (token, line) => return Err(error(line, format!("unexpected {} in list", token))),
+                    }
+                }
+                Ok(if numbers { Value::Numbers(ns) } else { Value::List(nodes) })
+            }
+            (token, line) => Err(error(line, format!("expected value, found {}", token))),
+        }
+    }
+}
+
+fn error(line: usize, message: impl fmt::Display) -> Error {
+    Error::new(ErrorKind::InvalidData, format!("line {}: {}", line, message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let node = Node::new("image")
+            .with("width", 400.0)
+            .with("zero", -0.0)
+            .with("tiny", 1.0e-300)
+            .with("third", 1.0 / 3.0)
+            .with("center", Vec3::new(0.1, -0.2, f64::INFINITY))
+            .with("spectral", false)
+            .with("path", "a \"quoted\" \\ path")
+            .with("camera", Node::new("pinhole"))
+            .with("objects", vec![Node::new("sphere").with("radius", 0.2), Node::new("sphere")])
+            .with("empty", Vec::<Node>::new());
+        let text = node.to_string();
+        let parsed = Node::parse(&text).unwrap();
+        assert_eq!(parsed, node);
+        assert_eq!(parsed.number("third").unwrap().to_bits(), (1.0f64 / 3.0).to_bits());
+        assert_eq!(parsed.vector("center").unwrap().z, f64::INFINITY);
+        assert_eq!(parsed.integer("width").unwrap(), 400);
+        assert!(text.contains("width 400\n") && parsed.number("zero").unwrap().is_sign_negative());
+        assert!(parsed.list("empty").unwrap().is_empty());
+        assert_eq!(parsed.list("objects").unwrap()[0].number("radius").unwrap(), 0.2);
+
+        let comment = "# saved scene\nimage { # image\n  width 400 }";
+        assert_eq!(Node::parse(comment).unwrap().integer("width").unwrap(), 400);
+
+        let err = |text: &str| Node::parse(text).unwrap_err().to_string();
+        assert_eq!(err("image {\n width\n}"), "line 3: expected value, found '}'");
+        assert_eq!(err("image {\n width 1\n width 2 }"), "line 3: duplicate field 'width'");
+        assert_eq!(err("image {\n center [1 2 x] }"), "line 2: expected number, found 'x'");
+        assert_eq!(err("image {\n path \"open }"), "line 2: unterminated string");
+        assert_eq!(err("image {}\n}"), "line 2: unexpected '}' after the node");
+        assert_eq!(err("image {\n"), "line 1: unexpected end of file");
+        assert_eq!(Node::parse("image { width 0.5 }").unwrap().integer("width").unwrap_err().to_string(),
+                   "image: field 'width' must be a non-negative integer");
+    }
+}
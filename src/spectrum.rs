@@ -0,0 +1,217 @@
+//! Wavelength sampling and color conversions for spectral rendering.
+use rand::{Rng, RngCore};
+
+use crate::Vec3;
+
+pub const LAMBDA_MIN: f64 = 360.0;
+pub const LAMBDA_MAX: f64 = 830.0;
+/// Number of wavelengths carried by each path
+pub const N_WAVELENGTHS: usize = 4;
+const CIE_Y_INTEGRAL: f64 = 106.856895;
+
+/// Hero wavelength and equally spaced secondary wavelengths of a path with their probability densities.
+#[derive(Copy, Clone, Debug)]
+pub struct SampledWavelengths {
+    pub lambda: [f64; N_WAVELENGTHS],
+    pub pdf: [f64; N_WAVELENGTHS],
+}
+
+impl SampledWavelengths {
+    /// Samples wavelengths proportional to the sensitivity of the human eye.
+    pub fn sample_visible(rng: &mut dyn RngCore) -> Self {
+        let u: f64 = rng.gen();
+        let mut lambda = [0.0; N_WAVELENGTHS];
+        let mut pdf = [0.0; N_WAVELENGTHS];
+        for i in 0..N_WAVELENGTHS {
+            let ui = (u + i as f64 / N_WAVELENGTHS as f64).fract();
+            lambda[i] = sample_visible_wavelength(ui);
+            pdf[i] = visible_wavelength_pdf(lambda[i]);
+        }
+        SampledWavelengths { lambda, pdf }
+    }
+
+    pub fn hero(&self) -> f64 {
+        self.lambda[0]
+    }
+
+    /// Keeps only the hero wavelength, e.g. after wavelength-dependent refraction.
+    pub fn terminate_secondary(&mut self) {
+        if self.secondary_terminated() {
+            return;
+        }
+        for pdf in self.pdf.iter_mut().skip(1) {
+            *pdf = 0.0;
+        }
+        self.pdf[0] /= N_WAVELENGTHS as f64;
+    }
+
+    pub fn secondary_terminated(&self) -> bool {
+        self.pdf[1..].iter().all(|&pdf| pdf == 0.0)
+    }
+
+    /// Converts radiance sampled at these wavelengths to linear sRGB.
+    pub fn to_rgb(&self, radiance: [f64; N_WAVELENGTHS]) -> Vec3 {
+        let mut xyz = Vec3::new(0.0, 0.0, 0.0);
+        for ((&lambda, &pdf), &radiance) in self.lambda.iter().zip(self.pdf.iter()).zip(radiance.iter()) {
+            if pdf != 0.0 {
+                xyz = xyz + (radiance / pdf) * cie_xyz(lambda);
+            }
+        }
+        xyz_to_rgb(xyz / (N_WAVELENGTHS as f64 * CIE_Y_INTEGRAL))
+    }
+}
+
+fn sample_visible_wavelength(u: f64) -> f64 {
+    538.0 - 138.888889 * (0.85691062 - 1.82750197 * u).atanh()
+}
+
+fn visible_wavelength_pdf(lambda: f64) -> f64 {
+    if !(LAMBDA_MIN..=LAMBDA_MAX).contains(&lambda) {
+        return 0.0;
+    }
+    0.0039398042 / (0.0072 * (lambda - 538.0)).cosh().powi(2)
+}
+
+/// Piecewise Gaussian used by the analytic fit of the color matching functions.
+fn gaussian(x: f64, mu: f64, sigma1: f64, sigma2: f64) -> f64 {
+    let sigma = if x < mu { sigma1 } else { sigma2 };
+    let t = (x - mu) / sigma;
+    (-0.5 * t * t).exp()
+}
+
+/// CIE 1931 color matching functions, multi-lobe fit by Wyman, Sloan and Shirley (2013).
+pub fn cie_xyz(lambda: f64) -> Vec3 {
+    Vec3::new(
+        1.056 * gaussian(lambda, 599.8, 37.9, 31.0) + 0.362 * gaussian(lambda, 442.0, 16.0, 26.7)
+            - 0.065 * gaussian(lambda, 501.1, 20.4, 26.2),
+        0.821 * gaussian(lambda, 568.8, 46.9, 40.5) + 0.286 * gaussian(lambda, 530.9, 16.3, 31.1),
+        1.217 * gaussian(lambda, 437.0, 11.8, 36.0) + 0.681 * gaussian(lambda, 459.0, 26.0, 13.8),
+    )
+}
+
+/// Converts XYZ to linear sRGB, including a Bradford adaptation from the equal-energy white
+/// point to D65, so that flat spectra map to gray.
+pub fn xyz_to_rgb(xyz: Vec3) -> Vec3 {
+    Vec3::new(
+        3.1462510 * xyz.x - 1.6661239 * xyz.y - 0.4801271 * xyz.z,
+        -0.9955350 * xyz.x + 1.9557634 * xyz.y + 0.0397715 * xyz.z,
+        0.0635978 * xyz.x - 0.2145965 * xyz.y + 1.1509987 * xyz.z,
+    )
+}
+
+// Basis spectra of Smits (1999), sampled at 10 wavelengths between 380 and 720 nm
+const SMITS_WHITE: [f64; 10] = [1.0000, 1.0000, 0.9999, 0.9993, 0.9992, 0.9998, 1.0000, 1.0000, 1.0000, 1.0000];
+const SMITS_CYAN: [f64; 10] = [0.9710, 0.9426, 1.0007, 1.0007, 1.0007, 1.0007, 0.1564, 0.0000, 0.0000, 0.0000];
+const SMITS_MAGENTA: [f64; 10] = [1.0000, 1.0000, 0.9685, 0.2229, 0.0000, 0.0458, 0.8369, 1.0000, 1.0000, 0.9959];
+const SMITS_YELLOW: [f64; 10] = [0.0001, 0.0000, 0.1088, 0.6651, 1.0000, 1.0000, 0.9996, 0.9586, 0.9685, 0.9840];
+const SMITS_RED: [f64; 10] = [0.1012, 0.0515, 0.0000, 0.0000, 0.0000, 0.0000, 0.8325, 1.0149, 1.0149, 1.0149];
+const SMITS_GREEN: [f64; 10] = [0.0000, 0.0000, 0.0273, 0.7937, 1.0000, 0.9418, 0.1719, 0.0000, 0.0000, 0.0025];
+const SMITS_BLUE: [f64; 10] = [1.0000, 1.0000, 0.8916, 0.3323, 0.0000, 0.0000, 0.0003, 0.0369, 0.0483, 0.0496];
+
+fn smits_basis(basis: &[f64; 10], lambda: f64) -> f64 {
+    let x = ((lambda - 380.0) / (720.0 - 380.0) * 9.0).clamp(0.0, 9.0);
+    let i = (x as usize).min(8);
+    let t = x - i as f64;
+    (1.0 - t) * basis[i] + t * basis[i + 1]
+}
+
+/// Evaluates smooth spectrum matching the given RGB color at `lambda`, following Smits (1999).
+pub fn rgb_to_spectrum(rgb: Vec3, lambda: f64) -> f64 {
+    let (r, g, b) = (rgb.x, rgb.y, rgb.z);
+    let s = |basis: &[f64; 10]| smits_basis(basis, lambda);
+    if r <= g && r <= b {
+        r * s(&SMITS_WHITE) + if g <= b {
+            (g - r) * s(&SMITS_CYAN) + (b - g) * s(&SMITS_BLUE)
+        } else {
+            (b - r) * s(&SMITS_CYAN) + (g - b) * s(&SMITS_GREEN)
+        }
+    } else if g <= r && g <= b {
+        g * s(&SMITS_WHITE) + if r <= b {
+            (r - g) * s(&SMITS_MAGENTA) + (b - r) * s(&SMITS_BLUE)
+        } else {
+            (b - g) * s(&SMITS_MAGENTA) + (r - b) * s(&SMITS_RED)
+        }
+    } else {
+        b * s(&SMITS_WHITE) + if r <= g {
+            (r - b) * s(&SMITS_YELLOW) + (g - r) * s(&SMITS_GREEN)
+        } else {
+            (g - b) * s(&SMITS_YELLOW) + (r - g) * s(&SMITS_RED)
+        }
+    }
+}
+
+/// Evaluates spectrum matching the given RGB color at each of the sampled wavelengths.
+pub fn sample_rgb(rgb: Vec3, wavelengths: &SampledWavelengths) -> [f64; N_WAVELENGTHS] {
+    wavelengths.lambda.map(|lambda| rgb_to_spectrum(rgb, lambda))
+}
+
+/// Returns spectral radiance of a black body at `temperature` Kelvin and wavelength `lambda` in
+/// nanometers (Planck's law), in W / (sr m^2 nm).
+pub fn blackbody(lambda: f64, temperature: f64) -> f64 {
+    if temperature <= 0.0 {
+        return 0.0;
+    }
+    const C: f64 = 299792458.0;
+    const H: f64 = 6.62606957e-34;
+    const KB: f64 = 1.3806488e-23;
+    let l = lambda * 1.0e-9;
+    2.0 * H * C * C / (l.powi(5) * ((H * C / (l * KB * temperature)).exp() - 1.0)) * 1.0e-9
+}
+
+/// Returns linear sRGB color of a black body at `temperature` Kelvin, with the spectrum scaled to
+/// a peak of one such that the brightness varies little with temperature.
+pub fn blackbody_rgb(temperature: f64) -> Vec3 {
+    if temperature <= 0.0 {
+        return Vec3::new(0.0, 0.0, 0.0);
+    }
+    // Wien's displacement law
+    let peak = blackbody(2.8977721e-3 / temperature * 1.0e9, temperature);
+    let step = 5.0;
+    let mut xyz = Vec3::new(0.0, 0.0, 0.0);
+    let mut lambda = LAMBDA_MIN;
+    while lambda <= LAMBDA_MAX {
+        xyz = xyz + (blackbody(lambda, temperature) / peak * step) * cie_xyz(lambda);
+        lambda += step;
+    }
+    xyz_to_rgb(xyz / CIE_Y_INTEGRAL)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gray_round_trip() {
+        let mut rng = rand::thread_rng();
+        let mut rgb = Vec3::new(0.0, 0.0, 0.0);
+        let n = 20000;
+        for _ in 0..n {
+            let wavelengths = SampledWavelengths::sample_visible(&mut rng);
+            rgb = rgb + wavelengths.to_rgb(sample_rgb(Vec3::new(0.5, 0.5, 0.5), &wavelengths));
+        }
+        let rgb = rgb / n as f64;
+        for c in [rgb.x, rgb.y, rgb.z] {
+            assert!((c - 0.5).abs() < 0.02, "{:?}", rgb);
+        }
+    }
+
+    #[test]
+    fn test_terminate_secondary() {
+        let mut wavelengths = SampledWavelengths::sample_visible(&mut rand::thread_rng());
+        let hero_pdf = wavelengths.pdf[0];
+        wavelengths.terminate_secondary();
+        wavelengths.terminate_secondary();
+        assert!(wavelengths.secondary_terminated());
+        assert_eq!(wavelengths.pdf[0], hero_pdf / N_WAVELENGTHS as f64);
+    }
+
+    #[test]
+    fn test_blackbody() {
+        // Low temperatures glow red, high temperatures blue
+        let warm = blackbody_rgb(1500.0);
+        let cold = blackbody_rgb(12000.0);
+        assert!(warm.x > warm.y && warm.y > warm.z);
+        assert!(cold.z > cold.x);
+        assert_eq!(blackbody_rgb(0.0), Vec3::new(0.0, 0.0, 0.0));
+    }
+}
@@ -0,0 +1,275 @@
+//! Image textures with a pyramid of prefiltered versions (mip levels), from which lookups pick
+//! the resolution matching the footprint of the ray differentials.
+use std::io::{self, Error, ErrorKind};
+use std::path::Path;
+
+use image::{ColorType, ImageError};
+
+use crate::netpbm;
+use crate::netpbm::Image;
+use crate::objects::Hit;
+use crate::scene::{Node, Value};
+use crate::Vec3;
+
+use super::Texture;
+
+/// Handling of texture coordinates outside [0, 1].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Wrap {
+    Repeat,
+    /// Extends the border texels
+    Clamp,
+    /// Repeats with every other copy flipped, which avoids seams at the borders
+    Mirror,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Filter {
+    /// Bilinear interpolation in the full resolution image, aliases under minification
+    Bilinear,
+    /// Bilinear interpolation in the two mip levels closest to the footprint, blended linearly
+    Trilinear,
+}
+
+/// Interpretation of the values stored in an image.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ColorSpace {
+    Linear,
+    /// Gamma encoded as usual for 8-bit color images, decoded to linear values on load
+    Srgb,
+}
+
+/// Image mapped by texture coordinates.
+pub struct ImageTexture {
+    /// Full resolution image followed by versions of half the size, down to a single texel
+    levels: Vec<Image>,
+    pub wrap: Wrap,
+    pub filter: Filter,
+}
+
+impl ImageTexture {
+    /// Returns repeating, trilinearly filtered texture of an image with linear values.
+    pub fn new(image: Image) -> Self {
+        let mut levels = vec![image];
+        while let Some(level) = downsample(&levels[levels.len() - 1]) {
+            levels.push(level);
+        }
+        ImageTexture { levels, wrap: Wrap::Repeat, filter: Filter::Trilinear }
+    }
+
+    pub fn with_color_space(mut image: Image, color_space: ColorSpace) -> Self {
+        if color_space == ColorSpace::Srgb {
+            image.data.iter_mut().for_each(|v| *v = srgb_to_linear(*v));
+        }
+        ImageTexture::new(image)
+    }
+
+    /// Loads PNG, JPEG, Radiance HDR or Netpbm (.pgm, .ppm) image. HDR images are always linear,
+    /// `color_space` applies to the others.
+    pub fn load<P: AsRef<Path>>(path: P, color_space: ColorSpace) -> io::Result<Self> {
+        let path = path.as_ref();
+        let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("").to_ascii_lowercase();
+        if extension == "pgm" || extension == "ppm" {
+            return Ok(ImageTexture::with_color_space(netpbm::read(path)?, color_space));
+        }
+        let image = image::open(path).map_err(|e| match e {
+            ImageError::IoError(e) => e,
+            e => Error::new(ErrorKind::InvalidData, e),
+        })?;
+        let color_space = match image.color() {
+            ColorType::Rgb32F | ColorType::Rgba32F => ColorSpace::Linear,
+            _ => color_space,
+        };
+        // Alpha is dropped, masks are read from grayscale images
+        let (channels, data) = if image.color().has_color() {
+            (3, image.to_rgb32f().into_raw())
+        } else {
+            (1, image.to_luma32f().into_raw())
+        };
+        let image = Image {
+            width: image.width() as usize,
+            height: image.height() as usize,
+            channels,
+            data: data.into_iter().map(f64::from).collect(),
+        };
+        Ok(ImageTexture::with_color_space(image, color_space))
+    }
+
+    /// Returns full resolution image.
+    pub fn image(&self) -> &Image {
+        &self.levels[0]
+    }
+
+    pub fn levels(&self) -> &[Image] {
+        &self.levels
+    }
+
+    fn texel(&self, level: usize, x: i64, y: i64) -> Vec3 {
+        let image = &self.levels[level];
+        let wrap = |i: i64, n: usize| {
+            let n = n as i64;
+            let i = match self.wrap {
+                Wrap::Repeat => i.rem_euclid(n),
+                Wrap::Clamp => i.clamp(0, n - 1),
+                Wrap::Mirror => {
+                    let i = i.rem_euclid(2 * n);
+                    if i < n { i } else { 2 * n - 1 - i }
+                }
+            };
+            i as usize
+        };
+        let (x, y) = (wrap(x, image.width), wrap(y, image.height));
+        if image.channels == 1 {
+            let g = image.get(x, y, 0);
+            Vec3::new(g, g, g)
+        } else {
+            Vec3::new(image.get(x, y, 0), image.get(x, y, 1), image.get(x, y, 2))
+        }
+    }
+
+    fn bilinear(&self, level: usize, u: f64, v: f64) -> Vec3 {
+        let image = &self.levels[level];
+        // Image rows run top to bottom, v bottom to top
+        let x = u * image.width as f64 - 0.5;
+        let y = (1.0 - v) * image.height as f64 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (tx, ty) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+        (1.0 - ty) * ((1.0 - tx) * self.texel(level, x0, y0) + tx * self.texel(level, x0 + 1, y0))
+            + ty * ((1.0 - tx) * self.texel(level, x0, y0 + 1) + tx * self.texel(level, x0 + 1, y0 + 1))
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, hit: &Hit) -> Vec3 {
+        if self.filter == Filter::Bilinear {
+            return self.bilinear(0, hit.u, hit.v);
+        }
+        // Width of the footprint in texels of the full resolution image, each level halves it
+        let (width, height) = (self.image().width as f64, self.image().height as f64);
+        let f = hit.footprint;
+        let texels = [f.dudx * width, f.dvdx * height, f.dudy * width, f.dvdy * height]
+            .iter()
+            .fold(0.0f64, |max, d| max.max(d.abs()));
+        let level = texels.max(1.0).log2().min((self.levels.len() - 1) as f64);
+        let lower = level.floor() as usize;
+        let t = level - lower as f64;
+        if t == 0.0 {
+            return self.bilinear(lower, hit.u, hit.v);
+        }
+        (1.0 - t) * self.bilinear(lower, hit.u, hit.v) + t * self.bilinear(lower + 1, hit.u, hit.v)
+    }
+
+    /// Describes the full resolution image with its linear values, the mip levels follow from it.
+    fn describe(&self) -> Option<Value> {
+        let image = self.image();
+        let wrap = match self.wrap {
+            Wrap::Repeat => "repeat",
+            Wrap::Clamp => "clamp",
+            Wrap::Mirror => "mirror",
+        };
+        let filter = match self.filter {
+            Filter::Bilinear => "bilinear",
+            Filter::Trilinear => "trilinear",
+        };
+        Some(Node::new("image")
+            .with("width", image.width as f64)
+            .with("height", image.height as f64)
+            .with("channels", image.channels as f64)
+            .with("data", Value::Numbers(image.data.clone()))
+            .with("wrap", wrap)
+            .with("filter", filter)
+            .into())
+    }
+}
+
+/// Returns image of half the width and height (rounded up) by averaging blocks of 2x2 texels,
+/// `None` for a single texel.
+fn downsample(image: &Image) -> Option<Image> {
+    if image.width == 1 && image.height == 1 {
+        return None;
+    }
+    let (width, height) = (image.width.div_ceil(2), image.height.div_ceil(2));
+    let mut data = Vec::with_capacity(width * height * image.channels);
+    for y in 0..height {
+        for x in 0..width {
+            // Texels of odd-sized images at the border are repeated
+            let xs = [2 * x, (2 * x + 1).min(image.width - 1)];
+            let ys = [2 * y, (2 * y + 1).min(image.height - 1)];
+            for c in 0..image.channels {
+                let sum: f64 = ys.iter().flat_map(|&y| xs.iter().map(move |&x| (x, y))).map(|(x, y)| image.get(x, y, c)).sum();
+                data.push(sum / 4.0);
+            }
+        }
+    }
+    Some(Image { width, height, channels: image.channels, data })
+}
+
+/// Returns linear value of sRGB encoded value in [0, 1].
+pub fn srgb_to_linear(value: f64) -> f64 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::material::Lambertian;
+    use crate::objects::{Hittable, Quad};
+    use crate::{Differentials, Ray};
+
+    #[test]
+    fn test_mip_levels() {
+        // Black and white columns
+        let image = Image { width: 4, height: 2, channels: 1, data: vec![0.0, 1.0, 0.0, 1.0, 0.0, 1.0, 0.0, 1.0] };
+        let mut texture = ImageTexture::new(image);
+        assert_eq!(texture.levels().iter().map(|l| (l.width, l.height)).collect::<Vec<_>>(), [(4, 2), (2, 1), (1, 1)]);
+        assert!(texture.levels()[1].data.iter().all(|&v| v == 0.5));
+
+        // Quad spanning the texture, viewed head-on
+        let material = Arc::new(Lambertian::new(Vec3::new(0.5, 0.5, 0.5)));
+        let quad = Quad { q: Vec3::new(0.0, 0.0, 0.0), u: Vec3::new(4.0, 0.0, 0.0), v: Vec3::new(0.0, 2.0, 0.0), material };
+        let value = |texture: &ImageTexture, offset: f64| {
+            let origin = Vec3::new(1.5, 0.5, 1.0);
+            let direction = Vec3::new(0.0, 0.0, -1.0);
+            let differentials = Differentials {
+                rx_origin: origin + Vec3::new(offset, 0.0, 0.0),
+                rx_direction: direction,
+                ry_origin: origin + Vec3::new(0.0, offset, 0.0),
+                ry_direction: direction,
+            };
+            let ray = Ray { origin, direction, time: 0.0, wavelength: None, differentials: Some(differentials) };
+            let mut hit = quad.hit(&ray, 0.0, f64::MAX).unwrap();
+            hit.compute_footprint(&ray);
+            texture.value(&hit).x
+        };
+        // Texel center of a white column up close, the average from afar
+        assert!((value(&texture, 0.5) - 1.0).abs() < 1e-9);
+        assert!((value(&texture, 2.0) - 0.5).abs() < 1e-9);
+        texture.filter = Filter::Bilinear;
+        assert!((value(&texture, 2.0) - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_wrap() {
+        let image = Image { width: 4, height: 1, channels: 1, data: vec![0.1, 0.2, 0.3, 0.4] };
+        let mut texture = ImageTexture::with_color_space(image, ColorSpace::Linear);
+        let texel = |texture: &ImageTexture, x: i64| texture.texel(0, x, 0).x;
+        assert_eq!(texel(&texture, -1), 0.4);
+        assert_eq!(texel(&texture, 5), 0.2);
+        texture.wrap = Wrap::Clamp;
+        assert_eq!(texel(&texture, -1), 0.1);
+        assert_eq!(texel(&texture, 5), 0.4);
+        texture.wrap = Wrap::Mirror;
+        assert_eq!(texel(&texture, -1), 0.1);
+        assert_eq!(texel(&texture, 5), 0.3);
+
+        assert!((srgb_to_linear(0.5) - 0.214).abs() < 1e-3);
+        assert_eq!(srgb_to_linear(1.0), 1.0);
+    }
+}
@@ -0,0 +1,85 @@
+//! Textures vary material parameters over a surface.
+use std::sync::Arc;
+
+use crate::objects::Hit;
+use crate::scene::{Node, Value};
+use crate::Vec3;
+
+pub use mipmap::*;
+pub use procedural::*;
+
+mod mipmap;
+mod procedural;
+
+pub trait Texture: Send + Sync {
+    fn value(&self, hit: &Hit) -> Vec3;
+
+    /// Returns color (for constant textures) or node describing the texture for saving scenes,
+    /// `None` if it can't be saved.
+    fn describe(&self) -> Option<Value> {
+        None
+    }
+}
+
+pub struct SolidColor {
+    pub color: Vec3,
+}
+
+impl SolidColor {
+    pub fn new(color: Vec3) -> Self {
+        SolidColor { color }
+    }
+
+    pub fn gray(value: f64) -> Self {
+        SolidColor { color: Vec3::new(value, value, value) }
+    }
+}
+
+impl Texture for SolidColor {
+    fn value(&self, _hit: &Hit) -> Vec3 {
+        self.color
+    }
+
+    fn describe(&self) -> Option<Value> {
+        Some(self.color.into())
+    }
+}
+
+/// Color attribute of the surface at the hit, e.g. per point of a [crate::objects::PointCloud],
+/// `fallback` where there is none.
+pub struct VertexColor {
+    pub fallback: Arc<dyn Texture>,
+}
+
+impl Texture for VertexColor {
+    fn value(&self, hit: &Hit) -> Vec3 {
+        hit.color.unwrap_or_else(|| self.fallback.value(hit))
+    }
+
+    fn describe(&self) -> Option<Value> {
+        Some(Node::new("vertex_color").with("fallback", self.fallback.describe()?).into())
+    }
+}
+
+/// Solid 3D checker pattern alternating between two textures in cubes of size `scale`.
+pub struct Checker {
+    pub even: Arc<dyn Texture>,
+    pub odd: Arc<dyn Texture>,
+    pub scale: f64,
+}
+
+impl Texture for Checker {
+    fn value(&self, hit: &Hit) -> Vec3 {
+        let p = hit.point / self.scale;
+        let parity = p.x.floor() as i64 + p.y.floor() as i64 + p.z.floor() as i64;
+        if parity % 2 == 0 { self.even.value(hit) } else { self.odd.value(hit) }
+    }
+
+    fn describe(&self) -> Option<Value> {
+        Some(Node::new("checker")
+            .with("even", self.even.describe()?)
+            .with("odd", self.odd.describe()?)
+            .with("scale", self.scale)
+            .into())
+    }
+}
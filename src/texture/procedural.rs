@@ -0,0 +1,248 @@
+//! Solid textures computed from noise at the hit point.
+use std::f64::consts::PI;
+
+use crate::noise::{fbm, turbulence, warp, Noise};
+use crate::objects::Hit;
+use crate::scene::{Node, Value};
+use crate::Vec3;
+
+use super::Texture;
+
+/// Coordinate system procedural textures are evaluated in.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Space {
+    World,
+    /// Relative to the object, such that patterns move along with it
+    Object,
+}
+
+/// Maps hit points to texture space as `point * scale + offset`.
+#[derive(Copy, Clone, Debug)]
+pub struct Mapping {
+    pub space: Space,
+    pub scale: Vec3,
+    pub offset: Vec3,
+}
+
+impl Mapping {
+    /// Returns object space mapping scaled uniformly by `scale`.
+    pub fn scaled(scale: f64) -> Self {
+        Mapping { space: Space::Object, scale: Vec3::new(scale, scale, scale), offset: Vec3::new(0.0, 0.0, 0.0) }
+    }
+
+    pub fn point(&self, hit: &Hit) -> Vec3 {
+        let p = match self.space {
+            Space::World => hit.point,
+            Space::Object => hit.object_point,
+        };
+        p * self.scale + self.offset
+    }
+
+    /// Adds the mapping and seed of `noise` to the description of a texture, the seed as decimal
+    /// text since numbers beyond 2^53 aren't exact.
+    fn describe(&self, node: Node, noise: &Noise) -> Value {
+        let space = match self.space {
+            Space::World => "world",
+            Space::Object => "object",
+        };
+        let seed = noise.seed().to_string();
+        node.with("space", space).with("scale", self.scale).with("offset", self.offset).with("seed", seed.as_str()).into()
+    }
+}
+
+impl Default for Mapping {
+    fn default() -> Self {
+        Mapping::scaled(1.0)
+    }
+}
+
+fn lerp(a: Vec3, b: Vec3, t: f64) -> Vec3 {
+    (1.0 - t) * a + t * b
+}
+
+fn smoothstep(edge0: f64, edge1: f64, x: f64) -> f64 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+/// Veins along x, a sine wave distorted by Perlin turbulence.
+pub struct Marble {
+    pub base: Vec3,
+    pub vein: Vec3,
+    /// Amplitude of the distortion, zero gives straight stripes
+    pub turbulence: f64,
+    pub octaves: u32,
+    pub mapping: Mapping,
+    pub noise: Noise,
+}
+
+impl Marble {
+    pub fn new(base: Vec3, vein: Vec3) -> Self {
+        Marble { base, vein, turbulence: 5.0, octaves: 6, mapping: Mapping::default(), noise: Noise::default() }
+    }
+}
+
+impl Texture for Marble {
+    fn value(&self, hit: &Hit) -> Vec3 {
+        let p = self.mapping.point(hit);
+        let phase = p.x + self.turbulence * turbulence(|q| self.noise.perlin(q), p, self.octaves);
+        // Narrow veins where the sine is close to one
+        let t = (0.5 + 0.5 * (PI * phase).sin()).powi(4);
+        lerp(self.base, self.vein, t)
+    }
+
+    fn describe(&self) -> Option<Value> {
+        let node = Node::new("marble")
+            .with("base", self.base)
+            .with("vein", self.vein)
+            .with("turbulence", self.turbulence)
+            .with("octaves", self.octaves as f64);
+        Some(self.mapping.describe(node, &self.noise))
+    }
+}
+
+/// Growth rings around the y axis, perturbed by low-frequency noise and fine grain.
+pub struct Wood {
+    pub light: Vec3,
+    pub dark: Vec3,
+    /// Number of rings per unit distance from the axis
+    pub rings: f64,
+    /// Amplitude of the ring distortion in units of ring widths
+    pub distortion: f64,
+    pub mapping: Mapping,
+    pub noise: Noise,
+}
+
+impl Wood {
+    pub fn new(light: Vec3, dark: Vec3) -> Self {
+        Wood { light, dark, rings: 8.0, distortion: 0.6, mapping: Mapping::default(), noise: Noise::default() }
+    }
+}
+
+impl Texture for Wood {
+    fn value(&self, hit: &Hit) -> Vec3 {
+        let p = self.mapping.point(hit);
+        let radius = (p.x * p.x + p.z * p.z).sqrt() * self.rings;
+        let ring = (radius + self.distortion * fbm(|q| self.noise.perlin(q), p, 3)).fract();
+        // Late wood at the end of each ring is darker, with fibers stretched along the axis
+        let grain = 0.5 + 0.5 * self.noise.perlin(Vec3::new(40.0 * p.x, 2.0 * p.y, 40.0 * p.z));
+        let t = smoothstep(0.5, 0.9, ring) * (0.7 + 0.3 * grain);
+        lerp(self.light, self.dark, t)
+    }
+
+    fn describe(&self) -> Option<Value> {
+        let node = Node::new("wood")
+            .with("light", self.light)
+            .with("dark", self.dark)
+            .with("rings", self.rings)
+            .with("distortion", self.distortion);
+        Some(self.mapping.describe(node, &self.noise))
+    }
+}
+
+/// Speckled crystals from cellular noise, with mineral colors picked per cell.
+pub struct Granite {
+    /// Colors of the minerals, chosen per crystal
+    pub minerals: Vec<Vec3>,
+    /// Color of the thin seams between crystals
+    pub seam: Vec3,
+    pub mapping: Mapping,
+    pub noise: Noise,
+}
+
+impl Granite {
+    pub fn new() -> Self {
+        Granite {
+            minerals: vec![Vec3::new(0.75, 0.72, 0.7), Vec3::new(0.55, 0.45, 0.42), Vec3::new(0.1, 0.1, 0.1)],
+            seam: Vec3::new(0.2, 0.2, 0.2),
+            mapping: Mapping::scaled(10.0),
+            noise: Noise::default(),
+        }
+    }
+}
+
+impl Default for Granite {
+    fn default() -> Self {
+        Granite::new()
+    }
+}
+
+impl Texture for Granite {
+    fn value(&self, hit: &Hit) -> Vec3 {
+        let p = self.mapping.point(hit);
+        let cell = self.noise.worley(p);
+        let mineral = if self.minerals.is_empty() { self.seam } else { self.minerals[cell.id % self.minerals.len()] };
+        let speckle = 0.85 + 0.15 * fbm(|q| self.noise.simplex(q), 4.0 * p, 3);
+        lerp(self.seam, speckle * mineral, smoothstep(0.0, 0.08, cell.f2 - cell.f1))
+    }
+
+    fn describe(&self) -> Option<Value> {
+        let minerals = Value::Numbers(self.minerals.iter().flat_map(|m| [m.x, m.y, m.z]).collect());
+        Some(self.mapping.describe(Node::new("granite").with("minerals", minerals).with("seam", self.seam), &self.noise))
+    }
+}
+
+/// Clouds over a sky color, from warped fractal simplex noise.
+pub struct Cloud {
+    pub sky: Vec3,
+    pub cloud: Vec3,
+    /// Fraction of the sky covered by clouds, in [0, 1]
+    pub coverage: f64,
+    /// Strength of the domain warping that swirls the clouds
+    pub warp: f64,
+    pub octaves: u32,
+    pub mapping: Mapping,
+    pub noise: Noise,
+}
+
+impl Cloud {
+    pub fn new(sky: Vec3, cloud: Vec3) -> Self {
+        Cloud { sky, cloud, coverage: 0.5, warp: 0.8, octaves: 6, mapping: Mapping::default(), noise: Noise::default() }
+    }
+}
+
+impl Texture for Cloud {
+    fn value(&self, hit: &Hit) -> Vec3 {
+        let noise = |q: Vec3| self.noise.simplex(q);
+        let p = warp(|q: Vec3| fbm(noise, q, 2), self.mapping.point(hit), self.warp);
+        let density = 0.5 + 0.5 * fbm(noise, p, self.octaves);
+        lerp(self.sky, self.cloud, smoothstep(1.0 - self.coverage, 1.2 - 0.8 * self.coverage, density))
+    }
+
+    fn describe(&self) -> Option<Value> {
+        let node = Node::new("cloud")
+            .with("sky", self.sky)
+            .with("cloud", self.cloud)
+            .with("coverage", self.coverage)
+            .with("warp", self.warp)
+            .with("octaves", self.octaves as f64);
+        Some(self.mapping.describe(node, &self.noise))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::material::Lambertian;
+    use crate::objects::{Hittable, Sphere};
+    use crate::Ray;
+
+    #[test]
+    fn test_object_space() {
+        let material = Arc::new(Lambertian::new(Vec3::new(0.5, 0.5, 0.5)));
+        let spheres = [Vec3::new(0.0, 0.0, 0.0), Vec3::new(3.3, 0.0, 0.0)]
+            .map(|center| Sphere { center, radius: 1.0, material: material.clone() });
+        let hit_color = |texture: &dyn Texture, sphere: &Sphere| {
+            let ray = Ray { origin: sphere.center + Vec3::new(0.3, 0.2, 5.0), direction: Vec3::new(0.0, 0.0, -1.0), time: 0.0, wavelength: None, differentials: None };
+            texture.value(&sphere.hit(&ray, 0.0, f64::MAX).unwrap())
+        };
+        let mut marble = Marble::new(Vec3::new(1.0, 1.0, 1.0), Vec3::new(0.0, 0.0, 0.0));
+        // Patterns move along with the object
+        let difference = |marble: &Marble| (hit_color(marble, &spheres[0]) - hit_color(marble, &spheres[1])).length();
+        assert!(difference(&marble) < 1e-9);
+        marble.mapping.space = Space::World;
+        assert!(difference(&marble) > 1e-3);
+    }
+}
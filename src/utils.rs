@@ -12,4 +12,93 @@ pub fn clamp(x: f64, min: f64, max: f64) -> f64 {
 
 pub fn degrees_to_radians(degrees: f64) -> f64 {
     degrees * PI / 180.0
-}
\ No newline at end of file
+}
+
+/// Returns the real roots in [`lo`, `hi`] of the polynomial with `coefficients` (constant term
+/// first) in ascending order. The interval is split at the roots of the derivative, found
+/// recursively, such that the polynomial is monotonic in each part and has at most one root
+/// there, which safeguarded Newton iterations find. Unlike closed-form solutions (e.g. Ferrari's
+/// for quartics) this does not suffer from catastrophic cancellation. Roots of even multiplicity
+/// may be missed.
+pub fn polynomial_roots(coefficients: &[f64], lo: f64, hi: f64) -> Vec<f64> {
+    let Some(degree) = coefficients.iter().rposition(|&c| c != 0.0) else {
+        return vec![];
+    };
+    let c = &coefficients[..=degree];
+    if degree == 0 {
+        return vec![];
+    }
+    if degree == 1 {
+        let root = -c[0] / c[1];
+        return if (lo..=hi).contains(&root) { vec![root] } else { vec![] };
+    }
+    let derivative: Vec<f64> = (1..=degree).map(|i| i as f64 * c[i]).collect();
+    let eval = |c: &[f64], x: f64| c.iter().rev().fold(0.0, |sum, &c| sum * x + c);
+
+    let mut bounds = vec![lo];
+    bounds.extend(polynomial_roots(&derivative, lo, hi));
+    bounds.push(hi);
+    let mut roots: Vec<f64> = vec![];
+    for pair in bounds.windows(2) {
+        let (mut a, mut b) = (pair[0], pair[1]);
+        let (fa, fb) = (eval(c, a), eval(c, b));
+        if fa == 0.0 {
+            if roots.last() != Some(&a) {
+                roots.push(a);
+            }
+            continue;
+        }
+        if fb == 0.0 || fa.signum() == fb.signum() {
+            continue;
+        }
+        let tolerance = 1.0e-12 * a.abs().max(b.abs()).max(1.0);
+        let mut x = 0.5 * (a + b);
+        for _ in 0..100 {
+            let fx = eval(c, x);
+            if fx == 0.0 {
+                break;
+            }
+            if fx.signum() == fa.signum() { a = x } else { b = x }
+            let step = fx / eval(&derivative, x);
+            if step.abs() < tolerance {
+                x -= step;
+                break;
+            }
+            // Fall back to bisection where Newton leaves the bracket
+            x = if x - step > a && x - step < b { x - step } else { 0.5 * (a + b) };
+            if b - a < tolerance {
+                break;
+            }
+        }
+        roots.push(x);
+    }
+    if eval(c, hi) == 0.0 && roots.last() != Some(&hi) {
+        roots.push(hi);
+    }
+    roots
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_polynomial_roots() {
+        // (x - 1)(x - 2)(x - 3)(x - 4)
+        let roots = polynomial_roots(&[24.0, -50.0, 35.0, -10.0, 1.0], -10.0, 10.0);
+        assert_eq!(roots.len(), 4);
+        for (root, expected) in roots.iter().zip([1.0, 2.0, 3.0, 4.0]) {
+            assert!((root - expected).abs() < 1e-9, "{:?}", roots);
+        }
+        assert_eq!(polynomial_roots(&[24.0, -50.0, 35.0, -10.0, 1.0], 1.5, 3.5).len(), 2);
+        // x^2 + 1 and a constant have no roots, leading zeros are ignored
+        assert!(polynomial_roots(&[1.0, 0.0, 1.0], -10.0, 10.0).is_empty());
+        assert!(polynomial_roots(&[1.0], -10.0, 10.0).is_empty());
+        assert_eq!(polynomial_roots(&[-2.0, 1.0, 0.0, 0.0], 0.0, 10.0), [2.0]);
+        // Nearly coincident roots 1 and 1 + 1e-6 of (x - 1)(x - 1 - 1e-6)(x + 1e3)
+        let (a, b, c) = (1.0, 1.0 + 1e-6, -1e3);
+        let coefficients = [-a * b * c, a * b + a * c + b * c, -(a + b + c), 1.0];
+        let roots = polynomial_roots(&coefficients, 0.0, 2.0);
+        assert!(roots.len() == 2 && (roots[0] - a).abs() < 1e-9 && (roots[1] - b).abs() < 1e-9, "{:?}", roots);
+    }
+}
@@ -94,7 +94,7 @@ impl Vec3 {
         Vec3 { x, y, z }
     }
 
-    pub fn random<R>(rng: &mut ThreadRng, range: R) -> Self
+    pub fn random<R>(rng: &mut dyn RngCore, range: R) -> Self
         where R: SampleRange<f64> + std::clone::Clone
     {
         Vec3 {
@@ -104,7 +104,7 @@ impl Vec3 {
         }
     }
 
-    pub fn random_in_unit_sphere(rng: &mut ThreadRng) -> Self {
+    pub fn random_in_unit_sphere(rng: &mut dyn RngCore) -> Self {
         loop {
             let p = Vec3::random(rng, -1.0..1.0);
             if p.length_squared() < 1.0 {
@@ -113,7 +113,7 @@ impl Vec3 {
         }
     }
 
-    pub fn random_in_hemisphere(rng: &mut ThreadRng, normal: Vec3) -> Self {
+    pub fn random_in_hemisphere(rng: &mut dyn RngCore, normal: Vec3) -> Self {
         let in_unit_sphere = Vec3::random_in_unit_sphere(rng);
         // In same hemisphere as normal
         if in_unit_sphere.dot(normal) > 0.0 {
@@ -123,7 +123,7 @@ impl Vec3 {
         }
     }
 
-    pub fn random_in_unit_disk(rng: &mut ThreadRng) -> Self {
+    pub fn random_in_unit_disk(rng: &mut dyn RngCore) -> Self {
         loop {
             let p = Vec3::new(
                 rng.gen_range(-1.0..1.0),